@@ -1,8 +1,8 @@
+use color_eyre::eyre::eyre;
 use color_eyre::eyre::Result;
-use image::{DynamicImage, ImageBuffer};
-use ninres::{Bfres, EmbeddedFile, NinRes, NinResFile, Sarc};
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use ninres::{tag_png_srgb, Bfres, EmbeddedFile, NinRes, NinResFile, Sarc, SrgbConversion};
 use std::{
-    cmp,
     fs::{self, read},
     path::PathBuf,
 };
@@ -20,6 +20,8 @@ struct Opt {
 pub enum Cmd {
     /// Extract assets from given input file
     Extract(ExtractOpt),
+    /// Print statistics of the models in given input file
+    Stats(StatsOpt),
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
@@ -28,6 +30,24 @@ pub struct ExtractOpt {
     pub input: PathBuf,
     #[structopt(short, long, parse(from_os_str))]
     pub output: PathBuf,
+    /// How to export sRGB textures: keep, linearize or tag
+    #[structopt(long, default_value = "keep", parse(try_from_str = parse_srgb))]
+    pub srgb: SrgbConversion,
+}
+
+fn parse_srgb(src: &str) -> Result<SrgbConversion> {
+    match src {
+        "keep" => Ok(SrgbConversion::Keep),
+        "linearize" => Ok(SrgbConversion::Linearize),
+        "tag" => Ok(SrgbConversion::Tag),
+        _ => Err(eyre!("unknown sRGB conversion {}", src)),
+    }
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub struct StatsOpt {
+    #[structopt(short, long, parse(from_os_str))]
+    pub input: PathBuf,
 }
 
 fn main() -> Result<()> {
@@ -42,13 +62,18 @@ fn main() -> Result<()> {
 
             match &ninres {
                 NinResFile::Bfres(bfres) => {
-                    extract_bfres(bfres, extract_options.output)?;
+                    extract_bfres(bfres, extract_options.output, extract_options.srgb)?;
                 }
                 NinResFile::Sarc(sarc) => {
-                    extract_sarc(sarc, extract_options.output)?;
+                    extract_sarc(sarc, extract_options.output, extract_options.srgb)?;
                 }
+                NinResFile::Bfsha(_) | NinResFile::Bnsh(_) => {}
             }
         }
+        Some(Cmd::Stats(stats_options)) => {
+            let buffer = read(&stats_options.input)?;
+            print_stats(&buffer.as_ninres()?, &stats_options.input.to_string_lossy());
+        }
         None => {
             Opt::clap().print_help()?;
         }
@@ -57,21 +82,46 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn extract_bfres(bfres: &Bfres, out_path: PathBuf) -> Result<()> {
-    for file in bfres.get_embedded_files().iter() {
-        match file {
-            EmbeddedFile::BNTX(bntx) => {
+/// Name stored in a file, which is only allowed to name a file inside the output directory.
+fn file_name(name: &str) -> Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(eyre!("invalid file name {:?}", name));
+    }
+    Ok(name)
+}
+
+fn extract_bfres(bfres: &Bfres, out_path: PathBuf, srgb: SrgbConversion) -> Result<()> {
+    for model in bfres.get_models().iter() {
+        let model_name = file_name(model.get_name())?;
+        let mtl_name = format!("{}.mtl", model_name);
+        let wavefront = model.to_obj(&mtl_name, |texture| format!("{}_0_0.png", texture));
+
+        if !out_path.exists() {
+            fs::create_dir_all(out_path.clone())?;
+        }
+        let mut path = out_path.clone();
+        path.push(format!("{}.obj", model_name));
+        fs::write(path, wavefront.obj)?;
+        let mut path = out_path.clone();
+        path.push(mtl_name);
+        fs::write(path, wavefront.mtl)?;
+    }
+    for file in bfres.get_external_files().iter() {
+        match file.get_file() {
+            Some(EmbeddedFile::BNTX(bntx)) => {
                 for texture in bntx.get_textures().iter() {
                     for (tex_count, mips) in texture.get_texture_data().iter().enumerate() {
-                        for (mip_level, mip) in mips.iter().enumerate() {
-                            let width = cmp::max(1, texture.width >> mip_level);
-                            let height = cmp::max(1, texture.height >> mip_level);
-                            let buf = if let Some(image) =
-                                ImageBuffer::from_raw(width, height, mip.clone())
+                        for mip_level in 0..mips.len() {
+                            // Depth slices of 3D textures are stacked vertically
+                            let (width, height, depth) = texture.get_mip_size(mip_level);
+                            let height = height * depth;
+                            let buf = if let Some(image) = texture
+                                .to_rgba8_with(tex_count, mip_level, srgb)
+                                .and_then(|data| ImageBuffer::from_raw(width, height, data))
                             {
                                 image
                             } else {
-                                // TODO ?
+                                // Formats without decoder, like ETC and PVRTC
                                 continue;
                             };
                             let image = DynamicImage::ImageRgba8(buf);
@@ -80,25 +130,90 @@ fn extract_bfres(bfres: &Bfres, out_path: PathBuf) -> Result<()> {
                             if !path.exists() {
                                 fs::create_dir(path.clone())?;
                             }
-                            path.push(&format!(
+                            path.push(format!(
                                 "{}_{}_{}.png",
-                                texture.get_name(),
+                                file_name(texture.get_name())?,
                                 tex_count,
                                 mip_level
                             ));
-                            if let Err(_err) = image.save(&path) {
+                            let mut png = vec![];
+                            if let Err(_err) = image.write_to(&mut png, ImageOutputFormat::Png) {
                                 // TODO
+                                continue;
                             }
+                            if srgb == SrgbConversion::Tag && texture.is_srgb() {
+                                png = tag_png_srgb(&png);
+                            }
+                            fs::write(&path, png)?;
                         }
                     }
                 }
             }
+            _ => {
+                if !out_path.exists() {
+                    fs::create_dir_all(out_path.clone())?;
+                }
+                let mut path = out_path.clone();
+                path.push(file_name(file.get_name())?);
+                fs::write(path, file.get_data())?;
+            }
         }
     }
     Ok(())
 }
 
-fn extract_sarc(sarc: &Sarc, out_path: PathBuf) -> Result<()> {
+fn print_stats(ninres: &NinResFile, path: &str) {
+    match ninres {
+        NinResFile::Bfres(bfres) => {
+            for stats in bfres.get_model_stats() {
+                println!(
+                    "{}: {}, {} bones, {} materials, {} buffer bytes, {} texture bytes",
+                    path,
+                    stats.name,
+                    stats.bone_count,
+                    stats.material_count,
+                    stats.buffer_size,
+                    stats.texture_size
+                );
+                for (lod, lod_stats) in stats.lods.iter().enumerate() {
+                    println!(
+                        "  LOD {}: {} vertices, {} indices, {} triangles",
+                        lod,
+                        lod_stats.vertex_count,
+                        lod_stats.index_count,
+                        lod_stats.triangle_count
+                    );
+                }
+                for shape in stats.shapes.iter() {
+                    if let Some(bounds) = shape.bounds {
+                        println!(
+                            "  {}: center {:?}, extent {:?}",
+                            shape.name, bounds.center, bounds.extent
+                        );
+                    }
+                }
+                for texture in stats.missing_textures.iter() {
+                    println!("  missing texture {}", texture);
+                }
+            }
+        }
+        NinResFile::Sarc(sarc) => {
+            for sfat in sarc.get_sfat_nodes().iter() {
+                let data = if let Some(data) = sfat.get_data_decompressed() {
+                    data
+                } else {
+                    sfat.get_data()
+                };
+                if let (Some(sfat_path), Ok(file)) = (sfat.get_path(), data.as_ninres()) {
+                    print_stats(&file, &format!("{}/{}", path, sfat_path));
+                }
+            }
+        }
+        NinResFile::Bfsha(_) | NinResFile::Bnsh(_) => {}
+    }
+}
+
+fn extract_sarc(sarc: &Sarc, out_path: PathBuf, srgb: SrgbConversion) -> Result<()> {
     sarc.get_sfat_nodes()
         .iter()
         .map(move |sfat| -> Result<_> {
@@ -118,20 +233,21 @@ fn extract_sarc(sarc: &Sarc, out_path: PathBuf) -> Result<()> {
                 };
 
                 if let Ok(file) = data.as_ninres() {
-                    path.set_extension(file.get_extension().to_string());
+                    path.set_extension(file.get_extension());
                     match &file {
                         NinResFile::Bfres(bfres) => {
                             let mut base_path = path.clone();
                             base_path.pop();
                             base_path.push(path.file_stem().unwrap());
-                            extract_bfres(bfres, base_path)?;
+                            extract_bfres(bfres, base_path, srgb)?;
                         }
                         NinResFile::Sarc(sarc) => {
                             let mut base_path = path.clone();
                             base_path.pop();
                             base_path.push(path.file_stem().unwrap());
-                            extract_sarc(sarc, base_path)?;
+                            extract_sarc(sarc, base_path, srgb)?;
                         }
+                        NinResFile::Bfsha(_) | NinResFile::Bnsh(_) => {}
                     }
                 }
                 fs::write(path, data)?;
@@ -29,7 +29,7 @@ fn extract_sarc(sarc: Sarc, path: PathBuf) -> Result<()> {
                 };
 
                 if let Ok(file) = data.as_ninres() {
-                    path.set_extension(file.get_extension().to_string());
+                    path.set_extension(file.get_extension());
                     if let NinResFile::Sarc(sarc) = file {
                         let mut base_path = path.clone();
                         base_path.pop();
@@ -2,7 +2,49 @@
 //!
 //! See http://mk8.tockdom.com/wiki/BFRES_(File_Format)
 
-use crate::{ByteOrderMark, Error, BNTX};
+mod anim_curve;
+mod buffer_info;
+mod material;
+mod material_anim;
+mod model;
+mod obj;
+mod scene_anim;
+mod shape;
+mod shape_anim;
+mod skeletal_anim;
+mod skeleton;
+mod skinning;
+mod stats;
+mod string_pool;
+#[cfg(test)]
+mod test_support;
+mod texture;
+mod user_data;
+mod vertex;
+mod visibility_anim;
+mod wiiu;
+mod writer;
+
+pub use anim_curve::*;
+pub use buffer_info::*;
+pub use material::*;
+pub use material_anim::*;
+pub use model::*;
+pub use obj::*;
+pub use scene_anim::*;
+pub use shape::*;
+pub use shape_anim::*;
+pub use skeletal_anim::*;
+pub use skeleton::*;
+pub use skinning::*;
+pub use stats::*;
+pub use string_pool::*;
+pub use texture::*;
+pub use user_data::*;
+pub use vertex::*;
+pub use visibility_anim::*;
+
+use crate::{Bfsha, ByteOrderMark, Error, RelocationTable, ResDict, BNSH, BNTX};
 
 use std::io::SeekFrom;
 #[cfg(target_arch = "wasm32")]
@@ -11,27 +53,113 @@ use wasm_bindgen::prelude::*;
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[derive(Clone, Debug)]
 pub struct Bfres {
-    version_number: u32,
     bom: ByteOrderMark,
     byte_alignment: u8,
-    file_name_offset: u32,
-    flags: u16,
-    block_offset: u16,
-    relocation_table_offset: u32,
-    bfres_size: u32,
-    file_name_length_offset: u64,
-    embedded_files_offset: u64,
-    embedded_files_dictionary_offset: u64,
-    embedded_files_data_offset: u64,
-    embedded_files_data_size: u64,
-    embedded_files_count: u32,
-    string_table_offset: u64,
-    string_table_size: u32,
-    embedded_files: Vec<EmbeddedFile>,
+    platform: Platform,
+    relocation_table: RelocationTable,
+    external_files_offset: u64,
+    string_pool: StringPool,
+    buffer_info: Option<BufferInfo>,
+    models: Vec<Model>,
+    model_dict: ResDict,
+    skeletal_anims: Vec<SkeletalAnim>,
+    skeletal_anim_dict: ResDict,
+    material_anims: Vec<MaterialAnim>,
+    material_anim_dict: ResDict,
+    bone_visibility_anims: Vec<VisibilityAnim>,
+    bone_visibility_anim_dict: ResDict,
+    shape_anims: Vec<ShapeAnim>,
+    shape_anim_dict: ResDict,
+    scene_anims: Vec<SceneAnim>,
+    scene_anim_dict: ResDict,
+    external_files: Vec<ExternalFile>,
+    external_file_dict: ResDict,
+    textures: Vec<GX2Texture>,
+    texture_dict: ResDict,
+}
+
+/// Console a BFRES file was made for, which determines its layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// Version 3 and 4 files with 32 bit relative offsets and GX2 resources.
+    WiiU,
+    Switch,
+}
+
+/// Shared state while reading sections of a BFRES file.
+pub(crate) struct BfresReader<'a> {
+    pub bom: &'a mut ByteOrderMark,
+    pub buffer: &'a [u8],
+    /// GPU buffer data, which contains vertex and index buffers.
+    pub buffer_info: BufferInfo,
+    pub version_number: u32,
+}
+
+/// Read a string, which is prefixed by its length.
+pub(crate) fn read_string(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+) -> Result<String, Error> {
+    bom.set_position(offset);
+    let size = bom.read_u16()? as usize;
+    let start = offset as usize + 2;
+    Ok(
+        std::str::from_utf8(buffer.get(start..start + size).ok_or(Error::CorruptData)?)?
+            .to_string(),
+    )
+}
+
+/// Read a 32 bit offset of a Wii U file, which is relative to its own position.
+/// A value of zero is a null offset.
+pub(crate) fn read_relative_offset(bom: &mut ByteOrderMark) -> Result<u64, Error> {
+    let position = bom.position();
+    Ok(match bom.read_i32()? {
+        0 => 0,
+        offset => (position as i64 + offset as i64) as u64,
+    })
+}
+
+/// Read a string of a Wii U file, which is preceded by its length.
+pub(crate) fn read_wiiu_string(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+) -> Result<String, Error> {
+    if offset < 4 {
+        return Ok(String::new());
+    }
+    bom.set_position(offset - 4);
+    let size = bom.read_u32()? as usize;
+    let start = offset as usize;
+    Ok(
+        std::str::from_utf8(buffer.get(start..start + size).ok_or(Error::CorruptData)?)?
+            .to_string(),
+    )
+}
+
+/// Read an array of string offsets.
+pub(crate) fn read_string_array(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+    count: usize,
+) -> Result<Vec<String>, Error> {
+    let mut strings = Vec::with_capacity(count);
+    for i in 0..count as u64 {
+        bom.set_position(offset + i * 8);
+        let string_offset = bom.read_u64()?;
+        strings.push(read_string(bom, buffer, string_offset)?);
+    }
+    Ok(strings)
 }
 
 impl Bfres {
     pub fn new(buffer: &[u8]) -> Result<Bfres, Error> {
+        // Wii U files store their version right after the magic
+        if matches!(buffer.get(..5), Some([b'F', b'R', b'E', b'S', 3..=4])) {
+            return Self::new_wiiu(buffer);
+        }
         let mut bom = ByteOrderMark::try_new(
             buffer.to_vec(),
             u16::from_be_bytes([buffer[0xC], buffer[0xD]]),
@@ -39,85 +167,317 @@ impl Bfres {
         bom.set_position(8);
         let version_number = bom.read_u32()?;
         let byte_alignment = buffer[0xE];
-        bom.seek(SeekFrom::Current(4))?;
-        let file_name_offset = bom.read_u32()?;
-        let flags = bom.read_u16()?;
-        let block_offset = bom.read_u16()?;
+        bom.set_position(0x18);
         let relocation_table_offset = bom.read_u32()?;
-        let bfres_size = bom.read_u32()?;
-        let file_name_length_offset = bom.read_u64()?;
+        bom.set_position(0x28);
+        let model_array_offset = bom.read_u64()?;
+        let model_dict_offset = bom.read_u64()?;
+
+        bom.set_position(0x58);
+        let skeletal_anim_array_offset = bom.read_u64()?;
+        let skeletal_anim_dict_offset = bom.read_u64()?;
+        let material_anim_array_offset = bom.read_u64()?;
+        let material_anim_dict_offset = bom.read_u64()?;
+        let bone_visibility_anim_array_offset = bom.read_u64()?;
+        let bone_visibility_anim_dict_offset = bom.read_u64()?;
+        let shape_anim_array_offset = bom.read_u64()?;
+        let shape_anim_dict_offset = bom.read_u64()?;
+        let scene_anim_array_offset = bom.read_u64()?;
+        let scene_anim_dict_offset = bom.read_u64()?;
 
-        bom.set_position(0xB8);
-        let embedded_files_offset = bom.read_u64()?;
-        let embedded_files_dictionary_offset = bom.read_u64()?;
+        bom.set_position(0xB0);
+        let buffer_info_offset = bom.read_u64()?;
+        let external_files_offset = bom.read_u64()?;
+        let external_file_dict_offset = bom.read_u64()?;
 
         bom.seek(SeekFrom::Current(8))?;
         let string_table_offset = bom.read_u64()?;
         let string_table_size = bom.read_u32()?;
+        let model_count = bom.read_u16()?;
+        bom.seek(SeekFrom::Current(4))?;
+        let skeletal_anim_count = bom.read_u16()?;
+        let material_anim_count = bom.read_u16()?;
+        let bone_visibility_anim_count = bom.read_u16()?;
+        let shape_anim_count = bom.read_u16()?;
+        let scene_anim_count = bom.read_u16()?;
+        let external_file_count = bom.read_u16()?;
 
-        bom.set_position(embedded_files_offset);
-        let embedded_files_data_offset = bom.read_u64()?;
-        let embedded_files_data_size = bom.read_u64()?;
-        bom.set_position(embedded_files_dictionary_offset + 4);
-        let embedded_files_count = bom.read_u32()?;
-
-        let mut embedded_files = vec![];
-        for n in 0..embedded_files_count {
-            let offset = if let Some(offset) = u64::checked_add(
-                embedded_files_data_offset,
-                if let Some(x) = u64::checked_mul(n as u64, embedded_files_data_size) {
-                    x
-                } else {
-                    continue;
-                },
-            ) {
-                offset
-            } else {
-                continue;
-            };
-            let end_offset =
-                if let Some(end_offset) = u64::checked_add(offset, embedded_files_data_size) {
-                    end_offset as usize
-                } else {
-                    continue;
-                };
-            if buffer.len() < end_offset {
-                continue;
-            }
-            let data = &buffer[offset as usize..end_offset];
+        let buffer_info = if buffer_info_offset != 0 {
+            Some(BufferInfo::read(&mut bom, buffer, buffer_info_offset)?)
+        } else {
+            None
+        };
+        let relocation_table =
+            RelocationTable::read(&mut bom, buffer, relocation_table_offset as u64)?;
+        let string_pool =
+            StringPool::read(&mut bom, buffer, string_table_offset, string_table_size)?;
+        let model_dict = ResDict::read(&mut bom, buffer, model_dict_offset)?;
+        let skeletal_anim_dict = ResDict::read(&mut bom, buffer, skeletal_anim_dict_offset)?;
+        let material_anim_dict = ResDict::read(&mut bom, buffer, material_anim_dict_offset)?;
+        let bone_visibility_anim_dict =
+            ResDict::read(&mut bom, buffer, bone_visibility_anim_dict_offset)?;
+        let shape_anim_dict = ResDict::read(&mut bom, buffer, shape_anim_dict_offset)?;
+        let scene_anim_dict = ResDict::read(&mut bom, buffer, scene_anim_dict_offset)?;
+        let external_file_dict = ResDict::read(&mut bom, buffer, external_file_dict_offset)?;
 
-            let file = match std::str::from_utf8(&data[..4])? {
-                "BNTX" => EmbeddedFile::BNTX(BNTX::try_new(data)?),
-                _ => continue,
-            };
+        let mut reader = BfresReader {
+            bom: &mut bom,
+            buffer,
+            buffer_info: buffer_info.unwrap_or_default(),
+            version_number,
+        };
+        let mut models = Vec::with_capacity(model_count as usize);
+        for i in 0..model_count as u64 {
+            models.push(Model::read(&mut reader, model_array_offset + i * 0x78)?);
+        }
+        let mut skeletal_anims = Vec::with_capacity(skeletal_anim_count as usize);
+        for i in 0..skeletal_anim_count as u64 {
+            skeletal_anims.push(SkeletalAnim::read(
+                &mut reader,
+                skeletal_anim_array_offset + i * 0x50,
+            )?);
+        }
+        let mut material_anims = Vec::with_capacity(material_anim_count as usize);
+        for i in 0..material_anim_count as u64 {
+            material_anims.push(MaterialAnim::read(
+                &mut reader,
+                material_anim_array_offset + i * 0x70,
+            )?);
+        }
+        let mut bone_visibility_anims = Vec::with_capacity(bone_visibility_anim_count as usize);
+        for i in 0..bone_visibility_anim_count as u64 {
+            bone_visibility_anims.push(VisibilityAnim::read(
+                &mut reader,
+                bone_visibility_anim_array_offset + i * 0x60,
+            )?);
+        }
+        let mut shape_anims = Vec::with_capacity(shape_anim_count as usize);
+        for i in 0..shape_anim_count as u64 {
+            shape_anims.push(ShapeAnim::read(
+                &mut reader,
+                shape_anim_array_offset + i * 0x50,
+            )?);
+        }
+        let mut scene_anims = Vec::with_capacity(scene_anim_count as usize);
+        for i in 0..scene_anim_count as u64 {
+            scene_anims.push(SceneAnim::read(
+                &mut reader,
+                scene_anim_array_offset + i * 0x60,
+            )?);
+        }
 
-            embedded_files.push(file)
+        if external_file_dict.len() != external_file_count as usize {
+            return Err(Error::CorruptData);
+        }
+        let mut external_files = Vec::with_capacity(external_file_count as usize);
+        for (i, name) in external_file_dict.get_keys().cloned().enumerate() {
+            bom.set_position(external_files_offset + i as u64 * 0x10);
+            let offset = bom.read_u64()?;
+            let size = bom.read_u32()?;
+            let end_offset = u64::checked_add(offset, size as u64).ok_or(Error::CorruptData)?;
+            let data = buffer
+                .get(offset as usize..end_offset as usize)
+                .ok_or(Error::CorruptData)?
+                .to_vec();
+            external_files.push(ExternalFile::new(name, data));
         }
 
         Ok(Bfres {
-            version_number,
             bom,
             byte_alignment,
-            file_name_offset,
-            flags,
-            block_offset,
-            relocation_table_offset,
-            bfres_size,
-            file_name_length_offset,
-            embedded_files_offset,
-            embedded_files_dictionary_offset,
-            embedded_files_data_offset,
-            embedded_files_data_size,
-            embedded_files_count,
-            string_table_offset,
-            string_table_size,
-            embedded_files,
+            platform: Platform::Switch,
+            relocation_table,
+            external_files_offset,
+            string_pool,
+            buffer_info,
+            models,
+            model_dict,
+            skeletal_anims,
+            skeletal_anim_dict,
+            material_anims,
+            material_anim_dict,
+            bone_visibility_anims,
+            bone_visibility_anim_dict,
+            shape_anims,
+            shape_anim_dict,
+            scene_anims,
+            scene_anim_dict,
+            external_files,
+            external_file_dict,
+            textures: vec![],
+            texture_dict: ResDict::default(),
         })
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    pub fn get_embedded_files(&self) -> &Vec<EmbeddedFile> {
-        &self.embedded_files
+    pub fn get_platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Location of the vertex and index buffers, which is missing in files without models.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_buffer_info(&self) -> Option<&BufferInfo> {
+        self.buffer_info.as_ref()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_relocation_table(&self) -> &RelocationTable {
+        &self.relocation_table
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_string_pool(&self) -> &StringPool {
+        &self.string_pool
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_models(&self) -> &Vec<Model> {
+        &self.models
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_model(&self, name: &str) -> Option<&Model> {
+        self.models.get(self.model_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_model_mut(&mut self, name: &str) -> Option<&mut Model> {
+        self.models.get_mut(self.model_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_skeletal_anims(&self) -> &Vec<SkeletalAnim> {
+        &self.skeletal_anims
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_skeletal_anim(&self, name: &str) -> Option<&SkeletalAnim> {
+        self.skeletal_anims.get(self.skeletal_anim_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_material_anims(&self) -> &Vec<MaterialAnim> {
+        &self.material_anims
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_material_anim(&self, name: &str) -> Option<&MaterialAnim> {
+        self.material_anims.get(self.material_anim_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_bone_visibility_anims(&self) -> &Vec<VisibilityAnim> {
+        &self.bone_visibility_anims
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_bone_visibility_anim(&self, name: &str) -> Option<&VisibilityAnim> {
+        self.bone_visibility_anims
+            .get(self.bone_visibility_anim_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_shape_anims(&self) -> &Vec<ShapeAnim> {
+        &self.shape_anims
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_shape_anim(&self, name: &str) -> Option<&ShapeAnim> {
+        self.shape_anims.get(self.shape_anim_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_scene_anims(&self) -> &Vec<SceneAnim> {
+        &self.scene_anims
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_scene_anim(&self, name: &str) -> Option<&SceneAnim> {
+        self.scene_anims.get(self.scene_anim_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_external_files(&self) -> &Vec<ExternalFile> {
+        &self.external_files
+    }
+
+    /// Parsed external files, skipping those, which aren't a known file format.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[deprecated(note = "use `get_external_files` and `ExternalFile::get_file` instead")]
+    pub fn get_embedded_files(&self) -> Vec<&EmbeddedFile> {
+        self.external_files
+            .iter()
+            .filter_map(ExternalFile::get_file)
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_external_file(&self, name: &str) -> Option<&ExternalFile> {
+        self.external_files.get(self.external_file_dict.find(name)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_external_file_mut(&mut self, name: &str) -> Option<&mut ExternalFile> {
+        self.external_files
+            .get_mut(self.external_file_dict.find(name)?)
+    }
+
+    /// Add an external file, which is parsed if its format is known.
+    /// Fails if a file with this name already exists.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_external_file(&mut self, name: &str, data: Vec<u8>) -> Result<(), Error> {
+        if self.external_file_dict.find(name).is_some() {
+            return Err(Error::DuplicateKey(name.to_string()));
+        }
+        self.external_files
+            .push(ExternalFile::new(name.to_string(), data));
+        self.update_external_file_dict()
+    }
+
+    /// Rename an external file. Fails if another file already has the new name.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rename_external_file(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        let index = self
+            .external_file_dict
+            .find(name)
+            .ok_or_else(|| Error::FileNotFound(name.to_string()))?;
+        if self.external_file_dict.find(new_name).is_some() {
+            return Err(Error::DuplicateKey(new_name.to_string()));
+        }
+        self.external_files[index].name = new_name.to_string();
+        self.update_external_file_dict()
+    }
+
+    /// Remove an external file and return it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn remove_external_file(&mut self, name: &str) -> Result<ExternalFile, Error> {
+        let index = self
+            .external_file_dict
+            .find(name)
+            .ok_or_else(|| Error::FileNotFound(name.to_string()))?;
+        let file = self.external_files.remove(index);
+        self.update_external_file_dict()?;
+        Ok(file)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_external_file_dict(&mut self) -> Result<(), Error> {
+        let mut dict = ResDict::new(self.external_files.iter().map(|file| file.name.clone()))?;
+        // Dictionaries of BFRES files have no signature
+        dict.signature = [0; 4];
+        self.external_file_dict = dict;
+        Ok(())
+    }
+
+    /// Textures of Wii U files. Switch files embed their textures in a BNTX file instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_textures(&self) -> &Vec<GX2Texture> {
+        &self.textures
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_texture(&self, name: &str) -> Option<&GX2Texture> {
+        self.textures.get(self.texture_dict.find(name)?)
     }
 }
 
@@ -131,23 +491,69 @@ impl Bfres {
 
     #[wasm_bindgen(js_name = intoBntxFiles)]
     pub fn into_bntx_files(self) -> Box<[JsValue]> {
-        self.embedded_files
+        self.external_files
             .into_iter()
-            .map(|t| match t {
-                EmbeddedFile::BNTX(bntx) => bntx.into(),
+            .filter_map(|file| match file.file? {
+                EmbeddedFile::BNTX(bntx) => Some(bntx.into()),
+                _ => None,
             })
             .collect()
     }
 }
 
+/// File embedded in a BFRES file, e.g. textures or shader archives.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct ExternalFile {
+    name: String,
+    #[derivative(Debug = "ignore")]
+    data: Vec<u8>,
+    file: Option<EmbeddedFile>,
+}
+
+/// Parsed external file of a known format.
 #[derive(Clone, Debug)]
 pub enum EmbeddedFile {
     BNTX(BNTX),
+    BFSHA(Bfsha),
+    BNSH(BNSH),
+}
+
+impl ExternalFile {
+    /// Files which fail to parse are kept as raw data only.
+    pub(crate) fn new(name: String, data: Vec<u8>) -> Self {
+        let file = match data.get(..4) {
+            Some(b"BNTX") => BNTX::try_new(&data).ok().map(EmbeddedFile::BNTX),
+            Some(b"FSHA") => Bfsha::try_new(&data).ok().map(EmbeddedFile::BFSHA),
+            Some(b"BNSH") => BNSH::try_new(&data).ok().map(EmbeddedFile::BNSH),
+            _ => None,
+        };
+        Self { name, data, file }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Parsed file, if its format is recognized and it could be parsed.
+    pub fn get_file(&self) -> Option<&EmbeddedFile> {
+        self.file.as_ref()
+    }
+
+    /// Replace the contents of the file, which are parsed again.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        *self = Self::new(std::mem::take(&mut self.name), data);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use byteorder::{ByteOrder, LE};
     use test_case::test_case;
 
     static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../assets/M1_Player_MarioMdl.bfres");
@@ -159,4 +565,99 @@ mod tests {
 
         assert!(bfres_file.is_ok());
     }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_read_string_pool(bfres_file: &[u8]) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+        let string_pool = bfres.get_string_pool();
+
+        assert_eq!(string_pool.get(0).unwrap(), "");
+        let model = &bfres.get_models()[0];
+        let index = string_pool.find(model.get_name()).unwrap();
+        let offset = string_pool.get_offset(index).unwrap();
+        assert_eq!(
+            &bfres_file[offset as usize + 2..][..model.get_name().len()],
+            model.get_name().as_bytes()
+        );
+        assert_eq!(string_pool.get_at_offset(offset), Some(model.get_name()));
+        assert!(model.get_user_data().get_entries().is_empty());
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_rebuild_string_pool(bfres_file: &[u8]) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+        let string_pool = bfres.get_string_pool();
+        let offset = string_pool.get_offset(0).unwrap();
+
+        let rebuilt = StringPool::new(string_pool.get_strings().clone(), offset);
+        assert_eq!(&rebuilt, string_pool);
+        let data = rebuilt.write::<LE>();
+        assert_eq!(&bfres_file[offset as usize..][..data.len()], &data[..]);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_read_buffer_info(bfres_file: &[u8]) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+        let buffer_info = bfres.get_buffer_info().unwrap();
+
+        assert_eq!(buffer_info.property, 0x24);
+        let model = &bfres.get_models()[0];
+        for vertex_buffer in model.get_vertex_buffers() {
+            for buffer in vertex_buffer.get_buffers() {
+                assert!(buffer_info.contains(buffer.offset, buffer.data.len() as u64));
+            }
+        }
+        for mesh in model
+            .get_shapes()
+            .iter()
+            .flat_map(|shape| shape.get_meshes())
+        {
+            assert!(buffer_info.contains(mesh.get_buffer_offset(), mesh.get_buffer_size() as u64));
+        }
+        assert!(model.get_buffer_size() <= buffer_info.get_size() as u64);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_read_external_files(bfres_file: &[u8]) {
+        let bfres_file = Bfres::new(bfres_file).unwrap();
+
+        assert!(bfres_file.get_external_file("textures").is_none());
+        let file = bfres_file.get_external_file("textures.bntx").unwrap();
+        assert_eq!(&file.get_data()[..4], b"BNTX");
+        let bntx = match file.get_file() {
+            Some(EmbeddedFile::BNTX(bntx)) => bntx,
+            _ => panic!("textures.bntx is not a BNTX"),
+        };
+        assert_eq!(bntx.get_texture("wait.0").unwrap().get_name(), "wait.0");
+        assert!(bntx.get_texture("wait").is_none());
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_read_external_file_out_of_range(bfres_file: &[u8]) {
+        let mut data = bfres_file.to_vec();
+        let external_files_offset = LE::read_u64(&data[0xb8..]) as usize;
+        LE::write_u64(&mut data[external_files_offset..], u64::MAX);
+        assert!(matches!(Bfres::new(&data), Err(Error::CorruptData)));
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_get_embedded_files(bfres_file: &[u8]) {
+        let bfres_file = Bfres::new(bfres_file).unwrap();
+        #[allow(deprecated)]
+        let files = bfres_file.get_embedded_files();
+
+        assert_eq!(files.len(), 1);
+        assert!(matches!(files[0], EmbeddedFile::BNTX(_)));
+    }
+
+    #[test_case(b"BNTX"; "bntx")]
+    #[test_case(b"FSHA    "; "bfsha")]
+    #[test_case(b"BNSH"; "bnsh")]
+    fn test_read_corrupt_external_file(magic: &[u8]) {
+        let mut data = magic.to_vec();
+        data.resize(0x20, 0xff);
+        let file = ExternalFile::new("corrupt".to_owned(), data.clone());
+        assert_eq!(file.get_data(), &data[..]);
+        assert!(file.get_file().is_none());
+    }
 }
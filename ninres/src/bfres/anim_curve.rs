@@ -0,0 +1,354 @@
+use super::BfresReader;
+use crate::Error;
+
+/// Animation curve shared by all animation sections.
+///
+/// Keys are stored quantized and are already converted with `scale` and `offset`.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct AnimCurve {
+    flags: u16,
+    pub anim_data_offset: u32,
+    pub start_frame: f32,
+    pub end_frame: f32,
+    pub scale: f32,
+    /// Offset of all keys. Integer curves store the bits of an `i32`.
+    pub offset: f32,
+    pub delta: f32,
+    #[derivative(Debug = "ignore")]
+    frames: Vec<f32>,
+    #[derivative(Debug = "ignore")]
+    keys: Vec<Vec<f32>>,
+}
+
+/// Animated value, which stays the same for all frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnimConstant {
+    pub anim_data_offset: u32,
+    /// Raw value, which is either a float or an integer depending on the animated target.
+    pub value: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameType {
+    Single,
+    Decimal10x5,
+    Byte,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    Single,
+    Int16,
+    SByte,
+}
+
+/// Interpolation and value type of a curve. Values match the curve type bits of the flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveType {
+    Cubic = 0,
+    Linear = 1,
+    BakedFloat = 2,
+    StepInt = 4,
+    BakedInt = 5,
+    StepBool = 6,
+    BakedBool = 7,
+}
+
+impl CurveType {
+    /// Number of coefficients stored per key.
+    pub fn get_coefficient_count(&self) -> usize {
+        match self {
+            Self::Cubic => 4,
+            Self::Linear => 2,
+            _ => 1,
+        }
+    }
+
+    fn is_int(&self) -> bool {
+        matches!(self, Self::StepInt | Self::BakedInt)
+    }
+
+    fn is_bool(&self) -> bool {
+        matches!(self, Self::StepBool | Self::BakedBool)
+    }
+}
+
+impl AnimConstant {
+    pub const SIZE: u64 = 0x8;
+
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        Ok(Self {
+            anim_data_offset: bom.read_u32()?,
+            value: bom.read_u32()?,
+        })
+    }
+
+    pub fn as_float(&self) -> f32 {
+        f32::from_bits(self.value)
+    }
+
+    pub fn as_int(&self) -> i32 {
+        self.value as i32
+    }
+}
+
+/// Evaluate curves at given frame and write their values
+/// into the animated data, which consists of 4 byte values.
+pub(crate) fn apply_curves(values: &mut [f32], curves: &[AnimCurve], frame: f32) {
+    for curve in curves.iter() {
+        if let Some(value) = values.get_mut(curve.anim_data_offset as usize / 4) {
+            *value = curve.evaluate(frame);
+        }
+    }
+}
+
+/// Wrap frame for looping animations, otherwise clamp it.
+pub(crate) fn wrap_frame(frame: f32, frame_count: f32, looping: bool) -> f32 {
+    if frame_count <= 0. {
+        0.
+    } else if looping {
+        frame.rem_euclid(frame_count)
+    } else {
+        frame.max(0.).min(frame_count)
+    }
+}
+
+impl AnimCurve {
+    pub const SIZE: u64 = 0x30;
+
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let frame_array_offset = bom.read_u64()?;
+        let key_array_offset = bom.read_u64()?;
+        let flags = bom.read_u16()?;
+        let key_count = bom.read_u16()?;
+        let anim_data_offset = bom.read_u32()?;
+        let start_frame = bom.read_f32()?;
+        let end_frame = bom.read_f32()?;
+        let scale = bom.read_f32()?;
+        let offset = bom.read_f32()?;
+        let delta = bom.read_f32()?;
+
+        let mut curve = Self {
+            flags,
+            anim_data_offset,
+            start_frame,
+            end_frame,
+            scale,
+            offset,
+            delta,
+            frames: Vec::with_capacity(key_count as usize),
+            keys: Vec::with_capacity(key_count as usize),
+        };
+        let frame_type = curve.get_frame_type()?;
+        let key_type = curve.get_key_type()?;
+        let curve_type = curve.get_curve_type()?;
+
+        bom.set_position(frame_array_offset);
+        for _ in 0..key_count {
+            curve.frames.push(match frame_type {
+                FrameType::Single => bom.read_f32()?,
+                FrameType::Decimal10x5 => bom.read_i16()? as f32 / 32.,
+                FrameType::Byte => bom.read_u8()? as f32,
+            });
+        }
+
+        bom.set_position(key_array_offset);
+        if curve_type.is_bool() {
+            let mut bits = 0;
+            for i in 0..key_count {
+                if i % 32 == 0 {
+                    bits = bom.read_u32()?;
+                }
+                curve.keys.push(vec![((bits >> (i % 32)) & 1) as f32]);
+            }
+            return Ok(curve);
+        }
+
+        let coefficient_count = curve_type.get_coefficient_count();
+        for _ in 0..key_count {
+            let mut coefficients = Vec::with_capacity(coefficient_count);
+            for i in 0..coefficient_count {
+                let key = match key_type {
+                    KeyType::Single if curve_type.is_int() => bom.read_i32()? as f32,
+                    KeyType::Single => bom.read_f32()?,
+                    KeyType::Int16 => bom.read_i16()? as f32,
+                    KeyType::SByte => bom.read_i8()? as f32,
+                };
+                coefficients.push(if curve_type.is_int() {
+                    key + offset.to_bits() as i32 as f32
+                } else if i == 0 {
+                    key * scale + offset
+                } else {
+                    key * scale
+                });
+            }
+            curve.keys.push(coefficients);
+        }
+
+        Ok(curve)
+    }
+
+    pub fn get_frame_type(&self) -> Result<FrameType, Error> {
+        Ok(match self.flags & 0x3 {
+            0 => FrameType::Single,
+            1 => FrameType::Decimal10x5,
+            2 => FrameType::Byte,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    pub fn get_key_type(&self) -> Result<KeyType, Error> {
+        Ok(match (self.flags >> 2) & 0x3 {
+            0 => KeyType::Single,
+            1 => KeyType::Int16,
+            2 => KeyType::SByte,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    pub fn get_curve_type(&self) -> Result<CurveType, Error> {
+        Ok(match (self.flags >> 4) & 0x7 {
+            0 => CurveType::Cubic,
+            1 => CurveType::Linear,
+            2 => CurveType::BakedFloat,
+            // 3 is not used
+            4 => CurveType::StepInt,
+            5 => CurveType::BakedInt,
+            6 => CurveType::StepBool,
+            7 => CurveType::BakedBool,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    pub fn get_frames(&self) -> &Vec<f32> {
+        &self.frames
+    }
+
+    /// Keys of all frames. Every key holds the coefficients of its curve type,
+    /// e.g. four coefficients of the cubic polynomial describing the segment
+    /// until the next frame.
+    pub fn get_keys(&self) -> &Vec<Vec<f32>> {
+        &self.keys
+    }
+
+    /// Evaluate the curve at given frame.
+    /// Frames outside of the curve are clamped.
+    pub fn evaluate(&self, frame: f32) -> f32 {
+        let curve_type = if let Ok(curve_type) = self.get_curve_type() {
+            curve_type
+        } else {
+            return 0.;
+        };
+        if self.frames.is_empty() || self.keys.is_empty() {
+            return 0.;
+        }
+
+        let index = match self
+            .frames
+            .iter()
+            .rposition(|&key_frame| key_frame <= frame)
+        {
+            Some(index) => index,
+            None => return self.keys[0][0],
+        };
+        let key = &self.keys[index];
+        let next_frame = if let Some(next_frame) = self.frames.get(index + 1) {
+            *next_frame
+        } else {
+            return key[0];
+        };
+        let t = if next_frame > self.frames[index] {
+            (frame - self.frames[index]) / (next_frame - self.frames[index])
+        } else {
+            0.
+        };
+
+        match curve_type {
+            CurveType::Cubic => key[0] + key[1] * t + key[2] * t * t + key[3] * t * t * t,
+            CurveType::Linear => key[0] + key[1] * t,
+            _ => key[0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bfres::BufferInfo, Bfres, ByteOrderMark};
+    use byteorder::{ByteOrder, LE};
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test]
+    fn test_read_step_int_curve() {
+        let bfres = Bfres::new(M1_PLAYER_MARIOMDL).unwrap();
+        let material_anim = &bfres.get_material_anims()[0];
+        let data = material_anim
+            .get_material_anims()
+            .iter()
+            .find(|data| {
+                data.get_pattern_anims()
+                    .iter()
+                    .any(|pattern_anim| pattern_anim.curve_index >= 0)
+            })
+            .unwrap();
+        let pattern_anim = data
+            .get_pattern_anims()
+            .iter()
+            .find(|pattern_anim| pattern_anim.curve_index >= 0)
+            .unwrap();
+        let curve = &data.get_curves()[pattern_anim.curve_index as usize];
+        assert_eq!(curve.get_curve_type().unwrap(), CurveType::StepInt);
+        let texture_count = material_anim.get_texture_names().len() as f32;
+        assert!(curve.get_keys().iter().all(|key| key.len() == 1
+            && key[0].fract() == 0.
+            && (0. ..texture_count).contains(&key[0])));
+    }
+
+    #[test]
+    fn test_read_baked_bool_curve() {
+        let mut data = vec![0; 0x44];
+        LE::write_u64(&mut data[0..], 0x30);
+        LE::write_u64(&mut data[8..], 0x40);
+        // Single frames and keys, baked bool curve type
+        LE::write_u16(&mut data[0x10..], 7 << 4);
+        LE::write_u16(&mut data[0x12..], 3);
+        LE::write_f32(&mut data[0x1c..], 2.);
+        for (i, frame) in [0., 1., 2.].iter().enumerate() {
+            LE::write_f32(&mut data[0x30 + i * 4..], *frame);
+        }
+        LE::write_u32(&mut data[0x40..], 0b101);
+
+        let mut bom = ByteOrderMark::try_new(data.clone(), 0xfffe).unwrap();
+        let mut reader = BfresReader {
+            bom: &mut bom,
+            buffer: &data,
+            buffer_info: BufferInfo::default(),
+            version_number: 0,
+        };
+        let curve = AnimCurve::read(&mut reader, 0).unwrap();
+        assert_eq!(curve.get_curve_type().unwrap(), CurveType::BakedBool);
+        assert_eq!(curve.get_keys(), &vec![vec![1.], vec![0.], vec![1.]]);
+        assert_eq!(curve.evaluate(1.), 0.);
+        assert_eq!(curve.evaluate(2.), 1.);
+    }
+
+    #[test]
+    fn test_unused_curve_type() {
+        let mut data = vec![0; 0x30];
+        LE::write_u16(&mut data[0x10..], 3 << 4);
+        let mut bom = ByteOrderMark::try_new(data.clone(), 0xfffe).unwrap();
+        let mut reader = BfresReader {
+            bom: &mut bom,
+            buffer: &data,
+            buffer_info: BufferInfo::default(),
+            version_number: 0,
+        };
+        assert!(AnimCurve::read(&mut reader, 0).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{ByteOrderMark, Error};
+
+/// Memory pool info of a Switch BFRES file, which describes the GPU buffer
+/// data holding the vertex and index buffers of all models.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferInfo {
+    /// `nn::gfx::MemoryPoolProperty` flags of the pool.
+    pub property: u32,
+    size: u32,
+    offset: u64,
+}
+
+impl BufferInfo {
+    pub(crate) fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        bom.set_position(offset);
+        let property = bom.read_u32()?;
+        let size = bom.read_u32()?;
+        let offset = bom.read_u64()?;
+        let end = offset.checked_add(size as u64).ok_or(Error::CorruptData)?;
+        if end > buffer.len() as u64 {
+            return Err(Error::CorruptData);
+        }
+        Ok(Self {
+            property,
+            size,
+            offset,
+        })
+    }
+
+    /// Offset of the buffer data within the file.
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether the buffer data contains `size` bytes starting at file offset `offset`.
+    pub fn contains(&self, offset: u64, size: u64) -> bool {
+        offset >= self.offset
+            && offset
+                .checked_add(size)
+                .is_some_and(|end| end <= self.offset + self.size as u64)
+    }
+}
@@ -0,0 +1,501 @@
+use super::{
+    read_relative_offset, read_string, read_string_array, read_wiiu_string, BfresReader,
+    UserDataList,
+};
+use crate::{Error, ResDict};
+
+use byteorder::{ByteOrder, BE, LE};
+
+/// FMAT section of a model.
+#[derive(Clone, Debug)]
+pub struct Material {
+    name: String,
+    flags: u32,
+    index: u16,
+    texture_names: Vec<String>,
+    samplers: Vec<Sampler>,
+    shader_params: Vec<ShaderParam>,
+    shader_param_dict: ResDict,
+    shader_assign: Option<ShaderAssign>,
+    user_data: UserDataList,
+}
+
+/// Shading model of a material and how its inputs are bound to the shader.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShaderAssign {
+    shader_archive: String,
+    shading_model: String,
+    pub revision: u32,
+    attrib_assigns: Vec<(String, String)>,
+    sampler_assigns: Vec<(String, String)>,
+    options: Vec<(String, String)>,
+}
+
+/// Uniform value of a material, e.g. a color or a texture transform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderParam {
+    name: String,
+    param_type: ShaderParamType,
+    data_offset: u16,
+    data: Vec<u8>,
+    big_endian: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderParamType {
+    Bool(u8),
+    Int(u8),
+    UInt(u8),
+    Float(u8),
+    /// Float matrix with the given number of rows and columns.
+    FloatMatrix(u8, u8),
+    Srt2D,
+    Srt3D,
+    TexSrt,
+    TexSrtEx,
+}
+
+/// Texture sampler. The texture of a sampler is found at the same index
+/// in the material's texture names.
+///
+/// Samplers of Wii U files hold the values of the corresponding `GX2Sampler` fields,
+/// where `filter_flags` contains the filter bits of the first sampler register.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    name: String,
+    pub wrap_mode_u: u8,
+    pub wrap_mode_v: u8,
+    pub wrap_mode_w: u8,
+    pub compare_func: u8,
+    pub border_color_type: u8,
+    pub max_anisotropy: u8,
+    pub filter_flags: u16,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub lod_bias: f32,
+}
+
+impl ShaderParamType {
+    fn try_new(value: u8) -> Result<Self, Error> {
+        let size = value % 4 + 1;
+        Ok(match value {
+            0..=3 => Self::Bool(size),
+            4..=7 => Self::Int(size),
+            8..=11 => Self::UInt(size),
+            12..=15 => Self::Float(size),
+            17..=19 | 21..=23 | 25..=27 => Self::FloatMatrix(value / 4 - 2, size),
+            28 => Self::Srt2D,
+            29 => Self::Srt3D,
+            30 => Self::TexSrt,
+            31 => Self::TexSrtEx,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+}
+
+impl Material {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FMAT") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x20);
+        let shader_assign_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x30);
+        let texture_name_array_offset = bom.read_u64()?;
+        let _sampler_slot_array_offset = bom.read_u64()?;
+        let sampler_array_offset = bom.read_u64()?;
+        let sampler_dict_offset = bom.read_u64()?;
+        let shader_param_array_offset = bom.read_u64()?;
+        let shader_param_dict_offset = bom.read_u64()?;
+        let param_source_offset = bom.read_u64()?;
+        let user_data_array_offset = bom.read_u64()?;
+        let user_data_dict_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x98);
+        let index = bom.read_u16()?;
+        let _render_info_count = bom.read_u16()?;
+        let sampler_count = bom.read_u8()?;
+        let texture_count = bom.read_u8()?;
+        let shader_param_count = bom.read_u16()?;
+        bom.set_position(offset + 0xA6);
+        let user_data_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let texture_names = read_string_array(
+            bom,
+            reader.buffer,
+            texture_name_array_offset,
+            texture_count as usize,
+        )?;
+
+        let sampler_dict = ResDict::read(bom, reader.buffer, sampler_dict_offset)?;
+        if sampler_dict.len() != sampler_count as usize {
+            return Err(Error::CorruptData);
+        }
+        let mut samplers = Vec::with_capacity(sampler_count as usize);
+        for (i, name) in sampler_dict.get_keys().cloned().enumerate() {
+            bom.set_position(sampler_array_offset + i as u64 * 0x20);
+            samplers.push(Sampler {
+                name,
+                wrap_mode_u: bom.read_u8()?,
+                wrap_mode_v: bom.read_u8()?,
+                wrap_mode_w: bom.read_u8()?,
+                compare_func: bom.read_u8()?,
+                border_color_type: bom.read_u8()?,
+                max_anisotropy: bom.read_u8()?,
+                filter_flags: bom.read_u16()?,
+                min_lod: bom.read_f32()?,
+                max_lod: bom.read_f32()?,
+                lod_bias: bom.read_f32()?,
+            });
+        }
+
+        let shader_param_dict = ResDict::read(bom, reader.buffer, shader_param_dict_offset)?;
+        if shader_param_dict.len() != shader_param_count as usize {
+            return Err(Error::CorruptData);
+        }
+        let mut shader_params = Vec::with_capacity(shader_param_count as usize);
+        for (i, name) in shader_param_dict.get_keys().cloned().enumerate() {
+            bom.set_position(shader_param_array_offset + i as u64 * 0x20 + 0x10);
+            let param_type = ShaderParamType::try_new(bom.read_u8()?)?;
+            let size = bom.read_u8()? as u64;
+            let data_offset = bom.read_u16()?;
+            let start = param_source_offset + data_offset as u64;
+            shader_params.push(ShaderParam {
+                name,
+                param_type,
+                data_offset,
+                data: reader
+                    .buffer
+                    .get(start as usize..(start + size) as usize)
+                    .ok_or(Error::CorruptData)?
+                    .to_vec(),
+                big_endian: bom.is_big_endian(),
+            });
+        }
+
+        let shader_assign = if shader_assign_offset == 0 {
+            None
+        } else {
+            Some(ShaderAssign::read(reader, shader_assign_offset)?)
+        };
+
+        let user_data = UserDataList::read(
+            reader,
+            user_data_array_offset,
+            user_data_dict_offset,
+            user_data_count,
+        )?;
+
+        Ok(Self {
+            name,
+            flags,
+            index,
+            texture_names,
+            samplers,
+            shader_params,
+            shader_param_dict,
+            shader_assign,
+            user_data,
+        })
+    }
+
+    /// Read a material of a Wii U file.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FMAT") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let name_offset = read_relative_offset(bom)?;
+        let flags = bom.read_u32()?;
+        let index = bom.read_u16()?;
+        let _render_info_count = bom.read_u16()?;
+        let _sampler_count = bom.read_u8()?;
+        let texture_count = bom.read_u8()?;
+        bom.set_position(offset + 0x24);
+        let texture_ref_array_offset = read_relative_offset(bom)?;
+        let _sampler_array_offset = read_relative_offset(bom)?;
+        let sampler_group_offset = read_relative_offset(bom)?;
+        bom.set_position(offset + 0x3c);
+        let user_data_group_offset = read_relative_offset(bom)?;
+
+        let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+
+        let mut texture_names = Vec::with_capacity(texture_count as usize);
+        for i in 0..texture_count as u64 {
+            bom.set_position(texture_ref_array_offset + i * 8);
+            let name_offset = read_relative_offset(bom)?;
+            texture_names.push(read_wiiu_string(bom, reader.buffer, name_offset)?);
+        }
+
+        let (sampler_dict, sampler_offsets) =
+            ResDict::read_index_group(bom, reader.buffer, sampler_group_offset)?;
+        let mut samplers = Vec::with_capacity(sampler_offsets.len());
+        for (name, sampler_offset) in sampler_dict.get_keys().cloned().zip(sampler_offsets) {
+            bom.set_position(sampler_offset);
+            let filter = bom.read_u32()?;
+            let lod = bom.read_u32()?;
+            // LODs are fixed point numbers with six fractional bits
+            let lod_bias = ((lod as i32) >> 20) as f32 / 64.;
+            samplers.push(Sampler {
+                name,
+                wrap_mode_u: (filter & 7) as u8,
+                wrap_mode_v: ((filter >> 3) & 7) as u8,
+                wrap_mode_w: ((filter >> 6) & 7) as u8,
+                compare_func: ((filter >> 26) & 7) as u8,
+                border_color_type: ((filter >> 22) & 3) as u8,
+                max_anisotropy: ((filter >> 19) & 7) as u8,
+                filter_flags: ((filter >> 9) & 0x3ff) as u16,
+                min_lod: (lod & 0x3ff) as f32 / 64.,
+                max_lod: ((lod >> 10) & 0x3ff) as f32 / 64.,
+                lod_bias,
+            });
+        }
+
+        let user_data = UserDataList::read_wiiu(reader, user_data_group_offset)?;
+
+        Ok(Self {
+            name,
+            flags,
+            index,
+            texture_names,
+            samplers,
+            shader_params: vec![],
+            shader_param_dict: ResDict::default(),
+            shader_assign: None,
+            user_data,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn get_texture_names(&self) -> &Vec<String> {
+        &self.texture_names
+    }
+
+    pub fn get_samplers(&self) -> &Vec<Sampler> {
+        &self.samplers
+    }
+
+    /// Name of the texture bound to the sampler with given name, e.g. `_a0` for albedo.
+    pub fn get_sampler_texture(&self, sampler_name: &str) -> Option<&String> {
+        self.samplers
+            .iter()
+            .position(|sampler| sampler.name == sampler_name)
+            .and_then(|i| self.texture_names.get(i))
+    }
+
+    pub fn get_sampler_mut(&mut self, name: &str) -> Option<&mut Sampler> {
+        self.samplers
+            .iter_mut()
+            .find(|sampler| sampler.name == name)
+    }
+
+    /// Shader parameters of Switch files.
+    pub fn get_shader_params(&self) -> &Vec<ShaderParam> {
+        &self.shader_params
+    }
+
+    pub fn get_shader_param(&self, name: &str) -> Option<&ShaderParam> {
+        self.shader_params.get(self.shader_param_dict.find(name)?)
+    }
+
+    pub fn get_shader_param_mut(&mut self, name: &str) -> Option<&mut ShaderParam> {
+        self.shader_params
+            .get_mut(self.shader_param_dict.find(name)?)
+    }
+
+    /// Shader assignment of Switch files.
+    pub fn get_shader_assign(&self) -> Option<&ShaderAssign> {
+        self.shader_assign.as_ref()
+    }
+
+    pub fn get_user_data(&self) -> &UserDataList {
+        &self.user_data
+    }
+
+    /// Write samplers and shader parameters back into the material at `offset`.
+    pub(crate) fn write<B: ByteOrder>(&self, data: &mut [u8], offset: usize) {
+        let sampler_array_offset = B::read_u64(&data[offset + 0x40..]) as usize;
+        for (i, sampler) in self.samplers.iter().enumerate() {
+            let position = sampler_array_offset + i * 0x20;
+            let entry = &mut data[position..position + 0x14];
+            entry[..6].copy_from_slice(&[
+                sampler.wrap_mode_u,
+                sampler.wrap_mode_v,
+                sampler.wrap_mode_w,
+                sampler.compare_func,
+                sampler.border_color_type,
+                sampler.max_anisotropy,
+            ]);
+            B::write_u16(&mut entry[6..], sampler.filter_flags);
+            B::write_f32(&mut entry[8..], sampler.min_lod);
+            B::write_f32(&mut entry[0xc..], sampler.max_lod);
+            B::write_f32(&mut entry[0x10..], sampler.lod_bias);
+        }
+
+        let param_source_offset = B::read_u64(&data[offset + 0x60..]) as usize;
+        for shader_param in self.shader_params.iter() {
+            let start = param_source_offset + shader_param.data_offset as usize;
+            data[start..start + shader_param.data.len()].copy_from_slice(&shader_param.data);
+        }
+    }
+}
+
+impl ShaderParam {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_type(&self) -> ShaderParamType {
+        self.param_type
+    }
+
+    /// Raw value in the byte order of the file.
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Components of float parameters. The first component of texture transforms is
+    /// their integer mode, which is returned as is.
+    pub fn get_floats(&self) -> Vec<f32> {
+        if self.big_endian {
+            self.data.chunks_exact(4).map(BE::read_f32).collect()
+        } else {
+            self.data.chunks_exact(4).map(LE::read_f32).collect()
+        }
+    }
+
+    /// Overwrite the value with float components, which are stored in the byte order of the file.
+    /// Fails if the number of components does not match the parameter's size.
+    pub fn set_floats(&mut self, values: &[f32]) -> Result<(), Error> {
+        if values.len() * 4 != self.data.len() {
+            return Err(Error::ValueLengthInvalid(self.data.len() / 4, values.len()));
+        }
+        if self.big_endian {
+            BE::write_f32_into(values, &mut self.data);
+        } else {
+            LE::write_f32_into(values, &mut self.data);
+        }
+        Ok(())
+    }
+}
+
+impl ShaderAssign {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let buffer = reader.buffer;
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let shader_archive_offset = bom.read_u64()?;
+        let shading_model_offset = bom.read_u64()?;
+        let mut arrays = [(0, 0); 3];
+        for (array_offset, dict_offset) in arrays.iter_mut() {
+            *array_offset = bom.read_u64()?;
+            *dict_offset = bom.read_u64()?;
+        }
+        let revision = bom.read_u32()?;
+        let attrib_assign_count = bom.read_u8()? as usize;
+        let sampler_assign_count = bom.read_u8()? as usize;
+        let option_count = bom.read_u16()? as usize;
+
+        let mut read_pairs = |(array_offset, dict_offset): (u64, u64), count: usize| {
+            let dict = ResDict::read(bom, buffer, dict_offset)?;
+            let values = read_string_array(bom, buffer, array_offset, count)?;
+            if dict.len() != count {
+                return Err(Error::CorruptData);
+            }
+            Ok(dict.get_keys().cloned().zip(values).collect())
+        };
+        let attrib_assigns = read_pairs(arrays[0], attrib_assign_count)?;
+        let sampler_assigns = read_pairs(arrays[1], sampler_assign_count)?;
+        let options = read_pairs(arrays[2], option_count)?;
+
+        Ok(Self {
+            shader_archive: read_string(bom, buffer, shader_archive_offset)?,
+            shading_model: read_string(bom, buffer, shading_model_offset)?,
+            revision,
+            attrib_assigns,
+            sampler_assigns,
+            options,
+        })
+    }
+
+    /// Name of the shader archive, which is usually an external file of the BFRES file.
+    pub fn get_shader_archive(&self) -> &String {
+        &self.shader_archive
+    }
+
+    pub fn get_shading_model(&self) -> &String {
+        &self.shading_model
+    }
+
+    /// Pairs of shader attribute names and vertex attribute names.
+    pub fn get_attrib_assigns(&self) -> &Vec<(String, String)> {
+        &self.attrib_assigns
+    }
+
+    /// Pairs of shader sampler names and material sampler names.
+    pub fn get_sampler_assigns(&self) -> &Vec<(String, String)> {
+        &self.sampler_assigns
+    }
+
+    /// Pairs of shader option names and their choices.
+    pub fn get_options(&self) -> &Vec<(String, String)> {
+        &self.options
+    }
+
+    pub fn get_option(&self, name: &str) -> Option<&String> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, choice)| choice)
+    }
+}
+
+impl Sampler {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(false, [0, 0, 0x80, 0x3f]; "little endian")]
+    #[test_case(true, [0x3f, 0x80, 0, 0]; "big endian")]
+    fn test_shader_param_floats(big_endian: bool, data: [u8; 4]) {
+        let mut param = ShaderParam {
+            name: "emission_intensity".to_owned(),
+            param_type: ShaderParamType::Float(1),
+            data_offset: 0,
+            data: data.to_vec(),
+            big_endian,
+        };
+        assert_eq!(param.get_floats(), [1.]);
+        param.set_floats(&[0.5]).unwrap();
+        assert_eq!(param.get_floats(), [0.5]);
+        assert_eq!(param.get_data().len(), 4);
+        assert!(matches!(
+            param.set_floats(&[0.25, 0.5]),
+            Err(Error::ValueLengthInvalid(1, 2))
+        ));
+        assert_eq!(param.get_floats(), [0.5]);
+    }
+}
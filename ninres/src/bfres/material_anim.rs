@@ -0,0 +1,737 @@
+use super::{read_string, read_string_array, wrap_frame, AnimConstant, AnimCurve, BfresReader};
+use crate::Error;
+
+use std::convert::TryFrom;
+
+/// FMAA section containing shader parameter, texture pattern
+/// and visibility animations of materials.
+///
+/// Files before version 0.9 store separate FSHU sections for shader parameter and FTXP
+/// sections for texture pattern animations, which are read into the same structure.
+#[derive(Clone, Debug)]
+pub struct MaterialAnim {
+    name: String,
+    path: String,
+    flags: u32,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    bind_indices: Vec<u16>,
+    texture_names: Vec<String>,
+    material_anims: Vec<MaterialAnimData>,
+}
+
+/// Animation of a single material.
+#[derive(Clone, Debug)]
+pub struct MaterialAnimData {
+    name: String,
+    param_anims: Vec<ParamAnim>,
+    pattern_anims: Vec<PatternAnim>,
+    curves: Vec<AnimCurve>,
+    constants: Vec<AnimConstant>,
+    visibility_curve_index: u16,
+    visibility_constant_index: u16,
+}
+
+/// Animation of a shader parameter, e.g. a color or a texture SRT.
+///
+/// Float curves come first, followed by integer curves.
+#[derive(Clone, Debug)]
+pub struct ParamAnim {
+    name: String,
+    pub begin_curve: u16,
+    pub float_curve_count: u16,
+    pub int_curve_count: u16,
+    pub begin_constant: u16,
+    pub constant_count: u16,
+}
+
+/// Texture pattern animation of a sampler.
+#[derive(Clone, Debug)]
+pub struct PatternAnim {
+    name: String,
+    pub curve_index: i16,
+    pub begin_constant: i16,
+}
+
+/// Evaluated state of a material at a single frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialAnimSample {
+    pub name: String,
+    /// Shader parameter names with their values.
+    /// Values are laid out by their animation data offset.
+    pub params: Vec<(String, Vec<f32>)>,
+    /// Sampler names with the name of the bound texture.
+    pub textures: Vec<(String, String)>,
+    pub visible: Option<bool>,
+}
+
+impl MaterialAnim {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        match reader.buffer.get(offset as usize..offset as usize + 4) {
+            Some(b"FMAA") => {}
+            Some(b"FSHU") => return Self::read_shader_param_anim(reader, offset),
+            Some(b"FTXP") => return Self::read_texture_pattern_anim(reader, offset),
+            _ => return Err(Error::CorruptData),
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_model_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let material_anim_array_offset = bom.read_u64()?;
+        let _texture_array_offset = bom.read_u64()?;
+        let texture_name_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let _texture_bind_array_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let baked_size = bom.read_u32()?;
+        let _user_data_count = bom.read_u16()?;
+        let material_anim_count = bom.read_u16()?;
+        let _curve_count = bom.read_u16()?;
+        let _param_anim_count = bom.read_u16()?;
+        let _pattern_anim_count = bom.read_u16()?;
+        let _visibility_anim_count = bom.read_u16()?;
+        let texture_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+        let texture_names = read_string_array(
+            bom,
+            reader.buffer,
+            texture_name_array_offset,
+            texture_count as usize,
+        )?;
+        let bind_indices = read_bind_indices(reader, bind_index_array_offset, material_anim_count)?;
+
+        let mut material_anims = Vec::with_capacity(material_anim_count as usize);
+        for i in 0..material_anim_count as u64 {
+            material_anims.push(MaterialAnimData::read(
+                reader,
+                material_anim_array_offset + i * 0x40,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            texture_names,
+            material_anims,
+        })
+    }
+
+    /// Read a FSHU section of files before version 0.9, which only animates shader parameters.
+    fn read_shader_param_anim(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_model_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let material_anim_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let baked_size = bom.read_u32()?;
+        let _user_data_count = bom.read_u16()?;
+        let material_anim_count = bom.read_u16()?;
+        let _curve_count = bom.read_u32()?;
+        let _param_anim_count = bom.read_u32()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+        let bind_indices = read_bind_indices(reader, bind_index_array_offset, material_anim_count)?;
+
+        let mut material_anims = Vec::with_capacity(material_anim_count as usize);
+        for i in 0..material_anim_count as u64 {
+            material_anims.push(MaterialAnimData::read_shader_param_anim(
+                reader,
+                material_anim_array_offset + i * 0x30,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            texture_names: vec![],
+            material_anims,
+        })
+    }
+
+    /// Read a FTXP section of files before version 0.9, which only animates texture patterns.
+    fn read_texture_pattern_anim(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_model_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let material_anim_array_offset = bom.read_u64()?;
+        let _texture_array_offset = bom.read_u64()?;
+        let texture_name_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let baked_size = bom.read_u32()?;
+        let _user_data_count = bom.read_u16()?;
+        let material_anim_count = bom.read_u16()?;
+        let _curve_count = bom.read_u32()?;
+        let _pattern_anim_count = bom.read_u16()?;
+        let texture_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+        let texture_names = read_string_array(
+            bom,
+            reader.buffer,
+            texture_name_array_offset,
+            texture_count as usize,
+        )?;
+        let bind_indices = read_bind_indices(reader, bind_index_array_offset, material_anim_count)?;
+
+        let mut material_anims = Vec::with_capacity(material_anim_count as usize);
+        for i in 0..material_anim_count as u64 {
+            material_anims.push(MaterialAnimData::read_texture_pattern_anim(
+                reader,
+                material_anim_array_offset + i * 0x30,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            texture_names,
+            material_anims,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    /// Indices of the animated materials in the bound model.
+    pub fn get_bind_indices(&self) -> &Vec<u16> {
+        &self.bind_indices
+    }
+
+    /// Textures referenced by texture pattern animations.
+    pub fn get_texture_names(&self) -> &Vec<String> {
+        &self.texture_names
+    }
+
+    pub fn get_material_anims(&self) -> &Vec<MaterialAnimData> {
+        &self.material_anims
+    }
+
+    /// Evaluate all animated materials at given frame.
+    ///
+    /// Looping animations wrap around, otherwise the frame is clamped.
+    pub fn sample(&self, frame: f32) -> Vec<MaterialAnimSample> {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        self.material_anims
+            .iter()
+            .map(|material_anim| MaterialAnimSample {
+                name: material_anim.name.clone(),
+                params: material_anim
+                    .param_anims
+                    .iter()
+                    .map(|param_anim| {
+                        (
+                            param_anim.name.clone(),
+                            material_anim.sample_param(param_anim, frame),
+                        )
+                    })
+                    .collect(),
+                textures: material_anim
+                    .pattern_anims
+                    .iter()
+                    .filter_map(|pattern_anim| {
+                        let index = material_anim.sample_pattern(pattern_anim, frame)?;
+                        Some((
+                            pattern_anim.name.clone(),
+                            self.texture_names.get(index)?.clone(),
+                        ))
+                    })
+                    .collect(),
+                visible: material_anim.sample_visibility(frame),
+            })
+            .collect()
+    }
+}
+
+impl MaterialAnimData {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let param_anim_array_offset = bom.read_u64()?;
+        let pattern_anim_array_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let constant_array_offset = bom.read_u64()?;
+        let _begin_param_curve = bom.read_u16()?;
+        let _begin_pattern_curve = bom.read_u16()?;
+        let _begin_visibility_curve = bom.read_u16()?;
+        let visibility_curve_index = bom.read_u16()?;
+        let visibility_constant_index = bom.read_u16()?;
+        let param_anim_count = bom.read_u16()?;
+        let pattern_anim_count = bom.read_u16()?;
+        let constant_count = bom.read_u16()?;
+        let curve_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut param_anims = Vec::with_capacity(param_anim_count as usize);
+        for i in 0..param_anim_count as u64 {
+            param_anims.push(ParamAnim::read(reader, param_anim_array_offset + i * 0x18)?);
+        }
+
+        let mut pattern_anims = Vec::with_capacity(pattern_anim_count as usize);
+        for i in 0..pattern_anim_count as u64 {
+            let bom = &mut reader.bom;
+            bom.set_position(pattern_anim_array_offset + i * 0x10);
+            let name_offset = bom.read_u64()?;
+            let curve_index = bom.read_i16()?;
+            let begin_constant = bom.read_i16()?;
+            pattern_anims.push(PatternAnim {
+                name: read_string(bom, reader.buffer, name_offset)?,
+                curve_index,
+                begin_constant,
+            });
+        }
+
+        Ok(Self {
+            name,
+            param_anims,
+            pattern_anims,
+            curves: read_curves(reader, curve_array_offset, curve_count)?,
+            constants: read_constants(reader, constant_array_offset, constant_count)?,
+            visibility_curve_index,
+            visibility_constant_index,
+        })
+    }
+
+    /// Read the animation of a material in a FSHU section.
+    fn read_shader_param_anim(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let param_anim_array_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let constant_array_offset = bom.read_u64()?;
+        let param_anim_count = bom.read_u16()?;
+        let curve_count = bom.read_u16()?;
+        let constant_count = bom.read_u16()?;
+        let _padding = bom.read_u16()?;
+        let _begin_curve = bom.read_i32()?;
+        let _begin_param_anim = bom.read_i32()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut param_anims = Vec::with_capacity(param_anim_count as usize);
+        for i in 0..param_anim_count as u64 {
+            param_anims.push(ParamAnim::read(reader, param_anim_array_offset + i * 0x18)?);
+        }
+
+        Ok(Self {
+            name,
+            param_anims,
+            pattern_anims: vec![],
+            curves: read_curves(reader, curve_array_offset, curve_count)?,
+            constants: read_constants(reader, constant_array_offset, constant_count)?,
+            visibility_curve_index: u16::MAX,
+            visibility_constant_index: u16::MAX,
+        })
+    }
+
+    /// Read the animation of a material in a FTXP section.
+    ///
+    /// The base texture index of every pattern is stored as a constant, so patterns
+    /// without a curve are sampled like the ones of FMAA sections.
+    fn read_texture_pattern_anim(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let pattern_anim_array_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_value_array_offset = bom.read_u64()?;
+        let pattern_anim_count = bom.read_u16()?;
+        let curve_count = bom.read_u16()?;
+        let _begin_curve = bom.read_i32()?;
+        let _begin_pattern_anim = bom.read_i32()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut pattern_anims = Vec::with_capacity(pattern_anim_count as usize);
+        let mut constants = Vec::with_capacity(pattern_anim_count as usize);
+        for i in 0..pattern_anim_count {
+            bom.set_position(pattern_anim_array_offset + i as u64 * 0x10);
+            let name_offset = bom.read_u64()?;
+            let curve_index = bom.read_i8()?;
+            let _sub_bind_index = bom.read_i8()?;
+            bom.set_position(base_value_array_offset + i as u64 * 2);
+            let base_value = bom.read_u16()?;
+            pattern_anims.push(PatternAnim {
+                name: read_string(bom, reader.buffer, name_offset)?,
+                curve_index: curve_index as i16,
+                begin_constant: i as i16,
+            });
+            constants.push(AnimConstant {
+                anim_data_offset: 0,
+                value: base_value as u32,
+            });
+        }
+
+        Ok(Self {
+            name,
+            param_anims: vec![],
+            pattern_anims,
+            curves: read_curves(reader, curve_array_offset, curve_count)?,
+            constants,
+            visibility_curve_index: u16::MAX,
+            visibility_constant_index: u16::MAX,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_param_anims(&self) -> &Vec<ParamAnim> {
+        &self.param_anims
+    }
+
+    pub fn get_pattern_anims(&self) -> &Vec<PatternAnim> {
+        &self.pattern_anims
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    pub fn get_constants(&self) -> &Vec<AnimConstant> {
+        &self.constants
+    }
+
+    /// Evaluate a shader parameter at given frame.
+    ///
+    /// Returns one value per 4 bytes of the parameter.
+    pub fn sample_param(&self, param_anim: &ParamAnim, frame: f32) -> Vec<f32> {
+        let begin_curve = param_anim.begin_curve as usize;
+        let curve_count = (param_anim.float_curve_count + param_anim.int_curve_count) as usize;
+        let curves = self
+            .curves
+            .get(begin_curve..begin_curve + curve_count)
+            .unwrap_or_default();
+        let begin_constant = param_anim.begin_constant as usize;
+        let constants = self
+            .constants
+            .get(begin_constant..begin_constant + param_anim.constant_count as usize)
+            .unwrap_or_default();
+
+        let mut values = vec![];
+        let mut set_value = |anim_data_offset: u32, value: f32| {
+            let index = anim_data_offset as usize / 4;
+            if values.len() <= index {
+                values.resize(index + 1, 0.);
+            }
+            values[index] = value;
+        };
+        for constant in constants.iter() {
+            set_value(constant.anim_data_offset, constant.as_float());
+        }
+        for curve in curves.iter() {
+            set_value(curve.anim_data_offset, curve.evaluate(frame));
+        }
+        values
+    }
+
+    /// Evaluate a texture pattern at given frame.
+    ///
+    /// Returns the index into the texture names of the animation.
+    pub fn sample_pattern(&self, pattern_anim: &PatternAnim, frame: f32) -> Option<usize> {
+        let index = if pattern_anim.curve_index >= 0 {
+            self.curves
+                .get(pattern_anim.curve_index as usize)?
+                .evaluate(frame)
+                .round() as i32
+        } else {
+            self.constants
+                .get(usize::try_from(pattern_anim.begin_constant).ok()?)?
+                .as_int()
+        };
+        usize::try_from(index).ok()
+    }
+
+    /// Evaluate the visibility at given frame, if it is animated.
+    pub fn sample_visibility(&self, frame: f32) -> Option<bool> {
+        if let Some(curve) = self.curves.get(self.visibility_curve_index as usize) {
+            Some(curve.evaluate(frame) != 0.)
+        } else {
+            self.constants
+                .get(self.visibility_constant_index as usize)
+                .map(|constant| constant.value != 0)
+        }
+    }
+}
+
+impl ParamAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let begin_curve = bom.read_u16()?;
+        let float_curve_count = bom.read_u16()?;
+        let int_curve_count = bom.read_u16()?;
+        let begin_constant = bom.read_u16()?;
+        let constant_count = bom.read_u16()?;
+        Ok(Self {
+            name: read_string(bom, reader.buffer, name_offset)?,
+            begin_curve,
+            float_curve_count,
+            int_curve_count,
+            begin_constant,
+            constant_count,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+impl PatternAnim {
+    /// Name of the animated sampler.
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+fn read_bind_indices(reader: &mut BfresReader, offset: u64, count: u16) -> Result<Vec<u16>, Error> {
+    let bom = &mut reader.bom;
+    bom.set_position(offset);
+    let mut bind_indices = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        bind_indices.push(bom.read_u16()?);
+    }
+    Ok(bind_indices)
+}
+
+fn read_curves(reader: &mut BfresReader, offset: u64, count: u16) -> Result<Vec<AnimCurve>, Error> {
+    let mut curves = Vec::with_capacity(count as usize);
+    for i in 0..count as u64 {
+        curves.push(AnimCurve::read(reader, offset + i * AnimCurve::SIZE)?);
+    }
+    Ok(curves)
+}
+
+fn read_constants(
+    reader: &mut BfresReader,
+    offset: u64,
+    count: u16,
+) -> Result<Vec<AnimConstant>, Error> {
+    let mut constants = Vec::with_capacity(count as usize);
+    for i in 0..count as u64 {
+        constants.push(AnimConstant::read(reader, offset + i * AnimConstant::SIZE)?);
+    }
+    Ok(constants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bfres::{test_support::Writer, CurveType},
+        Bfres,
+    };
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_sample_material_anim(bfres_file: &[u8]) {
+        let bfres_file = Bfres::new(bfres_file).unwrap();
+        let material_anims = bfres_file.get_material_anims();
+        assert!(!material_anims.is_empty());
+
+        for material_anim in material_anims.iter() {
+            for frame in 0..=material_anim.frame_count {
+                let samples = material_anim.sample(frame as f32);
+                assert_eq!(samples.len(), material_anim.get_material_anims().len());
+                for (sample, data) in samples.iter().zip(material_anim.get_material_anims()) {
+                    assert_eq!(sample.textures.len(), data.get_pattern_anims().len());
+                    assert!(sample
+                        .textures
+                        .iter()
+                        .all(|(_, texture)| material_anim.get_texture_names().contains(texture)));
+                }
+            }
+        }
+    }
+
+    fn build_shader_param_anim() -> Writer {
+        let mut w = Writer::default();
+        let header = w.alloc(0x70);
+        w.data[header..header + 4].copy_from_slice(b"FSHU");
+        w.u32(header + 4, 1 << 2);
+        w.name(header + 8, "color_anim");
+        w.name(header + 0x10, "");
+        let bind_indices = w.alloc(2);
+        w.u16(bind_indices, 3);
+        w.u64(header + 0x20, bind_indices as u64);
+        w.u32(header + 0x40, 10);
+        w.u16(header + 0x4a, 1);
+        w.u32(header + 0x4c, 1);
+        w.u32(header + 0x50, 1);
+
+        let material_anim = w.alloc(0x30);
+        w.u64(header + 0x28, material_anim as u64);
+        w.name(material_anim, "body");
+        let param_anims = w.alloc(0x18);
+        w.u64(material_anim + 8, param_anims as u64);
+        w.name(param_anims, "albedo_color");
+        w.u16(param_anims + 0xa, 1);
+        w.u16(param_anims + 0x10, 2);
+        let curves = w.curves(&[(CurveType::Linear, 4, &[0., 10.], &[&[0., 1.], &[1., 0.]])]);
+        w.u64(material_anim + 0x10, curves as u64);
+        let constants = w.alloc(0x10);
+        w.u32(constants + 4, 0.5f32.to_bits());
+        w.u32(constants + 8, 8);
+        w.u32(constants + 0xc, 1f32.to_bits());
+        w.u64(material_anim + 0x18, constants as u64);
+        w.u16(material_anim + 0x20, 1);
+        w.u16(material_anim + 0x22, 1);
+        w.u16(material_anim + 0x24, 2);
+        w
+    }
+
+    fn build_texture_pattern_anim() -> Writer {
+        let mut w = Writer::default();
+        let header = w.alloc(0x70);
+        w.data[header..header + 4].copy_from_slice(b"FTXP");
+        w.name(header + 8, "eye_anim");
+        w.name(header + 0x10, "");
+        let bind_indices = w.alloc(2);
+        w.u16(bind_indices, 5);
+        w.u64(header + 0x20, bind_indices as u64);
+        let texture_names = w.alloc(3 * 8);
+        for (i, name) in ["eye_open", "eye_half", "eye_closed"].iter().enumerate() {
+            w.name(texture_names + i * 8, name);
+        }
+        w.u64(header + 0x38, texture_names as u64);
+        w.u32(header + 0x50, 10);
+        w.u16(header + 0x5a, 1);
+        w.u32(header + 0x5c, 1);
+        w.u16(header + 0x60, 2);
+        w.u16(header + 0x62, 3);
+
+        let material_anim = w.alloc(0x30);
+        w.u64(header + 0x28, material_anim as u64);
+        w.name(material_anim, "eye");
+        let pattern_anims = w.alloc(0x20);
+        w.u64(material_anim + 8, pattern_anims as u64);
+        w.name(pattern_anims, "_a0");
+        w.name(pattern_anims + 0x10, "_n0");
+        w.u8(pattern_anims + 0x18, -1i8 as u8);
+        // Keys of integer curves are stored as integers
+        let keys: &[&[f32]] = &[&[f32::from_bits(0)], &[f32::from_bits(2)]];
+        let curves = w.curves(&[(CurveType::StepInt, 0, &[0., 5.], keys)]);
+        w.u64(material_anim + 0x10, curves as u64);
+        let base_values = w.alloc(4);
+        w.u16(base_values + 2, 1);
+        w.u64(material_anim + 0x18, base_values as u64);
+        w.u16(material_anim + 0x20, 2);
+        w.u16(material_anim + 0x22, 1);
+        w
+    }
+
+    #[test]
+    fn test_read_shader_param_anim() {
+        let w = build_shader_param_anim();
+        let mut bom = w.bom();
+        let material_anim = MaterialAnim::read(&mut w.reader(&mut bom), 0).unwrap();
+
+        assert_eq!(material_anim.get_name(), "color_anim");
+        assert_eq!(material_anim.frame_count, 10);
+        assert!(material_anim.is_looping());
+        assert_eq!(material_anim.get_bind_indices(), &[3]);
+        assert!(material_anim.get_texture_names().is_empty());
+
+        let data = &material_anim.get_material_anims()[0];
+        assert_eq!(data.get_name(), "body");
+        assert_eq!(data.get_param_anims()[0].get_name(), "albedo_color");
+        assert_eq!(data.get_curves().len(), 1);
+        assert_eq!(data.get_constants().len(), 2);
+
+        let samples = material_anim.sample(5.);
+        assert_eq!(
+            samples,
+            [MaterialAnimSample {
+                name: "body".to_string(),
+                params: vec![("albedo_color".to_string(), vec![0.5, 0.5, 1.])],
+                textures: vec![],
+                visible: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_texture_pattern_anim() {
+        let w = build_texture_pattern_anim();
+        let mut bom = w.bom();
+        let material_anim = MaterialAnim::read(&mut w.reader(&mut bom), 0).unwrap();
+
+        assert_eq!(material_anim.get_name(), "eye_anim");
+        assert!(!material_anim.is_looping());
+        assert_eq!(material_anim.get_bind_indices(), &[5]);
+        assert_eq!(
+            material_anim.get_texture_names(),
+            &["eye_open", "eye_half", "eye_closed"]
+        );
+
+        let data = &material_anim.get_material_anims()[0];
+        assert_eq!(data.get_name(), "eye");
+        let pattern_anims = data.get_pattern_anims();
+        assert_eq!(
+            (pattern_anims[0].curve_index, pattern_anims[1].curve_index),
+            (0, -1)
+        );
+
+        let texture = |frame: f32| material_anim.sample(frame).remove(0).textures;
+        let textures = |a0: &str, n0: &str| {
+            vec![
+                ("_a0".to_string(), a0.to_string()),
+                ("_n0".to_string(), n0.to_string()),
+            ]
+        };
+        assert_eq!(texture(0.), textures("eye_open", "eye_half"));
+        assert_eq!(texture(6.), textures("eye_closed", "eye_half"));
+    }
+}
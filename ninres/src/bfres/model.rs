@@ -0,0 +1,278 @@
+use super::{
+    read_relative_offset, read_string, read_wiiu_string, BfresReader, Material, Shape, Skeleton,
+    UserDataList, VertexBuffer,
+};
+use crate::{Error, ResDict};
+
+use byteorder::ByteOrder;
+
+/// FMDL section of a BFRES file.
+#[derive(Clone, Debug)]
+pub struct Model {
+    name: String,
+    path: String,
+    flags: u32,
+    skeleton: Skeleton,
+    vertex_buffers: Vec<VertexBuffer>,
+    shapes: Vec<Shape>,
+    shape_dict: ResDict,
+    materials: Vec<Material>,
+    material_dict: ResDict,
+    user_data: UserDataList,
+    total_process_vertex: u32,
+}
+
+impl Model {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FMDL") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let skeleton_offset = bom.read_u64()?;
+        let vertex_buffer_array_offset = bom.read_u64()?;
+        let shape_array_offset = bom.read_u64()?;
+        let shape_dict_offset = bom.read_u64()?;
+        let material_array_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x48);
+        let material_dict_offset = bom.read_u64()?;
+        let user_data_array_offset = bom.read_u64()?;
+        let user_data_dict_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x68);
+        let vertex_buffer_count = bom.read_u16()?;
+        let shape_count = bom.read_u16()?;
+        let material_count = bom.read_u16()?;
+        let user_data_count = bom.read_u16()?;
+        let total_process_vertex = bom.read_u32()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+        let shape_dict = ResDict::read(bom, reader.buffer, shape_dict_offset)?;
+        let material_dict = ResDict::read(bom, reader.buffer, material_dict_offset)?;
+        let skeleton = Skeleton::read(reader, skeleton_offset)?;
+        let user_data = UserDataList::read(
+            reader,
+            user_data_array_offset,
+            user_data_dict_offset,
+            user_data_count,
+        )?;
+
+        let mut vertex_buffers = Vec::with_capacity(vertex_buffer_count as usize);
+        for i in 0..vertex_buffer_count as u64 {
+            vertex_buffers.push(VertexBuffer::read(
+                reader,
+                vertex_buffer_array_offset + i * 0x58,
+            )?);
+        }
+
+        let mut shapes = Vec::with_capacity(shape_count as usize);
+        for i in 0..shape_count as u64 {
+            shapes.push(Shape::read(reader, shape_array_offset + i * 0x60)?);
+        }
+
+        let mut materials = Vec::with_capacity(material_count as usize);
+        for i in 0..material_count as u64 {
+            materials.push(Material::read(reader, material_array_offset + i * 0xa8)?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            skeleton,
+            vertex_buffers,
+            shapes,
+            shape_dict,
+            materials,
+            material_dict,
+            user_data,
+            total_process_vertex,
+        })
+    }
+
+    /// Read a model of a Wii U file.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FMDL") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let name_offset = read_relative_offset(bom)?;
+        let path_offset = read_relative_offset(bom)?;
+        let skeleton_offset = read_relative_offset(bom)?;
+        let vertex_buffer_array_offset = read_relative_offset(bom)?;
+        let shape_group_offset = read_relative_offset(bom)?;
+        let material_group_offset = read_relative_offset(bom)?;
+        let user_data_group_offset = read_relative_offset(bom)?;
+        let vertex_buffer_count = bom.read_u16()?;
+        let _shape_count = bom.read_u16()?;
+        let _material_count = bom.read_u16()?;
+        let _user_data_count = bom.read_u16()?;
+        let total_process_vertex = bom.read_u32()?;
+
+        let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+        let path = read_wiiu_string(bom, reader.buffer, path_offset)?;
+        let (shape_dict, shape_offsets) =
+            ResDict::read_index_group(bom, reader.buffer, shape_group_offset)?;
+        let (material_dict, material_offsets) =
+            ResDict::read_index_group(bom, reader.buffer, material_group_offset)?;
+        let skeleton = Skeleton::read_wiiu(reader, skeleton_offset)?;
+        let user_data = UserDataList::read_wiiu(reader, user_data_group_offset)?;
+
+        let mut vertex_buffers = Vec::with_capacity(vertex_buffer_count as usize);
+        for i in 0..vertex_buffer_count as u64 {
+            vertex_buffers.push(VertexBuffer::read_wiiu(
+                reader,
+                vertex_buffer_array_offset + i * 0x20,
+            )?);
+        }
+
+        let shapes = shape_offsets
+            .into_iter()
+            .map(|offset| Shape::read_wiiu(reader, offset))
+            .collect::<Result<_, _>>()?;
+        let materials = material_offsets
+            .into_iter()
+            .map(|offset| Material::read_wiiu(reader, offset))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name,
+            path,
+            flags: 0,
+            skeleton,
+            vertex_buffers,
+            shapes,
+            shape_dict,
+            materials,
+            material_dict,
+            user_data,
+            total_process_vertex,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn get_skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn get_skeleton_mut(&mut self) -> &mut Skeleton {
+        &mut self.skeleton
+    }
+
+    pub fn get_vertex_buffers(&self) -> &Vec<VertexBuffer> {
+        &self.vertex_buffers
+    }
+
+    pub fn get_shapes(&self) -> &Vec<Shape> {
+        &self.shapes
+    }
+
+    /// Total size of the vertex and index buffers of all shapes and levels of detail.
+    pub fn get_buffer_size(&self) -> u64 {
+        let vertex_size: usize = self
+            .vertex_buffers
+            .iter()
+            .flat_map(|vertex_buffer| vertex_buffer.get_buffers())
+            .map(|buffer| buffer.data.len())
+            .sum();
+        let index_size: u64 = self
+            .shapes
+            .iter()
+            .flat_map(|shape| shape.get_meshes())
+            .map(|mesh| mesh.get_buffer_size() as u64)
+            .sum();
+        vertex_size as u64 + index_size
+    }
+
+    pub fn get_shape(&self, name: &str) -> Option<&Shape> {
+        self.shapes.get(self.shape_dict.find(name)?)
+    }
+
+    pub fn get_materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+
+    pub fn get_material(&self, name: &str) -> Option<&Material> {
+        self.materials.get(self.material_dict.find(name)?)
+    }
+
+    pub fn get_material_mut(&mut self, name: &str) -> Option<&mut Material> {
+        self.materials.get_mut(self.material_dict.find(name)?)
+    }
+
+    pub fn get_user_data(&self) -> &UserDataList {
+        &self.user_data
+    }
+
+    pub fn get_total_process_vertex(&self) -> u32 {
+        self.total_process_vertex
+    }
+
+    /// Vertex buffer used by given shape.
+    pub fn get_shape_vertex_buffer(&self, shape: &Shape) -> Option<&VertexBuffer> {
+        self.vertex_buffers
+            .get(shape.get_vertex_buffer_index() as usize)
+    }
+
+    /// Write editable values of the skeleton and materials back into the model at `offset`.
+    pub(crate) fn write<B: ByteOrder>(&self, data: &mut [u8], offset: usize) {
+        let skeleton_offset = B::read_u64(&data[offset + 0x18..]) as usize;
+        self.skeleton.write::<B>(data, skeleton_offset);
+        let material_array_offset = B::read_u64(&data[offset + 0x38..]) as usize;
+        for (i, material) in self.materials.iter().enumerate() {
+            material.write::<B>(data, material_array_offset + i * 0xa8);
+        }
+    }
+
+    /// Append the index buffers of all shapes to `data`, where the GPU buffer data starts at
+    /// `buffer_offset`, and point the shapes of the model at `offset` to them.
+    pub(crate) fn write_index_buffers<B: ByteOrder>(
+        &self,
+        data: &mut Vec<u8>,
+        offset: usize,
+        buffer_offset: usize,
+    ) {
+        let shape_array_offset = B::read_u64(&data[offset + 0x28..]) as usize;
+        for (i, shape) in self.shapes.iter().enumerate() {
+            shape.write_index_buffers::<B>(data, shape_array_offset + i * 0x60, buffer_offset);
+        }
+    }
+
+    /// Append the vertex buffers to `data`, where the GPU buffer data starts at
+    /// `buffer_offset`, and point the vertex buffers of the model at `offset` to them.
+    pub(crate) fn write_vertex_buffers<B: ByteOrder>(
+        &self,
+        data: &mut Vec<u8>,
+        offset: usize,
+        buffer_offset: usize,
+    ) {
+        let vertex_buffer_array_offset = B::read_u64(&data[offset + 0x20..]) as usize;
+        for (i, vertex_buffer) in self.vertex_buffers.iter().enumerate() {
+            vertex_buffer.write_buffers::<B>(
+                data,
+                vertex_buffer_array_offset + i * 0x58,
+                buffer_offset,
+            );
+        }
+    }
+
+    /// Material used by given shape.
+    pub fn get_shape_material(&self, shape: &Shape) -> Option<&Material> {
+        self.materials.get(shape.get_material_index() as usize)
+    }
+}
@@ -0,0 +1,148 @@
+//! Wavefront OBJ export.
+//!
+//! See http://paulbourke.net/dataformats/obj/
+
+use super::Model;
+
+use std::fmt::Write;
+
+/// Wavefront OBJ file and its MTL material library.
+#[derive(Clone, Debug, Default)]
+pub struct WavefrontObj {
+    pub obj: String,
+    pub mtl: String,
+}
+
+impl Model {
+    /// Export the most detailed level of detail of all shapes as Wavefront OBJ.
+    ///
+    /// `mtl_name` is the file name of the material library referenced by the OBJ file.
+    /// `texture_path` maps texture names to the path of the extracted image,
+    /// which will then be referenced by the material library.
+    ///
+    /// Positions and normals are transformed into model space in the rest pose of the skeleton.
+    pub fn to_obj<F>(&self, mtl_name: &str, texture_path: F) -> WavefrontObj
+    where
+        F: Fn(&str) -> String,
+    {
+        let mut obj = String::new();
+        let mut mtl = String::new();
+
+        writeln!(obj, "# {}", self.get_name()).unwrap();
+        writeln!(obj, "mtllib {}", mtl_name).unwrap();
+
+        for material in self.get_materials().iter() {
+            writeln!(mtl, "newmtl {}", material.get_name()).unwrap();
+            writeln!(mtl, "Ka 1.000000 1.000000 1.000000").unwrap();
+            writeln!(mtl, "Kd 1.000000 1.000000 1.000000").unwrap();
+            writeln!(mtl, "Ks 0.000000 0.000000 0.000000").unwrap();
+            writeln!(mtl, "d 1.000000").unwrap();
+            writeln!(mtl, "illum 1").unwrap();
+            if let Some(texture) = material
+                .get_sampler_texture("_a0")
+                .or_else(|| material.get_texture_names().first())
+            {
+                writeln!(mtl, "map_Kd {}", texture_path(texture)).unwrap();
+            }
+            if let Some(texture) = material.get_sampler_texture("_n0") {
+                writeln!(mtl, "map_Bump {}", texture_path(texture)).unwrap();
+            }
+            writeln!(mtl).unwrap();
+        }
+
+        let (mut position_offset, mut uv_offset, mut normal_offset) = (1, 1, 1);
+        for shape in self.get_shapes().iter() {
+            let mesh = if let Some(mesh) = shape.get_meshes().first() {
+                mesh
+            } else {
+                continue;
+            };
+            let vertex_buffer = if let Some(vertex_buffer) = self.get_shape_vertex_buffer(shape) {
+                vertex_buffer
+            } else {
+                continue;
+            };
+            let positions = if let Some(positions) = self.get_model_space_positions(shape) {
+                positions
+            } else {
+                continue;
+            };
+            let uvs = vertex_buffer.decode_attribute("_u0");
+            let normals = self.get_model_space_normals(shape);
+
+            writeln!(obj, "o {}", shape.get_name()).unwrap();
+            for [x, y, z] in positions.iter() {
+                writeln!(obj, "v {:.6} {:.6} {:.6}", x, y, z).unwrap();
+            }
+            if let Some(uvs) = &uvs {
+                for [u, v, _, _] in uvs.iter() {
+                    writeln!(obj, "vt {:.6} {:.6}", u, 1. - v).unwrap();
+                }
+            }
+            if let Some(normals) = &normals {
+                for [x, y, z] in normals.iter() {
+                    writeln!(obj, "vn {:.6} {:.6} {:.6}", x, y, z).unwrap();
+                }
+            }
+            if let Some(material) = self.get_shape_material(shape) {
+                writeln!(obj, "usemtl {}", material.get_name()).unwrap();
+            }
+            writeln!(obj, "s 1").unwrap();
+
+            for triangle in mesh.get_triangles() {
+                if triangle.iter().any(|&i| i as usize >= positions.len()) {
+                    continue;
+                }
+                obj.push('f');
+                for i in triangle.iter() {
+                    let i = *i as usize;
+                    match (&uvs, &normals) {
+                        (Some(_), Some(_)) => write!(
+                            obj,
+                            " {}/{}/{}",
+                            position_offset + i,
+                            uv_offset + i,
+                            normal_offset + i
+                        ),
+                        (Some(_), None) => {
+                            write!(obj, " {}/{}", position_offset + i, uv_offset + i)
+                        }
+                        (None, Some(_)) => {
+                            write!(obj, " {}//{}", position_offset + i, normal_offset + i)
+                        }
+                        (None, None) => write!(obj, " {}", position_offset + i),
+                    }
+                    .unwrap();
+                }
+                obj.push('\n');
+            }
+
+            position_offset += positions.len();
+            uv_offset += uvs.map(|uvs| uvs.len()).unwrap_or_default();
+            normal_offset += normals.map(|normals| normals.len()).unwrap_or_default();
+        }
+
+        WavefrontObj { obj, mtl }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Bfres;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_model_to_obj(bfres_file: &[u8]) {
+        let bfres_file = Bfres::new(bfres_file).unwrap();
+
+        for model in bfres_file.get_models().iter() {
+            let wavefront = model.to_obj("model.mtl", |texture| format!("{}.png", texture));
+
+            assert!(wavefront.obj.contains("mtllib model.mtl"));
+            assert!(wavefront.obj.lines().any(|line| line.starts_with("f ")));
+            assert!(wavefront.mtl.contains("map_Kd wait.0.png"));
+        }
+    }
+}
@@ -0,0 +1,648 @@
+use super::{apply_curves, read_string, wrap_frame, AnimCurve, BfresReader};
+use crate::{ByteOrderMark, Error};
+
+/// FSCN section containing camera, light and fog animations.
+#[derive(Clone, Debug)]
+pub struct SceneAnim {
+    name: String,
+    path: String,
+    camera_anims: Vec<CameraAnim>,
+    light_anims: Vec<LightAnim>,
+    fog_anims: Vec<FogAnim>,
+}
+
+/// FCAM section containing a camera animation.
+#[derive(Clone, Debug)]
+pub struct CameraAnim {
+    name: String,
+    flags: u16,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    base: CameraAnimData,
+    curves: Vec<AnimCurve>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraAnimData {
+    pub clip_near: f32,
+    pub clip_far: f32,
+    pub aspect_ratio: f32,
+    /// Field of view in radians for perspective projection,
+    /// otherwise the height of the view.
+    pub field_of_view: f32,
+    pub position: [f32; 3],
+    /// Euler angles or the aim target, depending on the rotation mode.
+    pub rotation: [f32; 3],
+    pub twist: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraRotationMode {
+    Aim,
+    EulerZXY,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraProjectionMode {
+    Orthographic,
+    Perspective,
+}
+
+/// FLIT section containing a light animation.
+#[derive(Clone, Debug)]
+pub struct LightAnim {
+    name: String,
+    flags: u16,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    light_type: String,
+    distance_attn_func: String,
+    angle_attn_func: String,
+    base: LightAnimData,
+    curves: Vec<AnimCurve>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LightAnimData {
+    pub enable: bool,
+    pub position: [f32; 3],
+    /// Direction for directional lights, otherwise the aim target.
+    pub rotation: [f32; 3],
+    pub distance_attn: [f32; 2],
+    pub angle_attn: [f32; 2],
+    pub color0: [f32; 3],
+    pub color1: [f32; 3],
+}
+
+/// FFOG section containing a fog animation.
+#[derive(Clone, Debug)]
+pub struct FogAnim {
+    name: String,
+    flags: u16,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    distance_attn_func: String,
+    base: FogAnimData,
+    curves: Vec<AnimCurve>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FogAnimData {
+    pub distance_attn: [f32; 2],
+    pub color: [f32; 3],
+}
+
+fn read_curves(
+    reader: &mut BfresReader,
+    offset: u64,
+    count: usize,
+) -> Result<Vec<AnimCurve>, Error> {
+    let mut curves = Vec::with_capacity(count);
+    for i in 0..count as u64 {
+        curves.push(AnimCurve::read(reader, offset + i * AnimCurve::SIZE)?);
+    }
+    Ok(curves)
+}
+
+fn read_vec<const N: usize>(bom: &mut ByteOrderMark) -> Result<[f32; N], Error> {
+    let mut values = [0.; N];
+    for value in values.iter_mut() {
+        *value = bom.read_f32()?;
+    }
+    Ok(values)
+}
+
+fn read_optional_string(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+) -> Result<String, Error> {
+    if offset == 0 {
+        Ok(String::new())
+    } else {
+        read_string(bom, buffer, offset)
+    }
+}
+
+impl SceneAnim {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSCN") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 8);
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let camera_anim_array_offset = bom.read_u64()?;
+        let _camera_anim_dict_offset = bom.read_u64()?;
+        let light_anim_array_offset = bom.read_u64()?;
+        let _light_anim_dict_offset = bom.read_u64()?;
+        let fog_anim_array_offset = bom.read_u64()?;
+        let _fog_anim_dict_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let _user_data_count = bom.read_u16()?;
+        let camera_anim_count = bom.read_u16()?;
+        let light_anim_count = bom.read_u16()?;
+        let fog_anim_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+
+        let mut camera_anims = Vec::with_capacity(camera_anim_count as usize);
+        for i in 0..camera_anim_count as u64 {
+            camera_anims.push(CameraAnim::read(
+                reader,
+                camera_anim_array_offset + i * 0x40,
+            )?);
+        }
+        let mut light_anims = Vec::with_capacity(light_anim_count as usize);
+        for i in 0..light_anim_count as u64 {
+            light_anims.push(LightAnim::read(reader, light_anim_array_offset + i * 0x58)?);
+        }
+        let mut fog_anims = Vec::with_capacity(fog_anim_count as usize);
+        for i in 0..fog_anim_count as u64 {
+            fog_anims.push(FogAnim::read(reader, fog_anim_array_offset + i * 0x48)?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            camera_anims,
+            light_anims,
+            fog_anims,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn get_camera_anims(&self) -> &Vec<CameraAnim> {
+        &self.camera_anims
+    }
+
+    pub fn get_light_anims(&self) -> &Vec<LightAnim> {
+        &self.light_anims
+    }
+
+    pub fn get_fog_anims(&self) -> &Vec<FogAnim> {
+        &self.fog_anims
+    }
+}
+
+impl CameraAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FCAM") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u16()?;
+        bom.set_position(offset + 8);
+        let frame_count = bom.read_i32()?;
+        let curve_count = bom.read_u8()?;
+        bom.set_position(offset + 0x10);
+        let baked_size = bom.read_u32()?;
+        bom.set_position(offset + 0x18);
+        let name_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_data_offset = bom.read_u64()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        bom.set_position(base_data_offset);
+        let base = CameraAnimData::from_values(read_vec::<11>(bom)?);
+        let curves = read_curves(reader, curve_array_offset, curve_count as usize)?;
+
+        Ok(Self {
+            name,
+            flags,
+            frame_count,
+            baked_size,
+            base,
+            curves,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    pub fn get_rotation_mode(&self) -> CameraRotationMode {
+        if self.flags & (1 << 8) != 0 {
+            CameraRotationMode::EulerZXY
+        } else {
+            CameraRotationMode::Aim
+        }
+    }
+
+    pub fn get_projection_mode(&self) -> CameraProjectionMode {
+        if self.flags & (1 << 10) != 0 {
+            CameraProjectionMode::Perspective
+        } else {
+            CameraProjectionMode::Orthographic
+        }
+    }
+
+    pub fn get_base(&self) -> &CameraAnimData {
+        &self.base
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Evaluate the camera at given frame.
+    pub fn sample(&self, frame: f32) -> CameraAnimData {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        let mut values = self.base.to_values();
+        apply_curves(&mut values, &self.curves, frame);
+        CameraAnimData::from_values(values)
+    }
+}
+
+impl CameraAnimData {
+    fn from_values(values: [f32; 11]) -> Self {
+        Self {
+            clip_near: values[0],
+            clip_far: values[1],
+            aspect_ratio: values[2],
+            field_of_view: values[3],
+            position: [values[4], values[5], values[6]],
+            rotation: [values[7], values[8], values[9]],
+            twist: values[10],
+        }
+    }
+
+    fn to_values(self) -> [f32; 11] {
+        [
+            self.clip_near,
+            self.clip_far,
+            self.aspect_ratio,
+            self.field_of_view,
+            self.position[0],
+            self.position[1],
+            self.position[2],
+            self.rotation[0],
+            self.rotation[1],
+            self.rotation[2],
+            self.twist,
+        ]
+    }
+}
+
+const LIGHT_BASE_ENABLE: u16 = 1 << 8;
+const LIGHT_BASE_POSITION: u16 = 1 << 9;
+const LIGHT_BASE_ROTATION: u16 = 1 << 10;
+const LIGHT_BASE_DISTANCE_ATTN: u16 = 1 << 11;
+const LIGHT_BASE_ANGLE_ATTN: u16 = 1 << 12;
+const LIGHT_BASE_COLOR0: u16 = 1 << 13;
+const LIGHT_BASE_COLOR1: u16 = 1 << 14;
+
+impl LightAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FLIT") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u16()?;
+        bom.set_position(offset + 8);
+        let frame_count = bom.read_i32()?;
+        let curve_count = bom.read_u8()?;
+        bom.set_position(offset + 0x10);
+        let baked_size = bom.read_u32()?;
+        bom.set_position(offset + 0x18);
+        let name_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_data_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let light_type_offset = bom.read_u64()?;
+        let distance_attn_func_offset = bom.read_u64()?;
+        let angle_attn_func_offset = bom.read_u64()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let light_type = read_optional_string(bom, reader.buffer, light_type_offset)?;
+        let distance_attn_func =
+            read_optional_string(bom, reader.buffer, distance_attn_func_offset)?;
+        let angle_attn_func = read_optional_string(bom, reader.buffer, angle_attn_func_offset)?;
+
+        // Base data only contains values, which are flagged
+        let mut base = LightAnimData::default();
+        bom.set_position(base_data_offset);
+        if flags & LIGHT_BASE_ENABLE != 0 {
+            base.enable = bom.read_i32()? != 0;
+        }
+        if flags & LIGHT_BASE_POSITION != 0 {
+            base.position = read_vec(bom)?;
+        }
+        if flags & LIGHT_BASE_ROTATION != 0 {
+            base.rotation = read_vec(bom)?;
+        }
+        if flags & LIGHT_BASE_DISTANCE_ATTN != 0 {
+            base.distance_attn = read_vec(bom)?;
+        }
+        if flags & LIGHT_BASE_ANGLE_ATTN != 0 {
+            base.angle_attn = read_vec(bom)?;
+        }
+        if flags & LIGHT_BASE_COLOR0 != 0 {
+            base.color0 = read_vec(bom)?;
+        }
+        if flags & LIGHT_BASE_COLOR1 != 0 {
+            base.color1 = read_vec(bom)?;
+        }
+        let curves = read_curves(reader, curve_array_offset, curve_count as usize)?;
+
+        Ok(Self {
+            name,
+            flags,
+            frame_count,
+            baked_size,
+            light_type,
+            distance_attn_func,
+            angle_attn_func,
+            base,
+            curves,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    pub fn get_light_type(&self) -> &String {
+        &self.light_type
+    }
+
+    pub fn get_distance_attn_func(&self) -> &String {
+        &self.distance_attn_func
+    }
+
+    pub fn get_angle_attn_func(&self) -> &String {
+        &self.angle_attn_func
+    }
+
+    pub fn get_base(&self) -> &LightAnimData {
+        &self.base
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Evaluate the light at given frame.
+    pub fn sample(&self, frame: f32) -> LightAnimData {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        let mut values = self.base.to_values();
+        apply_curves(&mut values, &self.curves, frame);
+        LightAnimData::from_values(values)
+    }
+}
+
+impl LightAnimData {
+    fn from_values(values: [f32; 17]) -> Self {
+        Self {
+            enable: values[0] != 0.,
+            position: [values[1], values[2], values[3]],
+            rotation: [values[4], values[5], values[6]],
+            distance_attn: [values[7], values[8]],
+            angle_attn: [values[9], values[10]],
+            color0: [values[11], values[12], values[13]],
+            color1: [values[14], values[15], values[16]],
+        }
+    }
+
+    fn to_values(self) -> [f32; 17] {
+        [
+            if self.enable { 1. } else { 0. },
+            self.position[0],
+            self.position[1],
+            self.position[2],
+            self.rotation[0],
+            self.rotation[1],
+            self.rotation[2],
+            self.distance_attn[0],
+            self.distance_attn[1],
+            self.angle_attn[0],
+            self.angle_attn[1],
+            self.color0[0],
+            self.color0[1],
+            self.color0[2],
+            self.color1[0],
+            self.color1[1],
+            self.color1[2],
+        ]
+    }
+}
+
+impl FogAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FFOG") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u16()?;
+        bom.set_position(offset + 8);
+        let frame_count = bom.read_i32()?;
+        let curve_count = bom.read_u8()?;
+        bom.set_position(offset + 0x10);
+        let baked_size = bom.read_u32()?;
+        bom.set_position(offset + 0x18);
+        let name_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_data_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let distance_attn_func_offset = bom.read_u64()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let distance_attn_func =
+            read_optional_string(bom, reader.buffer, distance_attn_func_offset)?;
+
+        bom.set_position(base_data_offset);
+        let base = FogAnimData {
+            distance_attn: read_vec(bom)?,
+            color: read_vec(bom)?,
+        };
+        let curves = read_curves(reader, curve_array_offset, curve_count as usize)?;
+
+        Ok(Self {
+            name,
+            flags,
+            frame_count,
+            baked_size,
+            distance_attn_func,
+            base,
+            curves,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    pub fn get_distance_attn_func(&self) -> &String {
+        &self.distance_attn_func
+    }
+
+    pub fn get_base(&self) -> &FogAnimData {
+        &self.base
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Evaluate the fog at given frame.
+    pub fn sample(&self, frame: f32) -> FogAnimData {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        let mut values = [
+            self.base.distance_attn[0],
+            self.base.distance_attn[1],
+            self.base.color[0],
+            self.base.color[1],
+            self.base.color[2],
+        ];
+        apply_curves(&mut values, &self.curves, frame);
+        FogAnimData {
+            distance_attn: [values[0], values[1]],
+            color: [values[2], values[3], values[4]],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfres::{test_support::Writer, CurveType};
+
+    fn build_scene_anim() -> Writer {
+        let mut w = Writer::default();
+        let header = w.alloc(0x60);
+        w.data[header..header + 4].copy_from_slice(b"FSCN");
+        w.name(header + 8, "demo");
+        w.name(header + 0x10, "");
+        w.u16(header + 0x5a, 1);
+        w.u16(header + 0x5c, 1);
+        w.u16(header + 0x5e, 1);
+
+        let camera = w.alloc(0x40);
+        w.u64(header + 0x18, camera as u64);
+        w.data[camera..camera + 4].copy_from_slice(b"FCAM");
+        w.u16(camera + 4, 1 << 8 | 1 << 10);
+        w.u32(camera + 8, 20);
+        w.u8(camera + 0xc, 1);
+        w.name(camera + 0x18, "camera");
+        // Animate the x coordinate of the position
+        let curves = w.curves(&[(
+            CurveType::Linear,
+            0x10,
+            &[0., 20.],
+            &[&[0., 10.], &[10., 0.]],
+        )]);
+        w.u64(camera + 0x20, curves as u64);
+        let base = w.alloc(11 * 4);
+        w.f32s(base, &[1., 1000., 1.5, 0.8, 0., 5., 10., 0., 0.5, 0., 0.]);
+        w.u64(camera + 0x28, base as u64);
+
+        let light = w.alloc(0x58);
+        w.u64(header + 0x28, light as u64);
+        w.data[light..light + 4].copy_from_slice(b"FLIT");
+        w.u16(light + 4, LIGHT_BASE_ENABLE | LIGHT_BASE_COLOR0);
+        w.u32(light + 8, 4);
+        w.u8(light + 0xc, 1);
+        w.name(light + 0x18, "sun");
+        // Step the red channel of the first color
+        let curves = w.curves(&[(CurveType::BakedFloat, 0x2c, &[0., 2.], &[&[0.5], &[1.]])]);
+        w.u64(light + 0x20, curves as u64);
+        let base = w.alloc(4 * 4);
+        w.u32(base, 1);
+        w.f32s(base + 4, &[0.25, 0.5, 0.75]);
+        w.u64(light + 0x28, base as u64);
+        w.name(light + 0x38, "Directional");
+        w.name(light + 0x48, "angle_attn");
+
+        let fog = w.alloc(0x48);
+        w.u64(header + 0x38, fog as u64);
+        w.data[fog..fog + 4].copy_from_slice(b"FFOG");
+        w.u32(fog + 8, 1);
+        w.name(fog + 0x18, "mist");
+        let base = w.alloc(5 * 4);
+        w.f32s(base, &[10., 100., 1., 1., 1.]);
+        w.u64(fog + 0x28, base as u64);
+        w.name(fog + 0x38, "linear");
+        w
+    }
+
+    #[test]
+    fn test_read_scene_anim() {
+        let w = build_scene_anim();
+        let mut bom = w.bom();
+        let scene_anim = SceneAnim::read(&mut w.reader(&mut bom), 0).unwrap();
+        assert_eq!(scene_anim.get_name(), "demo");
+
+        let camera = &scene_anim.get_camera_anims()[0];
+        assert_eq!(camera.get_name(), "camera");
+        assert_eq!(camera.frame_count, 20);
+        assert_eq!(camera.get_rotation_mode(), CameraRotationMode::EulerZXY);
+        assert_eq!(
+            camera.get_projection_mode(),
+            CameraProjectionMode::Perspective
+        );
+        assert_eq!(camera.get_base().clip_far, 1000.);
+        assert_eq!(camera.get_base().rotation, [0., 0.5, 0.]);
+        let sample = camera.sample(10.);
+        assert_eq!(sample.position, [5., 5., 10.]);
+        assert_eq!(sample.field_of_view, 0.8);
+        // Frames are clamped, as the animation doesn't loop
+        assert_eq!(camera.sample(30.).position, [10., 5., 10.]);
+
+        let light = &scene_anim.get_light_anims()[0];
+        assert_eq!(light.get_name(), "sun");
+        assert_eq!(light.get_light_type(), "Directional");
+        assert_eq!(light.get_distance_attn_func(), "");
+        assert_eq!(light.get_angle_attn_func(), "angle_attn");
+        assert!(light.get_base().enable);
+        assert_eq!(light.get_base().color0, [0.25, 0.5, 0.75]);
+        assert_eq!(light.get_base().position, [0.; 3]);
+        assert_eq!(light.sample(1.).color0, [0.5, 0.5, 0.75]);
+        assert_eq!(light.sample(3.).color0, [1., 0.5, 0.75]);
+        assert!(light.sample(3.).enable);
+
+        let fog = &scene_anim.get_fog_anims()[0];
+        assert_eq!(fog.get_name(), "mist");
+        assert_eq!(fog.get_distance_attn_func(), "linear");
+        assert!(fog.get_curves().is_empty());
+        assert_eq!(fog.sample(0.).distance_attn, [10., 100.]);
+        assert_eq!(fog.sample(0.).color, [1.; 3]);
+    }
+}
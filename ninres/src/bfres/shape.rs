@@ -0,0 +1,638 @@
+use super::{
+    read_gx2_buffer, read_relative_offset, read_string, read_wiiu_string, writer::align,
+    BfresReader,
+};
+use crate::Error;
+
+use byteorder::{ByteOrder, BE, LE};
+use std::cmp;
+
+/// FSHP section describing a polygon shape of a model.
+#[derive(Clone, Debug)]
+pub struct Shape {
+    name: String,
+    flags: u32,
+    index: u16,
+    material_index: u16,
+    bone_index: u16,
+    vertex_buffer_index: u16,
+    skin_bone_indices: Vec<u16>,
+    vertex_skin_count: u8,
+    meshes: Vec<Mesh>,
+    bounding_boxes: Vec<Bounding>,
+    radius: Vec<f32>,
+    target_attrib_count: u8,
+}
+
+/// A single level of detail of a shape.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct Mesh {
+    primitive_type: PrimitiveType,
+    index_format: IndexFormat,
+    first_vertex: u32,
+    sub_meshes: Vec<SubMesh>,
+    buffer_offset: u64,
+    buffer_size: u32,
+    #[derivative(Debug = "ignore")]
+    indices: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubMesh {
+    pub offset: u32,
+    pub count: u32,
+}
+
+/// Axis aligned bounding box given by center and half extent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounding {
+    pub center: [f32; 3],
+    pub extent: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveType {
+    Points,
+    Lines,
+    LineStrip,
+    Triangles,
+    TriangleStrip,
+    LinesAdjacency,
+    LineStripAdjacency,
+    TrianglesAdjacency,
+    TriangleStripAdjacency,
+    Patches,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    UInt8,
+    UInt16,
+    UInt32,
+}
+
+impl PrimitiveType {
+    fn try_new(value: u32) -> Result<Self, Error> {
+        Ok(match value {
+            0 => Self::Points,
+            1 => Self::Lines,
+            2 => Self::LineStrip,
+            3 => Self::Triangles,
+            4 => Self::TriangleStrip,
+            5 => Self::LinesAdjacency,
+            6 => Self::LineStripAdjacency,
+            7 => Self::TrianglesAdjacency,
+            8 => Self::TriangleStripAdjacency,
+            9 => Self::Patches,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    /// Convert a `GX2PrimitiveMode` of a Wii U file.
+    fn try_from_gx2(value: u32) -> Result<Self, Error> {
+        Ok(match value {
+            0x01 => Self::Points,
+            0x02 => Self::Lines,
+            0x03 => Self::LineStrip,
+            0x04 => Self::Triangles,
+            0x06 => Self::TriangleStrip,
+            0x0a => Self::LinesAdjacency,
+            0x0b => Self::LineStripAdjacency,
+            0x0c => Self::TrianglesAdjacency,
+            0x0d => Self::TriangleStripAdjacency,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+}
+
+impl IndexFormat {
+    fn try_new(value: u32) -> Result<Self, Error> {
+        Ok(match value {
+            0 => Self::UInt8,
+            1 => Self::UInt16,
+            2 => Self::UInt32,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    pub fn get_size(&self) -> u32 {
+        match self {
+            Self::UInt8 => 1,
+            Self::UInt16 => 2,
+            Self::UInt32 => 4,
+        }
+    }
+}
+
+impl Shape {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSHP") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let _vertex_buffer_offset = bom.read_u64()?;
+        let mesh_array_offset = bom.read_u64()?;
+        let skin_bone_index_array_offset = bom.read_u64()?;
+        let _key_shape_array_offset = bom.read_u64()?;
+        let _key_shape_dict_offset = bom.read_u64()?;
+        let bounding_box_array_offset = bom.read_u64()?;
+        let radius_array_offset = bom.read_u64()?;
+        let _user_pointer = bom.read_u64()?;
+        let index = bom.read_u16()?;
+        let material_index = bom.read_u16()?;
+        let bone_index = bom.read_u16()?;
+        let vertex_buffer_index = bom.read_u16()?;
+        let skin_bone_index_count = bom.read_u16()?;
+        let vertex_skin_count = bom.read_u8()?;
+        let mesh_count = bom.read_u8()?;
+        let _key_shape_count = bom.read_u8()?;
+        let target_attrib_count = bom.read_u8()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut meshes = Vec::with_capacity(mesh_count as usize);
+        for i in 0..mesh_count as u64 {
+            meshes.push(Mesh::read(reader, mesh_array_offset + i * 0x38)?);
+        }
+
+        let bom = &mut reader.bom;
+        let mut skin_bone_indices = Vec::with_capacity(skin_bone_index_count as usize);
+        bom.set_position(skin_bone_index_array_offset);
+        for _ in 0..skin_bone_index_count {
+            skin_bone_indices.push(bom.read_u16()?);
+        }
+
+        let bounding_count = meshes.iter().map(|m| m.sub_meshes.len() + 1).sum::<usize>();
+        let mut bounding_boxes = Vec::with_capacity(bounding_count);
+        bom.set_position(bounding_box_array_offset);
+        for _ in 0..bounding_count {
+            let center = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            let extent = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            bounding_boxes.push(Bounding { center, extent });
+        }
+
+        let mut radius = Vec::with_capacity(mesh_count as usize);
+        bom.set_position(radius_array_offset);
+        for _ in 0..mesh_count {
+            radius.push(bom.read_f32()?);
+        }
+
+        Ok(Self {
+            name,
+            flags,
+            index,
+            material_index,
+            bone_index,
+            vertex_buffer_index,
+            skin_bone_indices,
+            vertex_skin_count,
+            meshes,
+            bounding_boxes,
+            radius,
+            target_attrib_count,
+        })
+    }
+
+    /// Read a shape of a Wii U file.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSHP") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let name_offset = read_relative_offset(bom)?;
+        let flags = bom.read_u32()?;
+        let index = bom.read_u16()?;
+        let material_index = bom.read_u16()?;
+        let bone_index = bom.read_u16()?;
+        let vertex_buffer_index = bom.read_u16()?;
+        let skin_bone_index_count = bom.read_u16()?;
+        let vertex_skin_count = bom.read_u8()?;
+        let mesh_count = bom.read_u8()?;
+        let _key_shape_count = bom.read_u8()?;
+        let target_attrib_count = bom.read_u8()?;
+        let bounding_node_count = bom.read_u16()?;
+        // Since version 4.5 every mesh has its own radius
+        let radius_array_offset = if reader.version_number >= 0x0405_0000 {
+            read_relative_offset(bom)?
+        } else {
+            offset + 0x1c
+        };
+        let radius_count = if reader.version_number >= 0x0405_0000 {
+            mesh_count
+        } else {
+            1
+        };
+        bom.set_position(offset + 0x20);
+        let _vertex_buffer_offset = read_relative_offset(bom)?;
+        let mesh_array_offset = read_relative_offset(bom)?;
+        let skin_bone_index_array_offset = read_relative_offset(bom)?;
+        let _key_shape_group_offset = read_relative_offset(bom)?;
+        let bounding_box_array_offset = if bounding_node_count == 0 {
+            read_relative_offset(bom)?
+        } else {
+            bom.set_position(offset + 0x34);
+            read_relative_offset(bom)?
+        };
+
+        let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+
+        let mut meshes = Vec::with_capacity(mesh_count as usize);
+        for i in 0..mesh_count as u64 {
+            meshes.push(Mesh::read_wiiu(reader, mesh_array_offset + i * 0x1c)?);
+        }
+
+        let bom = &mut reader.bom;
+        let mut skin_bone_indices = Vec::with_capacity(skin_bone_index_count as usize);
+        bom.set_position(skin_bone_index_array_offset);
+        for _ in 0..skin_bone_index_count {
+            skin_bone_indices.push(bom.read_u16()?);
+        }
+
+        // Only the most detailed mesh has bounding boxes
+        let bounding_count = if bounding_node_count == 0 {
+            meshes.first().map_or(0, |m| m.sub_meshes.len() + 1)
+        } else {
+            bounding_node_count as usize
+        };
+        let mut bounding_boxes = Vec::with_capacity(bounding_count);
+        if bounding_box_array_offset != 0 {
+            bom.set_position(bounding_box_array_offset);
+            for _ in 0..bounding_count {
+                let center = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+                let extent = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+                bounding_boxes.push(Bounding { center, extent });
+            }
+        }
+
+        let mut radius = Vec::with_capacity(radius_count as usize);
+        bom.set_position(radius_array_offset);
+        for _ in 0..radius_count {
+            radius.push(bom.read_f32()?);
+        }
+
+        Ok(Self {
+            name,
+            flags,
+            index,
+            material_index,
+            bone_index,
+            vertex_buffer_index,
+            skin_bone_indices,
+            vertex_skin_count,
+            meshes,
+            bounding_boxes,
+            radius,
+            target_attrib_count,
+        })
+    }
+
+    /// Append the index buffers of all meshes to `data`, where the GPU buffer data starts at
+    /// `buffer_offset`, and point the meshes of the shape at `offset` to them.
+    pub(crate) fn write_index_buffers<B: ByteOrder>(
+        &self,
+        data: &mut Vec<u8>,
+        offset: usize,
+        buffer_offset: usize,
+    ) {
+        let mesh_array_offset = B::read_u64(&data[offset + 0x18..]) as usize;
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            mesh.write_index_buffer::<B>(data, mesh_array_offset + i * 0x38, buffer_offset);
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn get_material_index(&self) -> u16 {
+        self.material_index
+    }
+
+    pub fn get_bone_index(&self) -> u16 {
+        self.bone_index
+    }
+
+    pub fn get_vertex_buffer_index(&self) -> u16 {
+        self.vertex_buffer_index
+    }
+
+    pub fn get_skin_bone_indices(&self) -> &Vec<u16> {
+        &self.skin_bone_indices
+    }
+
+    pub fn get_vertex_skin_count(&self) -> u8 {
+        self.vertex_skin_count
+    }
+
+    /// All levels of detail, starting with the most detailed one.
+    pub fn get_meshes(&self) -> &Vec<Mesh> {
+        &self.meshes
+    }
+
+    /// Bounding boxes of all sub meshes.
+    /// Each mesh is followed by the bounding box of the whole mesh.
+    pub fn get_bounding_boxes(&self) -> &Vec<Bounding> {
+        &self.bounding_boxes
+    }
+
+    pub fn get_radius(&self) -> &Vec<f32> {
+        &self.radius
+    }
+
+    /// Number of vertex attributes affected by key shapes.
+    pub fn get_target_attrib_count(&self) -> u8 {
+        self.target_attrib_count
+    }
+}
+
+impl Mesh {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let BfresReader {
+            bom,
+            buffer,
+            buffer_info,
+            ..
+        } = reader;
+        bom.set_position(offset);
+        let sub_mesh_array_offset = bom.read_u64()?;
+        let _memory_pool_offset = bom.read_u64()?;
+        let _buffer_offset = bom.read_u64()?;
+        let buffer_size_offset = bom.read_u64()?;
+        let index_buffer_offset = bom.read_u32()?;
+        let primitive_type = PrimitiveType::try_new(bom.read_u32()?)?;
+        let index_format = IndexFormat::try_new(bom.read_u32()?)?;
+        let index_count = bom.read_u32()?;
+        let first_vertex = bom.read_u32()?;
+        let sub_mesh_count = bom.read_u16()?;
+
+        let mut sub_meshes = Vec::with_capacity(sub_mesh_count as usize);
+        bom.set_position(sub_mesh_array_offset);
+        for _ in 0..sub_mesh_count {
+            let offset = bom.read_u32()?;
+            let count = bom.read_u32()?;
+            sub_meshes.push(SubMesh { offset, count });
+        }
+
+        bom.set_position(buffer_size_offset);
+        let size = bom.read_u32()?;
+        let data_offset = buffer_info
+            .get_offset()
+            .checked_add(index_buffer_offset as u64)
+            .ok_or(Error::CorruptData)?;
+        let data_end = data_offset
+            .checked_add(size as u64)
+            .ok_or(Error::CorruptData)?;
+        if !buffer_info.contains(data_offset, size as u64)
+            || (buffer.len() as u64) < data_end
+            || (size as u64) < index_count as u64 * index_format.get_size() as u64
+        {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(data_offset);
+        let mut indices = Vec::with_capacity(index_count as usize);
+        for _ in 0..index_count {
+            indices.push(match index_format {
+                IndexFormat::UInt8 => bom.read_u8()? as u32,
+                IndexFormat::UInt16 => bom.read_u16()? as u32,
+                IndexFormat::UInt32 => bom.read_u32()?,
+            });
+        }
+
+        Ok(Self {
+            primitive_type,
+            index_format,
+            first_vertex,
+            sub_meshes,
+            buffer_offset: data_offset,
+            buffer_size: size,
+            indices,
+        })
+    }
+
+    /// Read a mesh of a Wii U file, whose index buffer is stored inline.
+    fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let BfresReader { bom, buffer, .. } = reader;
+        bom.set_position(offset);
+        let primitive_type = PrimitiveType::try_from_gx2(bom.read_u32()?)?;
+        // GX2IndexFormat, which also specifies the byte order of the indices
+        let (index_format, big_endian) = match bom.read_u32()? {
+            0 => (IndexFormat::UInt16, false),
+            1 => (IndexFormat::UInt32, false),
+            4 => (IndexFormat::UInt16, true),
+            9 => (IndexFormat::UInt32, true),
+            _ => return Err(Error::CorruptData),
+        };
+        let index_count = bom.read_u32()?;
+        let sub_mesh_count = bom.read_u16()?;
+        bom.set_position(offset + 0x10);
+        let sub_mesh_array_offset = read_relative_offset(bom)?;
+        let index_buffer_offset = read_relative_offset(bom)?;
+        let first_vertex = bom.read_u32()?;
+
+        let mut sub_meshes = Vec::with_capacity(sub_mesh_count as usize);
+        bom.set_position(sub_mesh_array_offset);
+        for _ in 0..sub_mesh_count {
+            let offset = bom.read_u32()?;
+            let count = bom.read_u32()?;
+            sub_meshes.push(SubMesh { offset, count });
+        }
+
+        let (_, buffer_offset, data) = read_gx2_buffer(bom, buffer, index_buffer_offset)?;
+        let size = index_format.get_size() as usize;
+        if data.len() < index_count as usize * size {
+            return Err(Error::CorruptData);
+        }
+        let indices = data
+            .chunks_exact(size)
+            .take(index_count as usize)
+            .map(|index| match (index_format, big_endian) {
+                (IndexFormat::UInt16, true) => BE::read_u16(index) as u32,
+                (IndexFormat::UInt16, false) => LE::read_u16(index) as u32,
+                (_, true) => BE::read_u32(index),
+                (_, false) => LE::read_u32(index),
+            })
+            .collect();
+
+        Ok(Self {
+            primitive_type,
+            index_format,
+            first_vertex,
+            sub_meshes,
+            buffer_offset,
+            buffer_size: data.len() as u32,
+            indices,
+        })
+    }
+
+    /// Append the index buffer to `data`, where the GPU buffer data starts at `buffer_offset`,
+    /// and point the mesh at `offset` to it.
+    fn write_index_buffer<B: ByteOrder>(
+        &self,
+        data: &mut Vec<u8>,
+        offset: usize,
+        buffer_offset: usize,
+    ) {
+        let buffer_size_offset = B::read_u64(&data[offset + 0x18..]) as usize;
+        let position = align(data.len(), 8);
+        B::write_u32(
+            &mut data[offset + 0x20..],
+            (position - buffer_offset) as u32,
+        );
+        let index_size = self.index_format.get_size() as usize;
+        let size = cmp::max(self.buffer_size as usize, self.indices.len() * index_size);
+        B::write_u32(&mut data[buffer_size_offset..], size as u32);
+        data.resize(position + size, 0);
+        for (index, chunk) in self
+            .indices
+            .iter()
+            .zip(data[position..].chunks_exact_mut(index_size))
+        {
+            match self.index_format {
+                IndexFormat::UInt8 => chunk[0] = *index as u8,
+                IndexFormat::UInt16 => B::write_u16(chunk, *index as u16),
+                IndexFormat::UInt32 => B::write_u32(chunk, *index),
+            }
+        }
+    }
+
+    pub fn get_primitive_type(&self) -> PrimitiveType {
+        self.primitive_type
+    }
+
+    pub fn get_index_format(&self) -> IndexFormat {
+        self.index_format
+    }
+
+    pub fn get_first_vertex(&self) -> u32 {
+        self.first_vertex
+    }
+
+    pub fn get_sub_meshes(&self) -> &Vec<SubMesh> {
+        &self.sub_meshes
+    }
+
+    /// Offset of the index buffer within the file.
+    pub fn get_buffer_offset(&self) -> u64 {
+        self.buffer_offset
+    }
+
+    /// Size of the index buffer in bytes.
+    pub fn get_buffer_size(&self) -> u32 {
+        self.buffer_size
+    }
+
+    pub fn get_indices(&self) -> &Vec<u32> {
+        &self.indices
+    }
+
+    /// Triangle list of this mesh with `first_vertex` already applied.
+    /// Triangle strips are converted into lists.
+    pub fn get_triangles(&self) -> Vec<[u32; 3]> {
+        let triangles: Vec<[u32; 3]> = match self.primitive_type {
+            PrimitiveType::Triangles => self
+                .indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            PrimitiveType::TriangleStrip => self
+                .indices
+                .windows(3)
+                .enumerate()
+                .filter(|(_, t)| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+                .map(|(i, t)| {
+                    if i % 2 == 0 {
+                        [t[0], t[1], t[2]]
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+                .collect(),
+            _ => vec![],
+        };
+        triangles
+            .into_iter()
+            .map(|t| t.map(|i| i + self.first_vertex))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfres::{test_support::Writer, BufferInfo};
+
+    /// Mesh with three 16 bit indices, whose buffer has given size.
+    fn build_mesh(buffer_size: u32) -> (Writer, usize, usize) {
+        let mut w = Writer::default();
+        let buffer_info = w.alloc(0x10);
+        let mesh = w.alloc(0x38);
+        let sub_meshes = w.alloc(8);
+        let size = w.alloc(4);
+        let data = w.alloc(8);
+        w.u32(buffer_info + 4, 8);
+        w.u64(buffer_info + 8, data as u64);
+        w.u64(mesh, sub_meshes as u64);
+        w.u64(mesh + 0x18, size as u64);
+        w.u32(mesh + 0x24, 3);
+        w.u32(mesh + 0x28, 1);
+        w.u32(mesh + 0x2c, 3);
+        w.u16(mesh + 0x34, 1);
+        w.u32(sub_meshes + 4, 3);
+        w.u32(size, buffer_size);
+        for (i, index) in [0, 2, 1].iter().enumerate() {
+            w.u16(data + i * 2, *index);
+        }
+        (w, buffer_info, mesh)
+    }
+
+    fn read_mesh(buffer_size: u32) -> Result<Mesh, Error> {
+        let (w, buffer_info, mesh) = build_mesh(buffer_size);
+        let mut bom = w.bom();
+        let buffer_info = BufferInfo::read(&mut bom, &w.data, buffer_info as u64).unwrap();
+        let mut reader = w.reader(&mut bom);
+        reader.buffer_info = buffer_info;
+        Mesh::read(&mut reader, mesh as u64)
+    }
+
+    #[test]
+    fn test_read_mesh() {
+        let mesh = read_mesh(6).unwrap();
+        assert_eq!(mesh.get_primitive_type(), PrimitiveType::Triangles);
+        assert_eq!(mesh.get_index_format(), IndexFormat::UInt16);
+        assert_eq!(
+            mesh.get_sub_meshes(),
+            &[SubMesh {
+                offset: 0,
+                count: 3
+            }]
+        );
+        assert_eq!(mesh.get_indices(), &[0, 2, 1]);
+    }
+
+    #[test]
+    fn test_read_mesh_out_of_buffer() {
+        assert!(matches!(read_mesh(0x10), Err(Error::CorruptData)));
+        assert!(matches!(read_mesh(u32::MAX), Err(Error::CorruptData)));
+
+        let (mut w, buffer_info, _) = build_mesh(6);
+        w.u64(buffer_info + 8, u64::MAX);
+        let mut bom = w.bom();
+        assert!(matches!(
+            BufferInfo::read(&mut bom, &w.data, buffer_info as u64),
+            Err(Error::CorruptData)
+        ));
+    }
+}
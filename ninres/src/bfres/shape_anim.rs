@@ -0,0 +1,288 @@
+use super::{read_string, wrap_frame, AnimCurve, BfresReader};
+use crate::Error;
+
+use std::convert::TryFrom;
+
+/// FSHA section containing a shape animation, which morphs
+/// vertices between the key shapes of a shape.
+#[derive(Clone, Debug)]
+pub struct ShapeAnim {
+    name: String,
+    path: String,
+    flags: u32,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    bind_indices: Vec<u16>,
+    vertex_shape_anims: Vec<VertexShapeAnim>,
+}
+
+/// Animation of the key shape weights of a single shape.
+#[derive(Clone, Debug)]
+pub struct VertexShapeAnim {
+    name: String,
+    key_shape_anims: Vec<KeyShapeAnim>,
+    base_values: Vec<f32>,
+    curves: Vec<AnimCurve>,
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyShapeAnim {
+    name: String,
+    pub curve_index: i8,
+    pub sub_bind_index: i8,
+}
+
+impl ShapeAnim {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSHA") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_model_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let vertex_shape_anim_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let baked_size = bom.read_u32()?;
+        let _user_data_count = bom.read_u16()?;
+        let vertex_shape_anim_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+
+        let mut bind_indices = Vec::with_capacity(vertex_shape_anim_count as usize);
+        bom.set_position(bind_index_array_offset);
+        for _ in 0..vertex_shape_anim_count {
+            bind_indices.push(bom.read_u16()?);
+        }
+
+        let mut vertex_shape_anims = Vec::with_capacity(vertex_shape_anim_count as usize);
+        for i in 0..vertex_shape_anim_count as u64 {
+            vertex_shape_anims.push(VertexShapeAnim::read(
+                reader,
+                vertex_shape_anim_array_offset + i * 0x30,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            vertex_shape_anims,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    /// Indices of the animated shapes in the bound model.
+    pub fn get_bind_indices(&self) -> &Vec<u16> {
+        &self.bind_indices
+    }
+
+    pub fn get_vertex_shape_anims(&self) -> &Vec<VertexShapeAnim> {
+        &self.vertex_shape_anims
+    }
+
+    /// Evaluate the key shape weights of all animated shapes at given frame.
+    pub fn sample(&self, frame: f32) -> Vec<Vec<f32>> {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        self.vertex_shape_anims
+            .iter()
+            .map(|vertex_shape_anim| vertex_shape_anim.sample(frame))
+            .collect()
+    }
+}
+
+impl VertexShapeAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let key_shape_anim_array_offset = bom.read_u64()?;
+        let base_value_array_offset = bom.read_u64()?;
+        let curve_count = bom.read_u16()?;
+        let key_shape_anim_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut key_shape_anims = Vec::with_capacity(key_shape_anim_count as usize);
+        for i in 0..key_shape_anim_count as u64 {
+            bom.set_position(key_shape_anim_array_offset + i * 0x10);
+            let curve_index = bom.read_i8()?;
+            let sub_bind_index = bom.read_i8()?;
+            bom.set_position(key_shape_anim_array_offset + i * 0x10 + 8);
+            let name_offset = bom.read_u64()?;
+            key_shape_anims.push(KeyShapeAnim {
+                name: read_string(bom, reader.buffer, name_offset)?,
+                curve_index,
+                sub_bind_index,
+            });
+        }
+
+        // The first key shape is the base shape, which has no weight
+        let mut base_values = Vec::with_capacity(key_shape_anim_count as usize);
+        bom.set_position(base_value_array_offset);
+        for _ in 1..key_shape_anim_count {
+            base_values.push(bom.read_f32()?);
+        }
+
+        let mut curves = Vec::with_capacity(curve_count as usize);
+        for i in 0..curve_count as u64 {
+            curves.push(AnimCurve::read(
+                reader,
+                curve_array_offset + i * AnimCurve::SIZE,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            key_shape_anims,
+            base_values,
+            curves,
+        })
+    }
+
+    /// Name of the animated shape.
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_key_shape_anims(&self) -> &Vec<KeyShapeAnim> {
+        &self.key_shape_anims
+    }
+
+    /// Weights of all key shapes except the base shape, which are used if there are no curves.
+    pub fn get_base_values(&self) -> &Vec<f32> {
+        &self.base_values
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Evaluate the weight of every key shape at given frame.
+    /// The base shape has a weight of zero.
+    pub fn sample(&self, frame: f32) -> Vec<f32> {
+        self.key_shape_anims
+            .iter()
+            .enumerate()
+            .map(|(i, key_shape_anim)| {
+                if let Some(curve) = usize::try_from(key_shape_anim.curve_index)
+                    .ok()
+                    .and_then(|curve_index| self.curves.get(curve_index))
+                {
+                    curve.evaluate(frame)
+                } else if i > 0 {
+                    self.base_values.get(i - 1).copied().unwrap_or_default()
+                } else {
+                    0.
+                }
+            })
+            .collect()
+    }
+}
+
+impl KeyShapeAnim {
+    /// Name of the animated key shape.
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfres::{test_support::Writer, CurveType};
+
+    fn build_shape_anim() -> Writer {
+        let mut w = Writer::default();
+        let header = w.alloc(0x50);
+        w.data[header..header + 4].copy_from_slice(b"FSHA");
+        w.u32(header + 4, 1 << 2);
+        w.name(header + 8, "face_morph");
+        w.name(header + 0x10, "");
+        let bind_indices = w.alloc(2);
+        w.u16(bind_indices, 3);
+        w.u64(header + 0x20, bind_indices as u64);
+        w.u32(header + 0x40, 10);
+        w.u16(header + 0x4a, 1);
+
+        let vertex_shape_anim = w.alloc(0x30);
+        w.u64(header + 0x28, vertex_shape_anim as u64);
+        w.name(vertex_shape_anim, "face");
+        let curves = w.curves(&[(CurveType::Linear, 0, &[0., 10.], &[&[0., 1.], &[1., 0.]])]);
+        w.u64(vertex_shape_anim + 8, curves as u64);
+        let key_shape_anims = w.alloc(0x30);
+        w.u64(vertex_shape_anim + 0x10, key_shape_anims as u64);
+        for (i, (name, curve_index)) in [("base", -1), ("smile", 0), ("blink", -1)]
+            .iter()
+            .enumerate()
+        {
+            let at = key_shape_anims + i * 0x10;
+            w.u8(at, *curve_index as u8);
+            w.u8(at + 1, i as u8);
+            w.name(at + 8, name);
+        }
+        let base_values = w.alloc(8);
+        w.f32s(base_values, &[0.5, 0.25]);
+        w.u64(vertex_shape_anim + 0x18, base_values as u64);
+        w.u16(vertex_shape_anim + 0x20, 1);
+        w.u16(vertex_shape_anim + 0x22, 3);
+        w
+    }
+
+    #[test]
+    fn test_read_shape_anim() {
+        let w = build_shape_anim();
+        let mut bom = w.bom();
+        let shape_anim = ShapeAnim::read(&mut w.reader(&mut bom), 0).unwrap();
+
+        assert_eq!(shape_anim.get_name(), "face_morph");
+        assert_eq!(shape_anim.frame_count, 10);
+        assert!(shape_anim.is_looping());
+        assert!(!shape_anim.is_baked());
+        assert_eq!(shape_anim.get_bind_indices(), &[3]);
+
+        let vertex_shape_anim = &shape_anim.get_vertex_shape_anims()[0];
+        assert_eq!(vertex_shape_anim.get_name(), "face");
+        assert_eq!(vertex_shape_anim.get_base_values(), &[0.5, 0.25]);
+        let key_shape_anims = vertex_shape_anim.get_key_shape_anims();
+        assert_eq!(key_shape_anims[1].get_name(), "smile");
+        assert_eq!(
+            (
+                key_shape_anims[1].curve_index,
+                key_shape_anims[1].sub_bind_index
+            ),
+            (0, 1)
+        );
+        assert_eq!(vertex_shape_anim.get_curves().len(), 1);
+
+        assert_eq!(shape_anim.sample(0.), [vec![0., 0., 0.25]]);
+        assert_eq!(shape_anim.sample(5.), [vec![0., 0.5, 0.25]]);
+        // Looping animations wrap around
+        assert_eq!(shape_anim.sample(12.5), [vec![0., 0.25, 0.25]]);
+    }
+}
@@ -0,0 +1,276 @@
+use super::{read_string, wrap_frame, AnimCurve, BfresReader, RotationMode, ScalingMode};
+use crate::Error;
+
+/// FSKA section containing a skeletal animation.
+#[derive(Clone, Debug)]
+pub struct SkeletalAnim {
+    name: String,
+    path: String,
+    flags: u32,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    bind_indices: Vec<u16>,
+    bone_anims: Vec<BoneAnim>,
+}
+
+/// Animation of a single bone.
+#[derive(Clone, Debug)]
+pub struct BoneAnim {
+    name: String,
+    flags: u32,
+    begin_curve: u32,
+    base: BoneTransform,
+    curves: Vec<AnimCurve>,
+}
+
+/// Scale, rotation and translation of a bone.
+///
+/// Depending on the rotation mode of the animation,
+/// rotation is either a quaternion or euler angles in radians.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoneTransform {
+    pub scale: [f32; 3],
+    pub rotation: [f32; 4],
+    pub translation: [f32; 3],
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        Self {
+            scale: [1., 1., 1.],
+            rotation: [0., 0., 0., 1.],
+            translation: [0., 0., 0.],
+        }
+    }
+}
+
+const BASE_SCALE: u32 = 1 << 3;
+const BASE_ROTATE: u32 = 1 << 4;
+const BASE_TRANSLATE: u32 = 1 << 5;
+
+impl SkeletalAnim {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSKA") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_skeleton_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let bone_anim_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let _curve_count = bom.read_u32()?;
+        let baked_size = bom.read_u32()?;
+        let bone_anim_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+
+        let mut bind_indices = Vec::with_capacity(bone_anim_count as usize);
+        bom.set_position(bind_index_array_offset);
+        for _ in 0..bone_anim_count {
+            bind_indices.push(bom.read_u16()?);
+        }
+
+        let mut bone_anims = Vec::with_capacity(bone_anim_count as usize);
+        for i in 0..bone_anim_count as u64 {
+            bone_anims.push(BoneAnim::read(reader, bone_anim_array_offset + i * 0x38)?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            bone_anims,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    pub fn get_rotation_mode(&self) -> RotationMode {
+        if self.flags & 0x7000 == 0x1000 {
+            RotationMode::EulerXYZ
+        } else {
+            RotationMode::Quaternion
+        }
+    }
+
+    pub fn get_scaling_mode(&self) -> ScalingMode {
+        match self.flags & 0x300 {
+            0x100 => ScalingMode::Standard,
+            0x200 => ScalingMode::Maya,
+            0x300 => ScalingMode::Softimage,
+            _ => ScalingMode::None,
+        }
+    }
+
+    /// Indices of the animated bones in the bound skeleton.
+    /// `0xffff` means that the animation is not yet bound.
+    pub fn get_bind_indices(&self) -> &Vec<u16> {
+        &self.bind_indices
+    }
+
+    pub fn get_bone_anims(&self) -> &Vec<BoneAnim> {
+        &self.bone_anims
+    }
+
+    /// Evaluate the transforms of all animated bones at given frame.
+    ///
+    /// Looping animations wrap around, otherwise the frame is clamped.
+    pub fn sample(&self, frame: f32) -> Vec<BoneTransform> {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        self.bone_anims
+            .iter()
+            .map(|bone_anim| bone_anim.sample(frame))
+            .collect()
+    }
+}
+
+impl BoneAnim {
+    fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_data_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x28);
+        let flags = bom.read_u32()?;
+        let _begin_rotate = bom.read_u8()?;
+        let _begin_translate = bom.read_u8()?;
+        let curve_count = bom.read_u8()?;
+        let _begin_base_translate = bom.read_u8()?;
+        let begin_curve = bom.read_u32()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        let mut base = BoneTransform::default();
+        bom.set_position(base_data_offset);
+        if flags & BASE_SCALE != 0 {
+            for value in base.scale.iter_mut() {
+                *value = bom.read_f32()?;
+            }
+        }
+        if flags & BASE_ROTATE != 0 {
+            for value in base.rotation.iter_mut() {
+                *value = bom.read_f32()?;
+            }
+        }
+        if flags & BASE_TRANSLATE != 0 {
+            for value in base.translation.iter_mut() {
+                *value = bom.read_f32()?;
+            }
+        }
+
+        let mut curves = Vec::with_capacity(curve_count as usize);
+        for i in 0..curve_count as u64 {
+            curves.push(AnimCurve::read(
+                reader,
+                curve_array_offset + i * AnimCurve::SIZE,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            flags,
+            begin_curve,
+            base,
+            curves,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Index of the first curve of this bone within the animation.
+    pub fn get_begin_curve(&self) -> u32 {
+        self.begin_curve
+    }
+
+    /// Transform of the bone, which is used for all values without curves.
+    pub fn get_base(&self) -> &BoneTransform {
+        &self.base
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Evaluate the bone transform at given frame.
+    pub fn sample(&self, frame: f32) -> BoneTransform {
+        let mut transform = self.base;
+        for curve in self.curves.iter() {
+            let value = curve.evaluate(frame);
+            match curve.anim_data_offset {
+                0x04 => transform.scale[0] = value,
+                0x08 => transform.scale[1] = value,
+                0x0c => transform.scale[2] = value,
+                0x10 => transform.translation[0] = value,
+                0x14 => transform.translation[1] = value,
+                0x18 => transform.translation[2] = value,
+                0x20 => transform.rotation[0] = value,
+                0x24 => transform.rotation[1] = value,
+                0x28 => transform.rotation[2] = value,
+                0x2c => transform.rotation[3] = value,
+                _ => {}
+            }
+        }
+        transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Bfres;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_sample_skeletal_anim(bfres_file: &[u8]) {
+        let bfres_file = Bfres::new(bfres_file).unwrap();
+        let skeletal_anims = bfres_file.get_skeletal_anims();
+        assert!(!skeletal_anims.is_empty());
+
+        for skeletal_anim in skeletal_anims.iter() {
+            assert!(skeletal_anim.frame_count > 0);
+            let frame_count = skeletal_anim.frame_count as f32;
+            for frame in [0., frame_count / 2., frame_count] {
+                let transforms = skeletal_anim.sample(frame);
+                assert_eq!(transforms.len(), skeletal_anim.get_bone_anims().len());
+                assert!(transforms.iter().all(|transform| transform
+                    .scale
+                    .iter()
+                    .chain(transform.rotation.iter())
+                    .chain(transform.translation.iter())
+                    .all(|value| value.is_finite())));
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use super::{read_relative_offset, read_string, read_wiiu_string, BfresReader, UserDataList};
+use crate::{Error, ResDict};
+
+use byteorder::ByteOrder;
+
+/// FSKL section of a model.
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+    flags: u32,
+    bone_dict: ResDict,
+    bones: Vec<Bone>,
+    matrix_to_bone_list: Vec<u16>,
+    inverse_model_matrices: Vec<[[f32; 4]; 3]>,
+    smooth_matrix_count: u16,
+    rigid_matrix_count: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bone {
+    name: String,
+    index: u16,
+    parent_index: u16,
+    smooth_matrix_index: i16,
+    rigid_matrix_index: i16,
+    billboard_index: u16,
+    flags: u32,
+    pub scale: [f32; 3],
+    pub rotation: [f32; 4],
+    pub translation: [f32; 3],
+    user_data: UserDataList,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationMode {
+    Quaternion,
+    EulerXYZ,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    None,
+    Standard,
+    Maya,
+    Softimage,
+}
+
+impl Skeleton {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let BfresReader { bom, buffer, .. } = reader;
+        if buffer.get(offset as usize..offset as usize + 4) != Some(b"FSKL") {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let bone_dict_offset = bom.read_u64()?;
+        let bone_array_offset = bom.read_u64()?;
+        let matrix_to_bone_list_offset = bom.read_u64()?;
+        let inverse_model_matrices_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x38);
+        let bone_count = bom.read_u16()?;
+        let smooth_matrix_count = bom.read_u16()?;
+        let rigid_matrix_count = bom.read_u16()?;
+
+        let bone_dict = ResDict::read(bom, buffer, bone_dict_offset)?;
+        let mut bones = Vec::with_capacity(bone_count as usize);
+        let mut user_data_offsets = Vec::with_capacity(bone_count as usize);
+        for i in 0..bone_count as u64 {
+            bom.set_position(bone_array_offset + i * 0x60);
+            let name_offset = bom.read_u64()?;
+            let user_data_array_offset = bom.read_u64()?;
+            let user_data_dict_offset = bom.read_u64()?;
+            bom.set_position(bone_array_offset + i * 0x60 + 0x28);
+            let index = bom.read_u16()?;
+            let parent_index = bom.read_u16()?;
+            let smooth_matrix_index = bom.read_i16()?;
+            let rigid_matrix_index = bom.read_i16()?;
+            let billboard_index = bom.read_u16()?;
+            let user_data_count = bom.read_u16()?;
+            let flags = bom.read_u32()?;
+            let scale = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            let rotation = [
+                bom.read_f32()?,
+                bom.read_f32()?,
+                bom.read_f32()?,
+                bom.read_f32()?,
+            ];
+            let translation = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            user_data_offsets.push((
+                user_data_array_offset,
+                user_data_dict_offset,
+                user_data_count,
+            ));
+            bones.push(Bone {
+                name: read_string(bom, buffer, name_offset)?,
+                index,
+                parent_index,
+                smooth_matrix_index,
+                rigid_matrix_index,
+                billboard_index,
+                flags,
+                scale,
+                rotation,
+                translation,
+                user_data: UserDataList::default(),
+            });
+        }
+
+        let matrix_count = smooth_matrix_count as u64 + rigid_matrix_count as u64;
+        let mut matrix_to_bone_list = Vec::with_capacity(matrix_count as usize);
+        if matrix_to_bone_list_offset != 0 {
+            bom.set_position(matrix_to_bone_list_offset);
+            for _ in 0..matrix_count {
+                matrix_to_bone_list.push(bom.read_u16()?);
+            }
+        }
+
+        let mut inverse_model_matrices = Vec::with_capacity(smooth_matrix_count as usize);
+        if inverse_model_matrices_offset != 0 {
+            bom.set_position(inverse_model_matrices_offset);
+            for _ in 0..smooth_matrix_count {
+                let mut matrix = [[0.; 4]; 3];
+                for row in matrix.iter_mut() {
+                    for value in row.iter_mut() {
+                        *value = bom.read_f32()?;
+                    }
+                }
+                inverse_model_matrices.push(matrix);
+            }
+        }
+
+        for (bone, (array_offset, dict_offset, count)) in bones.iter_mut().zip(user_data_offsets) {
+            bone.user_data = UserDataList::read(reader, array_offset, dict_offset, count)?;
+        }
+
+        Ok(Self {
+            flags,
+            bone_dict,
+            bones,
+            matrix_to_bone_list,
+            inverse_model_matrices,
+            smooth_matrix_count,
+            rigid_matrix_count,
+        })
+    }
+
+    /// Read a skeleton of a Wii U file.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FSKL") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let bone_count = bom.read_u16()?;
+        let smooth_matrix_count = bom.read_u16()?;
+        let rigid_matrix_count = bom.read_u16()?;
+        bom.set_position(offset + 0x10);
+        let bone_group_offset = read_relative_offset(bom)?;
+        let _bone_array_offset = read_relative_offset(bom)?;
+        let matrix_to_bone_list_offset = read_relative_offset(bom)?;
+        // Only present since version 3.4
+        let inverse_model_matrices_offset = if reader.version_number >= 0x0304_0000 {
+            read_relative_offset(bom)?
+        } else {
+            0
+        };
+
+        let (bone_dict, bone_offsets) =
+            ResDict::read_index_group(bom, reader.buffer, bone_group_offset)?;
+        if bone_offsets.len() != bone_count as usize {
+            return Err(Error::CorruptData);
+        }
+        let mut bones = Vec::with_capacity(bone_count as usize);
+        for bone_offset in bone_offsets {
+            let bom = &mut reader.bom;
+            bom.set_position(bone_offset);
+            let name_offset = read_relative_offset(bom)?;
+            let index = bom.read_u16()?;
+            let parent_index = bom.read_u16()?;
+            let smooth_matrix_index = bom.read_i16()?;
+            let rigid_matrix_index = bom.read_i16()?;
+            let billboard_index = bom.read_u16()?;
+            let _user_data_count = bom.read_u16()?;
+            let flags = bom.read_u32()?;
+            let scale = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            let rotation = [
+                bom.read_f32()?,
+                bom.read_f32()?,
+                bom.read_f32()?,
+                bom.read_f32()?,
+            ];
+            let translation = [bom.read_f32()?, bom.read_f32()?, bom.read_f32()?];
+            let user_data_group_offset = read_relative_offset(bom)?;
+            let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+            bones.push(Bone {
+                name,
+                index,
+                parent_index,
+                smooth_matrix_index,
+                rigid_matrix_index,
+                billboard_index,
+                flags,
+                scale,
+                rotation,
+                translation,
+                user_data: UserDataList::read_wiiu(reader, user_data_group_offset)?,
+            });
+        }
+
+        let bom = &mut reader.bom;
+        let matrix_count = smooth_matrix_count as u64 + rigid_matrix_count as u64;
+        let mut matrix_to_bone_list = Vec::with_capacity(matrix_count as usize);
+        if matrix_to_bone_list_offset != 0 {
+            bom.set_position(matrix_to_bone_list_offset);
+            for _ in 0..matrix_count {
+                matrix_to_bone_list.push(bom.read_u16()?);
+            }
+        }
+
+        let mut inverse_model_matrices = Vec::with_capacity(smooth_matrix_count as usize);
+        if inverse_model_matrices_offset != 0 {
+            bom.set_position(inverse_model_matrices_offset);
+            for _ in 0..smooth_matrix_count {
+                let mut matrix = [[0.; 4]; 3];
+                for row in matrix.iter_mut() {
+                    for value in row.iter_mut() {
+                        *value = bom.read_f32()?;
+                    }
+                }
+                inverse_model_matrices.push(matrix);
+            }
+        }
+
+        Ok(Self {
+            flags,
+            bone_dict,
+            bones,
+            matrix_to_bone_list,
+            inverse_model_matrices,
+            smooth_matrix_count,
+            rigid_matrix_count,
+        })
+    }
+
+    pub fn get_bones(&self) -> &Vec<Bone> {
+        &self.bones
+    }
+
+    pub fn get_bone(&self, name: &str) -> Option<&Bone> {
+        self.bones.get(self.bone_dict.find(name)?)
+    }
+
+    pub fn get_bone_mut(&mut self, name: &str) -> Option<&mut Bone> {
+        self.bones.get_mut(self.bone_dict.find(name)?)
+    }
+
+    /// Write the bone transforms back into the skeleton at `offset`.
+    pub(crate) fn write<B: ByteOrder>(&self, data: &mut [u8], offset: usize) {
+        let bone_array_offset = B::read_u64(&data[offset + 0x10..]) as usize;
+        for (i, bone) in self.bones.iter().enumerate() {
+            let position = bone_array_offset + i * 0x60 + 0x38;
+            let values = bone
+                .scale
+                .iter()
+                .chain(&bone.rotation)
+                .chain(&bone.translation);
+            for (value, chunk) in values.zip(data[position..position + 0x28].chunks_exact_mut(4)) {
+                B::write_f32(chunk, *value);
+            }
+        }
+    }
+
+    /// Maps smooth and rigid matrix indices to bone indices.
+    /// Smooth matrices come first, followed by rigid matrices.
+    pub fn get_matrix_to_bone_list(&self) -> &Vec<u16> {
+        &self.matrix_to_bone_list
+    }
+
+    /// Inverse bind matrices of all smooth skinned bones as row major 3x4 matrices.
+    pub fn get_inverse_model_matrices(&self) -> &Vec<[[f32; 4]; 3]> {
+        &self.inverse_model_matrices
+    }
+
+    pub fn get_smooth_matrix_count(&self) -> u16 {
+        self.smooth_matrix_count
+    }
+
+    pub fn get_rigid_matrix_count(&self) -> u16 {
+        self.rigid_matrix_count
+    }
+
+    pub fn get_rotation_mode(&self) -> RotationMode {
+        if self.flags & 0x7000 == 0x1000 {
+            RotationMode::EulerXYZ
+        } else {
+            RotationMode::Quaternion
+        }
+    }
+
+    pub fn get_scaling_mode(&self) -> ScalingMode {
+        match self.flags & 0x300 {
+            0x100 => ScalingMode::Standard,
+            0x200 => ScalingMode::Maya,
+            0x300 => ScalingMode::Softimage,
+            _ => ScalingMode::None,
+        }
+    }
+}
+
+impl Bone {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_index(&self) -> u16 {
+        self.index
+    }
+
+    /// Index of the parent bone or `None` for root bones.
+    pub fn get_parent_index(&self) -> Option<u16> {
+        if self.parent_index == 0xffff {
+            None
+        } else {
+            Some(self.parent_index)
+        }
+    }
+
+    pub fn get_smooth_matrix_index(&self) -> Option<u16> {
+        if self.smooth_matrix_index < 0 {
+            None
+        } else {
+            Some(self.smooth_matrix_index as u16)
+        }
+    }
+
+    pub fn get_rigid_matrix_index(&self) -> Option<u16> {
+        if self.rigid_matrix_index < 0 {
+            None
+        } else {
+            Some(self.rigid_matrix_index as u16)
+        }
+    }
+
+    pub fn get_billboard_index(&self) -> u16 {
+        self.billboard_index
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn get_user_data(&self) -> &UserDataList {
+        &self.user_data
+    }
+}
@@ -0,0 +1,301 @@
+//! Resolution of vertex skinning and rest pose transforms.
+//!
+//! Matrices are row major 3x4 affine transforms, like the inverse model matrices of a skeleton.
+
+use super::{Bone, Model, RotationMode, Shape, Skeleton, VertexBuffer};
+
+const IDENTITY: [[f32; 4]; 3] = [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.]];
+
+/// Bones influencing a vertex with their weights, which add up to one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexSkin {
+    pub bone_indices: Vec<u16>,
+    pub weights: Vec<f32>,
+}
+
+fn multiply(a: &[[f32; 4]; 3], b: &[[f32; 4]; 3]) -> [[f32; 4]; 3] {
+    let mut res = [[0.; 4]; 3];
+    for (row, res_row) in a.iter().zip(res.iter_mut()) {
+        for (column, value) in res_row.iter_mut().enumerate() {
+            *value = (0..3).map(|i| row[i] * b[i][column]).sum();
+        }
+        res_row[3] += row[3];
+    }
+    res
+}
+
+fn transform_point(matrix: &[[f32; 4]; 3], point: [f32; 3]) -> [f32; 3] {
+    let [x, y, z] = transform_vector(matrix, point);
+    [x + matrix[0][3], y + matrix[1][3], z + matrix[2][3]]
+}
+
+fn transform_vector(matrix: &[[f32; 4]; 3], vector: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| (0..3).map(|i| matrix[row][i] * vector[i]).sum())
+}
+
+fn transform_normal(matrix: &[[f32; 4]; 3], normal: [f32; 3]) -> [f32; 3] {
+    let normal = transform_vector(matrix, normal);
+    let length = normal.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if length > 0. {
+        normal.map(|value| value / length)
+    } else {
+        normal
+    }
+}
+
+/// Resolve the bones and normalized weights of a vertex with given matrix indices and weights.
+/// Vertices without weights are fully bound to their first matrix.
+fn resolve_skin(
+    indices: &[f32],
+    weights: Option<&[f32]>,
+    matrix_to_bone_list: &[u16],
+) -> VertexSkin {
+    let mut skin = VertexSkin::default();
+    for (i, &index) in indices.iter().enumerate() {
+        let weight = match weights {
+            Some(weights) => weights.get(i).copied().unwrap_or_default(),
+            None if i == 0 => 1.,
+            None => 0.,
+        };
+        let bone_index = match matrix_to_bone_list.get(index as usize) {
+            Some(&bone_index) if weight > 0. => bone_index,
+            _ => continue,
+        };
+        match skin.bone_indices.iter().position(|&i| i == bone_index) {
+            Some(i) => skin.weights[i] += weight,
+            None => {
+                skin.bone_indices.push(bone_index);
+                skin.weights.push(weight);
+            }
+        }
+    }
+    let total: f32 = skin.weights.iter().sum();
+    if total > 0. {
+        skin.weights.iter_mut().for_each(|weight| *weight /= total);
+    }
+    skin
+}
+
+/// Decode the first `count` components of the attributes `{prefix}0`, `{prefix}1`,
+/// each of which holds up to four influences per vertex.
+fn decode_influences(
+    vertex_buffer: &VertexBuffer,
+    prefix: &str,
+    count: usize,
+) -> Option<Vec<Vec<f32>>> {
+    let mut influences = vec![vec![]; vertex_buffer.get_vertex_count() as usize];
+    for set in 0..count.div_ceil(4) {
+        let values = vertex_buffer.decode_attribute(&format!("{}{}", prefix, set))?;
+        let components = (count - set * 4).min(4);
+        for (vertex, value) in influences.iter_mut().zip(values) {
+            vertex.extend_from_slice(&value[..components]);
+        }
+    }
+    Some(influences)
+}
+
+impl Bone {
+    /// Transform relative to the parent bone.
+    pub fn get_local_matrix(&self, rotation_mode: RotationMode) -> [[f32; 4]; 3] {
+        let rotation = match rotation_mode {
+            RotationMode::Quaternion => {
+                let [x, y, z, w] = self.rotation;
+                [
+                    [
+                        1. - 2. * (y * y + z * z),
+                        2. * (x * y - z * w),
+                        2. * (x * z + y * w),
+                    ],
+                    [
+                        2. * (x * y + z * w),
+                        1. - 2. * (x * x + z * z),
+                        2. * (y * z - x * w),
+                    ],
+                    [
+                        2. * (x * z - y * w),
+                        2. * (y * z + x * w),
+                        1. - 2. * (x * x + y * y),
+                    ],
+                ]
+            }
+            RotationMode::EulerXYZ => {
+                let (sx, cx) = self.rotation[0].sin_cos();
+                let (sy, cy) = self.rotation[1].sin_cos();
+                let (sz, cz) = self.rotation[2].sin_cos();
+                [
+                    [cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx],
+                    [sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx],
+                    [-sy, cy * sx, cy * cx],
+                ]
+            }
+        };
+        let mut matrix = [[0.; 4]; 3];
+        for (row, matrix_row) in matrix.iter_mut().enumerate() {
+            for column in 0..3 {
+                matrix_row[column] = rotation[row][column] * self.scale[column];
+            }
+            matrix_row[3] = self.translation[row];
+        }
+        matrix
+    }
+}
+
+impl Skeleton {
+    /// Transforms of all bones in model space in their rest pose.
+    pub fn get_model_matrices(&self) -> Vec<[[f32; 4]; 3]> {
+        let bones = self.get_bones();
+        let rotation_mode = self.get_rotation_mode();
+        let mut matrices: Vec<Option<[[f32; 4]; 3]>> = vec![None; bones.len()];
+        // Parents usually come first, but resolve them in any order
+        for _ in 0..bones.len() {
+            for (i, bone) in bones.iter().enumerate() {
+                if matrices[i].is_some() {
+                    continue;
+                }
+                let local = bone.get_local_matrix(rotation_mode);
+                matrices[i] = match bone.get_parent_index() {
+                    Some(parent) if (parent as usize) < bones.len() && parent as usize != i => {
+                        matrices[parent as usize].map(|parent| multiply(&parent, &local))
+                    }
+                    _ => Some(local),
+                };
+            }
+            if matrices.iter().all(Option::is_some) {
+                break;
+            }
+        }
+        matrices
+            .into_iter()
+            .map(|matrix| matrix.unwrap_or(IDENTITY))
+            .collect()
+    }
+}
+
+impl Model {
+    /// Resolve the bones and weights influencing each vertex of a shape.
+    ///
+    /// Vertices of shapes with a vertex skin count of zero are relative to the bone of the shape.
+    /// With a count of one, each vertex is rigidly bound to a single bone, otherwise
+    /// up to eight weighted bones are blended.
+    pub fn get_vertex_skins(&self, shape: &Shape) -> Option<Vec<VertexSkin>> {
+        let vertex_buffer = self.get_shape_vertex_buffer(shape)?;
+        let vertex_count = vertex_buffer.get_vertex_count() as usize;
+        let skin_count = shape.get_vertex_skin_count() as usize;
+        if skin_count == 0 {
+            let skin = VertexSkin {
+                bone_indices: vec![shape.get_bone_index()],
+                weights: vec![1.],
+            };
+            return Some(vec![skin; vertex_count]);
+        }
+
+        let indices = decode_influences(vertex_buffer, "_i", skin_count)?;
+        let weights = decode_influences(vertex_buffer, "_w", skin_count);
+        let matrix_to_bone_list = self.get_skeleton().get_matrix_to_bone_list();
+        Some(
+            indices
+                .iter()
+                .enumerate()
+                .map(|(i, indices)| {
+                    let weights = weights.as_ref().map(|weights| &weights[i][..]);
+                    resolve_skin(indices, weights, matrix_to_bone_list)
+                })
+                .collect(),
+        )
+    }
+
+    /// Positions of a shape in model space in the rest pose of the skeleton.
+    pub fn get_model_space_positions(&self, shape: &Shape) -> Option<Vec<[f32; 3]>> {
+        self.to_model_space(shape, "_p0", transform_point)
+    }
+
+    /// Normals of a shape in model space in the rest pose of the skeleton.
+    pub fn get_model_space_normals(&self, shape: &Shape) -> Option<Vec<[f32; 3]>> {
+        self.to_model_space(shape, "_n0", transform_normal)
+    }
+
+    /// Transform an attribute of a bone relative or rigidly skinned shape into model space.
+    /// Smoothly skinned vertices are already stored in model space.
+    fn to_model_space<F>(&self, shape: &Shape, name: &str, transform: F) -> Option<Vec<[f32; 3]>>
+    where
+        F: Fn(&[[f32; 4]; 3], [f32; 3]) -> [f32; 3],
+    {
+        let values = self
+            .get_shape_vertex_buffer(shape)?
+            .decode_attribute(name)?;
+        let values = values.into_iter().map(|[x, y, z, _]| [x, y, z]);
+        if shape.get_vertex_skin_count() > 1 {
+            return Some(values.collect());
+        }
+
+        let matrices = self.get_skeleton().get_model_matrices();
+        let skins = self.get_vertex_skins(shape)?;
+        Some(
+            values
+                .zip(skins)
+                .map(|(value, skin)| {
+                    match skin
+                        .bone_indices
+                        .first()
+                        .and_then(|&bone_index| matrices.get(bone_index as usize))
+                    {
+                        Some(matrix) => transform(matrix, value),
+                        None => value,
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Bfres;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test]
+    fn test_resolve_skin() {
+        let matrix_to_bone_list = [4, 2, 7];
+        let skin = resolve_skin(
+            &[1., 0., 1., 2.],
+            Some(&[0.25, 0.25, 0.25, 0.]),
+            &matrix_to_bone_list,
+        );
+        assert_eq!(skin.bone_indices, vec![2, 4]);
+        assert!((skin.weights[0] - 2. / 3.).abs() < 1e-6);
+        assert!((skin.weights[1] - 1. / 3.).abs() < 1e-6);
+
+        let skin = resolve_skin(&[2.], None, &matrix_to_bone_list);
+        assert_eq!(skin.bone_indices, vec![7]);
+        assert_eq!(skin.weights, vec![1.]);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_model_space_positions(bfres_file: &[u8]) {
+        let mut bfres = Bfres::new(bfres_file).unwrap();
+        let model = bfres.get_model_mut("mario_model").unwrap();
+        let shape = model.get_shapes()[0].clone();
+        let positions = model.get_model_space_positions(&shape).unwrap();
+        let skins = model.get_vertex_skins(&shape).unwrap();
+        assert_eq!(skins.len(), positions.len());
+        assert!(skins
+            .iter()
+            .all(|skin| skin.weights.iter().sum::<f32>() == 1.));
+
+        // The shape is relative to a root bone, so it follows the bone
+        assert_eq!(shape.get_vertex_skin_count(), 0);
+        let bone_index = skins[0].bone_indices[0] as usize;
+        let skeleton = model.get_skeleton_mut();
+        let bone_name = skeleton.get_bones()[bone_index].get_name().clone();
+        let bone = skeleton.get_bone_mut(&bone_name).unwrap();
+        assert_eq!(bone.get_parent_index(), None);
+        bone.translation[1] += 10.;
+        let moved = model.get_model_space_positions(&shape).unwrap();
+        for (position, moved) in positions.iter().zip(moved) {
+            assert!((moved[1] - position[1] - 10.).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use super::{Bfres, Bounding, EmbeddedFile, Mesh, Model, Shape, VertexBuffer};
+
+use std::collections::HashSet;
+
+/// Summary of the geometry and resources used by a model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelStats {
+    pub name: String,
+    pub bone_count: usize,
+    pub material_count: usize,
+    /// Counts of each level of detail, summed over all shapes.
+    pub lods: Vec<LodStats>,
+    pub shapes: Vec<ShapeStats>,
+    /// Size of the vertex and index buffers in bytes.
+    pub buffer_size: u64,
+    /// Size of the textures used by the materials in bytes, as stored in the file.
+    pub texture_size: u64,
+    /// Textures used by the materials, which were not found in the file.
+    pub missing_textures: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeStats {
+    pub name: String,
+    pub lods: Vec<LodStats>,
+    /// Bounds of all vertex positions, in the space the positions are stored in.
+    pub bounds: Option<Bounding>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LodStats {
+    /// Number of distinct vertices referenced by the indices.
+    pub vertex_count: usize,
+    pub index_count: usize,
+    pub triangle_count: usize,
+}
+
+impl LodStats {
+    fn new(mesh: &Mesh) -> Self {
+        let vertices: HashSet<u32> = mesh.get_indices().iter().copied().collect();
+        Self {
+            vertex_count: vertices.len(),
+            index_count: mesh.get_indices().len(),
+            triangle_count: mesh.get_triangles().len(),
+        }
+    }
+}
+
+impl ShapeStats {
+    fn new(shape: &Shape, vertex_buffer: Option<&VertexBuffer>) -> Self {
+        let bounds = vertex_buffer
+            .and_then(|vertex_buffer| vertex_buffer.decode_attribute("_p0"))
+            .filter(|positions| !positions.is_empty())
+            .map(|positions| {
+                let mut min = [f32::INFINITY; 3];
+                let mut max = [f32::NEG_INFINITY; 3];
+                for position in positions {
+                    for axis in 0..3 {
+                        min[axis] = min[axis].min(position[axis]);
+                        max[axis] = max[axis].max(position[axis]);
+                    }
+                }
+                Bounding {
+                    center: [0, 1, 2].map(|axis| (min[axis] + max[axis]) / 2.),
+                    extent: [0, 1, 2].map(|axis| (max[axis] - min[axis]) / 2.),
+                }
+            });
+        Self {
+            name: shape.get_name().clone(),
+            lods: shape.get_meshes().iter().map(LodStats::new).collect(),
+            bounds,
+        }
+    }
+}
+
+impl Bfres {
+    /// Statistics of all models.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_model_stats(&self) -> Vec<ModelStats> {
+        self.models
+            .iter()
+            .map(|model| self.get_stats(model))
+            .collect()
+    }
+
+    fn get_stats(&self, model: &Model) -> ModelStats {
+        let shapes: Vec<ShapeStats> = model
+            .get_shapes()
+            .iter()
+            .map(|shape| {
+                let vertex_buffer = model
+                    .get_vertex_buffers()
+                    .get(shape.get_vertex_buffer_index() as usize);
+                ShapeStats::new(shape, vertex_buffer)
+            })
+            .collect();
+
+        let lod_count = shapes.iter().map(|shape| shape.lods.len()).max();
+        let lods = (0..lod_count.unwrap_or_default())
+            .map(|lod| {
+                let mut total = LodStats::default();
+                for stats in shapes.iter().filter_map(|shape| shape.lods.get(lod)) {
+                    total.vertex_count += stats.vertex_count;
+                    total.index_count += stats.index_count;
+                    total.triangle_count += stats.triangle_count;
+                }
+                total
+            })
+            .collect();
+
+        let mut texture_names: Vec<&String> = model
+            .get_materials()
+            .iter()
+            .flat_map(|material| material.get_texture_names())
+            .collect();
+        texture_names.sort_unstable();
+        texture_names.dedup();
+        let mut texture_size = 0;
+        let mut missing_textures = vec![];
+        for name in texture_names {
+            match self.get_texture_size(name) {
+                Some(size) => texture_size += size,
+                None => missing_textures.push(name.clone()),
+            }
+        }
+
+        ModelStats {
+            name: model.get_name().clone(),
+            bone_count: model.get_skeleton().get_bones().len(),
+            material_count: model.get_materials().len(),
+            lods,
+            shapes,
+            buffer_size: model.get_buffer_size(),
+            texture_size,
+            missing_textures,
+        }
+    }
+
+    /// Size of the image data of a texture in an embedded BNTX or of a Wii U texture.
+    fn get_texture_size(&self, name: &str) -> Option<u64> {
+        let bntx_size = self
+            .external_files
+            .iter()
+            .find_map(|file| match file.get_file() {
+                Some(EmbeddedFile::BNTX(bntx)) => bntx
+                    .get_texture(name)
+                    .map(|texture| texture.get_image_size() as u64),
+                _ => None,
+            });
+        bntx_size.or_else(|| {
+            self.textures
+                .get(self.texture_dict.find(name)?)
+                .map(|texture| (texture.get_data().len() + texture.get_mip_data().len()) as u64)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_model_stats(bfres_file: &[u8]) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+        let stats = &bfres.get_model_stats()[0];
+        let model = &bfres.get_models()[0];
+
+        assert_eq!(stats.name, "mario_model");
+        assert_eq!(stats.bone_count, model.get_skeleton().get_bones().len());
+        assert_eq!(stats.shapes.len(), model.get_shapes().len());
+        assert!(stats.missing_textures.is_empty());
+        assert!(stats.texture_size > 0);
+        let shape = &stats.shapes[0];
+        assert!(shape.lods[0].triangle_count > 0);
+        assert_eq!(shape.lods[0].index_count, 3 * shape.lods[0].triangle_count);
+        assert_eq!(
+            stats.lods[0].triangle_count,
+            stats
+                .shapes
+                .iter()
+                .map(|shape| shape.lods[0].triangle_count)
+                .sum::<usize>()
+        );
+        let bounds = shape.bounds.unwrap();
+        assert!(bounds.extent.iter().all(|&extent| extent >= 0.));
+    }
+}
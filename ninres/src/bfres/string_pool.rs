@@ -0,0 +1,141 @@
+use super::{read_string, read_wiiu_string};
+use crate::{ByteOrderMark, Error};
+
+use byteorder::ByteOrder;
+
+/// String pool (`_STR`) containing the names used throughout a BFRES file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringPool {
+    offsets: Vec<u64>,
+    strings: Vec<String>,
+}
+
+impl StringPool {
+    /// Build a pool, whose first string will be placed at `offset`.
+    /// The empty string comes first and every string is only stored once.
+    pub fn new<I, S>(strings: I, offset: u64) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut pool = Self {
+            offsets: vec![offset],
+            strings: vec![String::new()],
+        };
+        for string in strings {
+            let string = string.into();
+            if pool.find(&string).is_none() {
+                let last = pool.strings.len() - 1;
+                // Strings are null terminated and aligned to two bytes
+                let size = (pool.strings[last].len() as u64 + 4) & !1;
+                pool.offsets.push(pool.offsets[last] + size);
+                pool.strings.push(string);
+            }
+        }
+        pool
+    }
+
+    /// Read the pool, where `offset` points to the first string.
+    /// The first string is always empty and not included in the string count.
+    pub(crate) fn read(
+        bom: &mut ByteOrderMark,
+        buffer: &[u8],
+        offset: u64,
+        size: u32,
+    ) -> Result<Self, Error> {
+        if offset < 0x14
+            || buffer.get(offset as usize - 0x14..offset as usize - 0x10) != Some(b"_STR")
+        {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(offset - 4);
+        let count = bom.read_u32()? as usize + 1;
+
+        let mut offsets = Vec::with_capacity(count);
+        let mut strings = Vec::with_capacity(count);
+        let mut position = offset;
+        for _ in 0..count {
+            let string = read_string(bom, buffer, position)?;
+            offsets.push(position);
+            // Strings are null terminated and aligned to two bytes
+            position += (string.len() as u64 + 4) & !1;
+            strings.push(string);
+        }
+        if position > offset + size as u64 {
+            return Err(Error::CorruptData);
+        }
+
+        Ok(Self { offsets, strings })
+    }
+
+    /// Read the string table of a Wii U file, whose strings are preceded by
+    /// 32 bit lengths and aligned to four bytes. Offsets point to the characters.
+    pub(crate) fn read_wiiu(
+        bom: &mut ByteOrderMark,
+        buffer: &[u8],
+        offset: u64,
+        size: u32,
+    ) -> Result<Self, Error> {
+        let mut offsets = vec![];
+        let mut strings = vec![];
+        let end = offset + size as u64;
+        let mut position = offset;
+        while offset != 0 && position + 4 < end {
+            bom.set_position(position);
+            if bom.read_u32()? == 0 {
+                break;
+            }
+            let string = read_wiiu_string(bom, buffer, position + 4)?;
+            offsets.push(position + 4);
+            position += (string.len() as u64 + 8) & !3;
+            strings.push(string);
+        }
+
+        Ok(Self { offsets, strings })
+    }
+
+    /// Serialize the strings, starting with the first one. The `_STR` header in front
+    /// of them is left to the file, since its size also covers the following data.
+    pub fn write<B: ByteOrder>(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        for string in self.strings.iter() {
+            let position = buffer.len();
+            buffer.resize(position + ((string.len() + 4) & !1), 0);
+            B::write_u16(&mut buffer[position..], string.len() as u16);
+            buffer[position + 2..position + 2 + string.len()].copy_from_slice(string.as_bytes());
+        }
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn get_strings(&self) -> &Vec<String> {
+        &self.strings
+    }
+
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.strings.get(index)
+    }
+
+    /// Index of given string in the pool.
+    pub fn find(&self, string: &str) -> Option<usize> {
+        self.strings.iter().position(|s| s == string)
+    }
+
+    /// Offset of the string with given index within the file.
+    pub fn get_offset(&self, index: usize) -> Option<u64> {
+        self.offsets.get(index).copied()
+    }
+
+    /// String, which is located at given offset within the file.
+    pub fn get_at_offset(&self, offset: u64) -> Option<&String> {
+        let index = self.offsets.binary_search(&offset).ok()?;
+        self.strings.get(index)
+    }
+}
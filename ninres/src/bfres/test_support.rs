@@ -0,0 +1,183 @@
+//! Builder of synthetic BFRES data for tests.
+
+use super::{AnimCurve, BfresReader, BufferInfo, CurveType};
+use crate::{ByteOrderMark, ResDict};
+use byteorder::{ByteOrder, BE, LE};
+use std::collections::HashMap;
+
+/// Curve type, animation data offset, frames and keys of a curve.
+pub type CurveData<'a> = (CurveType, u32, &'a [f32], &'a [&'a [f32]]);
+
+/// Builds the data of a BFRES file or of parts of it.
+///
+/// Switch files are little endian with absolute 64 bit offsets. Wii U files are big endian with
+/// 32 bit offsets, which are relative to their own position.
+pub struct Writer {
+    pub data: Vec<u8>,
+    big_endian: bool,
+    relative_offsets: bool,
+    strings: HashMap<String, usize>,
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self::switch()
+    }
+}
+
+impl Writer {
+    pub fn switch() -> Self {
+        Self {
+            data: vec![],
+            big_endian: false,
+            relative_offsets: false,
+            strings: HashMap::new(),
+        }
+    }
+
+    pub fn wiiu() -> Self {
+        Self {
+            data: vec![],
+            big_endian: true,
+            relative_offsets: true,
+            strings: HashMap::new(),
+        }
+    }
+
+    /// Append zeroed data, which is aligned like the offsets of the file.
+    pub fn alloc(&mut self, size: usize) -> usize {
+        let alignment = if self.relative_offsets { 4 } else { 8 };
+        let offset = (self.data.len() + alignment - 1) & !(alignment - 1);
+        self.data.resize(offset + size, 0);
+        offset
+    }
+
+    pub fn u8(&mut self, at: usize, value: u8) {
+        self.data[at] = value;
+    }
+
+    pub fn u16(&mut self, at: usize, value: u16) {
+        if self.big_endian {
+            BE::write_u16(&mut self.data[at..], value);
+        } else {
+            LE::write_u16(&mut self.data[at..], value);
+        }
+    }
+
+    pub fn u32(&mut self, at: usize, value: u32) {
+        if self.big_endian {
+            BE::write_u32(&mut self.data[at..], value);
+        } else {
+            LE::write_u32(&mut self.data[at..], value);
+        }
+    }
+
+    pub fn u64(&mut self, at: usize, value: u64) {
+        if self.big_endian {
+            BE::write_u64(&mut self.data[at..], value);
+        } else {
+            LE::write_u64(&mut self.data[at..], value);
+        }
+    }
+
+    pub fn f32(&mut self, at: usize, value: f32) {
+        self.u32(at, value.to_bits());
+    }
+
+    pub fn f32s(&mut self, at: usize, values: &[f32]) {
+        for (i, value) in values.iter().enumerate() {
+            self.f32(at + i * 4, *value);
+        }
+    }
+
+    /// Write an offset pointing to `target`.
+    pub fn offset(&mut self, at: usize, target: usize) {
+        if self.relative_offsets {
+            self.u32(at, (target as i32 - at as i32) as u32);
+        } else {
+            self.u64(at, target as u64);
+        }
+    }
+
+    /// Add a string, which is prefixed by its length, once and return where names point to.
+    /// Switch names point to the length and Wii U names to the characters.
+    pub fn string(&mut self, string: &str) -> usize {
+        if let Some(&offset) = self.strings.get(string) {
+            return offset;
+        }
+        let (offset, start) = if self.relative_offsets {
+            let offset = self.alloc(4 + string.len() + 1);
+            self.u32(offset, string.len() as u32);
+            (offset + 4, offset + 4)
+        } else {
+            let offset = self.alloc(2 + string.len() + 1);
+            self.u16(offset, string.len() as u16);
+            (offset, offset + 2)
+        };
+        self.data[start..start + string.len()].copy_from_slice(string.as_bytes());
+        self.strings.insert(string.to_string(), offset);
+        offset
+    }
+
+    /// Write an offset to a name.
+    pub fn name(&mut self, at: usize, string: &str) {
+        let target = self.string(string);
+        self.offset(at, target);
+    }
+
+    /// Write an index group of a Wii U file, which maps names to the offsets of their entries.
+    pub fn group(&mut self, entries: &[(&str, usize)]) -> usize {
+        let dict = ResDict::new(entries.iter().map(|(name, _)| *name)).unwrap();
+        let offset = self.alloc(8 + dict.get_nodes().len() * 0x10);
+        self.u32(offset, (8 + dict.get_nodes().len() * 0x10) as u32);
+        self.u32(offset + 4, entries.len() as u32);
+        for (i, node) in dict.get_nodes().iter().enumerate() {
+            let at = offset + 8 + i * 0x10;
+            self.u32(at, node.reference);
+            self.u16(at + 4, node.left);
+            self.u16(at + 6, node.right);
+            if i > 0 {
+                self.name(at + 8, node.get_key());
+                self.offset(at + 0xc, entries[i - 1].1);
+            }
+        }
+        offset
+    }
+
+    /// Write curves of a Switch file with single precision frames and keys.
+    /// Keys contain the coefficients of their curve type.
+    pub fn curves(&mut self, curves: &[CurveData]) -> usize {
+        let offset = self.alloc(curves.len() * AnimCurve::SIZE as usize);
+        for (i, (curve_type, anim_data_offset, frames, keys)) in curves.iter().enumerate() {
+            let at = offset + i * AnimCurve::SIZE as usize;
+            let frame_array = self.alloc(frames.len() * 4);
+            self.f32s(frame_array, frames);
+            let keys: Vec<f32> = keys.iter().flat_map(|key| key.iter().copied()).collect();
+            let key_array = self.alloc(keys.len() * 4);
+            self.f32s(key_array, &keys);
+            self.offset(at, frame_array);
+            self.offset(at + 8, key_array);
+            self.u16(at + 0x10, (*curve_type as u16) << 4);
+            self.u16(at + 0x12, frames.len() as u16);
+            self.u32(at + 0x14, *anim_data_offset);
+            self.f32s(at + 0x18, &[frames[0], frames[frames.len() - 1], 1., 0.]);
+        }
+        offset
+    }
+
+    /// Byte order mark of the data.
+    pub fn bom(&self) -> ByteOrderMark {
+        let bom = if self.big_endian { 0xfeff } else { 0xfffe };
+        ByteOrderMark::try_new(self.data.clone(), bom).unwrap()
+    }
+
+    /// Reader of a version 0.9 Switch file without buffer data.
+    pub fn reader<'a>(&'a self, bom: &'a mut ByteOrderMark) -> BfresReader<'a> {
+        BfresReader {
+            bom,
+            buffer: &self.data,
+            buffer_info: BufferInfo::default(),
+            version_number: 0x0009_0000,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use super::{read_relative_offset, read_wiiu_string, BfresReader, UserDataList};
+use crate::Error;
+
+/// FTEX section of a Wii U BFRES file containing a GX2 surface.
+///
+/// The image data is stored as it is laid out in GPU memory,
+/// which is tiled according to `tile_mode`.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct GX2Texture {
+    name: String,
+    path: String,
+    pub dim: u32,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub mip_count: u32,
+    /// `GX2SurfaceFormat` of the image data.
+    pub format: u32,
+    pub aa_mode: u32,
+    pub usage: u32,
+    pub tile_mode: u32,
+    pub swizzle: u32,
+    pub alignment: u32,
+    pub pitch: u32,
+    /// Offsets of the mipmaps within the mipmap data, starting with the second level.
+    pub mip_offsets: [u32; 13],
+    /// Channel mapping of the texture view, one byte per channel.
+    pub comp_sel: u32,
+    pub array_length: u32,
+    #[derivative(Debug = "ignore")]
+    data: Vec<u8>,
+    #[derivative(Debug = "ignore")]
+    mip_data: Vec<u8>,
+    user_data: UserDataList,
+}
+
+impl GX2Texture {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if reader.buffer.get(offset as usize..offset as usize + 4) != Some(b"FTEX") {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let dim = bom.read_u32()?;
+        let width = bom.read_u32()?;
+        let height = bom.read_u32()?;
+        let depth = bom.read_u32()?;
+        let mip_count = bom.read_u32()?;
+        let format = bom.read_u32()?;
+        let aa_mode = bom.read_u32()?;
+        let usage = bom.read_u32()?;
+        let data_size = bom.read_u32()? as usize;
+        let _data_pointer = bom.read_u32()?;
+        let mip_data_size = bom.read_u32()? as usize;
+        let _mip_data_pointer = bom.read_u32()?;
+        let tile_mode = bom.read_u32()?;
+        let swizzle = bom.read_u32()?;
+        let alignment = bom.read_u32()?;
+        let pitch = bom.read_u32()?;
+        let mut mip_offsets = [0; 13];
+        for mip_offset in mip_offsets.iter_mut() {
+            *mip_offset = bom.read_u32()?;
+        }
+        bom.set_position(offset + 0x88);
+        let comp_sel = bom.read_u32()?;
+        bom.set_position(offset + 0xa4);
+        let array_length = bom.read_u32()?;
+        let name_offset = read_relative_offset(bom)?;
+        let path_offset = read_relative_offset(bom)?;
+        let data_offset = read_relative_offset(bom)? as usize;
+        let mip_data_offset = read_relative_offset(bom)? as usize;
+        let user_data_group_offset = read_relative_offset(bom)?;
+
+        let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+        let path = read_wiiu_string(bom, reader.buffer, path_offset)?;
+        let data = reader
+            .buffer
+            .get(data_offset..data_offset + data_size)
+            .ok_or(Error::CorruptData)?
+            .to_vec();
+        let mip_data = if mip_data_offset != 0 {
+            reader
+                .buffer
+                .get(mip_data_offset..mip_data_offset + mip_data_size)
+                .ok_or(Error::CorruptData)?
+                .to_vec()
+        } else {
+            vec![]
+        };
+        let user_data = UserDataList::read_wiiu(reader, user_data_group_offset)?;
+
+        Ok(Self {
+            name,
+            path,
+            dim,
+            width,
+            height,
+            depth,
+            mip_count,
+            format,
+            aa_mode,
+            usage,
+            tile_mode,
+            swizzle,
+            alignment,
+            pitch,
+            mip_offsets,
+            comp_sel,
+            array_length,
+            data,
+            mip_data,
+            user_data,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    /// Image data of the first mipmap level.
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Image data of all further mipmap levels.
+    pub fn get_mip_data(&self) -> &[u8] {
+        &self.mip_data
+    }
+
+    pub fn get_user_data(&self) -> &UserDataList {
+        &self.user_data
+    }
+}
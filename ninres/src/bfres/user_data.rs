@@ -0,0 +1,288 @@
+use super::{read_relative_offset, read_string, read_wiiu_string, BfresReader};
+use crate::{ByteOrderMark, Error, ResDict};
+
+/// Named array of values attached to models, bones, materials or animations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserData {
+    name: String,
+    pub value: UserDataValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UserDataValue {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+    String(Vec<String>),
+    /// UTF-16 strings.
+    WString(Vec<String>),
+    Bytes(Vec<u8>),
+}
+
+/// Read a null terminated UTF-16 string starting at `offset`.
+fn read_wstring(bom: &mut ByteOrderMark, offset: u64) -> Result<String, Error> {
+    bom.set_position(offset);
+    let mut units = vec![];
+    loop {
+        match bom.read_u16()? {
+            0 => break,
+            unit => units.push(unit),
+        }
+    }
+    String::from_utf16(&units).map_err(|_| Error::CorruptData)
+}
+
+impl UserData {
+    pub(crate) const SIZE: u64 = 0x40;
+
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let data_offset = bom.read_u64()?;
+        let count = bom.read_u32()? as u64;
+        let data_type = bom.read_u8()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+
+        bom.set_position(data_offset);
+        let value = match data_type {
+            0 => UserDataValue::Int(
+                (0..count)
+                    .map(|_| bom.read_i32())
+                    .collect::<Result<_, _>>()?,
+            ),
+            1 => UserDataValue::Float(
+                (0..count)
+                    .map(|_| bom.read_f32())
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 | 3 => {
+                let mut strings = Vec::with_capacity(count as usize);
+                for i in 0..count {
+                    bom.set_position(data_offset + i * 8);
+                    let string_offset = bom.read_u64()?;
+                    strings.push(if data_type == 2 {
+                        read_string(bom, reader.buffer, string_offset)?
+                    } else {
+                        // skip the size prefix
+                        read_wstring(bom, string_offset + 2)?
+                    });
+                }
+                if data_type == 2 {
+                    UserDataValue::String(strings)
+                } else {
+                    UserDataValue::WString(strings)
+                }
+            }
+            4 => UserDataValue::Bytes(
+                reader
+                    .buffer
+                    .get(data_offset as usize..(data_offset + count) as usize)
+                    .ok_or(Error::CorruptData)?
+                    .to_vec(),
+            ),
+            _ => return Err(Error::CorruptData),
+        };
+
+        Ok(Self { name, value })
+    }
+
+    /// Read user data of a Wii U file, where the values follow the header.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let bom = &mut reader.bom;
+        bom.set_position(offset);
+        let name_offset = read_relative_offset(bom)?;
+        let count = bom.read_u16()? as u64;
+        let data_type = bom.read_u8()?;
+        let data_offset = offset + 8;
+
+        let name = read_wiiu_string(bom, reader.buffer, name_offset)?;
+
+        bom.set_position(data_offset);
+        let value = match data_type {
+            0 => UserDataValue::Int(
+                (0..count)
+                    .map(|_| bom.read_i32())
+                    .collect::<Result<_, _>>()?,
+            ),
+            1 => UserDataValue::Float(
+                (0..count)
+                    .map(|_| bom.read_f32())
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 | 3 => {
+                let mut strings = Vec::with_capacity(count as usize);
+                for i in 0..count {
+                    bom.set_position(data_offset + i * 4);
+                    let string_offset = read_relative_offset(bom)?;
+                    strings.push(if data_type == 2 {
+                        read_wiiu_string(bom, reader.buffer, string_offset)?
+                    } else {
+                        read_wstring(bom, string_offset)?
+                    });
+                }
+                if data_type == 2 {
+                    UserDataValue::String(strings)
+                } else {
+                    UserDataValue::WString(strings)
+                }
+            }
+            4 => UserDataValue::Bytes(
+                reader
+                    .buffer
+                    .get(data_offset as usize..(data_offset + count) as usize)
+                    .ok_or(Error::CorruptData)?
+                    .to_vec(),
+            ),
+            _ => return Err(Error::CorruptData),
+        };
+
+        Ok(Self { name, value })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn as_ints(&self) -> Option<&Vec<i32>> {
+        match &self.value {
+            UserDataValue::Int(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_floats(&self) -> Option<&Vec<f32>> {
+        match &self.value {
+            UserDataValue::Float(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Values of both string and wide string user data.
+    pub fn as_strings(&self) -> Option<&Vec<String>> {
+        match &self.value {
+            UserDataValue::String(values) | UserDataValue::WString(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&Vec<u8>> {
+        match &self.value {
+            UserDataValue::Bytes(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// User data of a section together with its dictionary.
+#[derive(Clone, Debug, Default)]
+pub struct UserDataList {
+    entries: Vec<UserData>,
+    dict: ResDict,
+}
+
+impl UserDataList {
+    pub(crate) fn read(
+        reader: &mut BfresReader,
+        array_offset: u64,
+        dict_offset: u64,
+        count: u16,
+    ) -> Result<Self, Error> {
+        let dict = ResDict::read(reader.bom, reader.buffer, dict_offset)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for i in 0..count as u64 {
+            entries.push(UserData::read(reader, array_offset + i * UserData::SIZE)?);
+        }
+        Ok(Self { entries, dict })
+    }
+
+    /// Read user data of a Wii U file from its index group.
+    pub(crate) fn read_wiiu(
+        reader: &mut BfresReader,
+        index_group_offset: u64,
+    ) -> Result<Self, Error> {
+        let (dict, offsets) =
+            ResDict::read_index_group(reader.bom, reader.buffer, index_group_offset)?;
+        let entries = offsets
+            .into_iter()
+            .map(|offset| UserData::read_wiiu(reader, offset))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries, dict })
+    }
+
+    pub fn get_entries(&self) -> &Vec<UserData> {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UserData> {
+        self.entries.get(self.dict.find(name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_string(buffer: &mut Vec<u8>, string: &str) -> u64 {
+        let offset = buffer.len() as u64;
+        buffer.extend((string.len() as u16).to_le_bytes().iter());
+        buffer.extend(string.bytes());
+        buffer.push(0);
+        offset
+    }
+
+    #[test]
+    fn test_read_user_data() {
+        let mut buffer = vec![0; 0x140];
+        let name_offset = write_string(&mut buffer, "course_id");
+        let string_offset = write_string(&mut buffer, "lava");
+        let wstring_offset = buffer.len() as u64;
+        buffer.extend([8, 0, b'l', 0, b'a', 0, b'v', 0, b'a', 0, 0, 0].iter());
+
+        let entries: [(u8, u32, Vec<u8>); 5] = [
+            (0, 2, [7i32.to_le_bytes(), (-1i32).to_le_bytes()].concat()),
+            (1, 1, 0.5f32.to_le_bytes().to_vec()),
+            (2, 1, string_offset.to_le_bytes().to_vec()),
+            (3, 1, wstring_offset.to_le_bytes().to_vec()),
+            (4, 3, vec![1, 2, 3]),
+        ];
+        for (i, (data_type, count, data)) in entries.iter().enumerate() {
+            let data_offset = buffer.len() as u64;
+            buffer.extend(data.iter());
+            let entry = &mut buffer[i * 0x40..];
+            entry[..8].copy_from_slice(&name_offset.to_le_bytes());
+            entry[8..0x10].copy_from_slice(&data_offset.to_le_bytes());
+            entry[0x10..0x14].copy_from_slice(&count.to_le_bytes());
+            entry[0x14] = *data_type;
+        }
+
+        let mut bom = ByteOrderMark::try_new(buffer.clone(), 0xfffe).unwrap();
+        let mut reader = BfresReader {
+            bom: &mut bom,
+            buffer: &buffer,
+            buffer_info: Default::default(),
+            version_number: 0x0009_0000,
+        };
+        let values: Vec<UserDataValue> = (0..entries.len() as u64)
+            .map(|i| {
+                UserData::read(&mut reader, i * UserData::SIZE)
+                    .unwrap()
+                    .value
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                UserDataValue::Int(vec![7, -1]),
+                UserDataValue::Float(vec![0.5]),
+                UserDataValue::String(vec!["lava".to_string()]),
+                UserDataValue::WString(vec!["lava".to_string()]),
+                UserDataValue::Bytes(vec![1, 2, 3]),
+            ]
+        );
+        assert_eq!(
+            UserData::read(&mut reader, 0).unwrap().get_name(),
+            "course_id"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+use super::{read_relative_offset, read_string, read_wiiu_string, writer::align, BfresReader};
+use crate::{ByteOrderMark, Error};
+
+use byteorder::{ByteOrder, BE, LE};
+
+/// FVTX section containing all vertex attributes of a shape.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct VertexBuffer {
+    index: u16,
+    vertex_count: u32,
+    vertex_skin_count: u8,
+    attributes: Vec<VertexAttrib>,
+    buffer_offset: u32,
+    big_endian: bool,
+    #[derivative(Debug = "ignore")]
+    buffers: Vec<VertexBufferData>,
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexAttrib {
+    name: String,
+    format: AttribFormat,
+    offset: u16,
+    buffer_index: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexBufferData {
+    /// Offset of the data within the file.
+    pub offset: u64,
+    pub stride: u32,
+    pub divisor: u32,
+    pub data: Vec<u8>,
+}
+
+/// Vertex attribute format.
+///
+/// The high byte describes how the components are interpreted,
+/// the low byte describes the component layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttribFormat(pub u16);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttribType {
+    UNorm,
+    SNorm,
+    UInt,
+    SInt,
+    Float,
+    UIntToFloat,
+    SIntToFloat,
+}
+
+impl AttribFormat {
+    pub fn get_type(&self) -> Option<AttribType> {
+        match self.0 >> 8 {
+            0x1 => Some(AttribType::UNorm),
+            0x2 => Some(AttribType::SNorm),
+            0x3 => Some(AttribType::UInt),
+            0x4 => Some(AttribType::SInt),
+            0x5 => Some(AttribType::Float),
+            0x8 => Some(AttribType::UIntToFloat),
+            0xa => Some(AttribType::SIntToFloat),
+            _ => None,
+        }
+    }
+
+    /// Convert a `GX2AttribFormat` of a Wii U file.
+    /// Formats without an equivalent have no components.
+    pub fn from_gx2(format: u32) -> Self {
+        let layout = match format & 0xff {
+            0x00 => 0x02,
+            0x01 => 0x01,
+            0x02 | 0x03 => 0x0a,
+            0x04 => 0x09,
+            0x05 | 0x06 => 0x14,
+            0x07 | 0x08 => 0x12,
+            0x0a => 0x0b,
+            0x0b => 0x0e,
+            0x0c | 0x0d => 0x17,
+            0x0e | 0x0f => 0x15,
+            0x10 | 0x11 => 0x18,
+            0x12 | 0x13 => 0x19,
+            _ => 0,
+        };
+        let is_float = matches!(
+            format & 0xff,
+            0x03 | 0x06 | 0x08 | 0x0d | 0x0f | 0x11 | 0x13
+        );
+        let attrib_type = match format & 0xf00 {
+            0x000 => 0x1,
+            0x100 => 0x3,
+            0x200 => 0x2,
+            0x300 => 0x4,
+            0x800 if is_float => 0x5,
+            0x800 => 0x8,
+            0xa00 => 0xa,
+            _ => 0,
+        };
+        Self(attrib_type << 8 | layout)
+    }
+
+    /// Bit widths of all components.
+    pub fn get_components(&self) -> Option<&'static [u32]> {
+        Some(match self.0 & 0xff {
+            0x01 => &[4, 4],
+            0x02 => &[8],
+            0x09 => &[8, 8],
+            0x0a => &[16],
+            0x0b => &[8, 8, 8, 8],
+            0x0e => &[10, 10, 10, 2],
+            0x12 => &[16, 16],
+            0x14 | 0x16 => &[32],
+            0x15 => &[16, 16, 16, 16],
+            0x17 => &[32, 32],
+            0x18 => &[32, 32, 32],
+            0x19 => &[32, 32, 32, 32],
+            _ => return None,
+        })
+    }
+
+    /// Size of a single element in bytes.
+    pub fn get_size(&self) -> Option<u32> {
+        self.get_components()
+            .map(|components| components.iter().sum::<u32>() / 8)
+    }
+
+    /// Decode a single element. Missing components default to `(0, 0, 0, 1)`.
+    pub fn decode<B: ByteOrder>(&self, data: &[u8]) -> Option<[f32; 4]> {
+        let attrib_type = self.get_type()?;
+        let components = self.get_components()?;
+        let size = self.get_size()? as usize;
+        let data = data.get(..size)?;
+
+        let packed = match size {
+            1 => data[0] as u64,
+            2 => B::read_u16(data) as u64,
+            4 => B::read_u32(data) as u64,
+            _ => 0,
+        };
+
+        let mut res = [0., 0., 0., 1.];
+        let mut bit_offset = 0;
+        for (i, &bits) in components.iter().enumerate() {
+            let raw = match bits {
+                8 => data[i] as u64,
+                16 => B::read_u16(&data[i * 2..]) as u64,
+                32 => B::read_u32(&data[i * 4..]) as u64,
+                _ => (packed >> bit_offset) & ((1 << bits) - 1),
+            };
+            bit_offset += bits;
+            res[i] = match attrib_type {
+                AttribType::UNorm => raw as f32 / ((1u64 << bits) - 1) as f32,
+                AttribType::SNorm => {
+                    let max = ((1u64 << (bits - 1)) - 1) as f32;
+                    (sign_extend(raw, bits) as f32 / max).max(-1.)
+                }
+                AttribType::UInt | AttribType::UIntToFloat => raw as f32,
+                AttribType::SInt | AttribType::SIntToFloat => sign_extend(raw, bits) as f32,
+                AttribType::Float => match bits {
+                    16 => f16_to_f32(raw as u16),
+                    32 => f32::from_bits(raw as u32),
+                    _ => return None,
+                },
+            };
+        }
+        Some(res)
+    }
+}
+
+#[inline]
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Convert an IEEE 754 half precision float to single precision.
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half as u32) & 0x8000) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            let mut exponent = 127 - 15 + 1;
+            let mut mantissa = mantissa;
+            while mantissa & 0x400 == 0 {
+                mantissa <<= 1;
+                exponent -= 1;
+            }
+            sign | (exponent << 23) | ((mantissa & 0x3ff) << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+impl VertexBuffer {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let BfresReader {
+            bom,
+            buffer,
+            buffer_info,
+            ..
+        } = reader;
+        bom.set_position(offset);
+        if buffer.get(offset as usize..offset as usize + 4) != Some(b"FVTX") {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(offset + 8);
+        let attribute_array_offset = bom.read_u64()?;
+        let _attribute_dict_offset = bom.read_u64()?;
+        let _memory_pool_offset = bom.read_u64()?;
+        let _runtime_buffer_array_offset = bom.read_u64()?;
+        let _user_buffer_array_offset = bom.read_u64()?;
+        let buffer_size_array_offset = bom.read_u64()?;
+        let buffer_stride_array_offset = bom.read_u64()?;
+        let _user_pointer = bom.read_u64()?;
+        let buffer_offset = bom.read_u32()?;
+        let attribute_count = bom.read_u8()?;
+        let buffer_count = bom.read_u8()?;
+        let index = bom.read_u16()?;
+        let vertex_count = bom.read_u32()?;
+        let vertex_skin_count = bom.read_u32()? as u8;
+
+        let mut attributes = Vec::with_capacity(attribute_count as usize);
+        for i in 0..attribute_count as u64 {
+            bom.set_position(attribute_array_offset + i * 0x10);
+            let name_offset = bom.read_u64()?;
+            // the format is always stored as big endian
+            let format = if bom.is_big_endian() {
+                bom.read_u32()? >> 16
+            } else {
+                bom.read_u32()?.swap_bytes() >> 16
+            } as u16;
+            let offset = bom.read_u16()?;
+            let buffer_index = bom.read_u16()?;
+            attributes.push(VertexAttrib {
+                name: read_string(bom, buffer, name_offset)?,
+                format: AttribFormat(format),
+                offset,
+                buffer_index,
+            });
+        }
+
+        let mut buffers = Vec::with_capacity(buffer_count as usize);
+        let mut data_offset = buffer_info.get_offset() + buffer_offset as u64;
+        for i in 0..buffer_count as u64 {
+            bom.set_position(buffer_size_array_offset + i * 0x10);
+            let size = bom.read_u32()? as u64;
+            bom.set_position(buffer_stride_array_offset + i * 0x10);
+            let stride = bom.read_u32()?;
+            let divisor = bom.read_u32()?;
+            if !buffer_info.contains(data_offset, size) {
+                return Err(Error::CorruptData);
+            }
+            let data = buffer
+                .get(data_offset as usize..(data_offset + size) as usize)
+                .ok_or(Error::CorruptData)?
+                .to_vec();
+            buffers.push(VertexBufferData {
+                offset: data_offset,
+                stride,
+                divisor,
+                data,
+            });
+            data_offset = round_up(data_offset + size, 8);
+        }
+
+        Ok(Self {
+            index,
+            vertex_count,
+            vertex_skin_count,
+            attributes,
+            buffer_offset,
+            big_endian: bom.is_big_endian(),
+            buffers,
+        })
+    }
+
+    /// Read a vertex buffer of a Wii U file, whose buffers are stored inline.
+    pub(crate) fn read_wiiu(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        let BfresReader { bom, buffer, .. } = reader;
+        if buffer.get(offset as usize..offset as usize + 4) != Some(b"FVTX") {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(offset + 4);
+        let attribute_count = bom.read_u8()?;
+        let buffer_count = bom.read_u8()?;
+        let index = bom.read_u16()?;
+        let vertex_count = bom.read_u32()?;
+        let vertex_skin_count = bom.read_u8()?;
+        bom.set_position(offset + 0x10);
+        let attribute_array_offset = read_relative_offset(bom)?;
+        let _attribute_group_offset = read_relative_offset(bom)?;
+        let buffer_array_offset = read_relative_offset(bom)?;
+
+        let mut attributes = Vec::with_capacity(attribute_count as usize);
+        for i in 0..attribute_count as u64 {
+            bom.set_position(attribute_array_offset + i * 0xc);
+            let name_offset = read_relative_offset(bom)?;
+            let buffer_index = bom.read_u8()? as u16;
+            bom.read_u8()?;
+            let offset = bom.read_u16()?;
+            let format = AttribFormat::from_gx2(bom.read_u32()?);
+            attributes.push(VertexAttrib {
+                name: read_wiiu_string(bom, buffer, name_offset)?,
+                format,
+                offset,
+                buffer_index,
+            });
+        }
+
+        let mut buffers = Vec::with_capacity(buffer_count as usize);
+        for i in 0..buffer_count as u64 {
+            let (stride, offset, data) =
+                read_gx2_buffer(bom, buffer, buffer_array_offset + i * 0x18)?;
+            buffers.push(VertexBufferData {
+                offset,
+                stride,
+                divisor: 0,
+                data,
+            });
+        }
+
+        Ok(Self {
+            index,
+            vertex_count,
+            vertex_skin_count,
+            attributes,
+            buffer_offset: 0,
+            big_endian: true,
+            buffers,
+        })
+    }
+
+    /// Append the buffers to `data`, where the GPU buffer data starts at `buffer_offset`,
+    /// and update the buffer offset and sizes of the FVTX section at `offset`.
+    pub(crate) fn write_buffers<B: ByteOrder>(
+        &self,
+        data: &mut Vec<u8>,
+        offset: usize,
+        buffer_offset: usize,
+    ) {
+        let buffer_size_array_offset = B::read_u64(&data[offset + 0x30..]) as usize;
+        data.resize(align(data.len(), 8), 0);
+        let position = data.len();
+        B::write_u32(
+            &mut data[offset + 0x48..],
+            (position - buffer_offset) as u32,
+        );
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            data.resize(align(data.len(), 8), 0);
+            B::write_u32(
+                &mut data[buffer_size_array_offset + i * 0x10..],
+                buffer.data.len() as u32,
+            );
+            data.extend_from_slice(&buffer.data);
+        }
+    }
+
+    pub fn get_vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    pub fn get_vertex_skin_count(&self) -> u8 {
+        self.vertex_skin_count
+    }
+
+    pub fn get_attributes(&self) -> &Vec<VertexAttrib> {
+        &self.attributes
+    }
+
+    pub fn get_buffers(&self) -> &Vec<VertexBufferData> {
+        &self.buffers
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&VertexAttrib> {
+        self.attributes.iter().find(|attrib| attrib.name == name)
+    }
+
+    /// Decode all elements of the attribute with given name, e.g. `_p0` for positions.
+    pub fn decode_attribute(&self, name: &str) -> Option<Vec<[f32; 4]>> {
+        let attrib = self.get_attribute(name)?;
+        let buffer = self.buffers.get(attrib.buffer_index as usize)?;
+        (0..self.vertex_count as usize)
+            .map(|i| {
+                let offset = i * buffer.stride as usize + attrib.offset as usize;
+                let data = buffer.data.get(offset..)?;
+                if self.big_endian {
+                    attrib.format.decode::<BE>(data)
+                } else {
+                    attrib.format.decode::<LE>(data)
+                }
+            })
+            .collect()
+    }
+}
+
+impl VertexAttrib {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_format(&self) -> AttribFormat {
+        self.format
+    }
+
+    pub fn get_offset(&self) -> u16 {
+        self.offset
+    }
+
+    pub fn get_buffer_index(&self) -> u16 {
+        self.buffer_index
+    }
+}
+
+/// Read a GX2 buffer of a Wii U file. Returns the stride and the data.
+pub(crate) fn read_gx2_buffer(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+) -> Result<(u32, u64, Vec<u8>), Error> {
+    bom.set_position(offset + 4);
+    let size = bom.read_u32()? as usize;
+    bom.set_position(offset + 0xc);
+    let stride = bom.read_u16()? as u32;
+    bom.set_position(offset + 0x14);
+    let data_offset = read_relative_offset(bom)?;
+    let data = buffer
+        .get(data_offset as usize..data_offset as usize + size)
+        .ok_or(Error::CorruptData)?
+        .to_vec();
+    Ok((stride, data_offset, data))
+}
+
+#[inline]
+fn round_up(x: u64, y: u64) -> u64 {
+    x.div_ceil(y) * y
+}
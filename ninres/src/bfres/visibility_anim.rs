@@ -0,0 +1,144 @@
+use super::{read_string, read_string_array, wrap_frame, AnimCurve, BfresReader};
+use crate::Error;
+
+/// FBVS or FVIS section containing a bone or material visibility animation.
+#[derive(Clone, Debug)]
+pub struct VisibilityAnim {
+    name: String,
+    path: String,
+    flags: u32,
+    pub frame_count: i32,
+    pub baked_size: u32,
+    bind_indices: Vec<u16>,
+    names: Vec<String>,
+    curves: Vec<AnimCurve>,
+    base_values: Vec<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisibilityAnimType {
+    Bone,
+    Material,
+}
+
+impl VisibilityAnim {
+    pub(crate) fn read(reader: &mut BfresReader, offset: u64) -> Result<Self, Error> {
+        if !matches!(
+            reader.buffer.get(offset as usize..offset as usize + 4),
+            Some(b"FBVS") | Some(b"FVIS")
+        ) {
+            return Err(Error::CorruptData);
+        }
+        let bom = &mut reader.bom;
+        bom.set_position(offset + 4);
+        let flags = bom.read_u32()?;
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let _bind_model_offset = bom.read_u64()?;
+        let bind_index_array_offset = bom.read_u64()?;
+        let curve_array_offset = bom.read_u64()?;
+        let base_value_array_offset = bom.read_u64()?;
+        let name_array_offset = bom.read_u64()?;
+        let _user_data_array_offset = bom.read_u64()?;
+        let _user_data_dict_offset = bom.read_u64()?;
+        let frame_count = bom.read_i32()?;
+        let baked_size = bom.read_u32()?;
+        let anim_count = bom.read_u16()?;
+        let curve_count = bom.read_u16()?;
+
+        let name = read_string(bom, reader.buffer, name_offset)?;
+        let path = read_string(bom, reader.buffer, path_offset)?;
+        let names = read_string_array(bom, reader.buffer, name_array_offset, anim_count as usize)?;
+
+        let mut bind_indices = Vec::with_capacity(anim_count as usize);
+        bom.set_position(bind_index_array_offset);
+        for _ in 0..anim_count {
+            bind_indices.push(bom.read_u16()?);
+        }
+
+        let mut base_values = Vec::with_capacity(anim_count as usize);
+        bom.set_position(base_value_array_offset);
+        let mut bits = 0;
+        for i in 0..anim_count {
+            if i % 32 == 0 {
+                bits = bom.read_u32()?;
+            }
+            base_values.push((bits >> (i % 32)) & 1 != 0);
+        }
+
+        let mut curves = Vec::with_capacity(curve_count as usize);
+        for i in 0..curve_count as u64 {
+            curves.push(AnimCurve::read(
+                reader,
+                curve_array_offset + i * AnimCurve::SIZE,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            flags,
+            frame_count,
+            baked_size,
+            bind_indices,
+            names,
+            curves,
+            base_values,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & (1 << 2) != 0
+    }
+
+    pub fn get_type(&self) -> VisibilityAnimType {
+        if self.flags & (1 << 8) != 0 {
+            VisibilityAnimType::Material
+        } else {
+            VisibilityAnimType::Bone
+        }
+    }
+
+    /// Indices of the animated bones or materials in the bound model.
+    pub fn get_bind_indices(&self) -> &Vec<u16> {
+        &self.bind_indices
+    }
+
+    /// Names of the animated bones or materials.
+    pub fn get_names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn get_curves(&self) -> &Vec<AnimCurve> {
+        &self.curves
+    }
+
+    /// Visibility of all bones or materials without curves.
+    pub fn get_base_values(&self) -> &Vec<bool> {
+        &self.base_values
+    }
+
+    /// Evaluate the visibility of all animated bones or materials at given frame.
+    pub fn sample(&self, frame: f32) -> Vec<bool> {
+        let frame = wrap_frame(frame, self.frame_count as f32, self.is_looping());
+        let mut values = self.base_values.clone();
+        for curve in self.curves.iter() {
+            if let Some(value) = values.get_mut(curve.anim_data_offset as usize) {
+                *value = curve.evaluate(frame) != 0.;
+            }
+        }
+        values
+    }
+}
@@ -0,0 +1,309 @@
+//! Layout of Wii U BFRES files (version 3 and 4).
+//!
+//! Offsets are 32 bit and relative to their own position,
+//! and sections are found through index groups instead of dictionaries.
+
+use super::{
+    read_relative_offset, Bfres, BfresReader, BufferInfo, ExternalFile, GX2Texture, Model,
+    Platform, StringPool,
+};
+use crate::{ByteOrderMark, Error, RelocationTable, ResDict};
+
+const MODEL_GROUP: usize = 0;
+const TEXTURE_GROUP: usize = 1;
+const EXTERNAL_FILE_GROUP: usize = 11;
+
+impl Bfres {
+    pub(super) fn new_wiiu(buffer: &[u8]) -> Result<Bfres, Error> {
+        let mut bom =
+            ByteOrderMark::try_new(buffer.to_vec(), u16::from_be_bytes([buffer[8], buffer[9]]))?;
+        bom.set_position(4);
+        let version_number = bom.read_u32()?;
+        bom.set_position(0x10);
+        let byte_alignment = bom.read_u32()?.trailing_zeros() as u8;
+        bom.set_position(0x18);
+        let string_table_size = bom.read_u32()?;
+        let string_table_offset = read_relative_offset(&mut bom)?;
+        let mut group_offsets = [0; 12];
+        for group_offset in group_offsets.iter_mut() {
+            *group_offset = read_relative_offset(&mut bom)?;
+        }
+
+        let string_pool =
+            StringPool::read_wiiu(&mut bom, buffer, string_table_offset, string_table_size)?;
+        let (model_dict, model_offsets) =
+            ResDict::read_index_group(&mut bom, buffer, group_offsets[MODEL_GROUP])?;
+        let (texture_dict, texture_offsets) =
+            ResDict::read_index_group(&mut bom, buffer, group_offsets[TEXTURE_GROUP])?;
+        let (external_file_dict, external_file_offsets) =
+            ResDict::read_index_group(&mut bom, buffer, group_offsets[EXTERNAL_FILE_GROUP])?;
+
+        let mut external_files = Vec::with_capacity(external_file_offsets.len());
+        for (name, offset) in external_file_dict
+            .get_keys()
+            .cloned()
+            .zip(external_file_offsets)
+        {
+            bom.set_position(offset);
+            let data_offset = read_relative_offset(&mut bom)?;
+            let size = bom.read_u32()?;
+            let end_offset =
+                u64::checked_add(data_offset, size as u64).ok_or(Error::CorruptData)?;
+            let data = buffer
+                .get(data_offset as usize..end_offset as usize)
+                .ok_or(Error::CorruptData)?
+                .to_vec();
+            external_files.push(ExternalFile::new(name, data));
+        }
+
+        let mut reader = BfresReader {
+            bom: &mut bom,
+            buffer,
+            buffer_info: BufferInfo::default(),
+            version_number,
+        };
+        let models = model_offsets
+            .into_iter()
+            .map(|offset| Model::read_wiiu(&mut reader, offset))
+            .collect::<Result<_, _>>()?;
+        let textures = texture_offsets
+            .into_iter()
+            .map(|offset| GX2Texture::read(&mut reader, offset))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Bfres {
+            bom,
+            byte_alignment,
+            platform: Platform::WiiU,
+            relocation_table: RelocationTable::default(),
+            external_files_offset: group_offsets[EXTERNAL_FILE_GROUP],
+            string_pool,
+            buffer_info: None,
+            models,
+            model_dict,
+            skeletal_anims: vec![],
+            skeletal_anim_dict: ResDict::default(),
+            material_anims: vec![],
+            material_anim_dict: ResDict::default(),
+            bone_visibility_anims: vec![],
+            bone_visibility_anim_dict: ResDict::default(),
+            shape_anims: vec![],
+            shape_anim_dict: ResDict::default(),
+            scene_anims: vec![],
+            scene_anim_dict: ResDict::default(),
+            external_files,
+            external_file_dict,
+            textures,
+            texture_dict,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bfres::test_support::Writer, UserDataValue};
+
+    fn build_wiiu_bfres() -> Vec<u8> {
+        let mut w = Writer::wiiu();
+        let header = w.alloc(0x6c);
+        let string_table = w.alloc(0);
+        for string in ["mario", "root", "_p0", "body", "mat", "tex", "_a0", "id"].iter() {
+            w.string(string);
+        }
+        let string_table_size = w.data.len() - string_table;
+
+        let model = w.alloc(0x30);
+        let skeleton = w.alloc(0x24);
+        let bone = w.alloc(0x40);
+        let vertex_buffer = w.alloc(0x20);
+        let attribute = w.alloc(0xc);
+        let gpu_buffer = w.alloc(0x18);
+        let vertices = w.alloc(36);
+        let shape = w.alloc(0x38);
+        let mesh = w.alloc(0x1c);
+        let sub_mesh = w.alloc(8);
+        let index_buffer = w.alloc(0x18);
+        let indices = w.alloc(6);
+        let boundings = w.alloc(48);
+        let material = w.alloc(0x48);
+        let texture_ref = w.alloc(8);
+        let sampler = w.alloc(0x18);
+        let user_data = w.alloc(0x10);
+        let texture = w.alloc(0xc0);
+        let texture_data = w.alloc(64);
+        let matrix_to_bone_list = w.alloc(2);
+
+        w.data[header..header + 4].copy_from_slice(b"FRES");
+        w.u32(header + 4, 0x0304_0002);
+        w.u16(header + 8, 0xfeff);
+        w.u16(header + 0xa, 0x10);
+        w.u32(header + 0x10, 0x2000);
+        w.u32(header + 0x18, string_table_size as u32);
+        w.offset(header + 0x1c, string_table);
+        let model_group = w.group(&[("mario", model)]);
+        w.offset(header + 0x20, model_group);
+        let texture_group = w.group(&[("tex", texture)]);
+        w.offset(header + 0x24, texture_group);
+        w.u16(header + 0x50, 1);
+        w.u16(header + 0x52, 1);
+
+        w.data[model..model + 4].copy_from_slice(b"FMDL");
+        w.name(model + 4, "mario");
+        w.offset(model + 0xc, skeleton);
+        w.offset(model + 0x10, vertex_buffer);
+        let shape_group = w.group(&[("body", shape)]);
+        w.offset(model + 0x14, shape_group);
+        let material_group = w.group(&[("mat", material)]);
+        w.offset(model + 0x18, material_group);
+        let user_data_group = w.group(&[("id", user_data)]);
+        w.offset(model + 0x1c, user_data_group);
+        for i in 0..4 {
+            w.u16(model + 0x20 + i * 2, 1);
+        }
+        w.u32(model + 0x28, 3);
+
+        w.data[skeleton..skeleton + 4].copy_from_slice(b"FSKL");
+        w.u32(skeleton + 4, 0x1100);
+        w.u16(skeleton + 8, 1);
+        w.u16(skeleton + 0xc, 1);
+        let bone_group = w.group(&[("root", bone)]);
+        w.offset(skeleton + 0x10, bone_group);
+        w.offset(skeleton + 0x14, bone);
+        w.offset(skeleton + 0x18, matrix_to_bone_list);
+
+        w.name(bone, "root");
+        w.u16(bone + 6, 0xffff);
+        w.u16(bone + 8, 0xffff);
+        w.u16(bone + 0xc, 0xffff);
+        w.u32(bone + 0x10, 1);
+        for i in 0..3 {
+            w.f32(bone + 0x14 + i * 4, 1.);
+        }
+        w.f32(bone + 0x2c, 1.);
+        w.f32(bone + 0x34, 2.);
+
+        w.data[vertex_buffer..vertex_buffer + 4].copy_from_slice(b"FVTX");
+        w.u8(vertex_buffer + 4, 1);
+        w.u8(vertex_buffer + 5, 1);
+        w.u32(vertex_buffer + 8, 3);
+        w.offset(vertex_buffer + 0x10, attribute);
+        w.offset(vertex_buffer + 0x18, gpu_buffer);
+        w.name(attribute, "_p0");
+        w.u32(attribute + 8, 0x811);
+        w.u32(gpu_buffer + 4, 36);
+        w.u16(gpu_buffer + 0xc, 12);
+        w.offset(gpu_buffer + 0x14, vertices);
+        for i in 0..9 {
+            w.f32(vertices + i * 4, i as f32);
+        }
+
+        w.data[shape..shape + 4].copy_from_slice(b"FSHP");
+        w.name(shape + 4, "body");
+        w.u8(shape + 0x17, 1);
+        w.f32(shape + 0x1c, 5.);
+        w.offset(shape + 0x20, vertex_buffer);
+        w.offset(shape + 0x24, mesh);
+        w.offset(shape + 0x30, boundings);
+        w.u32(mesh, 4);
+        w.u32(mesh + 4, 4);
+        w.u32(mesh + 8, 3);
+        w.u16(mesh + 0xc, 1);
+        w.offset(mesh + 0x10, sub_mesh);
+        w.offset(mesh + 0x14, index_buffer);
+        w.u32(sub_mesh + 4, 3);
+        w.u32(index_buffer + 4, 6);
+        w.offset(index_buffer + 0x14, indices);
+        for i in 0..3 {
+            w.u16(indices + i * 2, 2 - i as u16);
+        }
+
+        w.data[material..material + 4].copy_from_slice(b"FMAT");
+        w.name(material + 4, "mat");
+        w.u32(material + 8, 1);
+        w.u8(material + 0x10, 1);
+        w.u8(material + 0x11, 1);
+        w.offset(material + 0x24, texture_ref);
+        w.offset(material + 0x28, sampler);
+        let sampler_group = w.group(&[("_a0", sampler)]);
+        w.offset(material + 0x2c, sampler_group);
+        w.name(texture_ref, "tex");
+        w.u32(sampler, 2 | 1 << 3);
+        w.u32(sampler + 4, (13 * 64) << 10);
+
+        w.name(user_data, "id");
+        w.u16(user_data + 4, 2);
+        w.u32(user_data + 8, 1);
+        w.u32(user_data + 0xc, -2i32 as u32);
+
+        w.data[texture..texture + 4].copy_from_slice(b"FTEX");
+        w.u32(texture + 4, 1);
+        w.u32(texture + 8, 4);
+        w.u32(texture + 0xc, 4);
+        w.u32(texture + 0x10, 1);
+        w.u32(texture + 0x14, 1);
+        w.u32(texture + 0x18, 0x1a);
+        w.u32(texture + 0x24, 64);
+        w.u32(texture + 0x34, 4);
+        w.name(texture + 0xa8, "tex");
+        w.offset(texture + 0xb0, texture_data);
+        w.data[texture_data] = 0xff;
+
+        let size = w.data.len() as u32;
+        w.u32(header + 0xc, size);
+        w.data
+    }
+
+    #[test]
+    fn test_read_wiiu_bfres() {
+        let bfres = Bfres::new(&build_wiiu_bfres()).unwrap();
+        assert_eq!(bfres.get_platform(), Platform::WiiU);
+        assert!(bfres.get_string_pool().find("body").is_some());
+
+        let model = bfres.get_model("mario").unwrap();
+        assert_eq!(
+            model.get_user_data().get("id").unwrap().value,
+            UserDataValue::Int(vec![1, -2])
+        );
+        let bone = model.get_skeleton().get_bone("root").unwrap();
+        assert_eq!(bone.get_parent_index(), None);
+        assert_eq!(bone.translation, [0., 2., 0.]);
+        assert_eq!(model.get_skeleton().get_matrix_to_bone_list(), &vec![0]);
+
+        let shape = model.get_shape("body").unwrap();
+        let mesh = &shape.get_meshes()[0];
+        assert_eq!(mesh.get_triangles(), vec![[2, 1, 0]]);
+        assert_eq!(shape.get_bounding_boxes().len(), 2);
+        assert_eq!(shape.get_radius(), &vec![5.]);
+        let positions = model
+            .get_shape_vertex_buffer(shape)
+            .unwrap()
+            .decode_attribute("_p0")
+            .unwrap();
+        assert_eq!(positions[2], [6., 7., 8., 1.]);
+
+        let material = model.get_shape_material(shape).unwrap();
+        assert_eq!(material.get_name(), "mat");
+        assert_eq!(material.get_sampler_texture("_a0").unwrap(), "tex");
+        let sampler = &material.get_samplers()[0];
+        assert_eq!((sampler.wrap_mode_u, sampler.wrap_mode_v), (2, 1));
+        assert_eq!(sampler.max_lod, 13.);
+
+        let texture = bfres.get_texture("tex").unwrap();
+        assert_eq!(
+            (texture.width, texture.height, texture.format),
+            (4, 4, 0x1a)
+        );
+        assert_eq!(texture.get_data().len(), 64);
+        assert_eq!(texture.get_data()[0], 0xff);
+    }
+
+    #[test]
+    fn test_write_wiiu_bfres() {
+        let bfres = Bfres::new(&build_wiiu_bfres()).unwrap();
+        assert!(matches!(
+            bfres.to_bytes(),
+            Err(Error::PlatformNotSupported(platform)) if platform == "WiiU"
+        ));
+    }
+}
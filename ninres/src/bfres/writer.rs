@@ -0,0 +1,460 @@
+use super::{Bfres, Platform, StringPool};
+use crate::{Error, RelocationSection, RelocationTable};
+use byteorder::{ByteOrder, BE, LE};
+use std::{collections::HashSet, ops::Range};
+
+/// Size of the memory pool, which is only filled at runtime.
+const MEMORY_POOL_SIZE: usize = 0x120;
+
+/// Header offsets of the dictionaries of models, animations and external files,
+/// each with the offset of its entry count.
+const DICTIONARY_OFFSETS: [(usize, usize); 7] = [
+    (0x30, 0xdc),
+    (0x60, 0xe2),
+    (0x70, 0xe4),
+    (0x80, 0xe6),
+    (0x90, 0xe8),
+    (0xa0, 0xea),
+    (0xc0, 0xec),
+];
+
+pub(super) fn align(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+/// Location a pointer points to, which is resolved once the file is laid out.
+enum Target<'a> {
+    Offset(usize),
+    String(&'a str),
+    /// Offset within the GPU buffer data.
+    Buffer(usize),
+    /// Offset within the memory pool.
+    MemoryPool(usize),
+}
+
+impl Bfres {
+    /// Serialize a Switch BFRES file.
+    ///
+    /// Models and animations keep the layout of the original file, with the current values
+    /// of bone transforms, samplers and shader params written into them. Everything else is
+    /// rebuilt: the dictionaries of models, animations and external files, the string pool,
+    /// which keeps the strings still in use, the vertex and index buffers, the memory pool,
+    /// the external files and the relocation table.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.platform != Platform::Switch {
+            return Err(Error::PlatformNotSupported(format!("{:?}", self.platform)));
+        }
+        if self.bom.is_big_endian() {
+            self.write::<BE>()
+        } else {
+            self.write::<LE>()
+        }
+    }
+
+    fn write<B: ByteOrder>(&self) -> Result<Vec<u8>, Error> {
+        let original = self.bom.get_ref();
+        let alignment = 1 << self.byte_alignment;
+        let read_u64 = |offset: usize| {
+            original
+                .get(offset..offset + 8)
+                .map(B::read_u64)
+                .ok_or(Error::CorruptData)
+        };
+        let read_u16 = |offset: usize| {
+            original
+                .get(offset..offset + 2)
+                .map(B::read_u16)
+                .ok_or(Error::CorruptData)
+        };
+
+        // Models and animations are stored in front of the string pool
+        let original_string_pool_offset =
+            self.string_pool.get_offset(0).ok_or(Error::CorruptData)? as usize;
+        let original_string_table_offset = original_string_pool_offset
+            .checked_sub(0x14)
+            .ok_or(Error::CorruptData)?;
+        let original_string_table_end = original
+            .get(0xd8..0xdc)
+            .map(B::read_u32)
+            .ok_or(Error::CorruptData)? as usize
+            + original_string_table_offset;
+        let mut data = original
+            .get(..original_string_table_offset)
+            .ok_or(Error::CorruptData)?
+            .to_vec();
+        let model_array_offset = read_u64(0x28)? as usize;
+        for (i, model) in self.models.iter().enumerate() {
+            model.write::<B>(&mut data, model_array_offset + i * 0x78);
+        }
+
+        // Dictionaries and the external file array are rebuilt in place if they still fit,
+        // otherwise they are added after the models and animations.
+        let dicts = [
+            &self.model_dict,
+            &self.skeletal_anim_dict,
+            &self.material_anim_dict,
+            &self.bone_visibility_anim_dict,
+            &self.shape_anim_dict,
+            &self.scene_anim_dict,
+            &self.external_file_dict,
+        ];
+        let mut blocks: Vec<(usize, Range<usize>, usize)> = Vec::with_capacity(dicts.len() + 1);
+        for ((header, count), dict) in DICTIONARY_OFFSETS.iter().zip(dicts.iter()) {
+            let offset = read_u64(*header)? as usize;
+            let size = if offset == 0 {
+                0
+            } else {
+                8 + (read_u16(*count)? as usize + 1) * 0x10
+            };
+            let new_size = if dict.is_empty() { 0 } else { dict.get_size() };
+            blocks.push((*header, offset..offset + size, new_size));
+        }
+        let external_file_count = read_u16(0xec)? as usize;
+        let external_files_offset = self.external_files_offset as usize;
+        blocks.push((
+            0xb8,
+            external_files_offset..external_files_offset + external_file_count * 0x10,
+            self.external_files.len() * 0x10,
+        ));
+        let mut block_offsets = Vec::with_capacity(blocks.len());
+        for (_, range, size) in blocks.iter() {
+            data.get_mut(range.clone())
+                .ok_or(Error::CorruptData)?
+                .fill(0);
+            let offset = if *size == 0 {
+                0
+            } else if *size <= range.len() {
+                range.start
+            } else {
+                let offset = align(data.len(), 8);
+                data.resize(offset + size, 0);
+                offset
+            };
+            block_offsets.push(offset);
+        }
+
+        let original_buffer = self
+            .buffer_info
+            .map(|info| {
+                info.get_offset() as usize..(info.get_offset() + info.get_size() as u64) as usize
+            })
+            .unwrap_or_default();
+        let original_memory_pool_offset = read_u64(0xa8)? as usize;
+        let original_memory_pool = if original_memory_pool_offset == 0 {
+            0..0
+        } else {
+            original_memory_pool_offset..original_memory_pool_offset + MEMORY_POOL_SIZE
+        };
+        let mut targets = vec![];
+        for pointer in self.relocation_table.get_pointer_offsets() {
+            let pointer = pointer as usize;
+            if blocks
+                .iter()
+                .any(|(header, range, _)| *header == pointer || range.contains(&pointer))
+            {
+                continue;
+            }
+            let value = read_u64(pointer)? as usize;
+            let target = if blocks.iter().any(|(_, range, _)| range.contains(&value)) {
+                return Err(Error::ModificationNotSupported(format!(
+                    "pointer at {:#x} into a dictionary or the external file array",
+                    pointer
+                )));
+            } else if value < original_string_table_offset {
+                Target::Offset(value)
+            } else if (original_string_pool_offset..original_string_table_end).contains(&value) {
+                Target::String(
+                    self.string_pool
+                        .get_at_offset(value as u64)
+                        .ok_or(Error::CorruptData)?,
+                )
+            } else if original_buffer.contains(&value) {
+                Target::Buffer(value - original_buffer.start)
+            } else if original_memory_pool.contains(&value) {
+                Target::MemoryPool(value - original_memory_pool.start)
+            } else {
+                return Err(Error::CorruptData);
+            };
+            targets.push((pointer, target));
+        }
+
+        // Strings keep their order, new names are added at the end
+        let mut used_strings: HashSet<&str> = targets
+            .iter()
+            .filter_map(|(_, target)| match target {
+                Target::String(string) => Some(*string),
+                _ => None,
+            })
+            .collect();
+        used_strings.extend(
+            dicts
+                .iter()
+                .flat_map(|dict| dict.get_keys())
+                .map(String::as_str),
+        );
+        data.resize(align(data.len(), 8), 0);
+        let string_table_offset = data.len();
+        let string_pool = StringPool::new(
+            self.string_pool
+                .get_strings()
+                .iter()
+                .filter(|string| used_strings.contains(string.as_str()))
+                .chain(dicts.iter().flat_map(|dict| dict.get_keys()))
+                .cloned(),
+            string_table_offset as u64 + 0x14,
+        );
+        let string_offset = |string: &str| {
+            string_pool
+                .find(string)
+                .and_then(|index| string_pool.get_offset(index))
+                .unwrap_or_default() as usize
+        };
+
+        for (dict, offset) in dicts.iter().zip(block_offsets.iter()) {
+            if dict.is_empty() {
+                continue;
+            }
+            let bytes = dict.write::<B, _>(|key| string_offset(key) as u64);
+            data[*offset..*offset + bytes.len()].copy_from_slice(&bytes);
+            for (i, node) in dict.get_nodes().iter().enumerate() {
+                targets.push((offset + 8 + i * 0x10 + 8, Target::String(node.get_key())));
+            }
+        }
+        for ((header, _, _), offset) in blocks.iter().zip(block_offsets.iter()) {
+            targets.push((*header, Target::Offset(*offset)));
+        }
+        B::write_u16(&mut data[0xec..], self.external_files.len() as u16);
+
+        data.extend_from_slice(b"_STR");
+        data.resize(string_table_offset + 0x14, 0);
+        B::write_u32(
+            &mut data[string_table_offset + 0x10..],
+            string_pool.len() as u32 - 1,
+        );
+        data.extend(string_pool.write::<B>());
+        let string_table_end = data.len();
+        B::write_u16(&mut data[0x16..], string_table_offset as u16);
+        B::write_u32(
+            &mut data[0xd8..],
+            (string_table_end - string_table_offset) as u32,
+        );
+
+        // Index buffers of all models come first, followed by their vertex buffers
+        let mut regions = Vec::with_capacity(5);
+        regions.push(0..string_table_end);
+        let mut buffer_offset = 0;
+        if self.buffer_info.is_some() {
+            let buffer_info_offset = read_u64(0xb0)? as usize;
+            data.resize(align(data.len(), alignment), 0);
+            buffer_offset = data.len();
+            for (i, model) in self.models.iter().enumerate() {
+                model.write_index_buffers::<B>(
+                    &mut data,
+                    model_array_offset + i * 0x78,
+                    buffer_offset,
+                );
+            }
+            data.resize(align(data.len(), 8), 0);
+            let vertex_buffer_offset = data.len();
+            for (i, model) in self.models.iter().enumerate() {
+                model.write_vertex_buffers::<B>(
+                    &mut data,
+                    model_array_offset + i * 0x78,
+                    buffer_offset,
+                );
+            }
+            data.resize(align(data.len(), alignment), 0);
+            let buffer_size = data.len() - buffer_offset;
+            B::write_u32(&mut data[buffer_info_offset + 4..], buffer_size as u32);
+            regions.push(buffer_offset..vertex_buffer_offset);
+            regions.push(vertex_buffer_offset..data.len());
+        }
+
+        let memory_pool_offset = align(data.len(), alignment);
+        if !original_memory_pool.is_empty() {
+            data.resize(memory_pool_offset + MEMORY_POOL_SIZE, 0);
+            regions.push(memory_pool_offset..data.len());
+        }
+
+        let external_files_offset = block_offsets[blocks.len() - 1];
+        let file_data_offset = align(data.len(), alignment);
+        for (i, file) in self.external_files.iter().enumerate() {
+            data.resize(align(data.len(), alignment), 0);
+            let entry = external_files_offset + i * 0x10;
+            let offset = data.len();
+            B::write_u32(&mut data[entry + 8..], file.data.len() as u32);
+            targets.push((entry, Target::Offset(offset)));
+            data.extend_from_slice(&file.data);
+        }
+        data.resize(align(data.len(), alignment), 0);
+        let relocation_table_offset = data.len();
+        if !self.external_files.is_empty() {
+            regions.push(file_data_offset..relocation_table_offset);
+        }
+        // The string table block covers everything up to the relocation table
+        B::write_u32(
+            &mut data[string_table_offset + 8..],
+            (relocation_table_offset - string_table_offset) as u32,
+        );
+
+        let mut pointers = Vec::with_capacity(targets.len());
+        for (pointer, target) in targets {
+            let value = match target {
+                Target::Offset(offset) => offset,
+                Target::String(string) => string_offset(string),
+                Target::Buffer(offset) => buffer_offset + offset,
+                Target::MemoryPool(offset) => memory_pool_offset + offset,
+            };
+            B::write_u64(&mut data[pointer..], value as u64);
+            pointers.push((pointer, value));
+        }
+        // The file name is also referenced by the offset of its characters
+        let name_offset = B::read_u64(&data[0x20..]) as u32 + 2;
+        B::write_u32(&mut data[0x10..], name_offset);
+
+        // A section lists all pointers, which point into it
+        let mut section_pointers = vec![vec![]; regions.len()];
+        for (pointer, value) in pointers {
+            if let Some(i) = regions
+                .iter()
+                .position(|region| region.contains(&value))
+                .or_else(|| regions.len().checked_sub(1))
+            {
+                section_pointers[i].push(pointer as u64);
+            }
+        }
+        let sections = self.relocation_table.get_sections();
+        let relocation_table = RelocationTable::new(
+            regions
+                .iter()
+                .zip(section_pointers)
+                .enumerate()
+                .map(|(i, (region, pointers))| {
+                    let (offset, size) = (region.start as u32, region.len() as u32);
+                    match sections.get(i) {
+                        Some(section) => section.with_pointers(offset, size, &pointers),
+                        None => RelocationSection::new(offset, size, &pointers),
+                    }
+                })
+                .collect(),
+        );
+        data.extend(relocation_table.write::<B>(relocation_table_offset as u32));
+
+        B::write_u32(&mut data[0x18..], relocation_table_offset as u32);
+        let size = data.len() as u32;
+        B::write_u32(&mut data[0x1c..], size);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_round_trip_bfres(bfres_file: &[u8]) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+
+        assert!(bfres.to_bytes().unwrap() == bfres_file);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_write_modified_bfres(bfres_file: &[u8]) {
+        let mut bfres = Bfres::new(bfres_file).unwrap();
+        let model = bfres.get_model_mut("mario_model").unwrap();
+        let bone = model.get_skeleton_mut().get_bone_mut("met").unwrap();
+        bone.translation = [1.0, 2.0, 3.0];
+        let material = model.get_material_mut("M1_body").unwrap();
+        let param = material.get_shader_param_mut("emission_intensity").unwrap();
+        param.set_floats(&[0.25]).unwrap();
+        assert!(param.set_floats(&[0.25, 0.5]).is_err());
+        let file = bfres.get_external_file_mut("textures.bntx").unwrap();
+        let textures = file.get_data().to_vec();
+        file.set_data(textures);
+
+        let bytes = bfres.to_bytes().unwrap();
+        assert_eq!(bytes.len(), bfres_file.len());
+        let bfres = Bfres::new(&bytes).unwrap();
+        let model = &bfres.get_models()[0];
+        let bone = model.get_skeleton().get_bone("met").unwrap();
+        assert_eq!(bone.translation, [1.0, 2.0, 3.0]);
+        let material = model.get_material("M1_body").unwrap();
+        let param = material.get_shader_param("emission_intensity").unwrap();
+        assert_eq!(param.get_floats(), vec![0.25]);
+        assert!(bfres.get_external_file("textures.bntx").is_some());
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_write_external_files(bfres_file: &[u8]) {
+        let original = Bfres::new(bfres_file).unwrap();
+        let mut bfres = original.clone();
+        // A long name moves the buffers behind the string pool
+        let name = "textures".repeat(0x200) + ".bntx";
+        bfres.rename_external_file("textures.bntx", &name).unwrap();
+        bfres.add_external_file("extra.bin", vec![1, 2, 3]).unwrap();
+        assert!(matches!(
+            bfres.add_external_file("extra.bin", vec![]),
+            Err(Error::DuplicateKey(_))
+        ));
+        assert!(matches!(
+            bfres.rename_external_file("textures.bntx", "other.bntx"),
+            Err(Error::FileNotFound(_))
+        ));
+
+        let bytes = bfres.to_bytes().unwrap();
+        let bfres = Bfres::new(&bytes).unwrap();
+        assert_eq!(bfres.to_bytes().unwrap(), bytes);
+        assert!(bfres.get_string_pool().find("textures.bntx").is_none());
+        assert_eq!(
+            bfres.get_external_file(&name).unwrap().get_data(),
+            original
+                .get_external_file("textures.bntx")
+                .unwrap()
+                .get_data()
+        );
+        assert_eq!(
+            bfres.get_external_file("extra.bin").unwrap().get_data(),
+            &[1, 2, 3]
+        );
+        let buffer_info = bfres.get_buffer_info().unwrap();
+        assert!(buffer_info.get_offset() > original.get_buffer_info().unwrap().get_offset());
+        let model = &bfres.get_models()[0];
+        let original_model = &original.get_models()[0];
+        for (vertex_buffer, original) in model
+            .get_vertex_buffers()
+            .iter()
+            .zip(original_model.get_vertex_buffers())
+        {
+            for (buffer, original) in vertex_buffer
+                .get_buffers()
+                .iter()
+                .zip(original.get_buffers())
+            {
+                assert!(buffer_info.contains(buffer.offset, buffer.data.len() as u64));
+                assert_eq!(buffer.data, original.data);
+            }
+        }
+        for (shape, original) in model.get_shapes().iter().zip(original_model.get_shapes()) {
+            assert_eq!(
+                shape.get_meshes()[0].get_indices(),
+                original.get_meshes()[0].get_indices()
+            );
+        }
+
+        let mut bfres = bfres;
+        bfres.remove_external_file(&name).unwrap();
+        bfres.remove_external_file("extra.bin").unwrap();
+        let bytes = bfres.to_bytes().unwrap();
+        let bfres = Bfres::new(&bytes).unwrap();
+        assert!(bfres.get_external_files().is_empty());
+        assert_eq!(bfres.get_relocation_table().get_sections().len(), 4);
+        assert_eq!(
+            bfres.get_models()[0].get_skeleton().get_bones().len(),
+            original_model.get_skeleton().get_bones().len()
+        );
+    }
+}
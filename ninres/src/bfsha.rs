@@ -0,0 +1,827 @@
+//! Shader archive (`FSHA`), which describes the shading models used by materials.
+//!
+//! Each shading model has static and dynamic options, whose choices select one of its
+//! programs. The compiled programs are stored in an embedded BNSH file.
+//!
+//! See https://github.com/Kinnay/Nintendo-File-Formats/wiki/BFSHA-File-Format
+
+use crate::{
+    bfres::read_string, ByteOrderMark, Error, RelocationTable, ResDict, ShaderStage, BNSH,
+};
+
+use std::{convert::TryFrom, io::SeekFrom};
+
+#[derive(Clone, Debug)]
+pub struct Bfsha {
+    name: String,
+    path: String,
+    version: u32,
+    relocation_table: RelocationTable,
+    shading_models: Vec<ShadingModel>,
+    shading_model_dict: ResDict,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShadingModel {
+    name: String,
+    static_options: Vec<ShaderOption>,
+    static_option_dict: ResDict,
+    dynamic_options: Vec<ShaderOption>,
+    dynamic_option_dict: ResDict,
+    attributes: Vec<ShaderAttribute>,
+    samplers: Vec<ShaderSampler>,
+    uniform_blocks: Vec<UniformBlock>,
+    uniform_block_dict: ResDict,
+    programs: Vec<ShaderProgram>,
+    default_program_index: Option<usize>,
+    static_key_length: usize,
+    dynamic_key_length: usize,
+    key_table: Vec<u32>,
+    bnsh: Option<BNSH>,
+}
+
+/// Option of a shading model, which is stored in a few bits of the program keys.
+#[derive(Clone, Debug)]
+pub struct ShaderOption {
+    name: String,
+    choices: Vec<String>,
+    default_index: u8,
+    word_index: u8,
+    shift: u8,
+    mask: u32,
+}
+
+/// Vertex attribute of a shading model, e.g. `_p0` for positions.
+#[derive(Clone, Debug)]
+pub struct ShaderAttribute {
+    name: String,
+    pub index: u8,
+    pub location: i8,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderSampler {
+    name: String,
+    /// Alternative name, e.g. the name of the texture in the authoring tool.
+    annotation: String,
+    pub index: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformBlock {
+    name: String,
+    pub index: u8,
+    pub block_type: UniformBlockType,
+    pub size: u16,
+    uniforms: Vec<Uniform>,
+    uniform_dict: ResDict,
+    default_data: Vec<u8>,
+}
+
+/// Source of the data of a uniform block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniformBlockType {
+    None,
+    Material,
+    Shape,
+    Skeleton,
+    Option,
+    Unknown(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct Uniform {
+    name: String,
+    pub index: i32,
+    offset: Option<u16>,
+    pub block_index: u8,
+}
+
+/// Program of a shading model, which is compiled for one combination of option choices.
+#[derive(Clone, Debug)]
+pub struct ShaderProgram {
+    sampler_locations: Vec<[i32; 6]>,
+    uniform_block_locations: Vec<[i32; 6]>,
+    /// Bit flags of the attributes, which are read by the program.
+    pub used_attribute_flags: u32,
+    variation_index: Option<usize>,
+}
+
+impl From<u8> for UniformBlockType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::Material,
+            2 => Self::Shape,
+            3 => Self::Skeleton,
+            4 => Self::Option,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+/// Read the keys of a dictionary, which must have `count` entries.
+fn read_dict(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+    count: usize,
+) -> Result<ResDict, Error> {
+    let dict = ResDict::read(bom, buffer, offset)?;
+    if offset != 0 && dict.len() != count {
+        return Err(Error::CorruptData);
+    }
+    Ok(dict)
+}
+
+/// Read the names of `count` entries from a dictionary, which may be missing.
+fn read_names(
+    bom: &mut ByteOrderMark,
+    buffer: &[u8],
+    offset: u64,
+    count: usize,
+) -> Result<Vec<String>, Error> {
+    let dict = read_dict(bom, buffer, offset, count)?;
+    Ok(get_names(&dict, count))
+}
+
+fn get_names(dict: &ResDict, count: usize) -> Vec<String> {
+    let mut names: Vec<String> = dict.get_keys().cloned().collect();
+    names.resize(count, String::new());
+    names
+}
+
+/// Read the shader locations of `count` resources, one for each stage.
+fn read_locations(
+    bom: &mut ByteOrderMark,
+    offset: u64,
+    count: u16,
+) -> Result<Vec<[i32; 6]>, Error> {
+    let mut locations = Vec::with_capacity(count as usize);
+    if offset == 0 {
+        return Ok(locations);
+    }
+    bom.set_position(offset);
+    for _ in 0..count {
+        let mut stages = [0; 6];
+        for location in stages.iter_mut() {
+            *location = bom.read_i32()?;
+        }
+        locations.push(stages);
+    }
+    Ok(locations)
+}
+
+impl Bfsha {
+    pub fn try_new(buffer: &[u8]) -> Result<Self, Error> {
+        if buffer.get(..8) != Some(b"FSHA    ") {
+            return Err(Error::CorruptData);
+        }
+        let mut bom = ByteOrderMark::try_new(
+            buffer.to_vec(),
+            u16::from_be_bytes([buffer[0xC], buffer[0xD]]),
+        )?;
+        bom.set_position(8);
+        let version = bom.read_u32()?;
+        if version >> 16 != 8 {
+            return Err(Error::TypeUnknownOrNotImplemented(*b"FSHA"));
+        }
+        bom.set_position(0x18);
+        let relocation_table_offset = bom.read_u32()?;
+        bom.set_position(0x20);
+        let archive_offset = bom.read_u64()?;
+        let relocation_table =
+            RelocationTable::read(&mut bom, buffer, relocation_table_offset as u64)?;
+
+        bom.set_position(archive_offset);
+        let name_offset = bom.read_u64()?;
+        let path_offset = bom.read_u64()?;
+        let shading_model_array_offset = bom.read_u64()?;
+        let shading_model_dict_offset = bom.read_u64()?;
+        bom.set_position(archive_offset + 0x40);
+        let shading_model_count = bom.read_u16()?;
+
+        let name = read_string(&mut bom, buffer, name_offset)?;
+        let path = read_string(&mut bom, buffer, path_offset)?;
+        let shading_model_dict = read_dict(
+            &mut bom,
+            buffer,
+            shading_model_dict_offset,
+            shading_model_count as usize,
+        )?;
+        let mut shading_models = Vec::with_capacity(shading_model_count as usize);
+        for i in 0..shading_model_count as u64 {
+            shading_models.push(ShadingModel::read(
+                &mut bom,
+                buffer,
+                shading_model_array_offset + i * 0x100,
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            path,
+            version,
+            relocation_table,
+            shading_models,
+            shading_model_dict,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get_relocation_table(&self) -> &RelocationTable {
+        &self.relocation_table
+    }
+
+    pub fn get_shading_models(&self) -> &Vec<ShadingModel> {
+        &self.shading_models
+    }
+
+    pub fn get_shading_model(&self, name: &str) -> Option<&ShadingModel> {
+        self.shading_models.get(self.shading_model_dict.find(name)?)
+    }
+}
+
+impl ShadingModel {
+    fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let static_option_array_offset = bom.read_u64()?;
+        let static_option_dict_offset = bom.read_u64()?;
+        let dynamic_option_array_offset = bom.read_u64()?;
+        let dynamic_option_dict_offset = bom.read_u64()?;
+        let attribute_array_offset = bom.read_u64()?;
+        let attribute_dict_offset = bom.read_u64()?;
+        let sampler_array_offset = bom.read_u64()?;
+        let sampler_dict_offset = bom.read_u64()?;
+        bom.seek(SeekFrom::Current(0x10))?;
+        let uniform_block_array_offset = bom.read_u64()?;
+        let uniform_block_dict_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x88);
+        let program_array_offset = bom.read_u64()?;
+        let key_table_offset = bom.read_u64()?;
+        bom.set_position(offset + 0xa8);
+        let bnsh_offset = bom.read_u64()?;
+        bom.set_position(offset + 0xd8);
+        let _uniform_count = bom.read_u32()?;
+        let _storage_buffer_count = bom.read_u32()?;
+        let default_program_index = bom.read_i32()?;
+        let static_option_count = bom.read_u16()?;
+        let dynamic_option_count = bom.read_u16()?;
+        let program_count = bom.read_u16()?;
+        let static_key_length = bom.read_u8()? as usize;
+        let dynamic_key_length = bom.read_u8()? as usize;
+        let attribute_count = bom.read_u8()?;
+        let sampler_count = bom.read_u8()?;
+        let _image_count = bom.read_u8()?;
+        let uniform_block_count = bom.read_u8()?;
+
+        let name = read_string(bom, buffer, name_offset)?;
+
+        let static_option_dict = read_dict(
+            bom,
+            buffer,
+            static_option_dict_offset,
+            static_option_count as usize,
+        )?;
+        let static_options = (0..static_option_count as u64)
+            .map(|i| ShaderOption::read(bom, buffer, static_option_array_offset + i * 0x28))
+            .collect::<Result<_, _>>()?;
+        let dynamic_option_dict = read_dict(
+            bom,
+            buffer,
+            dynamic_option_dict_offset,
+            dynamic_option_count as usize,
+        )?;
+        let dynamic_options = (0..dynamic_option_count as u64)
+            .map(|i| ShaderOption::read(bom, buffer, dynamic_option_array_offset + i * 0x28))
+            .collect::<Result<_, _>>()?;
+
+        let attribute_names =
+            read_names(bom, buffer, attribute_dict_offset, attribute_count as usize)?;
+        let mut attributes = Vec::with_capacity(attribute_count as usize);
+        for (i, name) in attribute_names.into_iter().enumerate() {
+            bom.set_position(attribute_array_offset + i as u64 * 2);
+            attributes.push(ShaderAttribute {
+                name,
+                index: bom.read_u8()?,
+                location: bom.read_i8()?,
+            });
+        }
+
+        let sampler_names = read_names(bom, buffer, sampler_dict_offset, sampler_count as usize)?;
+        let mut samplers = Vec::with_capacity(sampler_count as usize);
+        for (i, name) in sampler_names.into_iter().enumerate() {
+            bom.set_position(sampler_array_offset + i as u64 * 0x10);
+            let annotation_offset = bom.read_u64()?;
+            let index = bom.read_u8()?;
+            samplers.push(ShaderSampler {
+                name,
+                annotation: read_string(bom, buffer, annotation_offset)?,
+                index,
+            });
+        }
+
+        let uniform_block_dict = read_dict(
+            bom,
+            buffer,
+            uniform_block_dict_offset,
+            uniform_block_count as usize,
+        )?;
+        let uniform_blocks = get_names(&uniform_block_dict, uniform_block_count as usize)
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                UniformBlock::read(
+                    bom,
+                    buffer,
+                    uniform_block_array_offset + i as u64 * 0x20,
+                    name,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        let key_length = static_key_length + dynamic_key_length;
+        bom.set_position(key_table_offset);
+        let mut key_table = Vec::with_capacity(program_count as usize * key_length);
+        for _ in 0..program_count as usize * key_length {
+            key_table.push(bom.read_u32()?);
+        }
+
+        let bnsh = if bnsh_offset == 0 {
+            None
+        } else {
+            bom.set_position(bnsh_offset + 0x1c);
+            let size = bom.read_u32()? as usize;
+            let start = bnsh_offset as usize;
+            let data = buffer.get(start..start + size).ok_or(Error::CorruptData)?;
+            Some(BNSH::try_new(data)?)
+        };
+
+        let mut programs = Vec::with_capacity(program_count as usize);
+        for i in 0..program_count as u64 {
+            programs.push(ShaderProgram::read(
+                bom,
+                program_array_offset + i * 0x40,
+                bnsh_offset,
+                bnsh.as_ref(),
+            )?);
+        }
+
+        Ok(Self {
+            name,
+            static_options,
+            static_option_dict,
+            dynamic_options,
+            dynamic_option_dict,
+            attributes,
+            samplers,
+            uniform_blocks,
+            uniform_block_dict,
+            programs,
+            default_program_index: usize::try_from(default_program_index).ok(),
+            static_key_length,
+            dynamic_key_length,
+            key_table,
+            bnsh,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// Options, which are resolved when a material is loaded.
+    pub fn get_static_options(&self) -> &Vec<ShaderOption> {
+        &self.static_options
+    }
+
+    /// Options, which may change while rendering.
+    pub fn get_dynamic_options(&self) -> &Vec<ShaderOption> {
+        &self.dynamic_options
+    }
+
+    /// Static or dynamic option with given name.
+    pub fn get_option(&self, name: &str) -> Option<&ShaderOption> {
+        match self.static_option_dict.find(name) {
+            Some(i) => self.static_options.get(i),
+            None => self
+                .dynamic_options
+                .get(self.dynamic_option_dict.find(name)?),
+        }
+    }
+
+    pub fn get_attributes(&self) -> &Vec<ShaderAttribute> {
+        &self.attributes
+    }
+
+    pub fn get_samplers(&self) -> &Vec<ShaderSampler> {
+        &self.samplers
+    }
+
+    pub fn get_uniform_blocks(&self) -> &Vec<UniformBlock> {
+        &self.uniform_blocks
+    }
+
+    pub fn get_uniform_block(&self, name: &str) -> Option<&UniformBlock> {
+        self.uniform_blocks.get(self.uniform_block_dict.find(name)?)
+    }
+
+    pub fn get_programs(&self) -> &Vec<ShaderProgram> {
+        &self.programs
+    }
+
+    pub fn get_default_program_index(&self) -> Option<usize> {
+        self.default_program_index
+    }
+
+    /// Embedded shader container with the compiled programs.
+    pub fn get_bnsh(&self) -> Option<&BNSH> {
+        self.bnsh.as_ref()
+    }
+
+    /// Key of a program, which holds the static key followed by the dynamic key.
+    pub fn get_program_key(&self, index: usize) -> Option<&[u32]> {
+        let key_length = self.static_key_length + self.dynamic_key_length;
+        self.key_table
+            .get(index * key_length..(index + 1) * key_length)
+    }
+
+    /// Choices of all static and dynamic options of a program.
+    pub fn get_program_options(&self, index: usize) -> Option<Vec<(&String, &String)>> {
+        let key = self.get_program_key(index)?;
+        Some(
+            self.static_options
+                .iter()
+                .chain(self.dynamic_options.iter())
+                .filter_map(|option| Some((&option.name, option.get_choice(key)?)))
+                .collect(),
+        )
+    }
+
+    /// Find the program for given option choices, e.g. from a material's shader assign.
+    ///
+    /// The program must match all given choices. Options, which are not given,
+    /// should keep their default choice, so the program with the most matching defaults wins.
+    pub fn find_program<S: AsRef<str>>(&self, options: &[(S, S)]) -> Option<usize> {
+        let options = options
+            .iter()
+            .map(|(name, choice)| Some((self.get_option(name.as_ref())?, choice.as_ref())))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut best: Option<(usize, usize)> = None;
+        for index in 0..self.programs.len() {
+            let key = self.get_program_key(index)?;
+            if !options
+                .iter()
+                .all(|(option, choice)| option.get_choice(key).map(String::as_str) == Some(choice))
+            {
+                continue;
+            }
+            let defaults = self
+                .static_options
+                .iter()
+                .chain(self.dynamic_options.iter())
+                .filter(|option| !options.iter().any(|(given, _)| given.name == option.name))
+                .filter(|option| option.get_choice(key) == option.get_default())
+                .count();
+            if best.map_or(true, |(_, best_defaults)| defaults > best_defaults) {
+                best = Some((index, defaults));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+}
+
+impl ShaderOption {
+    fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        bom.set_position(offset);
+        let name_offset = bom.read_u64()?;
+        let choice_dict_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x18);
+        let choice_count = bom.read_u8()?;
+        let default_index = bom.read_u8()?;
+        bom.set_position(offset + 0x1e);
+        let word_index = bom.read_u8()?;
+        let shift = bom.read_u8()?;
+        let mask = bom.read_u32()?;
+
+        Ok(Self {
+            name: read_string(bom, buffer, name_offset)?,
+            choices: read_names(bom, buffer, choice_dict_offset, choice_count as usize)?,
+            default_index,
+            word_index,
+            shift,
+            mask,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_choices(&self) -> &Vec<String> {
+        &self.choices
+    }
+
+    pub fn get_default(&self) -> Option<&String> {
+        self.choices.get(self.default_index as usize)
+    }
+
+    /// Choice of this option in a program key.
+    pub fn get_choice(&self, key: &[u32]) -> Option<&String> {
+        let word = key.get(self.word_index as usize)?;
+        self.choices
+            .get(((word & self.mask) >> self.shift) as usize)
+    }
+}
+
+impl ShaderAttribute {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+impl ShaderSampler {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_annotation(&self) -> &String {
+        &self.annotation
+    }
+}
+
+impl UniformBlock {
+    fn read(
+        bom: &mut ByteOrderMark,
+        buffer: &[u8],
+        offset: u64,
+        name: String,
+    ) -> Result<Self, Error> {
+        bom.set_position(offset);
+        let uniform_array_offset = bom.read_u64()?;
+        let uniform_dict_offset = bom.read_u64()?;
+        let default_data_offset = bom.read_u64()?;
+        let index = bom.read_u8()?;
+        let block_type = bom.read_u8()?.into();
+        let size = bom.read_u16()?;
+        let uniform_count = bom.read_u16()?;
+
+        let uniform_dict = read_dict(bom, buffer, uniform_dict_offset, uniform_count as usize)?;
+        let mut uniforms = Vec::with_capacity(uniform_count as usize);
+        for (i, name) in get_names(&uniform_dict, uniform_count as usize)
+            .into_iter()
+            .enumerate()
+        {
+            bom.set_position(uniform_array_offset + i as u64 * 0x10 + 8);
+            let index = bom.read_i32()?;
+            // Offsets are stored plus one, so zero marks an unused uniform
+            let offset = bom.read_u16()?.checked_sub(1);
+            let block_index = bom.read_u8()?;
+            uniforms.push(Uniform {
+                name,
+                index,
+                offset,
+                block_index,
+            });
+        }
+
+        let default_data = if default_data_offset == 0 {
+            vec![]
+        } else {
+            let start = default_data_offset as usize;
+            buffer
+                .get(start..start + size as usize)
+                .ok_or(Error::CorruptData)?
+                .to_vec()
+        };
+
+        Ok(Self {
+            name,
+            index,
+            block_type,
+            size,
+            uniforms,
+            uniform_dict,
+            default_data,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_uniforms(&self) -> &Vec<Uniform> {
+        &self.uniforms
+    }
+
+    pub fn get_uniform(&self, name: &str) -> Option<&Uniform> {
+        self.uniforms.get(self.uniform_dict.find(name)?)
+    }
+
+    /// Initial contents of the block, if any.
+    pub fn get_default_data(&self) -> &[u8] {
+        &self.default_data
+    }
+}
+
+impl Uniform {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// Byte offset within the uniform block, if the uniform is used.
+    pub fn get_offset(&self) -> Option<u16> {
+        self.offset
+    }
+}
+
+impl ShaderProgram {
+    fn read(
+        bom: &mut ByteOrderMark,
+        offset: u64,
+        bnsh_offset: u64,
+        bnsh: Option<&BNSH>,
+    ) -> Result<Self, Error> {
+        bom.set_position(offset);
+        let sampler_table_offset = bom.read_u64()?;
+        let _image_table_offset = bom.read_u64()?;
+        let uniform_block_table_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x20);
+        let variation_offset = bom.read_u64()?;
+        bom.set_position(offset + 0x30);
+        let used_attribute_flags = bom.read_u32()?;
+        let _flag = bom.read_u16()?;
+        let sampler_count = bom.read_u16()?;
+        let _image_count = bom.read_u16()?;
+        let uniform_block_count = bom.read_u16()?;
+
+        let variation_index = match bnsh {
+            Some(bnsh) if variation_offset >= bnsh_offset => {
+                bnsh.get_variation_index(variation_offset - bnsh_offset)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            sampler_locations: read_locations(bom, sampler_table_offset, sampler_count)?,
+            uniform_block_locations: read_locations(
+                bom,
+                uniform_block_table_offset,
+                uniform_block_count,
+            )?,
+            used_attribute_flags,
+            variation_index,
+        })
+    }
+
+    /// Location of a sampler of the shading model in a shader stage, if it is used there.
+    pub fn get_sampler_location(&self, sampler_index: usize, stage: ShaderStage) -> Option<i32> {
+        self.sampler_locations
+            .get(sampler_index)
+            .map(|locations| locations[stage as usize])
+            .filter(|&location| location >= 0)
+    }
+
+    /// Location of a uniform block of the shading model in a shader stage, if it is used there.
+    pub fn get_uniform_block_location(
+        &self,
+        block_index: usize,
+        stage: ShaderStage,
+    ) -> Option<i32> {
+        self.uniform_block_locations
+            .get(block_index)
+            .map(|locations| locations[stage as usize])
+            .filter(|&location| location >= 0)
+    }
+
+    /// Index of the compiled variation in the embedded BNSH file.
+    pub fn get_variation_index(&self) -> Option<usize> {
+        self.variation_index
+    }
+}
+
+#[cfg(all(test, feature = "sarc", feature = "zstd"))]
+mod tests {
+    use super::*;
+    use crate::{Bfres, Sarc};
+
+    static MW_MODEL_PACK: &[u8] = include_bytes!("../../assets/MW_Model.pack");
+
+    fn read_sarc_file(sarc: &Sarc, path: &str) -> Vec<u8> {
+        let sfat = sarc
+            .get_sfat_nodes()
+            .iter()
+            .find(|sfat| sfat.get_path().map(String::as_str) == Some(path))
+            .unwrap();
+        sfat.get_data_decompressed()
+            .unwrap_or_else(|| sfat.get_data())
+            .clone()
+    }
+
+    fn read_block_uber() -> Vec<u8> {
+        let pack = Sarc::new(MW_MODEL_PACK).unwrap();
+        let archive =
+            Sarc::new(&read_sarc_file(&pack, "Model/MW_Object_key.Nin_NX_NVN.zs")).unwrap();
+        read_sarc_file(&archive, "Block_UBER.Nin_NX_NVN.bfsha")
+    }
+
+    #[test]
+    fn test_read_bfsha() {
+        let bfsha = Bfsha::try_new(&read_block_uber()).unwrap();
+        assert_eq!(bfsha.get_name(), "Block_UBER");
+        assert_eq!(bfsha.get_version(), 0x0008_0000);
+        assert_eq!(bfsha.get_shading_models().len(), 1);
+
+        let shading_model = bfsha.get_shading_model("block_uber").unwrap();
+        assert_eq!(shading_model.get_static_options().len(), 237);
+        assert_eq!(shading_model.get_dynamic_options().len(), 4);
+        let option = shading_model
+            .get_option("gsys_enable_color_buffer")
+            .unwrap();
+        assert_eq!(option.get_choices(), &["0", "1"]);
+        assert_eq!(option.get_default().unwrap(), "0");
+
+        let attribute = &shading_model.get_attributes()[3];
+        assert_eq!(
+            (attribute.get_name().as_str(), attribute.location),
+            ("_u0", 8)
+        );
+        assert_eq!(shading_model.get_samplers()[0].get_name(), "_a0");
+        let uniform_block = shading_model.get_uniform_block("gsys_material").unwrap();
+        assert_eq!(uniform_block.block_type, UniformBlockType::Material);
+        assert_eq!(uniform_block.size, 800);
+        assert_eq!(uniform_block.get_uniforms().len(), 80);
+        assert_eq!(shading_model.get_programs().len(), 8);
+        assert_eq!(shading_model.get_default_program_index(), None);
+        assert_eq!(shading_model.get_bnsh().unwrap().get_variations().len(), 8);
+    }
+
+    #[test]
+    fn test_read_bfsha_invalid() {
+        let mut data = read_block_uber();
+        assert!(matches!(
+            Bfsha::try_new(&data[..4]),
+            Err(Error::CorruptData)
+        ));
+        data[0xa] = 7;
+        assert!(matches!(
+            Bfsha::try_new(&data),
+            Err(Error::TypeUnknownOrNotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_program() {
+        let pack = Sarc::new(MW_MODEL_PACK).unwrap();
+        let archive =
+            Sarc::new(&read_sarc_file(&pack, "Model/MW_Object_key.Nin_NX_NVN.zs")).unwrap();
+        let bfsha = Bfsha::try_new(&read_block_uber()).unwrap();
+        let bfres = Bfres::new(&read_sarc_file(&archive, "output.bfres")).unwrap();
+
+        let material = &bfres.get_models()[0].get_materials()[0];
+        let shader_assign = material.get_shader_assign().unwrap();
+        assert_eq!(shader_assign.get_shader_archive(), bfsha.get_name());
+        let shading_model = bfsha
+            .get_shading_model(shader_assign.get_shading_model())
+            .unwrap();
+
+        // Some options of the material are not used by the shading model
+        let options: Vec<(&str, &str)> = shader_assign
+            .get_options()
+            .iter()
+            .filter(|(name, _)| shading_model.get_option(name).is_some())
+            .map(|(name, choice)| (name.as_str(), choice.as_str()))
+            .collect();
+        let index = shading_model.find_program(&options).unwrap();
+        let program_options = shading_model.get_program_options(index).unwrap();
+        for (name, choice) in options {
+            assert!(program_options
+                .iter()
+                .any(|(option, value)| *option == name && *value == choice));
+        }
+
+        let variation_index = shading_model.get_programs()[index]
+            .get_variation_index()
+            .unwrap();
+        let bnsh = shading_model.get_bnsh().unwrap();
+        let binary = bnsh.get_variations()[variation_index].get_binary().unwrap();
+        assert!(!binary
+            .get_stage(ShaderStage::Vertex)
+            .unwrap()
+            .get_code()
+            .is_empty());
+    }
+}
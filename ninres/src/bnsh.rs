@@ -0,0 +1,265 @@
+//! Shader container (`BNSH`), which holds the compiled variations of a shading model.
+//!
+//! See https://github.com/Kinnay/Nintendo-File-Formats/wiki/BNSH-File-Format
+
+use crate::{bfres::read_string, ByteOrderMark, Error, RelocationTable};
+
+use std::io::SeekFrom;
+
+#[derive(Clone, Debug)]
+pub struct BNSH {
+    name: String,
+    version: u32,
+    relocation_table: RelocationTable,
+    /// Graphics API the shaders were compiled for, e.g. 4 for NVN.
+    pub target_api_type: u16,
+    pub target_api_version: u16,
+    pub compiler_version: u32,
+    variation_array_offset: u64,
+    variations: Vec<ShaderVariation>,
+}
+
+/// Compiled shader program of one combination of shader options.
+#[derive(Clone, Debug)]
+pub struct ShaderVariation {
+    binary: Option<ShaderBinary>,
+}
+
+/// Machine code of all stages of a shader program.
+#[derive(Clone, Debug)]
+pub struct ShaderBinary {
+    pub code_type: u8,
+    pub source_format: u8,
+    pub binary_format: u32,
+    stages: [Option<ShaderCode>; 6],
+}
+
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct ShaderCode {
+    #[derivative(Debug = "ignore")]
+    control: Vec<u8>,
+    #[derivative(Debug = "ignore")]
+    code: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Hull,
+    Domain,
+    Geometry,
+    Pixel,
+    Compute,
+}
+
+fn read_slice(buffer: &[u8], offset: u64, size: u32) -> Result<Vec<u8>, Error> {
+    Ok(buffer
+        .get(offset as usize..offset as usize + size as usize)
+        .ok_or(Error::CorruptData)?
+        .to_vec())
+}
+
+impl BNSH {
+    pub fn try_new(buffer: &[u8]) -> Result<Self, Error> {
+        if buffer.get(..4) != Some(b"BNSH") {
+            return Err(Error::CorruptData);
+        }
+        let mut bom = ByteOrderMark::try_new(
+            buffer.to_vec(),
+            u16::from_be_bytes([buffer[0xC], buffer[0xD]]),
+        )?;
+        bom.set_position(8);
+        let version = bom.read_u32()?;
+        bom.set_position(0x10);
+        // The file name offset points to the characters after the length
+        let file_name_offset = bom.read_u32()?;
+        let _flag = bom.read_u16()?;
+        let block_offset = bom.read_u16()? as u64;
+        let relocation_table_offset = bom.read_u32()?;
+
+        let name = read_string(&mut bom, buffer, file_name_offset.saturating_sub(2) as u64)?;
+        let relocation_table =
+            RelocationTable::read(&mut bom, buffer, relocation_table_offset as u64)?;
+
+        if buffer.get(block_offset as usize..block_offset as usize + 4) != Some(b"grsc") {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(block_offset + 0x10);
+        let target_api_type = bom.read_u16()?;
+        let target_api_version = bom.read_u16()?;
+        bom.seek(SeekFrom::Current(4))?;
+        let compiler_version = bom.read_u32()?;
+        let variation_count = bom.read_u32()?;
+        let variation_array_offset = bom.read_u64()?;
+
+        let mut variations = Vec::with_capacity(variation_count as usize);
+        for i in 0..variation_count as u64 {
+            bom.set_position(variation_array_offset + i * 0x40 + 0x10);
+            let binary_offset = bom.read_u64()?;
+            let binary = if binary_offset == 0 {
+                None
+            } else {
+                Some(ShaderBinary::read(&mut bom, buffer, binary_offset)?)
+            };
+            variations.push(ShaderVariation { binary });
+        }
+
+        Ok(Self {
+            name,
+            version,
+            relocation_table,
+            target_api_type,
+            target_api_version,
+            compiler_version,
+            variation_array_offset,
+            variations,
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get_relocation_table(&self) -> &RelocationTable {
+        &self.relocation_table
+    }
+
+    pub fn get_variations(&self) -> &Vec<ShaderVariation> {
+        &self.variations
+    }
+
+    /// Index of the variation at `offset` relative to the start of the file.
+    pub(crate) fn get_variation_index(&self, offset: u64) -> Option<usize> {
+        let relative = offset.checked_sub(self.variation_array_offset)?;
+        let index = (relative / 0x40) as usize;
+        if relative % 0x40 == 0 && index < self.variations.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+impl ShaderBinary {
+    fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        bom.set_position(offset + 1);
+        let code_type = bom.read_u8()?;
+        let source_format = bom.read_u8()?;
+        bom.seek(SeekFrom::Current(1))?;
+        let binary_format = bom.read_u32()?;
+
+        let mut stages = [None, None, None, None, None, None];
+        for (i, stage) in stages.iter_mut().enumerate() {
+            bom.set_position(offset + 8 + i as u64 * 8);
+            let code_offset = bom.read_u64()?;
+            if code_offset == 0 {
+                continue;
+            }
+            bom.set_position(code_offset + 8);
+            let control_offset = bom.read_u64()?;
+            let data_offset = bom.read_u64()?;
+            let control_size = bom.read_u32()?;
+            let code_size = bom.read_u32()?;
+            *stage = Some(ShaderCode {
+                control: read_slice(buffer, control_offset, control_size)?,
+                code: read_slice(buffer, data_offset, code_size)?,
+            });
+        }
+
+        Ok(Self {
+            code_type,
+            source_format,
+            binary_format,
+            stages,
+        })
+    }
+
+    pub fn get_stage(&self, stage: ShaderStage) -> Option<&ShaderCode> {
+        self.stages[stage as usize].as_ref()
+    }
+}
+
+impl ShaderVariation {
+    /// Compiled binary, if the variation is not stored as source code only.
+    pub fn get_binary(&self) -> Option<&ShaderBinary> {
+        self.binary.as_ref()
+    }
+}
+
+impl ShaderCode {
+    /// Control section, which describes the machine code to the driver.
+    pub fn get_control(&self) -> &[u8] {
+        &self.control
+    }
+
+    pub fn get_code(&self) -> &[u8] {
+        &self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sarc;
+    use byteorder::{ByteOrder, LE};
+
+    static MW_MODEL_PACK: &[u8] = include_bytes!("../../assets/MW_Model.pack");
+
+    /// Shader container embedded in the shader archive of a model.
+    fn read_block_uber_bnsh() -> Vec<u8> {
+        let read_file = |sarc: &Sarc, path: &str| {
+            let sfat = sarc
+                .get_sfat_nodes()
+                .iter()
+                .find(|sfat| sfat.get_path().map(String::as_str) == Some(path))
+                .unwrap();
+            sfat.get_data_decompressed()
+                .unwrap_or_else(|| sfat.get_data())
+                .clone()
+        };
+        let pack = Sarc::new(MW_MODEL_PACK).unwrap();
+        let archive = Sarc::new(&read_file(&pack, "Model/MW_Object_key.Nin_NX_NVN.zs")).unwrap();
+        let bfsha = read_file(&archive, "Block_UBER.Nin_NX_NVN.bfsha");
+        let offset = bfsha.windows(4).position(|magic| magic == b"BNSH").unwrap();
+        let size = LE::read_u32(&bfsha[offset + 0x1c..]) as usize;
+        bfsha[offset..offset + size].to_vec()
+    }
+
+    #[test]
+    fn test_read_bnsh() {
+        let bnsh = BNSH::try_new(&read_block_uber_bnsh()).unwrap();
+        assert_eq!(bnsh.get_name(), "dummy");
+        assert_eq!(bnsh.get_version(), 0x0002_010b);
+        assert_eq!(bnsh.target_api_type, 4);
+        assert_eq!(bnsh.compiler_version, 0x0001_0802);
+        assert_eq!(bnsh.get_variations().len(), 8);
+
+        let binary = bnsh.get_variations()[0].get_binary().unwrap();
+        for stage in [ShaderStage::Vertex, ShaderStage::Pixel] {
+            let code = binary.get_stage(stage).unwrap();
+            assert!(!code.get_control().is_empty());
+            assert_eq!(code.get_code().len(), 2128);
+        }
+        assert!(binary.get_stage(ShaderStage::Geometry).is_none());
+
+        let offset = bnsh.variation_array_offset;
+        assert_eq!(bnsh.get_variation_index(offset + 0x40), Some(1));
+        assert_eq!(bnsh.get_variation_index(offset + 0x41), None);
+        assert_eq!(bnsh.get_variation_index(offset + 8 * 0x40), None);
+    }
+
+    #[test]
+    fn test_read_bnsh_invalid() {
+        let mut data = read_block_uber_bnsh();
+        assert!(matches!(BNSH::try_new(b"BNTX"), Err(Error::CorruptData)));
+        // Break the magic of the grsc block
+        let block_offset = LE::read_u16(&data[0x16..]) as usize;
+        data[block_offset] = 0;
+        assert!(matches!(BNSH::try_new(&data), Err(Error::CorruptData)));
+    }
+}
@@ -1,23 +1,45 @@
+mod astc;
+mod astc_encoder;
+mod bcn;
+mod bcn_encoder;
+mod dimension;
+mod format;
+mod mipmap;
+mod srgb;
+mod uncompressed;
 mod util;
+mod writer;
 
-use crate::{ByteOrderMark, Error};
+use crate::{bfres::read_string, ByteOrderMark, Error, RelocationTable, ResDict, StringPool};
 
+pub use astc::*;
+pub use astc_encoder::*;
+pub use bcn::*;
+pub use bcn_encoder::*;
+pub use dimension::*;
+pub use format::*;
 #[cfg(target_arch = "wasm32")]
 use js_sys::JsString;
-use std::{cmp, collections::HashMap, convert::TryFrom, io::SeekFrom};
+pub use mipmap::*;
+pub use srgb::*;
+use std::{cmp, convert::TryFrom, io::SeekFrom};
+pub use uncompressed::*;
 use util::*;
+pub use util::{deswizzle, get_block_height, swizzle};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+pub use writer::*;
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[derive(Clone, Debug)]
 pub struct BNTX {
+    bom: ByteOrderMark,
     header: BNTXHeader,
-    texture_count: i32,
+    name: String,
+    string_pool: StringPool,
     texture_array_offset: i64,
     texture_data_offset: i64,
-    texture_dict_offset: i64,
-    string_table_entries: HashMap<u64, StringTableEntry>,
+    texture_dict: ResDict,
     textures: Vec<Texture>,
 }
 
@@ -25,18 +47,8 @@ pub struct BNTX {
 #[derive(Clone, Debug)]
 pub struct BNTXHeader {
     alignment: u8,
-    target_address_size: u8,
-    file_name_offset: u32,
-    flag: u16,
-    block_offset: u16,
     relocation_table_offset: u32,
-    file_size: u32,
-}
-
-#[derive(Clone, Debug)]
-pub struct StringTableEntry {
-    size: u16,
-    string: String,
+    relocation_table: RelocationTable,
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -49,7 +61,8 @@ pub struct Texture {
     swizzle: u16,
     mip_count: u16,
     sample_count: u16,
-    format: u32,
+    surface_format: SurfaceFormat,
+    format_type: FormatType,
     access_flags: u32,
     pub width: u32,
     pub height: u32,
@@ -59,8 +72,8 @@ pub struct Texture {
     texture_layout2: u32,
     image_size: u32,
     alignment: u32,
-    channel_type: u32,
-    surface_dim: u8,
+    channel_sources: [ChannelSource; 4],
+    dimension: TextureDimension,
     name: String,
     parent_offset: u64,
     ptr_offset: u64,
@@ -70,6 +83,9 @@ pub struct Texture {
     desc_slot_data_offset: u64,
     user_dict_offset: u64,
     mip_offsets: Vec<u64>,
+    /// Swizzled image data of all array layers and mipmaps, like it is stored in the file.
+    #[derivative(Debug = "ignore")]
+    image_data: Vec<u8>,
     #[derivative(Debug = "ignore")]
     texture_data: Vec<Vec<Vec<u8>>>,
 }
@@ -81,37 +97,35 @@ impl BNTX {
             u16::from_be_bytes([buffer[0xC], buffer[0xD]]),
         )?;
         let alignment = buffer[0xE];
-        let target_address_size = buffer[0xF];
         bom.set_position(0x10);
-        let file_name_offset = bom.read_u32()?;
-        let flag = bom.read_u16()?;
-        let block_offset = bom.read_u16()?;
+        let name_offset = bom.read_u32()?;
+        bom.set_position(0x16);
+        let string_table_offset = bom.read_u16()?;
         let relocation_table_offset = bom.read_u32()?;
-        let file_size = bom.read_u32()?;
 
-        bom.seek(SeekFrom::Current(4))?;
+        bom.set_position(0x24);
         let texture_count = bom.read_i32()?;
         let texture_array_offset = bom.read_i64()?;
         let texture_data_offset = bom.read_i64()?;
         let texture_dict_offset = bom.read_i64()?;
 
-        bom.set_position(block_offset as u64 + 0x18);
-        let mut string_table_entries = HashMap::with_capacity(texture_count as usize);
-        for _ in 0..texture_count {
-            let offset = bom.position();
-            let size = bom.read_u16()?;
-            let string = std::str::from_utf8(
-                &buffer[bom.position() as usize..(bom.position() + size as u64) as usize],
-            )?
-            .to_string();
-            string_table_entries.insert(offset, StringTableEntry { size, string });
-            bom.seek(SeekFrom::Current(size as i64))?;
-            if bom.position() % 2 == 1 {
-                bom.seek(SeekFrom::Current(1))?;
-            } else {
-                bom.seek(SeekFrom::Current(2))?;
-            }
-        }
+        let relocation_table =
+            RelocationTable::read(&mut bom, buffer, relocation_table_offset as u64)?;
+        let texture_dict = ResDict::read(&mut bom, buffer, texture_dict_offset as u64)?;
+        // The name points to the characters after the length
+        let name = read_string(
+            &mut bom,
+            buffer,
+            name_offset.checked_sub(2).ok_or(Error::CorruptData)? as u64,
+        )?;
+        bom.set_position(string_table_offset as u64 + 8);
+        let string_table_size = bom.read_u32()?;
+        let string_pool = StringPool::read(
+            &mut bom,
+            buffer,
+            string_table_offset as u64 + 0x14,
+            string_table_size.saturating_sub(0x14),
+        )?;
 
         let mut textures = Vec::with_capacity(texture_count as usize);
         for i in 0..texture_count {
@@ -129,6 +143,8 @@ impl BNTX {
             let sample_count = bom.read_u16()?;
             bom.seek(SeekFrom::Current(2))?;
             let format = bom.read_u32()?;
+            let surface_format = SurfaceFormat::try_new(format >> 8)?;
+            let format_type = FormatType::try_new(format & 0xff)?;
 
             let access_flags = bom.read_u32()?;
             let width = bom.read_u32()?;
@@ -141,15 +157,16 @@ impl BNTX {
             let image_size = bom.read_u32()?;
 
             let alignment = bom.read_u32()?;
-            let channel_type = bom.read_u32()?;
-            let surface_dim = bom.read_u8()?;
+            let mut channel_sources = [ChannelSource::Zero; 4];
+            for source in channel_sources.iter_mut() {
+                *source = ChannelSource::try_new(bom.read_u8()?)?;
+            }
+            let dimension = TextureDimension::try_new(bom.read_u8()?)?;
             bom.seek(SeekFrom::Current(3))?;
             let name_offset = bom.read_u64()?;
-            let name = string_table_entries
-                .get(&name_offset)
-                .map_or_else(|| Err(Error::CorruptData), Ok)?
-                .string
-                .clone();
+            let position = bom.position();
+            let name = read_string(&mut bom, buffer, name_offset)?;
+            bom.set_position(position);
 
             let parent_offset = bom.read_u64()?;
             let ptr_offset = bom.read_u64()?;
@@ -167,52 +184,61 @@ impl BNTX {
                 mip_offsets.push(bom.read_u64()? - first_mip_offset);
             }
 
-            let mut texture_data = Vec::with_capacity(array_length as usize);
-            bom.set_position(first_mip_offset);
-
-            let (blk_width, blk_height) =
-                if let Some((w, h)) = BLK_DIMS.lock().unwrap().get(&(format >> 8)) {
-                    (*w, *h)
-                } else {
-                    (1, 1)
-                };
-            let bpp = *BPPS.lock().unwrap().get(&(format >> 8)).unwrap();
+            let (blk_width, blk_height) = surface_format.get_block_size();
+            let bpp = surface_format.get_bytes_per_block();
             let target = true; // "NX "
 
-            let block_height_log2 = texture_layout & 7;
-            let lines_per_block_height = (1 << block_height_log2) * 8;
-            let mut block_height_shift = 0;
-
-            for _ in 0..array_length {
+            let depth_or_one = if dimension == TextureDimension::D3 {
+                cmp::max(1, depth)
+            } else {
+                1
+            };
+            let mip_blocks = get_mip_blocks(
+                height,
+                depth_or_one,
+                blk_height,
+                texture_layout & 7,
+                mip_offsets.len(),
+            );
+            let image_data = buffer
+                .get(first_mip_offset as usize..(first_mip_offset + image_size as u64) as usize)
+                .ok_or(Error::CorruptData)?
+                .to_vec();
+
+            // Array layers, like the faces of cube maps, each contain all of their mipmaps
+            let layer_size = image_size as u64 / cmp::max(1, array_length) as u64;
+            let mut texture_data = Vec::with_capacity(array_length as usize);
+            for array_index in 0..array_length as u64 {
                 let mut mips = Vec::with_capacity(mip_count as usize);
                 for (mip_level, mip_offset) in mip_offsets.iter().enumerate() {
-                    let size = (image_size as u64 - mip_offset) / array_length as u64;
-                    bom.set_position(first_mip_offset + *mip_offset);
-                    let buffer = (buffer
-                        [bom.position() as usize..(bom.position() + size) as usize])
+                    let start = (first_mip_offset + array_index * layer_size + mip_offset) as usize;
+                    let end = (first_mip_offset + (array_index + 1) * layer_size) as usize;
+                    let buffer = buffer
+                        .get(start..cmp::min(end, buffer.len()))
+                        .ok_or(Error::CorruptData)?
                         .to_vec();
 
                     let width = cmp::max(1, width >> mip_level);
                     let height = cmp::max(1, height >> mip_level);
+                    let depth = cmp::max(1, depth_or_one >> mip_level);
 
-                    let size =
-                        div_round_up(width, blk_width) * div_round_up(height, blk_height) * bpp;
-
-                    if pow2_round_up(div_round_up(height, blk_height)) < lines_per_block_height {
-                        block_height_shift += 1;
-                    }
+                    let size = div_round_up(width, blk_width)
+                        * div_round_up(height, blk_height)
+                        * depth
+                        * bpp;
 
+                    let (block_height_log2, block_depth) = mip_blocks[mip_level];
                     let buffer = deswizzle(
                         width,
                         height,
+                        depth,
                         blk_width,
                         blk_height,
                         target,
                         bpp,
                         tile_mode,
-                        (block_height_log2)
-                            .checked_sub(block_height_shift)
-                            .unwrap_or_default(),
+                        block_height_log2,
+                        block_depth,
                         buffer,
                     )?;
                     mips.push(buffer[..size as usize].to_vec());
@@ -227,7 +253,8 @@ impl BNTX {
                 swizzle,
                 mip_count,
                 sample_count,
-                format,
+                surface_format,
+                format_type,
                 access_flags,
                 width,
                 height,
@@ -237,8 +264,8 @@ impl BNTX {
                 texture_layout2,
                 image_size,
                 alignment,
-                channel_type,
-                surface_dim,
+                channel_sources,
+                dimension,
                 name,
                 parent_offset,
                 ptr_offset,
@@ -248,34 +275,54 @@ impl BNTX {
                 desc_slot_data_offset,
                 user_dict_offset,
                 mip_offsets,
+                image_data,
                 texture_data,
             });
         }
 
         let header = BNTXHeader {
             alignment,
-            target_address_size,
-            file_name_offset,
-            flag,
-            block_offset,
             relocation_table_offset,
-            file_size,
+            relocation_table,
         };
         Ok(Self {
+            bom,
             header,
-            texture_count,
+            name,
+            string_pool,
             texture_array_offset,
             texture_data_offset,
-            texture_dict_offset,
-            string_table_entries,
+            texture_dict,
             textures,
         })
     }
 
+    /// Check the mip chains of all textures, see [`Texture::validate_mips`].
+    pub fn validate_mips(&self) -> Result<(), Error> {
+        self.textures.iter().try_for_each(Texture::validate_mips)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_header(&self) -> &BNTXHeader {
+        &self.header
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn get_textures(&self) -> &Vec<Texture> {
         &self.textures
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_texture(&self, name: &str) -> Option<&Texture> {
+        self.textures.get(self.texture_dict.find(name)?)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BNTXHeader {
+    pub fn get_relocation_table(&self) -> &RelocationTable {
+        &self.relocation_table
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -300,6 +347,250 @@ impl Texture {
     pub fn get_texture_data(&self) -> &Vec<Vec<Vec<u8>>> {
         &self.texture_data
     }
+
+    /// Size of the swizzled image data of all array layers and mipmaps.
+    pub fn get_image_size(&self) -> u32 {
+        self.image_size
+    }
+
+    pub fn get_surface_format(&self) -> SurfaceFormat {
+        self.surface_format
+    }
+
+    pub fn get_format_type(&self) -> FormatType {
+        self.format_type
+    }
+
+    /// Sources of the red, green, blue and alpha channel.
+    pub fn get_channel_sources(&self) -> &[ChannelSource; 4] {
+        &self.channel_sources
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.format_type == FormatType::Srgb
+    }
+
+    pub fn get_dimension(&self) -> TextureDimension {
+        self.dimension
+    }
+
+    /// Depth of 3D textures, which is one for all other dimensions.
+    pub fn get_depth(&self) -> u32 {
+        self.slice_count()
+    }
+
+    /// Number of array layers, which are six faces per cube map for cube maps.
+    pub fn get_array_length(&self) -> u32 {
+        self.array_length
+    }
+
+    /// Face of an array layer if this is a cube map.
+    pub fn get_cube_face(&self, array_index: usize) -> Option<CubeFace> {
+        if self.dimension.is_cube() {
+            Some(CubeFace::from_array_index(array_index))
+        } else {
+            None
+        }
+    }
+}
+
+impl Texture {
+    /// Width, height and depth of a mipmap.
+    pub fn get_mip_size(&self, mip_level: usize) -> (u32, u32, u32) {
+        (
+            cmp::max(1, self.width >> mip_level),
+            cmp::max(1, self.height >> mip_level),
+            cmp::max(1, self.slice_count() >> mip_level),
+        )
+    }
+
+    /// Check that the mipmaps follow each other without padding in every array layer
+    /// and that the image holds all of them. Textures are read even if this fails.
+    pub fn validate_mips(&self) -> Result<(), Error> {
+        let (blk_width, blk_height) = self.surface_format.get_block_size();
+        let mip_blocks = get_mip_blocks(
+            self.height,
+            self.slice_count(),
+            blk_height,
+            self.texture_layout & 7,
+            self.mip_offsets.len(),
+        );
+        let mip_sizes = get_mip_sizes(
+            (self.width, self.height, self.slice_count()),
+            (
+                blk_width,
+                blk_height,
+                self.surface_format.get_bytes_per_block(),
+            ),
+            self.tile_mode,
+            &mip_blocks,
+        );
+        let mut expected_offset = 0;
+        for (mip_level, (mip_offset, size)) in self.mip_offsets.iter().zip(&mip_sizes).enumerate() {
+            if *mip_offset != expected_offset {
+                return Err(Error::MipChainInvalid(format!(
+                    "mipmap {} of {} is at offset {:#x} instead of {:#x}",
+                    mip_level, self.name, mip_offset, expected_offset
+                )));
+            }
+            expected_offset += *size as u64;
+        }
+        let expected_size = expected_offset * self.array_length as u64;
+        if (self.image_size as u64) < expected_size {
+            return Err(Error::MipChainInvalid(format!(
+                "image of {} has {:#x} bytes instead of {:#x}",
+                self.name, self.image_size, expected_size
+            )));
+        }
+        Ok(())
+    }
+
+    fn slice_count(&self) -> u32 {
+        if self.dimension == TextureDimension::D3 {
+            cmp::max(1, self.depth)
+        } else {
+            1
+        }
+    }
+
+    fn get_mip(
+        &self,
+        array_index: usize,
+        mip_level: usize,
+        slice: u32,
+    ) -> Option<(&[u8], u32, u32)> {
+        let data = self.texture_data.get(array_index)?.get(mip_level)?;
+        let (width, height, depth) = self.get_mip_size(mip_level);
+        let slice_size = data.len() / depth as usize;
+        let data = data.get(slice as usize * slice_size..(slice as usize + 1) * slice_size)?;
+        Some((data, width, height))
+    }
+
+    /// Decode every depth slice of a mipmap and concatenate them.
+    fn decode_slices<T>(
+        &self,
+        mip_level: usize,
+        decode: impl Fn(u32) -> Option<Vec<T>>,
+    ) -> Option<Vec<T>> {
+        let (.., depth) = self.get_mip_size(mip_level);
+        let mut res = decode(0)?;
+        for slice in 1..depth {
+            res.extend(decode(slice)?);
+        }
+        Some(res)
+    }
+
+    fn decode_rgba8(&self, array_index: usize, mip_level: usize, slice: u32) -> Option<Vec<u8>> {
+        let (data, width, height) = self.get_mip(array_index, mip_level, slice)?;
+        let signed = self.format_type == FormatType::SNorm;
+        match self.surface_format {
+            SurfaceFormat::R8G8B8A8
+                if matches!(self.format_type, FormatType::UNorm | FormatType::Srgb) =>
+            {
+                Some(data.to_vec())
+            }
+            format if !format.is_compressed() => Some(rgba32f_to_rgba8(
+                &decode_uncompressed(data, format, self.format_type)?,
+                self.format_type,
+            )),
+            SurfaceFormat::BC1 => Some(decode_bc1(data, width, height)),
+            SurfaceFormat::BC2 => Some(decode_bc2(data, width, height)),
+            SurfaceFormat::BC3 => Some(decode_bc3(data, width, height)),
+            SurfaceFormat::BC4 => Some(decode_bc4(data, width, height, signed)),
+            SurfaceFormat::BC5 => Some(decode_bc5(data, width, height, signed)),
+            SurfaceFormat::BC6H => Some(
+                self.decode_rgba16f(array_index, mip_level, slice)?
+                    .into_iter()
+                    .map(|half| (half_to_f32(half).clamp(0., 1.) * 255.).round() as u8)
+                    .collect(),
+            ),
+            SurfaceFormat::BC7 => Some(decode_bc7(data, width, height)),
+            format if format.is_astc() => {
+                let (block_width, block_height) = format.get_block_size();
+                Some(decode_astc(data, width, height, block_width, block_height))
+            }
+            _ => None,
+        }
+    }
+
+    fn decode_rgba32f(&self, array_index: usize, mip_level: usize, slice: u32) -> Option<Vec<f32>> {
+        let (data, ..) = self.get_mip(array_index, mip_level, slice)?;
+        match self.surface_format {
+            SurfaceFormat::BC6H => Some(
+                self.decode_rgba16f(array_index, mip_level, slice)?
+                    .into_iter()
+                    .map(half_to_f32)
+                    .collect(),
+            ),
+            format if !format.is_compressed() => {
+                decode_uncompressed(data, format, self.format_type)
+            }
+            _ => Some(
+                self.decode_rgba8(array_index, mip_level, slice)?
+                    .into_iter()
+                    .map(|value| value as f32 / 255.)
+                    .collect(),
+            ),
+        }
+    }
+
+    fn decode_rgba16f(&self, array_index: usize, mip_level: usize, slice: u32) -> Option<Vec<u16>> {
+        let (data, width, height) = self.get_mip(array_index, mip_level, slice)?;
+        match self.surface_format {
+            SurfaceFormat::BC6H => Some(decode_bc6h(
+                data,
+                width,
+                height,
+                self.format_type == FormatType::Float,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Image of an array layer and mipmap as RGBA8, decoding BCn and ASTC compressed formats.
+    /// BC6H is clamped to the range between zero and one.
+    /// Channels are swizzled like the texture is sampled.
+    /// The depth slices of 3D textures follow each other.
+    pub fn to_rgba8(&self, array_index: usize, mip_level: usize) -> Option<Vec<u8>> {
+        let mut res = self.decode_slices(mip_level, |slice| {
+            self.decode_rgba8(array_index, mip_level, slice)
+        })?;
+        apply_channel_sources(&mut res, &self.channel_sources, 0, 255);
+        Some(res)
+    }
+
+    /// Like [`to_rgba8`](Self::to_rgba8), converting sRGB textures to linear color if requested.
+    pub fn to_rgba8_with(
+        &self,
+        array_index: usize,
+        mip_level: usize,
+        conversion: SrgbConversion,
+    ) -> Option<Vec<u8>> {
+        let mut res = self.to_rgba8(array_index, mip_level)?;
+        if conversion == SrgbConversion::Linearize && self.format_type == FormatType::Srgb {
+            linearize_rgba8(&mut res);
+        }
+        Some(res)
+    }
+
+    /// Image of an array layer and mipmap as RGBA32F.
+    /// Compressed formats other than BC6H have the precision of RGBA8.
+    pub fn to_rgba32f(&self, array_index: usize, mip_level: usize) -> Option<Vec<f32>> {
+        let mut res = self.decode_slices(mip_level, |slice| {
+            self.decode_rgba32f(array_index, mip_level, slice)
+        })?;
+        apply_channel_sources(&mut res, &self.channel_sources, 0., 1.);
+        Some(res)
+    }
+
+    /// Image of an array layer and mipmap as the bits of RGBA16F, for HDR formats like BC6H.
+    pub fn to_rgba16f(&self, array_index: usize, mip_level: usize) -> Option<Vec<u16>> {
+        let mut res = self.decode_slices(mip_level, |slice| {
+            self.decode_rgba16f(array_index, mip_level, slice)
+        })?;
+        apply_channel_sources(&mut res, &self.channel_sources, 0, 0x3c00);
+        Some(res)
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -323,6 +614,60 @@ impl Texture {
             })
     }
 
+    #[wasm_bindgen(js_name = getSurfaceFormat)]
+    pub fn get_surface_format(&self) -> SurfaceFormat {
+        self.surface_format
+    }
+
+    #[wasm_bindgen(js_name = getFormatType)]
+    pub fn get_format_type(&self) -> FormatType {
+        self.format_type
+    }
+
+    #[wasm_bindgen(js_name = isSrgb)]
+    pub fn is_srgb(&self) -> bool {
+        self.format_type == FormatType::Srgb
+    }
+
+    #[wasm_bindgen(js_name = getDimension)]
+    pub fn get_dimension(&self) -> TextureDimension {
+        self.dimension
+    }
+
+    #[wasm_bindgen(js_name = getDepth)]
+    pub fn get_depth(&self) -> u32 {
+        self.slice_count()
+    }
+
+    #[wasm_bindgen(js_name = getArrayLength)]
+    pub fn get_array_length(&self) -> u32 {
+        self.array_length
+    }
+
+    #[wasm_bindgen(js_name = getCubeFace)]
+    pub fn get_cube_face(&self, array_index: usize) -> Option<CubeFace> {
+        if self.dimension.is_cube() {
+            Some(CubeFace::from_array_index(array_index))
+        } else {
+            None
+        }
+    }
+
+    #[wasm_bindgen(js_name = getBlockWidth)]
+    pub fn get_block_width(&self) -> u32 {
+        self.surface_format.get_block_size().0
+    }
+
+    #[wasm_bindgen(js_name = getBlockHeight)]
+    pub fn get_block_height(&self) -> u32 {
+        self.surface_format.get_block_size().1
+    }
+
+    #[wasm_bindgen(js_name = getBytesPerBlock)]
+    pub fn get_bytes_per_block(&self) -> u32 {
+        self.surface_format.get_bytes_per_block()
+    }
+
     #[wasm_bindgen(js_name = getTexCount)]
     pub fn get_tex_count(&self) -> usize {
         self.texture_data.len()
@@ -336,23 +681,33 @@ impl Texture {
     #[cfg(feature = "png")]
     #[wasm_bindgen(js_name = asPng)]
     pub fn as_png(&self, tex_count: usize, mip_level: usize) -> Option<Box<[u8]>> {
+        self.as_png_with_srgb(tex_count, mip_level, SrgbConversion::Keep)
+    }
+
+    #[cfg(feature = "png")]
+    #[wasm_bindgen(js_name = asPngWithSrgb)]
+    pub fn as_png_with_srgb(
+        &self,
+        tex_count: usize,
+        mip_level: usize,
+        conversion: SrgbConversion,
+    ) -> Option<Box<[u8]>> {
         use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
 
-        let width = cmp::max(1, self.width >> mip_level);
-        let height = cmp::max(1, self.height >> mip_level);
-        if let Some(buf) = self
-            .texture_data
-            .get(tex_count)
-            .map(|d| d.get(mip_level))
-            .flatten()
-        {
-            if let Some(buf) = ImageBuffer::from_raw(width, height, buf.clone()) {
+        // Depth slices of 3D textures are stacked vertically
+        let (width, height, depth) = self.get_mip_size(mip_level);
+        let height = height * depth;
+        if let Some(buf) = self.to_rgba8_with(tex_count, mip_level, conversion) {
+            if let Some(buf) = ImageBuffer::from_raw(width, height, buf) {
                 let image = DynamicImage::ImageRgba8(buf);
                 let mut res = vec![];
                 if let Err(err) = image.write_to(&mut res, ImageOutputFormat::Png) {
                     web_sys::console::error_1(&format!("asPng threw an error: {}", err).into());
                     return None;
                 }
+                if conversion == SrgbConversion::Tag && self.is_srgb() {
+                    res = tag_png_srgb(&res);
+                }
                 Some(res.into_boxed_slice())
             } else {
                 None
@@ -0,0 +1,674 @@
+//! Software decoder of ASTC compressed textures of all 2D block sizes.
+//!
+//! Blocks are decoded with the LDR profile, so HDR endpoint modes decode to the error color.
+//! HDR void extent blocks are clamped to the range between zero and one.
+//!
+//! See https://registry.khronos.org/DataFormat/specs/1.3/dataformat.1.3.html#ASTC
+
+use super::{bcn::half_to_f32, util::div_round_up};
+
+/// Color of invalid blocks.
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Encoding {
+    Bits,
+    Trits,
+    Quints,
+}
+
+/// Ranges of integer sequences as number of levels, encoding and number of bits per value.
+/// Weights only use the first twelve ranges.
+pub(super) const RANGES: [(u32, Encoding, u32); 21] = [
+    (2, Encoding::Bits, 1),
+    (3, Encoding::Trits, 0),
+    (4, Encoding::Bits, 2),
+    (5, Encoding::Quints, 0),
+    (6, Encoding::Trits, 1),
+    (8, Encoding::Bits, 3),
+    (10, Encoding::Quints, 1),
+    (12, Encoding::Trits, 2),
+    (16, Encoding::Bits, 4),
+    (20, Encoding::Quints, 2),
+    (24, Encoding::Trits, 3),
+    (32, Encoding::Bits, 5),
+    (40, Encoding::Quints, 3),
+    (48, Encoding::Trits, 4),
+    (64, Encoding::Bits, 6),
+    (80, Encoding::Quints, 4),
+    (96, Encoding::Trits, 5),
+    (128, Encoding::Bits, 7),
+    (160, Encoding::Quints, 5),
+    (192, Encoding::Trits, 6),
+    (256, Encoding::Bits, 8),
+];
+
+/// Color endpoints need at least six levels.
+pub(super) const MIN_COLOR_RANGE: usize = 4;
+
+pub(super) struct BlockMode {
+    pub width: u32,
+    pub height: u32,
+    pub dual_plane: bool,
+    pub weight_range: usize,
+}
+
+pub(super) fn sequence_bit_count(count: u32, range: usize) -> u32 {
+    let (_, encoding, bits) = RANGES[range];
+    count * bits
+        + match encoding {
+            Encoding::Bits => 0,
+            Encoding::Trits => div_round_up(8 * count, 5),
+            Encoding::Quints => div_round_up(7 * count, 3),
+        }
+}
+
+/// Read bits of a block, where bits from `end` on are zero.
+fn read_bits(bits: u128, position: u32, count: u32, end: u32) -> u32 {
+    if position >= end {
+        return 0;
+    }
+    let count = count.min(end - position);
+    ((bits >> position) & ((1 << count) - 1)) as u32
+}
+
+pub(super) fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |value: u32, n: u32| (value >> n) & 1;
+    let (c, t3, t4) = if (t >> 2) & 7 == 7 {
+        (((t >> 5) & 7) << 2 | (t & 3), 2, 2)
+    } else if (t >> 5) & 3 == 3 {
+        (t & 0x1f, bit(t, 7), 2)
+    } else {
+        (t & 0x1f, (t >> 5) & 3, bit(t, 7))
+    };
+    let (t0, t1, t2) = if c & 3 == 3 {
+        (bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1), bit(c, 4), 2)
+    } else if (c >> 2) & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        (
+            bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1),
+            (c >> 2) & 3,
+            bit(c, 4),
+        )
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+pub(super) fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |n: u32| (q >> n) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (c, q2) = if (q >> 1) & 3 == 3 {
+        (((q >> 3) & 3) << 3 | (!(q >> 5) & 3) << 1 | bit(0), 4)
+    } else {
+        (q & 0x1f, (q >> 5) & 3)
+    };
+    let (q0, q1) = if c & 7 == 5 {
+        ((c >> 3) & 3, 4)
+    } else {
+        (c & 7, (c >> 3) & 3)
+    };
+    [q0, q1, q2]
+}
+
+/// Decode an integer sequence, whose trits or quints are interleaved with the bits of its values.
+pub(super) fn decode_sequence(bits: u128, start: u32, count: u32, range: usize) -> Vec<u32> {
+    let (_, encoding, bit_count) = RANGES[range];
+    let end = start + sequence_bit_count(count, range);
+    let mut position = start;
+    let mut read = |count: u32| {
+        let value = read_bits(bits, position, count, end);
+        position += count;
+        value
+    };
+
+    let mut values = Vec::with_capacity(count as usize + 4);
+    while values.len() < count as usize {
+        match encoding {
+            Encoding::Bits => values.push(read(bit_count)),
+            Encoding::Trits => {
+                let mut low_bits = [0; 5];
+                let mut t = 0;
+                for (i, &(shift, count)) in
+                    [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].iter().enumerate()
+                {
+                    low_bits[i] = read(bit_count);
+                    t |= read(count) << shift;
+                }
+                for (low_bits, trit) in low_bits.iter().zip(decode_trits(t).iter()) {
+                    values.push(trit << bit_count | low_bits);
+                }
+            }
+            Encoding::Quints => {
+                let mut low_bits = [0; 3];
+                let mut q = 0;
+                for (i, &(shift, count)) in [(0, 3), (3, 2), (5, 2)].iter().enumerate() {
+                    low_bits[i] = read(bit_count);
+                    q |= read(count) << shift;
+                }
+                for (low_bits, quint) in low_bits.iter().zip(decode_quints(q).iter()) {
+                    values.push(quint << bit_count | low_bits);
+                }
+            }
+        }
+    }
+    values.truncate(count as usize);
+    values
+}
+
+/// Repeat the bits of a value until it has `to` bits.
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let mut res = 0;
+    let mut filled = 0;
+    while filled < to {
+        res = (res << bits) | value;
+        filled += bits;
+    }
+    res >> (filled - to)
+}
+
+pub(super) fn unquantize_color(value: u32, range: usize) -> i32 {
+    let (_, encoding, bits) = RANGES[range];
+    if encoding == Encoding::Bits {
+        return replicate(value, bits, 8) as i32;
+    }
+    let (d, low_bits) = (value >> bits, value & ((1 << bits) - 1));
+    let a = if low_bits & 1 == 1 { 0x1ff } else { 0 };
+    let x = low_bits >> 1;
+    let (b, c) = match (encoding, bits) {
+        (Encoding::Trits, 1) => (0, 204),
+        (Encoding::Trits, 2) => (x << 8 | x << 4 | x << 2 | x << 1, 93),
+        (Encoding::Trits, 3) => (x << 7 | x << 2 | x, 44),
+        (Encoding::Trits, 4) => (x << 6 | x, 22),
+        (Encoding::Trits, 5) => (x << 5 | x >> 2, 11),
+        (Encoding::Trits, 6) => (x << 4 | x >> 4, 5),
+        (Encoding::Quints, 1) => (0, 113),
+        (Encoding::Quints, 2) => (x << 8 | x << 3 | x << 2, 54),
+        (Encoding::Quints, 3) => (x << 7 | x << 1 | x >> 1, 26),
+        (Encoding::Quints, 4) => (x << 6 | x >> 1, 13),
+        (Encoding::Quints, 5) => (x << 5 | x >> 3, 6),
+        _ => (0, 0),
+    };
+    ((a & 0x80) | (((d * c + b) ^ a) >> 2)) as i32
+}
+
+/// Unquantize a weight to the range between 0 and 64.
+pub(super) fn unquantize_weight(value: u32, range: usize) -> u32 {
+    let (_, encoding, bits) = RANGES[range];
+    let res = match (encoding, bits) {
+        (Encoding::Bits, _) => replicate(value, bits, 6),
+        (Encoding::Trits, 0) => [0, 32, 63][value as usize],
+        (Encoding::Quints, 0) => [0, 16, 32, 47, 63][value as usize],
+        _ => {
+            let (d, low_bits) = (value >> bits, value & ((1 << bits) - 1));
+            let a = if low_bits & 1 == 1 { 0x7f } else { 0 };
+            let x = low_bits >> 1;
+            let (b, c) = match (encoding, bits) {
+                (Encoding::Trits, 1) => (0, 50),
+                (Encoding::Trits, 2) => (x << 6 | x << 2 | x, 23),
+                (Encoding::Trits, 3) => (x << 5 | x, 11),
+                (Encoding::Quints, 1) => (0, 28),
+                (Encoding::Quints, 2) => (x << 6 | x << 1, 13),
+                _ => (0, 0),
+            };
+            (a & 0x20) | (((d * c + b) ^ a) >> 2)
+        }
+    };
+    if res > 32 {
+        res + 1
+    } else {
+        res
+    }
+}
+
+pub(super) fn decode_block_mode(mode: u32) -> Option<BlockMode> {
+    let a = (mode >> 5) & 3;
+    let mut high_precision = (mode >> 9) & 1 == 1;
+    let mut dual_plane = (mode >> 10) & 1 == 1;
+    let (range, width, height);
+    if mode & 3 != 0 {
+        range = (mode >> 4) & 1 | (mode & 3) << 1;
+        let b = (mode >> 7) & 3;
+        let size = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 != 0 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        };
+        width = size.0;
+        height = size.1;
+    } else {
+        range = (mode >> 4) & 1 | ((mode >> 2) & 3) << 1;
+        if (mode >> 2) & 3 == 0 {
+            return None;
+        }
+        let b = (mode >> 9) & 3;
+        let size = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                high_precision = false;
+                dual_plane = false;
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        width = size.0;
+        height = size.1;
+    }
+    Some(BlockMode {
+        width,
+        height,
+        dual_plane,
+        weight_range: (range - 2 + 6 * high_precision as u32) as usize,
+    })
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3f;
+    if a & 0x20 != 0 {
+        (a - 0x40, b)
+    } else {
+        (a, b)
+    }
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    let [r, g, b, a] = color;
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Decode the endpoints of an LDR color endpoint mode, or `None` for HDR modes.
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<[[i32; 4]; 2]> {
+    let endpoints = match mode {
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = l0 + (v[1] & 0x3f);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (l1, l0) = bit_transfer_signed(v[1], v[0]);
+            let (a1, a0) = bit_transfer_signed(v[3], v[2]);
+            [[l0, l0, l0, a0], [l0 + l1, l0 + l1, l0 + l1, a0 + a1]]
+        }
+        6 | 10 => {
+            let (a0, a1) = if mode == 10 { (v[4], v[5]) } else { (255, 255) };
+            [
+                [
+                    (v[0] * v[3]) >> 8,
+                    (v[1] * v[3]) >> 8,
+                    (v[2] * v[3]) >> 8,
+                    a0,
+                ],
+                [v[0], v[1], v[2], a1],
+            ]
+        }
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                ]
+            }
+        }
+        9 | 13 => {
+            let mut base = [255; 4];
+            let mut offset = [0; 4];
+            for i in 0..if mode == 13 { 4 } else { 3 } {
+                let (o, b) = bit_transfer_signed(v[i * 2 + 1], v[i * 2]);
+                offset[i] = o;
+                base[i] = b;
+            }
+            let mut sum = base;
+            for (sum, offset) in sum.iter_mut().zip(offset.iter()) {
+                *sum += offset;
+            }
+            if offset[0] + offset[1] + offset[2] >= 0 {
+                [base, sum]
+            } else {
+                [blue_contract(sum), blue_contract(base)]
+            }
+        }
+        _ => return None,
+    };
+    let mut res = endpoints;
+    for endpoint in res.iter_mut() {
+        for channel in endpoint.iter_mut() {
+            *channel = (*channel).clamp(0, 255);
+        }
+    }
+    Some(res)
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_mul(0xeede0891);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// Partition of a texel, computed from the partition index as seed.
+pub(super) fn select_partition(
+    seed: u32,
+    x: u32,
+    y: u32,
+    partition_count: u32,
+    small_block: bool,
+) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partition_count - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 8];
+    for (i, s) in seeds.iter_mut().enumerate() {
+        *s = (rnum >> (i * 4)) & 0xf;
+        *s *= *s;
+    }
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partition_count == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partition_count == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+    for (i, s) in seeds.iter_mut().enumerate() {
+        *s >>= if i % 2 == 0 { sh1 } else { sh2 };
+    }
+
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3f;
+    let c = if partition_count < 3 {
+        0
+    } else {
+        (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3f
+    };
+    let d = if partition_count < 4 {
+        0
+    } else {
+        (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3f
+    };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+/// Indices into a weight grid and their factors in sixteenths for bilinearly interpolating
+/// the grid at a texel.
+pub(super) fn infill_taps(
+    grid_size: (u32, u32),
+    block_size: (u32, u32),
+    x: u32,
+    y: u32,
+) -> [(u32, u32); 4] {
+    let ds = (1024 + block_size.0 / 2) / (block_size.0 - 1);
+    let dt = (1024 + block_size.1 / 2) / (block_size.1 - 1);
+    let gs = (ds * x * (grid_size.0 - 1) + 32) >> 6;
+    let gt = (dt * y * (grid_size.1 - 1) + 32) >> 6;
+    let (js, fs) = (gs >> 4, gs & 0xf);
+    let (jt, ft) = (gt >> 4, gt & 0xf);
+    let w11 = (fs * ft + 8) >> 4;
+    let w10 = ft - w11;
+    let w01 = fs - w11;
+    let w00 = 16 + w11 - fs - ft;
+
+    let v0 = js + jt * grid_size.0;
+    [
+        (v0, w00),
+        (v0 + 1, w01),
+        (v0 + grid_size.0, w10),
+        (v0 + grid_size.0 + 1, w11),
+    ]
+}
+
+/// Bilinearly interpolate the weight grid at a texel.
+fn infill_weight(weights: &[u32], mode: &BlockMode, block_size: (u32, u32), x: u32, y: u32) -> u32 {
+    let weight = |index: u32| weights.get(index as usize).copied().unwrap_or(0);
+    (infill_taps((mode.width, mode.height), block_size, x, y)
+        .iter()
+        .map(|(index, factor)| weight(*index) * factor)
+        .sum::<u32>()
+        + 8)
+        >> 4
+}
+
+fn decode_block(block: &[u8], block_width: u32, block_height: u32) -> Option<Vec<[u8; 4]>> {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&block[..16]);
+    let bits = u128::from_le_bytes(bytes);
+    let texel_count = (block_width * block_height) as usize;
+
+    if bits & 0x1ff == 0x1fc {
+        // Void extent blocks have a single color
+        let mut color = [0; 4];
+        for (i, channel) in color.iter_mut().enumerate() {
+            let value = (bits >> (64 + i * 16)) as u16;
+            *channel = if (bits >> 9) & 1 == 1 {
+                (half_to_f32(value).clamp(0., 1.) * 255.).round() as u8
+            } else {
+                (value >> 8) as u8
+            };
+        }
+        return Some(vec![color; texel_count]);
+    }
+
+    let mode = decode_block_mode(bits as u32 & 0x7ff)?;
+    let plane_count = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.width * mode.height * plane_count;
+    if mode.width > block_width || mode.height > block_height || weight_count > 64 {
+        return None;
+    }
+    let weight_bits = sequence_bit_count(weight_count, mode.weight_range);
+    if !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+
+    let partition_count = ((bits >> 11) & 3) as u32 + 1;
+    if partition_count == 4 && mode.dual_plane {
+        return None;
+    }
+    let mut below_weights = 128 - weight_bits;
+    let mut endpoint_modes = [0; 4];
+    let (partition_index, color_start, color_bits);
+    if partition_count == 1 {
+        endpoint_modes[0] = ((bits >> 13) & 0xf) as u32;
+        partition_index = 0;
+        color_start = 17;
+        color_bits = 111 - weight_bits as i32;
+    } else {
+        partition_index = ((bits >> 13) & 0x3ff) as u32;
+        let selector = ((bits >> 23) & 3) as u32;
+        let mut extra_bits = 0;
+        if selector == 0 {
+            let endpoint_mode = ((bits >> 25) & 0xf) as u32;
+            for mode in endpoint_modes.iter_mut() {
+                *mode = endpoint_mode;
+            }
+        } else {
+            // Endpoint modes of more partitions continue below the weights
+            extra_bits = 3 * partition_count - 4;
+            below_weights -= extra_bits;
+            let encoded =
+                ((bits >> 23) & 0x3f) as u32 | read_bits(bits, below_weights, extra_bits, 128) << 6;
+            for (i, mode) in endpoint_modes
+                .iter_mut()
+                .enumerate()
+                .take(partition_count as usize)
+            {
+                let class = ((encoded >> (2 + i)) & 1) + selector - 1;
+                *mode = class << 2 | ((encoded >> (2 + partition_count as usize + i * 2)) & 3);
+            }
+        }
+        color_start = 29;
+        color_bits = 99 - weight_bits as i32 - extra_bits as i32;
+    }
+    let color_bits = color_bits - if mode.dual_plane { 2 } else { 0 };
+
+    let value_count = endpoint_modes
+        .iter()
+        .take(partition_count as usize)
+        .map(|mode| ((mode >> 2) + 1) * 2)
+        .sum::<u32>();
+    if value_count > 18 {
+        return None;
+    }
+    let color_range = (MIN_COLOR_RANGE..RANGES.len())
+        .rev()
+        .find(|&range| sequence_bit_count(value_count, range) as i32 <= color_bits)?;
+    let values = decode_sequence(bits, color_start, value_count, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_range))
+        .collect::<Vec<_>>();
+    let mut endpoints = Vec::with_capacity(partition_count as usize);
+    let mut values = values.as_slice();
+    for mode in endpoint_modes.iter().take(partition_count as usize) {
+        let count = (((mode >> 2) + 1) * 2) as usize;
+        endpoints.push(decode_endpoints(*mode, &values[..count])?);
+        values = &values[count..];
+    }
+
+    // Weights are stored in reverse, starting at the most significant bit
+    let weights = decode_sequence(bits.reverse_bits(), 0, weight_count, mode.weight_range);
+    let mut planes = vec![Vec::with_capacity(weight_count as usize); plane_count as usize];
+    for (i, weight) in weights.iter().enumerate() {
+        planes[i % plane_count as usize].push(unquantize_weight(*weight, mode.weight_range));
+    }
+    let dual_plane_channel = if mode.dual_plane {
+        Some(read_bits(bits, below_weights - 2, 2, 128) as usize)
+    } else {
+        None
+    };
+
+    let mut texels = Vec::with_capacity(texel_count);
+    for y in 0..block_height {
+        for x in 0..block_width {
+            let partition = if partition_count > 1 {
+                select_partition(partition_index, x, y, partition_count, texel_count < 31)
+            } else {
+                0
+            };
+            let [e0, e1] = endpoints[partition];
+            let plane_weights = planes
+                .iter()
+                .map(|plane| infill_weight(plane, &mode, (block_width, block_height), x, y))
+                .collect::<Vec<_>>();
+            let mut texel = [0; 4];
+            for (channel, value) in texel.iter_mut().enumerate() {
+                let weight = if dual_plane_channel == Some(channel) {
+                    plane_weights[1]
+                } else {
+                    plane_weights[0]
+                };
+                let (c0, c1) = (e0[channel] as u32 * 257, e1[channel] as u32 * 257);
+                *value = (((c0 * (64 - weight) + c1 * weight + 32) >> 6) >> 8) as u8;
+            }
+            texels.push(texel);
+        }
+    }
+    Some(texels)
+}
+
+/// Decode an ASTC image with the given block size into RGBA8.
+pub fn decode_astc(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_width: u32,
+    block_height: u32,
+) -> Vec<u8> {
+    let mut res = vec![0; (width * height * 4) as usize];
+    let blocks_x = div_round_up(width, block_width);
+    for (i, block) in data
+        .chunks_exact(16)
+        .take((blocks_x * div_round_up(height, block_height)) as usize)
+        .enumerate()
+    {
+        let texels = decode_block(block, block_width, block_height)
+            .unwrap_or_else(|| vec![ERROR_COLOR; (block_width * block_height) as usize]);
+        let (block_x, block_y) = (
+            i as u32 % blocks_x * block_width,
+            i as u32 / blocks_x * block_height,
+        );
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (
+                block_x + j as u32 % block_width,
+                block_y + j as u32 / block_width,
+            );
+            if x < width && y < height {
+                let position = ((y * width + x) * 4) as usize;
+                res[position..position + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(7, &[0, 64, 17, 47, 5, 59, 23, 41, 11, 53, 28, 36]; "trits")]
+    #[test_case(9, &[0, 64, 16, 48, 3, 61, 19, 45, 6, 58, 23, 41, 9, 55, 26, 38, 13, 51, 29, 35]; "quints")]
+    fn test_unquantize_weight(range: usize, expected: &[u32]) {
+        let weights = (0..expected.len() as u32)
+            .map(|value| unquantize_weight(value, range))
+            .collect::<Vec<_>>();
+        assert_eq!(weights, expected);
+    }
+
+    #[test]
+    fn test_decode_void_extent() {
+        let bits = 0x1fc | 0xffff_ffff_ffff_fc00u128 | 0xffff_0000_8000_ffffu128 << 64;
+        let image = decode_astc(&bits.to_le_bytes(), 5, 4, 5, 4);
+        assert!(image.chunks(4).all(|texel| texel == [255, 128, 0, 255]));
+    }
+
+    #[test]
+    fn test_decode_rgb_direct() {
+        // 4x4 grid of two bit weights, one partition with direct RGB endpoints from black to white
+        let mut bits = 0x42 | 8 << 13;
+        for (i, value) in [0, 255, 0, 255, 0, 255].iter().enumerate() {
+            bits |= (*value as u128) << (17 + i * 8);
+        }
+        let weights: u128 = 3 << 2 | 1 << 4;
+        bits |= weights.reverse_bits();
+        let image = decode_astc(&bits.to_le_bytes(), 4, 4, 4, 4);
+        assert_eq!(
+            &image[..12],
+            &[0, 0, 0, 255, 255, 255, 255, 255, 84, 84, 84, 255]
+        );
+    }
+}
@@ -0,0 +1,711 @@
+//! Software encoder of ASTC textures of all 2D block sizes with the LDR profile.
+//!
+//! Blocks have one or two partitions with direct RGB or RGBA endpoints, or are void extent
+//! blocks if all their texels are equal. The weight grid and the quantization of weights
+//! and endpoints are chosen per block among the layouts that fit the block size.
+
+use super::astc::{
+    decode_block_mode, decode_quints, decode_trits, infill_taps, select_partition,
+    sequence_bit_count, unquantize_color, unquantize_weight, Encoding, MIN_COLOR_RANGE, RANGES,
+};
+use super::bcn_encoder::{fit_line, least_squares, line_error, BitWriter};
+use super::util::div_round_up;
+use super::EncodeQuality;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+/// Color endpoint modes with direct RGB and RGBA values.
+const RGB_DIRECT: u32 = 8;
+const RGBA_DIRECT: u32 = 12;
+
+/// Weight grid of a block and the ranges of its weights and color endpoint values.
+struct Layout {
+    block_mode: u32,
+    grid_size: (u32, u32),
+    weight_range: usize,
+    color_range: usize,
+    /// Unquantized weights and color values in ascending order with their quantized values.
+    weight_levels: Vec<(i32, u32)>,
+    color_levels: Vec<(i32, u32)>,
+    /// Grid weights every texel is interpolated from.
+    taps: Vec<[(u32, u32); 4]>,
+}
+
+impl Layout {
+    /// Whether the layout has at least the grid size and precision of another one.
+    fn covers(&self, other: &Layout) -> bool {
+        self.grid_size.0 >= other.grid_size.0
+            && self.grid_size.1 >= other.grid_size.1
+            && self.weight_range >= other.weight_range
+            && self.color_range >= other.color_range
+    }
+
+    /// Rough estimate of the error of the layout, which is lower for more promising layouts.
+    fn estimate_error(&self, texel_count: u32) -> f32 {
+        let step = |range: usize| 1. / (RANGES[range].0 - 1) as f32;
+        let coverage = (self.grid_size.0 * self.grid_size.1) as f32 / texel_count as f32;
+        (step(self.weight_range).powi(2) + step(self.color_range).powi(2)) / 12.
+            + (1. - coverage) / 20.
+    }
+}
+
+fn levels(count: u32, unquantize: impl Fn(u32) -> i32) -> Vec<(i32, u32)> {
+    let mut levels: Vec<(i32, u32)> = (0..count).map(|value| (unquantize(value), value)).collect();
+    levels.sort_unstable();
+    levels
+}
+
+/// Index of the level nearest to a value.
+fn nearest_level(levels: &[(i32, u32)], value: f32) -> usize {
+    let index = levels.partition_point(|level| (level.0 as f32) < value);
+    if index == 0 {
+        0
+    } else if index == levels.len()
+        || value - levels[index - 1].0 as f32 <= levels[index].0 as f32 - value
+    {
+        index - 1
+    } else {
+        index
+    }
+}
+
+/// Layouts with a single weight plane for a block size, number of endpoint values and bits
+/// left for weights and endpoint values, from the most to the least promising. Layouts that
+/// another layout covers are left out.
+fn get_layouts(block_size: (u32, u32), value_count: u32, available_bits: u32) -> Vec<Layout> {
+    let mut layouts: Vec<Layout> = vec![];
+    for block_mode in 0..0x800 {
+        // These bits mark void extent blocks
+        if block_mode & 0x1ff == 0x1fc {
+            continue;
+        }
+        let mode = match decode_block_mode(block_mode) {
+            Some(mode) => mode,
+            None => continue,
+        };
+        let weight_count = mode.width * mode.height;
+        let weight_bits = sequence_bit_count(weight_count, mode.weight_range);
+        if mode.dual_plane
+            || mode.width > block_size.0
+            || mode.height > block_size.1
+            || weight_count > 64
+            || !(24..=96).contains(&weight_bits)
+            || weight_bits >= available_bits
+        {
+            continue;
+        }
+        let color_range = match (MIN_COLOR_RANGE..RANGES.len())
+            .rev()
+            .find(|range| sequence_bit_count(value_count, *range) <= available_bits - weight_bits)
+        {
+            Some(range) => range,
+            None => continue,
+        };
+
+        // Levels and taps are only computed for the layouts that are kept
+        let layout = Layout {
+            block_mode,
+            grid_size: (mode.width, mode.height),
+            weight_range: mode.weight_range,
+            color_range,
+            weight_levels: vec![],
+            color_levels: vec![],
+            taps: vec![],
+        };
+        if layouts.iter().any(|other| other.covers(&layout)) {
+            continue;
+        }
+        layouts.retain(|other| !layout.covers(other));
+        layouts.push(layout);
+    }
+
+    let texel_count = block_size.0 * block_size.1;
+    layouts.sort_by(|a, b| {
+        a.estimate_error(texel_count)
+            .total_cmp(&b.estimate_error(texel_count))
+    });
+    // Start with the most promising layout of every grid size, since which grid size is best
+    // depends on the block
+    let mut ranks = HashMap::new();
+    let mut ranked: Vec<(usize, Layout)> = layouts
+        .into_iter()
+        .map(|layout| {
+            let rank = ranks.entry(layout.grid_size).or_insert(0);
+            *rank += 1;
+            (*rank, layout)
+        })
+        .collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked
+        .into_iter()
+        .map(|(_, mut layout)| {
+            let (weight_range, color_range) = (layout.weight_range, layout.color_range);
+            layout.weight_levels = levels(RANGES[weight_range].0, |value| {
+                unquantize_weight(value, weight_range) as i32
+            });
+            layout.color_levels = levels(RANGES[color_range].0, |value| {
+                unquantize_color(value, color_range)
+            });
+            layout.taps = (0..texel_count)
+                .map(|i| {
+                    infill_taps(
+                        layout.grid_size,
+                        block_size,
+                        i % block_size.0,
+                        i / block_size.0,
+                    )
+                })
+                .collect();
+            layout
+        })
+        .collect()
+}
+
+/// Encode an integer sequence, which is the reverse of decoding it.
+fn encode_sequence(values: &[u32], range: usize) -> u128 {
+    let (_, encoding, bit_count) = RANGES[range];
+    let low_bits = |value: u32| value & ((1 << bit_count) - 1);
+    let mut writer = BitWriter::new();
+    match encoding {
+        Encoding::Bits => {
+            for value in values {
+                writer.write(*value, bit_count);
+            }
+        }
+        Encoding::Trits => {
+            for chunk in values.chunks(5) {
+                let mut trits = [0; 5];
+                for (trit, value) in trits.iter_mut().zip(chunk) {
+                    *trit = value >> bit_count;
+                }
+                let t = (0..256).find(|t| decode_trits(*t) == trits).unwrap();
+                for (i, &(shift, count)) in
+                    [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].iter().enumerate()
+                {
+                    writer.write(chunk.get(i).map_or(0, |value| low_bits(*value)), bit_count);
+                    writer.write((t >> shift) & ((1 << count) - 1), count);
+                }
+            }
+        }
+        Encoding::Quints => {
+            for chunk in values.chunks(3) {
+                let mut quints = [0; 3];
+                for (quint, value) in quints.iter_mut().zip(chunk) {
+                    *quint = value >> bit_count;
+                }
+                let q = (0..128).find(|q| decode_quints(*q) == quints).unwrap();
+                for (i, &(shift, count)) in [(0, 3), (3, 2), (5, 2)].iter().enumerate() {
+                    writer.write(chunk.get(i).map_or(0, |value| low_bits(*value)), bit_count);
+                    writer.write((q >> shift) & ((1 << count) - 1), count);
+                }
+            }
+        }
+    }
+    // Values missing from the last group are zero and have no bits of their own
+    writer.bits & ((1 << sequence_bit_count(values.len() as u32, range)) - 1)
+}
+
+/// A block encoded with a layout, as indices into the levels of the layout.
+struct Encoded {
+    error: u64,
+    /// Endpoints of every partition.
+    endpoints: Vec<[[usize; 4]; 2]>,
+    weights: Vec<usize>,
+}
+
+/// Texels of a block with the partition of every texel.
+struct Block<'a> {
+    texels: &'a [[u8; 4]],
+    partitions: &'a [usize],
+    /// Texels of every partition as points.
+    points: Vec<Vec<[f32; 4]>>,
+    /// Color channels, which is three if alpha is always opaque.
+    channels: usize,
+}
+
+impl<'a> Block<'a> {
+    fn new(texels: &'a [[u8; 4]], partitions: &'a [usize], partition_count: usize) -> Self {
+        let points = (0..partition_count)
+            .map(|partition| {
+                texels
+                    .iter()
+                    .zip(partitions)
+                    .filter(|(_, p)| **p == partition)
+                    .map(|(texel, _)| texel.map(|value| value as f32))
+                    .collect()
+            })
+            .collect();
+        let opaque = texels.iter().all(|texel| texel[3] == 255);
+        Self {
+            texels,
+            partitions,
+            points,
+            channels: if opaque { 3 } else { 4 },
+        }
+    }
+
+    fn decode_endpoints(&self, layout: &Layout, endpoints: &[[usize; 4]; 2]) -> [[i32; 4]; 2] {
+        endpoints.map(|endpoint| {
+            let mut decoded = [255; 4];
+            for (decoded, level) in decoded.iter_mut().zip(&endpoint).take(self.channels) {
+                *decoded = layout.color_levels[*level].0;
+            }
+            decoded
+        })
+    }
+
+    fn quantize_endpoints(&self, layout: &Layout, line: &[[f32; 4]; 2]) -> [[usize; 4]; 2] {
+        let mut endpoints = [[0; 4]; 2];
+        for (endpoint, line) in endpoints.iter_mut().zip(line) {
+            for (level, value) in endpoint.iter_mut().zip(line).take(self.channels) {
+                *level = nearest_level(&layout.color_levels, *value);
+            }
+        }
+        // The decoder contracts blue if the second endpoint is darker, which is avoided by swapping
+        let decoded = self.decode_endpoints(layout, &endpoints);
+        if decoded[1][..3].iter().sum::<i32>() < decoded[0][..3].iter().sum::<i32>() {
+            endpoints.swap(0, 1);
+        }
+        endpoints
+    }
+
+    /// Decode the texels and return their squared error and interpolation weights.
+    fn evaluate(
+        &self,
+        layout: &Layout,
+        endpoints: &[[[i32; 4]; 2]],
+        weights: &[usize],
+    ) -> (u64, Vec<u32>) {
+        let mut error = 0;
+        let mut texel_weights = Vec::with_capacity(self.texels.len());
+        for ((texel, taps), partition) in self.texels.iter().zip(&layout.taps).zip(self.partitions)
+        {
+            let weight = (taps
+                .iter()
+                .map(|(index, factor)| {
+                    weights
+                        .get(*index as usize)
+                        .map_or(0, |level| layout.weight_levels[*level].0 as u32)
+                        * factor
+                })
+                .sum::<u32>()
+                + 8)
+                >> 4;
+            let [e0, e1] = endpoints[*partition];
+            for (channel, value) in texel.iter().enumerate() {
+                let (c0, c1) = (e0[channel] as u32 * 257, e1[channel] as u32 * 257);
+                let decoded = (((c0 * (64 - weight) + c1 * weight + 32) >> 6) >> 8) as i64;
+                error += (decoded - *value as i64).pow(2) as u64;
+            }
+            texel_weights.push(weight);
+        }
+        (error, texel_weights)
+    }
+
+    /// Encode the block with a layout, refining the endpoints with least squares.
+    fn encode(&self, layout: &Layout, iterations: usize) -> Encoded {
+        let mut lines: Vec<[[f32; 4]; 2]> =
+            self.points.iter().map(|points| fit_line(points)).collect();
+        let mut best: Option<Encoded> = None;
+        for _ in 0..iterations {
+            let endpoints: Vec<[[usize; 4]; 2]> = lines
+                .iter()
+                .map(|line| self.quantize_endpoints(layout, line))
+                .collect();
+            let decoded: Vec<[[i32; 4]; 2]> = endpoints
+                .iter()
+                .map(|endpoints| self.decode_endpoints(layout, endpoints))
+                .collect();
+
+            // Project texels onto their endpoints and average them for every grid weight
+            let mut sums = vec![(0., 0.); (layout.grid_size.0 * layout.grid_size.1) as usize];
+            for ((texel, taps), partition) in
+                self.texels.iter().zip(&layout.taps).zip(self.partitions)
+            {
+                let [e0, e1] = decoded[*partition];
+                let (mut dot, mut length) = (0., 0.);
+                for channel in 0..4 {
+                    let direction = (e1[channel] - e0[channel]) as f32;
+                    dot += (texel[channel] as f32 - e0[channel] as f32) * direction;
+                    length += direction * direction;
+                }
+                let ideal = if length > 0. {
+                    (dot / length).clamp(0., 1.) * 64.
+                } else {
+                    0.
+                };
+                for (index, factor) in taps {
+                    if let Some(sum) = sums.get_mut(*index as usize) {
+                        sum.0 += ideal * *factor as f32;
+                        sum.1 += *factor as f32;
+                    }
+                }
+            }
+            let weights: Vec<usize> = sums
+                .iter()
+                .map(|(sum, factor)| {
+                    let ideal = if *factor > 0. { sum / factor } else { 0. };
+                    nearest_level(&layout.weight_levels, ideal)
+                })
+                .collect();
+
+            let (error, texel_weights) = self.evaluate(layout, &decoded, &weights);
+            if best.as_ref().map_or(true, |best| error < best.error) {
+                best = Some(Encoded {
+                    error,
+                    endpoints,
+                    weights,
+                });
+            }
+            for (partition, (line, points)) in lines.iter_mut().zip(&self.points).enumerate() {
+                let interpolation: Vec<f32> = texel_weights
+                    .iter()
+                    .zip(self.partitions)
+                    .filter(|(_, p)| **p == partition)
+                    .map(|(weight, _)| *weight as f32 / 64.)
+                    .collect();
+                if let Some(refined) = least_squares(points, &interpolation) {
+                    *line = refined;
+                }
+            }
+        }
+        best.unwrap()
+    }
+
+    /// Try moving every grid weight to its neighboring levels until nothing improves.
+    fn refine_weights(&self, layout: &Layout, encoded: &mut Encoded) {
+        let decoded: Vec<[[i32; 4]; 2]> = encoded
+            .endpoints
+            .iter()
+            .map(|endpoints| self.decode_endpoints(layout, endpoints))
+            .collect();
+        for _ in 0..4 {
+            let mut improved = false;
+            for index in 0..encoded.weights.len() {
+                let level = encoded.weights[index];
+                for candidate in [level.wrapping_sub(1), level + 1] {
+                    if candidate >= layout.weight_levels.len() {
+                        continue;
+                    }
+                    encoded.weights[index] = candidate;
+                    let (error, _) = self.evaluate(layout, &decoded, &encoded.weights);
+                    if error < encoded.error {
+                        encoded.error = error;
+                        improved = true;
+                        break;
+                    }
+                    encoded.weights[index] = level;
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+    }
+}
+
+/// Split texels into two clusters of similar colors with k-means.
+fn cluster(texels: &[[u8; 4]]) -> Vec<usize> {
+    let points: Vec<[f32; 4]> = texels
+        .iter()
+        .map(|texel| texel.map(|value| value as f32))
+        .collect();
+    let distance =
+        |a: &[f32; 4], b: &[f32; 4]| a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+    let mut centers = fit_line(&points);
+    let mut labels = vec![0; points.len()];
+    for _ in 0..4 {
+        for (label, point) in labels.iter_mut().zip(&points) {
+            *label = (distance(point, &centers[1]) < distance(point, &centers[0])) as usize;
+        }
+        for (cluster, center) in centers.iter_mut().enumerate() {
+            let count = labels.iter().filter(|label| **label == cluster).count();
+            if count == 0 {
+                continue;
+            }
+            *center = [0.; 4];
+            for (point, _) in points.iter().zip(&labels).filter(|(_, l)| **l == cluster) {
+                for (center, value) in center.iter_mut().zip(point) {
+                    *center += value / count as f32;
+                }
+            }
+        }
+    }
+    labels
+}
+
+/// Layouts and partitionings of a block size, shared by all blocks of an image.
+struct Encoder {
+    /// Layouts of one and two partitions with RGB and RGBA endpoints.
+    layouts: [[Vec<Layout>; 2]; 2],
+    /// Distinct splits of the texels into two partitions with their partition index.
+    partitionings: Vec<(u32, Vec<usize>)>,
+    quality: EncodeQuality,
+}
+
+impl Encoder {
+    fn new(block_width: u32, block_height: u32, quality: EncodeQuality) -> Self {
+        let block_size = (block_width, block_height);
+        // Two partitions leave 12 bits less for the partition index and endpoint mode
+        let layouts = [
+            [
+                get_layouts(block_size, 6, 111),
+                get_layouts(block_size, 8, 111),
+            ],
+            [
+                get_layouts(block_size, 12, 99),
+                get_layouts(block_size, 16, 99),
+            ],
+        ];
+
+        let texel_count = block_width * block_height;
+        let mut seen = HashSet::new();
+        let partitionings = (0..1024)
+            .filter_map(|index| {
+                let partitions: Vec<usize> = (0..texel_count)
+                    .map(|i| {
+                        let (x, y) = (i % block_width, i / block_width);
+                        select_partition(index, x, y, 2, texel_count < 31)
+                    })
+                    .collect();
+                // Partitionings with swapped partitions are the same for direct endpoints
+                let canonical: Vec<bool> = partitions.iter().map(|p| *p != partitions[0]).collect();
+                if canonical.contains(&true) && seen.insert(canonical) {
+                    Some((index, partitions))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Self {
+            layouts,
+            partitionings,
+            quality,
+        }
+    }
+
+    /// Partitionings that split the texels into the two partitions best fit by lines, among
+    /// those closest to a clustering of the texels.
+    fn get_partitionings(
+        &self,
+        texels: &[[u8; 4]],
+        (closest_count, count): (usize, usize),
+    ) -> Vec<&(u32, Vec<usize>)> {
+        if count == 0 {
+            return vec![];
+        }
+        let labels = cluster(texels);
+        let mut closest: Vec<(usize, &(u32, Vec<usize>))> = self
+            .partitionings
+            .iter()
+            .map(|partitioning| {
+                let different = partitioning
+                    .1
+                    .iter()
+                    .zip(&labels)
+                    .filter(|(a, b)| a != b)
+                    .count();
+                // Swapping both partitions does not change the split
+                (cmp::min(different, texels.len() - different), partitioning)
+            })
+            .collect();
+        closest.sort_by_key(|(different, _)| *different);
+
+        let mut ranked: Vec<(f32, &(u32, Vec<usize>))> = closest
+            .into_iter()
+            .take(closest_count)
+            .map(|(_, partitioning)| {
+                let error = (0..2)
+                    .map(|partition| {
+                        let points: Vec<[f32; 4]> = texels
+                            .iter()
+                            .zip(&partitioning.1)
+                            .filter(|(_, p)| **p == partition)
+                            .map(|(texel, _)| texel.map(|value| value as f32))
+                            .collect();
+                        line_error(&points)
+                    })
+                    .sum::<f32>();
+                (error, partitioning)
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ranked
+            .into_iter()
+            .take(count)
+            .map(|(_, partitioning)| partitioning)
+            .collect()
+    }
+
+    fn encode_block(&self, texels: &[[u8; 4]]) -> [u8; 16] {
+        if texels.iter().all(|texel| *texel == texels[0]) {
+            // Void extent with UNORM16 values and all coordinates set, which means no extent
+            let mut bits = 0x1fc | 0xffff_ffff_ffff_fc00u128;
+            for (channel, value) in texels[0].iter().enumerate() {
+                bits |= ((*value as u128) * 257) << (64 + channel * 16);
+            }
+            return bits.to_le_bytes();
+        }
+
+        // Layouts tried with one and two partitions, partitionings and refined candidates
+        let (layout_counts, partitioning_count, refined_count) = match self.quality {
+            EncodeQuality::Fast => ([4, 2], (8, 1), 0),
+            EncodeQuality::Normal => ([16, 8], (32, 2), 2),
+            EncodeQuality::High => ([usize::MAX, 16], (256, 8), 4),
+        };
+        let single = vec![0; texels.len()];
+        let mut blocks = vec![(None, Block::new(texels, &single, 1))];
+        for (index, partitions) in self.get_partitionings(texels, partitioning_count) {
+            blocks.push((Some(*index), Block::new(texels, partitions, 2)));
+        }
+
+        // Compare layouts with endpoints fit once and refine the best of them
+        let mut candidates = vec![];
+        for (index, block) in &blocks {
+            let partition_count = block.points.len();
+            let layouts = &self.layouts[partition_count - 1][block.channels - 3];
+            for layout in layouts.iter().take(layout_counts[partition_count - 1]) {
+                candidates.push((block.encode(layout, 1), layout, *index, block));
+            }
+        }
+        candidates.sort_by_key(|(encoded, ..)| encoded.error);
+        for (encoded, layout, _, block) in candidates.iter_mut().take(refined_count) {
+            let mut refined = block.encode(layout, self.quality.iterations());
+            if self.quality == EncodeQuality::High {
+                block.refine_weights(layout, &mut refined);
+            }
+            if refined.error < encoded.error {
+                *encoded = refined;
+            }
+        }
+
+        let (encoded, layout, index, block) = candidates
+            .into_iter()
+            .min_by_key(|(encoded, ..)| encoded.error)
+            .unwrap();
+        let mut values = vec![];
+        for endpoints in &encoded.endpoints {
+            for channel in 0..block.channels {
+                for endpoint in endpoints {
+                    values.push(layout.color_levels[endpoint[channel]].1);
+                }
+            }
+        }
+        let weights: Vec<u32> = encoded
+            .weights
+            .iter()
+            .map(|level| layout.weight_levels[*level].1)
+            .collect();
+
+        let endpoint_mode = if block.channels == 3 {
+            RGB_DIRECT
+        } else {
+            RGBA_DIRECT
+        };
+        let mut bits = layout.block_mode as u128;
+        match index {
+            None => {
+                bits |= (endpoint_mode as u128) << 13;
+                bits |= encode_sequence(&values, layout.color_range) << 17;
+            }
+            Some(index) => {
+                // Both partitions share the endpoint mode, marked by a zero selector
+                bits |= 1 << 11 | (index as u128) << 13 | (endpoint_mode as u128) << 25;
+                bits |= encode_sequence(&values, layout.color_range) << 29;
+            }
+        }
+        bits |= encode_sequence(&weights, layout.weight_range).reverse_bits();
+        bits.to_le_bytes()
+    }
+}
+
+/// Encode an RGBA8 image into ASTC with the given block size.
+pub fn encode_astc(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_width: u32,
+    block_height: u32,
+    quality: EncodeQuality,
+) -> Vec<u8> {
+    let encoder = Encoder::new(block_width, block_height, quality);
+    let (blocks_x, blocks_y) = (
+        div_round_up(width, block_width),
+        div_round_up(height, block_height),
+    );
+    let mut res = Vec::with_capacity((blocks_x * blocks_y * 16) as usize);
+    let mut texels = vec![[0; 4]; (block_width * block_height) as usize];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let x = cmp::min(block_x * block_width + i as u32 % block_width, width - 1);
+                let y = cmp::min(block_y * block_height + i as u32 / block_width, height - 1);
+                let position = ((y * width + x) * 4) as usize;
+                if let Some(value) = data.get(position..position + 4) {
+                    texel.copy_from_slice(value);
+                }
+            }
+            res.extend_from_slice(&encoder.encode_block(&texels));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::astc::{decode_astc, decode_sequence};
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_encode_sequence() {
+        for (range, (levels, ..)) in RANGES.iter().enumerate() {
+            for count in 1..12 {
+                let values: Vec<u32> = (0..count).map(|i| (i * 7 + 3) % levels).collect();
+                let bits = encode_sequence(&values, range);
+                assert_eq!(decode_sequence(bits, 0, count, range), values);
+            }
+        }
+    }
+
+    #[test_case(4, 4, EncodeQuality::Fast, 6.; "4x4 fast")]
+    #[test_case(4, 4, EncodeQuality::High, 4.; "4x4 high")]
+    #[test_case(6, 5, EncodeQuality::Normal, 8.; "6x5 normal")]
+    #[test_case(8, 8, EncodeQuality::Normal, 10.; "8x8 normal")]
+    fn test_encode_astc(
+        block_width: u32,
+        block_height: u32,
+        quality: EncodeQuality,
+        max_error: f64,
+    ) {
+        let (width, height) = (30, 18);
+        let image: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                let alpha = if y < 8 { 255 } else { 255 - x * 8 };
+                [
+                    (x * 8) as u8,
+                    (y * 14) as u8,
+                    if x > y { 200 } else { 0 },
+                    alpha as u8,
+                ]
+            })
+            .collect();
+        let encoded = encode_astc(&image, width, height, block_width, block_height, quality);
+        let decoded = decode_astc(&encoded, width, height, block_width, block_height);
+        let error = image
+            .iter()
+            .zip(&decoded)
+            .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+            .sum::<f64>()
+            / image.len() as f64;
+        assert!(error.sqrt() < max_error, "{}", error.sqrt());
+    }
+
+    #[test]
+    fn test_encode_void_extent() {
+        let image = [10, 20, 30, 40].repeat(16);
+        let encoded = encode_astc(&image, 4, 4, 4, 4, EncodeQuality::Fast);
+        assert_eq!(decode_astc(&encoded, 4, 4, 4, 4), image);
+    }
+}
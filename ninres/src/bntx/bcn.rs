@@ -0,0 +1,793 @@
+//! Software decoders of the block compressed formats BC1 to BC7.
+//!
+//! All formats store 4x4 texel blocks. Images are returned as RGBA8,
+//! except for BC6H, which returns RGBA16F as the bits of half precision floats.
+//!
+//! See https://learn.microsoft.com/windows/win32/direct3d11/texture-block-compression-in-direct3d-11
+
+use super::util::div_round_up;
+
+/// Two subset partitions of BC6H and BC7, one bit per texel.
+const PARTITIONS2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Three subset partitions of BC7, two bits per texel.
+const PARTITIONS3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor texel of the second subset of two subset partitions.
+pub(super) const ANCHORS2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subset of three subset partitions.
+pub(super) const ANCHORS3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+const WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads bits of a 128 bit block, starting with the least significant bit.
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&block[..16]);
+        Self {
+            bits: u128::from_le_bytes(bytes),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+pub(super) fn subset_of(partition: usize, subsets: usize, texel: usize) -> usize {
+    match subsets {
+        2 => (PARTITIONS2[partition] >> texel) as usize & 1,
+        3 => (PARTITIONS3[partition] >> (texel * 2)) as usize & 3,
+        _ => 0,
+    }
+}
+
+/// Whether a texel is the anchor of its subset, whose index has one bit less.
+pub(super) fn is_anchor(partition: usize, subsets: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            2 => ANCHORS2[partition] as usize == texel,
+            3 => ANCHORS3[partition].contains(&(texel as u8)),
+            _ => false,
+        }
+}
+
+pub(super) fn interpolate(a: u32, b: u32, weight: u32) -> u32 {
+    ((64 - weight) * a + weight * b + 32) >> 6
+}
+
+/// Decode all blocks of an image, which is cropped to `width` and `height`.
+fn decode_blocks<T, F>(data: &[u8], width: u32, height: u32, block_size: usize, decode: F) -> Vec<T>
+where
+    T: Copy + Default,
+    F: Fn(&[u8]) -> [[T; 4]; 16],
+{
+    let mut res = vec![T::default(); (width * height * 4) as usize];
+    let blocks_x = div_round_up(width, 4);
+    for (i, block) in data
+        .chunks_exact(block_size)
+        .take((blocks_x * div_round_up(height, 4)) as usize)
+        .enumerate()
+    {
+        let texels = decode(block);
+        let (block_x, block_y) = (i as u32 % blocks_x * 4, i as u32 / blocks_x * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (block_x + j as u32 % 4, block_y + j as u32 / 4);
+            if x < width && y < height {
+                let position = ((y * width + x) * 4) as usize;
+                res[position..position + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    res
+}
+
+fn expand_565(color: u16) -> [u32; 3] {
+    let (r, g, b) = (
+        (color >> 11) as u32,
+        (color >> 5) as u32 & 0x3f,
+        color as u32 & 0x1f,
+    );
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Colors of a BC1 to BC3 block. BC2 and BC3 always use four colors.
+pub(super) fn color_palette(color0: u16, color1: u16, allow_transparent: bool) -> [[u8; 4]; 4] {
+    let (c0, c1) = (expand_565(color0), expand_565(color1));
+    let mut palette = [[0; 4]; 4];
+    for channel in 0..3 {
+        let (a, b) = (c0[channel], c1[channel]);
+        let values = if color0 > color1 || !allow_transparent {
+            [a, b, (2 * a + b) / 3, (a + 2 * b) / 3]
+        } else {
+            [a, b, (a + b) / 2, 0]
+        };
+        for (color, value) in palette.iter_mut().zip(values.iter()) {
+            color[channel] = *value as u8;
+        }
+    }
+    for (i, color) in palette.iter_mut().enumerate() {
+        color[3] = if color0 <= color1 && allow_transparent && i == 3 {
+            0
+        } else {
+            255
+        };
+    }
+    palette
+}
+
+/// Decode the color part of BC1 to BC3 blocks.
+fn decode_color_block(block: &[u8], allow_transparent: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(color0, color1, allow_transparent);
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2)) as usize & 3];
+    }
+    texels
+}
+
+/// Values of a BC4 block or the alpha part of a BC3 block.
+pub(super) fn alpha_palette(a: i32, b: i32, signed: bool) -> [i32; 8] {
+    let (min, max) = if signed { (-127, 127) } else { (0, 255) };
+    let mut palette = [a, b, 0, 0, 0, 0, 0, 0];
+    if a > b {
+        for (i, value) in palette.iter_mut().enumerate().skip(2) {
+            *value = ((8 - i as i32) * a + (i as i32 - 1) * b) / 7;
+        }
+    } else {
+        for (i, value) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *value = ((6 - i as i32) * a + (i as i32 - 1) * b) / 5;
+        }
+        palette[6] = min;
+        palette[7] = max;
+    }
+    palette
+}
+
+/// Decode a BC4 block or the alpha part of a BC3 block into values between -127 and 255.
+fn decode_alpha_block(block: &[u8], signed: bool) -> [i32; 16] {
+    let (a, b) = if signed {
+        (
+            (block[0] as i8).max(-127) as i32,
+            (block[1] as i8).max(-127) as i32,
+        )
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let palette = alpha_palette(a, b, signed);
+
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (i * 3)) as usize & 7];
+    }
+    values
+}
+
+/// Map values of BC4 and BC5 blocks to bytes.
+fn to_unorm(value: i32, signed: bool) -> u8 {
+    if signed {
+        ((value + 127) * 255 / 254) as u8
+    } else {
+        value as u8
+    }
+}
+
+/// Decode a BC1 image, where colors may be transparent.
+pub fn decode_bc1(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, |block| {
+        decode_color_block(block, true)
+    })
+}
+
+/// Decode a BC2 image with explicit four bit alpha values.
+pub fn decode_bc2(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let mut texels = decode_color_block(&block[8..], false);
+        for (i, texel) in texels.iter_mut().enumerate() {
+            texel[3] = ((block[i / 2] >> (i % 2 * 4)) & 0xf) * 0x11;
+        }
+        texels
+    })
+}
+
+/// Decode a BC3 image with interpolated alpha values.
+pub fn decode_bc3(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let mut texels = decode_color_block(&block[8..], false);
+        let alpha = decode_alpha_block(block, false);
+        for (texel, alpha) in texels.iter_mut().zip(alpha.iter()) {
+            texel[3] = *alpha as u8;
+        }
+        texels
+    })
+}
+
+/// Decode a BC4 image into the red channel.
+pub fn decode_bc4(data: &[u8], width: u32, height: u32, signed: bool) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, |block| {
+        let red = decode_alpha_block(block, signed);
+        let mut texels = [[0, 0, 0, 255]; 16];
+        for (texel, red) in texels.iter_mut().zip(red.iter()) {
+            texel[0] = to_unorm(*red, signed);
+        }
+        texels
+    })
+}
+
+/// Decode a BC5 image into the red and green channels.
+pub fn decode_bc5(data: &[u8], width: u32, height: u32, signed: bool) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let red = decode_alpha_block(block, signed);
+        let green = decode_alpha_block(&block[8..], signed);
+        let mut texels = [[0, 0, 0, 255]; 16];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            texel[0] = to_unorm(red[i], signed);
+            texel[1] = to_unorm(green[i], signed);
+        }
+        texels
+    })
+}
+
+/// Endpoint fields of BC6H modes.
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+const D: usize = 12;
+
+/// Layout of a BC6H mode after its mode bits.
+/// Each entry reads `count` bits into a field, starting at bit `first`.
+struct Bc6hMode {
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    fields: &'static [(usize, u32, u32)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        (GY, 4, 1), (BY, 4, 1), (BZ, 4, 1), (RW, 0, 10), (GW, 0, 10), (BW, 0, 10),
+        (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5),
+        (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        (GY, 5, 1), (GZ, 4, 1), (GZ, 5, 1), (RW, 0, 7), (BZ, 0, 1), (BZ, 1, 1),
+        (BY, 4, 1), (GW, 0, 7), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 7),
+        (BZ, 3, 1), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5), (RW, 10, 1), (GY, 0, 4),
+        (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1),
+        (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+        (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (GZ, 4, 1),
+        (GY, 0, 4), (GX, 0, 5), (GW, 10, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1),
+        (BZ, 1, 1), (BY, 0, 4), (RY, 0, 4), (BZ, 0, 1), (BZ, 2, 1), (RZ, 0, 4),
+        (GY, 4, 1), (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (BY, 4, 1),
+        (GY, 0, 4), (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5),
+        (BW, 10, 1), (BY, 0, 4), (RY, 0, 4), (BZ, 1, 1), (BZ, 2, 1), (RZ, 0, 4),
+        (BZ, 4, 1), (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        (RW, 0, 9), (BY, 4, 1), (GW, 0, 9), (GY, 4, 1), (BW, 0, 9), (BZ, 4, 1),
+        (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5),
+        (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        (RW, 0, 8), (GZ, 4, 1), (BY, 4, 1), (GW, 0, 8), (BZ, 2, 1), (GY, 4, 1),
+        (BW, 0, 8), (BZ, 3, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 5),
+        (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 6),
+        (RZ, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        (RW, 0, 8), (BZ, 0, 1), (BY, 4, 1), (GW, 0, 8), (GY, 5, 1), (GY, 4, 1),
+        (BW, 0, 8), (GZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 6), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5),
+        (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        (RW, 0, 8), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 8), (BY, 5, 1), (GY, 4, 1),
+        (BW, 0, 8), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 5),
+        (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        (RW, 0, 6), (GZ, 4, 1), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 6),
+        (GY, 5, 1), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 6), (GZ, 5, 1),
+        (BZ, 3, 1), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 10), (GX, 0, 10), (BX, 0, 10),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 9), (RW, 10, 1), (GX, 0, 9),
+        (GW, 10, 1), (BX, 0, 9), (BW, 10, 1),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 8), (RW, 11, 1), (RW, 10, 1),
+        (GX, 0, 8), (GW, 11, 1), (GW, 10, 1), (BX, 0, 8), (BW, 11, 1), (BW, 10, 1),
+    ] },
+    Bc6hMode { transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 15, 1), (RW, 14, 1),
+        (RW, 13, 1), (RW, 12, 1), (RW, 11, 1), (RW, 10, 1), (GX, 0, 4), (GW, 15, 1),
+        (GW, 14, 1), (GW, 13, 1), (GW, 12, 1), (GW, 11, 1), (GW, 10, 1), (BX, 0, 4),
+        (BW, 15, 1), (BW, 14, 1), (BW, 13, 1), (BW, 12, 1), (BW, 11, 1), (BW, 10, 1),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize_bc6h(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    } else if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scale an interpolated value to the bits of a half precision float.
+fn finish_unquantize_bc6h(value: i32, signed: bool) -> u16 {
+    if signed {
+        if value < 0 {
+            0x8000 | (((-value) * 31) >> 5) as u16
+        } else {
+            ((value * 31) >> 5) as u16
+        }
+    } else {
+        ((value * 31) >> 6) as u16
+    }
+}
+
+fn decode_bc6h_block(block: &[u8], signed: bool) -> [[u16; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    let mut mode = reader.read(2);
+    if mode > 1 {
+        mode |= reader.read(3) << 2;
+    }
+    let mode = match mode {
+        0b00 => 0,
+        0b01 => 1,
+        0b00010 => 2,
+        0b00110 => 3,
+        0b01010 => 4,
+        0b01110 => 5,
+        0b10010 => 6,
+        0b10110 => 7,
+        0b11010 => 8,
+        0b11110 => 9,
+        0b00011 => 10,
+        0b00111 => 11,
+        0b01011 => 12,
+        0b01111 => 13,
+        // Reserved modes decode to black
+        _ => return [[0, 0, 0, 0x3c00]; 16],
+    };
+    let mode = &BC6H_MODES[mode];
+
+    let mut fields = [0i32; 13];
+    for &(field, first, count) in mode.fields {
+        fields[field] |= (reader.read(count) << first) as i32;
+    }
+    let subsets = if mode.fields.iter().any(|&(field, ..)| field == D) {
+        2
+    } else {
+        1
+    };
+    let partition = fields[D] as usize;
+
+    // Endpoints of each subset per channel
+    let mut endpoints = [[[0i32; 2]; 2]; 3];
+    for channel in 0..3 {
+        let base = fields[RW + channel];
+        let mut values = [
+            base,
+            fields[RX + channel],
+            fields[RY + channel],
+            fields[RZ + channel],
+        ];
+        let endpoint_bits = mode.endpoint_bits;
+        if signed {
+            values[0] = sign_extend(values[0], endpoint_bits);
+        }
+        for value in values.iter_mut().skip(1) {
+            if mode.transformed {
+                let delta = sign_extend(*value, mode.delta_bits[channel]);
+                *value = (base + delta) & ((1 << endpoint_bits) - 1);
+                if signed {
+                    *value = sign_extend(*value, endpoint_bits);
+                }
+            } else if signed {
+                *value = sign_extend(*value, endpoint_bits);
+            }
+        }
+        for (i, value) in values.iter().enumerate() {
+            endpoints[channel][i / 2][i % 2] = unquantize_bc6h(*value, endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if subsets == 2 { 3 } else { 4 };
+    let weights: &[u32] = if subsets == 2 { &WEIGHTS3 } else { &WEIGHTS4 };
+    let mut texels = [[0, 0, 0, 0x3c00]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let bits = if is_anchor(partition, subsets, i) {
+            index_bits - 1
+        } else {
+            index_bits
+        };
+        let weight = weights[reader.read(bits) as usize] as i32;
+        let subset = subset_of(partition, subsets, i);
+        for channel in 0..3 {
+            let [a, b] = endpoints[channel][subset];
+            let value = ((64 - weight) * a + weight * b + 32) >> 6;
+            texel[channel] = finish_unquantize_bc6h(value, signed);
+        }
+    }
+    texels
+}
+
+/// Decode a BC6H image into half precision floats, whose alpha is one.
+pub fn decode_bc6h(data: &[u8], width: u32, height: u32, signed: bool) -> Vec<u16> {
+    decode_blocks(data, width, height, 16, |block| {
+        decode_bc6h_block(block, signed)
+    })
+}
+
+/// Layout of a BC7 mode.
+pub(super) struct Bc7Mode {
+    pub subsets: usize,
+    pub partition_bits: u32,
+    pub rotation_bits: u32,
+    pub index_selection_bits: u32,
+    pub color_bits: u32,
+    pub alpha_bits: u32,
+    pub endpoint_p_bits: bool,
+    pub shared_p_bits: bool,
+    pub index_bits: u32,
+    pub secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+pub(super) const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+pub(super) fn bc7_weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS2,
+        3 => &WEIGHTS3,
+        _ => &WEIGHTS4,
+    }
+}
+
+fn decode_bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mode_index = block[0].trailing_zeros();
+    // Blocks without mode bit are invalid and decode to transparent black
+    let mode = match BC7_MODES.get(mode_index as usize) {
+        Some(mode) => mode,
+        None => return [[0; 4]; 16],
+    };
+    let mut reader = BitReader::new(block);
+    reader.read(mode_index + 1);
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // Endpoints of each subset, with alpha as fourth channel
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..4 {
+        let bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = if bits == 0 { 255 } else { reader.read(bits) };
+            }
+        }
+    }
+
+    let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+    let mut p_bits = [[0; 2]; 3];
+    for subset in p_bits.iter_mut().take(mode.subsets) {
+        if mode.endpoint_p_bits {
+            subset[0] = reader.read(1);
+            subset[1] = reader.read(1);
+        } else if mode.shared_p_bits {
+            let p_bit = reader.read(1);
+            *subset = [p_bit, p_bit];
+        }
+    }
+    for (subset, p_bits) in endpoints.iter_mut().zip(p_bits.iter()).take(mode.subsets) {
+        for (endpoint, p_bit) in subset.iter_mut().zip(p_bits.iter()) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let mut bits = if channel < 3 {
+                    mode.color_bits
+                } else {
+                    mode.alpha_bits
+                };
+                if bits == 0 {
+                    continue;
+                }
+                if has_p_bits {
+                    *value = (*value << 1) | p_bit;
+                    bits += 1;
+                }
+                // Replicate the most significant bits into the lower bits
+                *value <<= 8 - bits;
+                *value |= *value >> bits;
+            }
+        }
+    }
+
+    let mut indices = [0u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(partition, mode.subsets, i);
+        *index = reader.read(mode.index_bits - anchor as u32);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (i, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (i == 0) as u32);
+        }
+    }
+
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let [a, b] = endpoints[subset_of(partition, mode.subsets, i)];
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = bc7_weights(mode.index_bits)[indices[i] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                bc7_weights(mode.index_bits)[indices[i] as usize],
+                bc7_weights(mode.secondary_index_bits)[secondary_indices[i] as usize],
+            )
+        } else {
+            (
+                bc7_weights(mode.secondary_index_bits)[secondary_indices[i] as usize],
+                bc7_weights(mode.index_bits)[indices[i] as usize],
+            )
+        };
+        for channel in 0..3 {
+            texel[channel] = interpolate(a[channel], b[channel], color_weight) as u8;
+        }
+        texel[3] = interpolate(a[3], b[3], alpha_weight) as u8;
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+    }
+    texels
+}
+
+/// Decode a BC7 image.
+pub fn decode_bc7(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, decode_bc7_block)
+}
+
+/// Convert the bits of a half precision float to a single precision float.
+pub(crate) fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0. => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1. + mantissa / 1024.) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// Pack `(value, bit count)` pairs into a block, starting with the least significant bit.
+    fn pack(fields: &[(u32, u32)]) -> Vec<u8> {
+        let mut bits = 0u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+        bits.to_le_bytes().to_vec()
+    }
+
+    #[test_case(&[0x00, 0xf8, 0x00, 0x00, 0, 0, 0, 0], [255, 0, 0, 255]; "opaque")]
+    #[test_case(&[0x00, 0x00, 0x00, 0xf8, 0xff, 0xff, 0xff, 0xff], [0, 0, 0, 0]; "transparent")]
+    fn test_decode_bc1(block: &[u8], texel: [u8; 4]) {
+        let image = decode_bc1(block, 4, 4);
+        assert_eq!(image.len(), 4 * 4 * 4);
+        assert!(image.chunks(4).all(|t| t == texel));
+    }
+
+    #[test]
+    fn test_decode_bc6h_bc7() {
+        // BC6H mode 11 from black to the largest unsigned value, texel 1 at the second endpoint
+        let mut fields = vec![(0b00011, 5), (0, 30), (1023, 10), (1023, 10), (1023, 10)];
+        fields.extend(&[(0, 3), (15, 4)]);
+        let image = decode_bc6h(&pack(&fields), 4, 4, false);
+        assert_eq!(
+            &image[..8],
+            &[0, 0, 0, 0x3c00, 0x7bff, 0x7bff, 0x7bff, 0x3c00]
+        );
+
+        // BC7 mode 6 from transparent black to opaque white, cropped to 2x1
+        let mut fields = vec![(1 << 6, 7)];
+        fields.extend([(0, 7), (127, 7)].iter().cycle().take(8));
+        fields.extend(&[(0, 1), (1, 1), (0, 3), (15, 4)]);
+        let image = decode_bc7(&pack(&fields), 2, 1);
+        assert_eq!(image, vec![0, 0, 0, 0, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_anchors_in_subsets() {
+        for partition in 0..64 {
+            assert_eq!(subset_of(partition, 2, ANCHORS2[partition] as usize), 1);
+            for (subset, anchor) in ANCHORS3[partition].iter().enumerate() {
+                assert_eq!(subset_of(partition, 3, *anchor as usize), subset + 1);
+            }
+        }
+    }
+}
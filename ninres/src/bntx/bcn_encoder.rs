@@ -0,0 +1,968 @@
+//! Software encoders of the block compressed formats BC1 to BC5 and BC7.
+//!
+//! Images are RGBA8 and partial blocks at the edges repeat the last row and column.
+//! Endpoints are fit to the principal axis of the texels of a block and refined with
+//! least squares, more often and with more candidates for higher qualities.
+
+use super::bcn::{
+    alpha_palette, bc7_weights, color_palette, interpolate, is_anchor, subset_of, Bc7Mode,
+    ANCHORS2, ANCHORS3, BC7_MODES,
+};
+use super::util::div_round_up;
+use std::cmp;
+use std::ops::Range;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Trade-off between encoding speed and quality.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EncodeQuality {
+    /// Fit endpoints once and only use the simplest modes.
+    Fast,
+    /// Refine endpoints and try the common modes.
+    #[default]
+    Normal,
+    /// Refine and search endpoints further and try all modes and more partitions.
+    High,
+}
+
+impl EncodeQuality {
+    /// Number of times endpoints are fit to the texels.
+    pub(super) fn iterations(self) -> usize {
+        match self {
+            EncodeQuality::Fast => 1,
+            EncodeQuality::Normal => 3,
+            EncodeQuality::High => 6,
+        }
+    }
+}
+
+type Block = [[u8; 4]; 16];
+
+/// Encode all blocks of an image.
+fn encode_blocks<F, const N: usize>(data: &[u8], width: u32, height: u32, encode: F) -> Vec<u8>
+where
+    F: Fn(&Block) -> [u8; N],
+{
+    let (blocks_x, blocks_y) = (div_round_up(width, 4), div_round_up(height, 4));
+    let mut res = Vec::with_capacity((blocks_x * blocks_y) as usize * N);
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let mut block = [[0; 4]; 16];
+            for (i, texel) in block.iter_mut().enumerate() {
+                let x = cmp::min(block_x * 4 + i as u32 % 4, width - 1);
+                let y = cmp::min(block_y * 4 + i as u32 / 4, height - 1);
+                let position = ((y * width + x) * 4) as usize;
+                if let Some(value) = data.get(position..position + 4) {
+                    texel.copy_from_slice(value);
+                }
+            }
+            res.extend_from_slice(&encode(&block));
+        }
+    }
+    res
+}
+
+fn dot<const N: usize>(a: &[f32; N], b: &[f32; N]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()
+}
+
+/// Endpoints of the line through the points along their principal axis.
+pub(super) fn fit_line<const N: usize>(points: &[[f32; N]]) -> [[f32; N]; 2] {
+    let mut mean = [0.; N];
+    for point in points {
+        for (mean, value) in mean.iter_mut().zip(point.iter()) {
+            *mean += value / points.len() as f32;
+        }
+    }
+    let mut covariance = [[0.; N]; N];
+    for point in points {
+        for (row, a) in covariance.iter_mut().zip(point.iter().zip(mean.iter())) {
+            for (value, b) in row.iter_mut().zip(point.iter().zip(mean.iter())) {
+                *value += (a.0 - a.1) * (b.0 - b.1);
+            }
+        }
+    }
+
+    // Power iteration, starting with the row of the channel with the largest variance
+    let largest = (0..N)
+        .max_by(|a, b| covariance[*a][*a].total_cmp(&covariance[*b][*b]))
+        .unwrap_or(0);
+    let mut axis = covariance[largest];
+    for _ in 0..8 {
+        let length = dot(&axis, &axis).sqrt();
+        if length < 1e-6 {
+            return [mean, mean];
+        }
+        let normalized = axis.map(|value| value / length);
+        axis = covariance.map(|row| dot(&row, &normalized));
+    }
+    let length = dot(&axis, &axis).sqrt();
+    if length < 1e-6 {
+        return [mean, mean];
+    }
+    let axis = axis.map(|value| value / length);
+
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for point in points {
+        let mut offset = *point;
+        for (value, mean) in offset.iter_mut().zip(mean.iter()) {
+            *value -= mean;
+        }
+        let t = dot(&offset, &axis);
+        min = min.min(t);
+        max = max.max(t);
+    }
+    let mut endpoints = [mean, mean];
+    for (channel, axis) in axis.iter().enumerate() {
+        endpoints[0][channel] += min * axis;
+        endpoints[1][channel] += max * axis;
+    }
+    endpoints
+}
+
+/// Squared distance of the points to the line through the endpoints from [`fit_line`].
+pub(super) fn line_error<const N: usize>(points: &[[f32; N]]) -> f32 {
+    let [start, end] = fit_line(points);
+    let mut direction = end;
+    for (value, start) in direction.iter_mut().zip(start.iter()) {
+        *value -= start;
+    }
+    let length = dot(&direction, &direction);
+    points
+        .iter()
+        .map(|point| {
+            let mut offset = *point;
+            for (value, start) in offset.iter_mut().zip(start.iter()) {
+                *value -= start;
+            }
+            let t = if length > 0. {
+                dot(&offset, &direction) / length
+            } else {
+                0.
+            };
+            offset
+                .iter()
+                .zip(direction.iter())
+                .map(|(value, direction)| (value - t * direction).powi(2))
+                .sum::<f32>()
+        })
+        .sum()
+}
+
+/// Endpoints minimizing the squared error of the points interpolated by weights from 0 to 1.
+pub(super) fn least_squares<const N: usize>(
+    points: &[[f32; N]],
+    weights: &[f32],
+) -> Option<[[f32; N]; 2]> {
+    let (mut aa, mut bb, mut ab) = (0., 0., 0.);
+    let (mut ax, mut bx) = ([0.; N], [0.; N]);
+    for (point, weight) in points.iter().zip(weights) {
+        let (a, b) = (1. - weight, *weight);
+        aa += a * a;
+        bb += b * b;
+        ab += a * b;
+        for (channel, value) in point.iter().enumerate() {
+            ax[channel] += a * value;
+            bx[channel] += b * value;
+        }
+    }
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None;
+    }
+    let mut endpoints = [[0.; N]; 2];
+    for channel in 0..N {
+        endpoints[0][channel] = (bb * ax[channel] - ab * bx[channel]) / determinant;
+        endpoints[1][channel] = (aa * bx[channel] - ab * ax[channel]) / determinant;
+    }
+    Some(endpoints)
+}
+
+fn to_565(color: [f32; 3]) -> u16 {
+    let quantize = |value: f32, max: f32| (value.clamp(0., 255.) * max / 255.).round() as u16;
+    (quantize(color[0], 31.) << 11) | (quantize(color[1], 63.) << 5) | quantize(color[2], 31.)
+}
+
+/// Order endpoints for a block with four colors or three colors and transparent black.
+fn order_565(color0: u16, color1: u16, four_colors: bool) -> (u16, u16) {
+    if (color0 > color1) == four_colors {
+        (color0, color1)
+    } else {
+        (color1, color0)
+    }
+}
+
+/// Squared error and indices of the nearest colors of a block, using index 3 for transparent texels.
+fn color_indices(
+    block: &Block,
+    transparent: &[bool; 16],
+    color0: u16,
+    color1: u16,
+    allow_transparent: bool,
+) -> (u32, [u32; 16]) {
+    let palette = color_palette(color0, color1, allow_transparent);
+    let color_count = if color0 > color1 || !allow_transparent {
+        4
+    } else {
+        3
+    };
+    let mut error = 0;
+    let mut indices = [3; 16];
+    for ((index, texel), transparent) in indices.iter_mut().zip(block).zip(transparent) {
+        if *transparent {
+            continue;
+        }
+        let (nearest, distance) = palette[..color_count]
+            .iter()
+            .map(|color| {
+                (0..3)
+                    .map(|c| (color[c] as i32 - texel[c] as i32).pow(2) as u32)
+                    .sum::<u32>()
+            })
+            .enumerate()
+            .min_by_key(|(_, distance)| *distance)
+            .unwrap();
+        *index = nearest as u32;
+        error += distance;
+    }
+    (error, indices)
+}
+
+/// Encode the colors of a BC1 to BC3 block. Texels with alpha below 128 are transparent
+/// if `allow_transparent` is set, which is only the case for BC1.
+fn encode_color_block(block: &Block, allow_transparent: bool, quality: EncodeQuality) -> [u8; 8] {
+    let mut transparent = [false; 16];
+    if allow_transparent {
+        for (transparent, texel) in transparent.iter_mut().zip(block) {
+            *transparent = texel[3] < 128;
+        }
+    }
+    let points: Vec<[f32; 3]> = block
+        .iter()
+        .zip(&transparent)
+        .filter(|(_, transparent)| !**transparent)
+        .map(|(texel, _)| [texel[0] as f32, texel[1] as f32, texel[2] as f32])
+        .collect();
+
+    // Transparency needs three colors, which may also fit opaque blocks better
+    let mut modes = vec![];
+    if points.len() == 16 {
+        modes.push(true);
+    }
+    if allow_transparent && (points.len() < 16 || quality != EncodeQuality::Fast) {
+        modes.push(false);
+    }
+
+    let mut best = (u32::MAX, 0, 0, [3; 16], true);
+    if points.is_empty() {
+        best = (0, 0, 0, [3; 16], false);
+    }
+    for &four_colors in &modes {
+        let mut endpoints = fit_line(&points);
+        for _ in 0..quality.iterations() {
+            let (color0, color1) =
+                order_565(to_565(endpoints[0]), to_565(endpoints[1]), four_colors);
+            let (error, indices) =
+                color_indices(block, &transparent, color0, color1, allow_transparent);
+            if error < best.0 {
+                best = (error, color0, color1, indices, four_colors);
+            }
+
+            let weights: Vec<f32> = indices
+                .iter()
+                .zip(&transparent)
+                .filter(|(_, transparent)| !**transparent)
+                .map(
+                    |(index, _)| match (index, color0 > color1 || !allow_transparent) {
+                        (0, _) => 0.,
+                        (1, _) => 1.,
+                        (2, true) => 1. / 3.,
+                        (3, true) => 2. / 3.,
+                        _ => 0.5,
+                    },
+                )
+                .collect();
+            match least_squares(&points, &weights) {
+                Some(refined) => endpoints = refined,
+                None => break,
+            }
+        }
+    }
+
+    // Try moving every channel of the endpoints by a step until nothing improves
+    if quality == EncodeQuality::High && !points.is_empty() {
+        for _ in 0..8 {
+            let mut improved = false;
+            let (_, color0, color1, _, four_colors) = best;
+            for (endpoint, (shift, max)) in (0..2).flat_map(|e| {
+                [(11, 31), (5, 63), (0, 31)]
+                    .iter()
+                    .map(move |channel| (e, *channel))
+            }) {
+                for delta in [-1i32, 1] {
+                    let mut colors = [color0, color1];
+                    let value = ((colors[endpoint] >> shift) & max) as i32 + delta;
+                    if value < 0 || value > max as i32 {
+                        continue;
+                    }
+                    colors[endpoint] =
+                        (colors[endpoint] & !(max << shift)) | ((value as u16) << shift);
+                    let (c0, c1) = order_565(colors[0], colors[1], four_colors);
+                    let (error, indices) =
+                        color_indices(block, &transparent, c0, c1, allow_transparent);
+                    if error < best.0 {
+                        best = (error, c0, c1, indices, four_colors);
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+    }
+
+    let (_, color0, color1, indices, _) = best;
+    let indices = indices
+        .iter()
+        .enumerate()
+        .fold(0u32, |bits, (i, index)| bits | (index << (i * 2)));
+    let mut res = [0; 8];
+    res[..2].copy_from_slice(&color0.to_le_bytes());
+    res[2..4].copy_from_slice(&color1.to_le_bytes());
+    res[4..].copy_from_slice(&indices.to_le_bytes());
+    res
+}
+
+/// Encode values between -127 and 127 or 0 and 255 into a BC4 block or the alpha of a BC3 block.
+fn encode_alpha_block(values: &[i32; 16], signed: bool, quality: EncodeQuality) -> [u8; 8] {
+    let evaluate = |a: i32, b: i32| {
+        let palette = alpha_palette(a, b, signed);
+        let mut error = 0;
+        let mut indices = [0u32; 16];
+        for (index, value) in indices.iter_mut().zip(values) {
+            let (nearest, distance) = palette
+                .iter()
+                .map(|p| (p - value).pow(2) as u32)
+                .enumerate()
+                .min_by_key(|(_, distance)| *distance)
+                .unwrap();
+            *index = nearest as u32;
+            error += distance;
+        }
+        (error, indices)
+    };
+    let (range_min, range_max) = if signed { (-127, 127) } else { (0, 255) };
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    // Eight interpolated values, or six and the limits of the range
+    let mut candidates = vec![(max, min)];
+    let inner: Vec<i32> = values
+        .iter()
+        .copied()
+        .filter(|value| *value != range_min && *value != range_max)
+        .collect();
+    if quality != EncodeQuality::Fast && !inner.is_empty() {
+        candidates.push((*inner.iter().min().unwrap(), *inner.iter().max().unwrap()));
+    }
+
+    let mut best = (u32::MAX, 0, 0, [0; 16]);
+    for (mut a, mut b) in candidates {
+        let eight_values = a > b;
+        for _ in 0..quality.iterations() {
+            let (error, indices) = evaluate(a, b);
+            if error < best.0 {
+                best = (error, a, b, indices);
+            }
+
+            let (points, weights): (Vec<[f32; 1]>, Vec<f32>) = values
+                .iter()
+                .zip(indices.iter())
+                .filter_map(|(value, index)| {
+                    let weight = match (index, eight_values) {
+                        (0, _) => 0.,
+                        (1, _) => 1.,
+                        (i, true) => (i - 1) as f32 / 7.,
+                        (i, false) if *i < 6 => (i - 1) as f32 / 5.,
+                        _ => return None,
+                    };
+                    Some(([*value as f32], weight))
+                })
+                .unzip();
+            let refined = match least_squares(&points, &weights) {
+                Some(refined) => refined,
+                None => break,
+            };
+            let (c0, c1) = (
+                (refined[0][0].round() as i32).clamp(range_min, range_max),
+                (refined[1][0].round() as i32).clamp(range_min, range_max),
+            );
+            let (c0, c1) = if (c0 > c1) == eight_values {
+                (c0, c1)
+            } else {
+                (c1, c0)
+            };
+            if c0 == c1 && eight_values {
+                break;
+            }
+            a = c0;
+            b = c1;
+        }
+    }
+
+    if quality == EncodeQuality::High {
+        let (_, a, b, _) = best;
+        for c0 in cmp::max(range_min, a - 3)..=cmp::min(range_max, a + 3) {
+            for c1 in cmp::max(range_min, b - 3)..=cmp::min(range_max, b + 3) {
+                // Stay with the kind of block that was found best
+                if (c0 > c1) != (a > b) {
+                    continue;
+                }
+                let (error, indices) = evaluate(c0, c1);
+                if error < best.0 {
+                    best = (error, c0, c1, indices);
+                }
+            }
+        }
+    }
+
+    let (_, a, b, indices) = best;
+    let indices = indices
+        .iter()
+        .enumerate()
+        .fold(0u64, |bits, (i, index)| bits | ((*index as u64) << (i * 3)));
+    let mut res = [0; 8];
+    res[0] = a as u8;
+    res[1] = b as u8;
+    res[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    res
+}
+
+/// Map bytes to values of BC4 and BC5 blocks.
+fn from_unorm(value: u8, signed: bool) -> i32 {
+    if signed {
+        (value as i32 * 254 + 127) / 255 - 127
+    } else {
+        value as i32
+    }
+}
+
+fn channel_values(block: &Block, channel: usize, signed: bool) -> [i32; 16] {
+    let mut values = [0; 16];
+    for (value, texel) in values.iter_mut().zip(block) {
+        *value = from_unorm(texel[channel], signed);
+    }
+    values
+}
+
+fn bc7_channel_bits(mode: &Bc7Mode, channel: usize) -> u32 {
+    if channel < 3 {
+        mode.color_bits
+    } else {
+        mode.alpha_bits
+    }
+}
+
+/// Expand a quantized BC7 endpoint value with an optional p-bit like the decoder.
+fn expand_bc7(value: u32, bits: u32, p_bit: Option<u32>) -> u32 {
+    let (mut value, mut bits) = (value, bits);
+    if let Some(p_bit) = p_bit {
+        value = (value << 1) | p_bit;
+        bits += 1;
+    }
+    value <<= 8 - bits;
+    value | (value >> bits)
+}
+
+/// Quantized value whose expansion is the nearest to `value`.
+fn quantize_bc7(value: f32, bits: u32, p_bit: Option<u32>) -> u32 {
+    let target = value.clamp(0., 255.).round() as i32;
+    let total_bits = bits + p_bit.is_some() as u32;
+    let estimate = ((target as u32 * ((1 << total_bits) - 1) + 127) / 255) >> (total_bits - bits);
+    (estimate.saturating_sub(1)..=cmp::min(estimate + 1, (1 << bits) - 1))
+        .min_by_key(|value| (expand_bc7(*value, bits, p_bit) as i32 - target).abs())
+        .unwrap()
+}
+
+/// Quantized endpoints of a BC7 subset.
+#[derive(Clone, Copy, Default)]
+struct Bc7Endpoints {
+    values: [[u32; 4]; 2],
+    p_bits: [u32; 2],
+}
+
+impl Bc7Endpoints {
+    fn quantize(endpoints: &[[f32; 4]; 2], mode: &Bc7Mode) -> Self {
+        let quantize_endpoint = |endpoint: &[f32; 4], p_bit: Option<u32>| {
+            let mut values = [0; 4];
+            let mut error = 0.;
+            for (channel, (value, target)) in values.iter_mut().zip(endpoint.iter()).enumerate() {
+                let bits = bc7_channel_bits(mode, channel);
+                if bits > 0 {
+                    *value = quantize_bc7(*target, bits, p_bit);
+                    error += (expand_bc7(*value, bits, p_bit) as f32 - target).powi(2);
+                }
+            }
+            (values, error)
+        };
+
+        let mut res = Self::default();
+        if mode.endpoint_p_bits {
+            for (i, endpoint) in endpoints.iter().enumerate() {
+                let (p_bit, (values, _)) = (0..2)
+                    .map(|p_bit| (p_bit, quantize_endpoint(endpoint, Some(p_bit))))
+                    .min_by(|a, b| (a.1).1.total_cmp(&(b.1).1))
+                    .unwrap();
+                res.values[i] = values;
+                res.p_bits[i] = p_bit;
+            }
+        } else if mode.shared_p_bits {
+            let (p_bit, values, _) = (0..2)
+                .map(|p_bit| {
+                    let (a, error_a) = quantize_endpoint(&endpoints[0], Some(p_bit));
+                    let (b, error_b) = quantize_endpoint(&endpoints[1], Some(p_bit));
+                    (p_bit, [a, b], error_a + error_b)
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2))
+                .unwrap();
+            res.values = values;
+            res.p_bits = [p_bit; 2];
+        } else {
+            for (values, endpoint) in res.values.iter_mut().zip(endpoints) {
+                *values = quantize_endpoint(endpoint, None).0;
+            }
+        }
+        res
+    }
+
+    fn expand(&self, mode: &Bc7Mode) -> [[u32; 4]; 2] {
+        let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+        let mut res = [[255; 4]; 2];
+        for ((expanded, values), p_bit) in res.iter_mut().zip(&self.values).zip(&self.p_bits) {
+            for (channel, (expanded, value)) in expanded.iter_mut().zip(values).enumerate() {
+                let bits = bc7_channel_bits(mode, channel);
+                if bits > 0 {
+                    *expanded = expand_bc7(*value, bits, has_p_bits.then_some(*p_bit));
+                }
+            }
+        }
+        res
+    }
+
+    /// Swap the endpoints in some channels, which inverts their indices.
+    fn swap(&mut self, channels: Range<usize>) {
+        let [a, b] = &mut self.values;
+        for channel in channels {
+            std::mem::swap(&mut a[channel], &mut b[channel]);
+        }
+        self.p_bits.swap(0, 1);
+    }
+}
+
+/// Squared error in some channels and indices of the nearest interpolated colors.
+fn bc7_indices(
+    texels: &[[u8; 4]],
+    endpoints: &[[u32; 4]; 2],
+    weights: &[u32],
+    channels: Range<usize>,
+) -> (u32, Vec<u32>) {
+    let palette: Vec<[u32; 4]> = weights
+        .iter()
+        .map(|weight| {
+            let mut color = [0; 4];
+            for (channel, value) in color.iter_mut().enumerate() {
+                *value = interpolate(endpoints[0][channel], endpoints[1][channel], *weight);
+            }
+            color
+        })
+        .collect();
+    let mut error = 0;
+    let mut indices = Vec::with_capacity(texels.len());
+    for texel in texels {
+        let (index, distance) = palette
+            .iter()
+            .map(|color| {
+                channels
+                    .clone()
+                    .map(|c| (color[c] as i32 - texel[c] as i32).pow(2) as u32)
+                    .sum::<u32>()
+            })
+            .enumerate()
+            .min_by_key(|(_, distance)| *distance)
+            .unwrap();
+        indices.push(index as u32);
+        error += distance;
+    }
+    (error, indices)
+}
+
+/// Fit the endpoints of a BC7 subset to its texels, considering only some channels.
+fn fit_bc7(
+    texels: &[[u8; 4]],
+    channels: Range<usize>,
+    mode: &Bc7Mode,
+    index_bits: u32,
+    quality: EncodeQuality,
+) -> (u32, Bc7Endpoints, Vec<u32>) {
+    let points: Vec<[f32; 4]> = texels
+        .iter()
+        .map(|texel| {
+            let mut point = [0.; 4];
+            for c in channels.clone() {
+                point[c] = texel[c] as f32;
+            }
+            point
+        })
+        .collect();
+    let weights = bc7_weights(index_bits);
+
+    let mut endpoints = fit_line(&points);
+    let mut best: Option<(u32, Bc7Endpoints, Vec<u32>)> = None;
+    for _ in 0..quality.iterations() {
+        let quantized = Bc7Endpoints::quantize(&endpoints, mode);
+        let (error, indices) =
+            bc7_indices(texels, &quantized.expand(mode), weights, channels.clone());
+        let interpolation: Vec<f32> = indices
+            .iter()
+            .map(|index| weights[*index as usize] as f32 / 64.)
+            .collect();
+        if best.as_ref().map_or(true, |best| error < best.0) {
+            best = Some((error, quantized, indices));
+        }
+        match least_squares(&points, &interpolation) {
+            Some(refined) => endpoints = refined,
+            None => break,
+        }
+    }
+    best.unwrap()
+}
+
+/// Texel whose index has one bit less in a subset.
+fn anchor_of(partition: usize, subsets: usize, subset: usize) -> usize {
+    match (subsets, subset) {
+        (_, 0) => 0,
+        (2, _) => ANCHORS2[partition] as usize,
+        _ => ANCHORS3[partition][subset - 1] as usize,
+    }
+}
+
+pub(super) struct BitWriter {
+    pub bits: u128,
+    pub position: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self {
+            bits: 0,
+            position: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u128) << self.position;
+        self.position += count;
+    }
+}
+
+/// Encode a block with a BC7 mode and partition, returning the squared error.
+fn encode_bc7_mode(
+    block: &Block,
+    mode_index: usize,
+    partition: usize,
+    quality: EncodeQuality,
+) -> (u32, [u8; 16]) {
+    let mode = &BC7_MODES[mode_index];
+    let mut error = 0;
+    let mut endpoints = [Bc7Endpoints::default(); 3];
+    let mut indices = [0; 16];
+    let mut secondary_indices = [0; 16];
+    let max_index = (1 << mode.index_bits) - 1;
+
+    if mode.secondary_index_bits > 0 {
+        // Separate color and alpha indices, without rotation or index selection
+        let (color_error, color, mut color_indices) =
+            fit_bc7(block, 0..3, mode, mode.index_bits, quality);
+        let (alpha_error, alpha, mut alpha_indices) =
+            fit_bc7(block, 3..4, mode, mode.secondary_index_bits, quality);
+        let mut combined = color;
+        combined.values[0][3] = alpha.values[0][3];
+        combined.values[1][3] = alpha.values[1][3];
+        if color_indices[0] > max_index / 2 {
+            combined.swap(0..3);
+            color_indices.iter_mut().for_each(|i| *i = max_index - *i);
+        }
+        let max_secondary = (1 << mode.secondary_index_bits) - 1;
+        if alpha_indices[0] > max_secondary / 2 {
+            combined.swap(3..4);
+            alpha_indices
+                .iter_mut()
+                .for_each(|i| *i = max_secondary - *i);
+        }
+        error = color_error + alpha_error;
+        endpoints[0] = combined;
+        indices.copy_from_slice(&color_indices);
+        secondary_indices.copy_from_slice(&alpha_indices);
+    } else {
+        for (subset, endpoints) in endpoints.iter_mut().enumerate().take(mode.subsets) {
+            let positions: Vec<usize> = (0..16)
+                .filter(|i| subset_of(partition, mode.subsets, *i) == subset)
+                .collect();
+            let texels: Vec<[u8; 4]> = positions.iter().map(|i| block[*i]).collect();
+            let (subset_error, mut fitted, subset_indices) =
+                fit_bc7(&texels, 0..4, mode, mode.index_bits, quality);
+
+            // The index of the anchor needs its highest bit cleared
+            let anchor = anchor_of(partition, mode.subsets, subset);
+            let anchor_index = positions.iter().position(|i| *i == anchor).unwrap();
+            let swap = subset_indices[anchor_index] > max_index / 2;
+            if swap {
+                fitted.swap(0..4);
+            }
+            for (position, index) in positions.iter().zip(subset_indices) {
+                indices[*position] = if swap { max_index - index } else { index };
+            }
+            *endpoints = fitted;
+            error += subset_error;
+        }
+    }
+
+    let mut writer = BitWriter::new();
+    writer.write(1 << mode_index, mode_index as u32 + 1);
+    writer.write(partition as u32, mode.partition_bits);
+    writer.write(0, mode.rotation_bits + mode.index_selection_bits);
+    for channel in 0..4 {
+        let bits = bc7_channel_bits(mode, channel);
+        for subset in endpoints.iter().take(mode.subsets) {
+            for endpoint in &subset.values {
+                writer.write(endpoint[channel], bits);
+            }
+        }
+    }
+    for subset in endpoints.iter().take(mode.subsets) {
+        if mode.endpoint_p_bits {
+            writer.write(subset.p_bits[0], 1);
+            writer.write(subset.p_bits[1], 1);
+        } else if mode.shared_p_bits {
+            writer.write(subset.p_bits[0], 1);
+        }
+    }
+    for (i, index) in indices.iter().enumerate() {
+        let anchor = is_anchor(partition, mode.subsets, i);
+        writer.write(*index, mode.index_bits - anchor as u32);
+    }
+    if mode.secondary_index_bits > 0 {
+        for (i, index) in secondary_indices.iter().enumerate() {
+            writer.write(*index, mode.secondary_index_bits - (i == 0) as u32);
+        }
+    }
+    (error, writer.bits.to_le_bytes())
+}
+
+fn encode_bc7_block(block: &Block, quality: EncodeQuality) -> [u8; 16] {
+    let opaque = block.iter().all(|texel| texel[3] == 255);
+    let (modes, partition_count): (&[usize], usize) = match quality {
+        EncodeQuality::Fast => (&[6], 1),
+        EncodeQuality::Normal => (&[1, 5, 6, 7], 2),
+        EncodeQuality::High => (&[0, 1, 2, 3, 5, 6, 7], 8),
+    };
+
+    let mut best = (u32::MAX, [0; 16]);
+    for &mode_index in modes {
+        let mode = &BC7_MODES[mode_index];
+        if mode.alpha_bits == 0 && !opaque {
+            continue;
+        }
+
+        // Fully encode only the partitions whose subsets lie closest to lines
+        let mut partitions: Vec<(f32, usize)> = (0..1 << mode.partition_bits)
+            .map(|partition| {
+                let error = (0..mode.subsets)
+                    .map(|subset| {
+                        let points: Vec<[f32; 4]> = (0..16)
+                            .filter(|i| subset_of(partition, mode.subsets, *i) == subset)
+                            .map(|i| block[i].map(|value| value as f32))
+                            .collect();
+                        line_error(&points)
+                    })
+                    .sum::<f32>();
+                (error, partition)
+            })
+            .collect();
+        partitions.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, partition) in partitions.iter().take(partition_count) {
+            let (error, encoded) = encode_bc7_mode(block, mode_index, *partition, quality);
+            if error < best.0 {
+                best = (error, encoded);
+            }
+        }
+    }
+    best.1
+}
+
+/// Encode a BC1 image, where texels with alpha below 128 become transparent.
+pub fn encode_bc1(data: &[u8], width: u32, height: u32, quality: EncodeQuality) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        encode_color_block(block, true, quality)
+    })
+}
+
+/// Encode a BC2 image with explicit four bit alpha values.
+pub fn encode_bc2(data: &[u8], width: u32, height: u32, quality: EncodeQuality) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        let mut res = [0; 16];
+        for (i, texel) in block.iter().enumerate() {
+            res[i / 2] |= (((texel[3] as u32 * 15 + 127) / 255) as u8) << (i % 2 * 4);
+        }
+        res[8..].copy_from_slice(&encode_color_block(block, false, quality));
+        res
+    })
+}
+
+/// Encode a BC3 image with interpolated alpha values.
+pub fn encode_bc3(data: &[u8], width: u32, height: u32, quality: EncodeQuality) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        let mut res = [0; 16];
+        res[..8].copy_from_slice(&encode_alpha_block(
+            &channel_values(block, 3, false),
+            false,
+            quality,
+        ));
+        res[8..].copy_from_slice(&encode_color_block(block, false, quality));
+        res
+    })
+}
+
+/// Encode the red channel of an image as BC4.
+pub fn encode_bc4(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    signed: bool,
+    quality: EncodeQuality,
+) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        encode_alpha_block(&channel_values(block, 0, signed), signed, quality)
+    })
+}
+
+/// Encode the red and green channels of an image as BC5.
+pub fn encode_bc5(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    signed: bool,
+    quality: EncodeQuality,
+) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        let mut res = [0; 16];
+        res[..8].copy_from_slice(&encode_alpha_block(
+            &channel_values(block, 0, signed),
+            signed,
+            quality,
+        ));
+        res[8..].copy_from_slice(&encode_alpha_block(
+            &channel_values(block, 1, signed),
+            signed,
+            quality,
+        ));
+        res
+    })
+}
+
+/// Encode a BC7 image.
+pub fn encode_bc7(data: &[u8], width: u32, height: u32, quality: EncodeQuality) -> Vec<u8> {
+    encode_blocks(data, width, height, |block| {
+        encode_bc7_block(block, quality)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bcn::*;
+    use super::*;
+    use test_case::test_case;
+
+    /// Smooth gradients with a sharp edge and varying alpha.
+    fn test_image(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                let edge = if x > y { 200 } else { 0 };
+                [
+                    (x * 255 / width) as u8,
+                    (y * 255 / height) as u8,
+                    edge as u8,
+                    255 - (x * 8) as u8,
+                ]
+            })
+            .collect()
+    }
+
+    /// Root mean square error of the channels before `channels`.
+    fn rmse(a: &[u8], b: &[u8], channels: usize) -> f64 {
+        let (sum, count) = a
+            .chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .flat_map(|(a, b)| a.iter().zip(b).take(channels))
+            .fold((0., 0), |(sum, count), (a, b)| {
+                (sum + (*a as f64 - *b as f64).powi(2), count + 1)
+            });
+        (sum / count as f64).sqrt()
+    }
+
+    #[test_case(EncodeQuality::Fast, 7.)]
+    #[test_case(EncodeQuality::Normal, 4.)]
+    #[test_case(EncodeQuality::High, 4.)]
+    fn test_encode_bcn(quality: EncodeQuality, bc7_error: f64) {
+        // Partial blocks at the edges and alpha that is only used by some formats
+        let (width, height) = (30, 18);
+        let image = test_image(width, height);
+        let opaque: Vec<u8> = image
+            .chunks_exact(4)
+            .flat_map(|texel| [texel[0], texel[1], texel[2], 255])
+            .collect();
+
+        let bc1 = decode_bc1(&encode_bc1(&opaque, width, height, quality), width, height);
+        assert!(rmse(&opaque, &bc1, 4) < 6.5);
+        let bc2 = decode_bc2(&encode_bc2(&image, width, height, quality), width, height);
+        assert!(rmse(&image, &bc2, 4) < 6.);
+        let bc3 = decode_bc3(&encode_bc3(&image, width, height, quality), width, height);
+        assert!(rmse(&image, &bc3, 4) < 6.);
+        for signed in [false, true] {
+            let bc4 = encode_bc4(&image, width, height, signed, quality);
+            assert!(rmse(&image, &decode_bc4(&bc4, width, height, signed), 1) < 1.);
+            let bc5 = encode_bc5(&image, width, height, signed, quality);
+            assert!(rmse(&image, &decode_bc5(&bc5, width, height, signed), 2) < 1.5);
+        }
+        let bc7 = decode_bc7(&encode_bc7(&image, width, height, quality), width, height);
+        assert!(rmse(&image, &bc7, 4) < bc7_error);
+    }
+
+    #[test_case(EncodeQuality::Fast)]
+    #[test_case(EncodeQuality::High)]
+    fn test_encode_bc2(quality: EncodeQuality) {
+        // Every alpha value is quantized to four bits, independently of the colors
+        let image: Vec<u8> = (0..16 * 16)
+            .flat_map(|i| [(i % 16 * 16) as u8, 0, 255 - (i % 16 * 16) as u8, i as u8])
+            .collect();
+        let bc2 = decode_bc2(&encode_bc2(&image, 16, 16, quality), 16, 16);
+        for (texel, decoded) in image.chunks_exact(4).zip(bc2.chunks_exact(4)) {
+            assert_eq!(decoded[3], ((texel[3] as u32 * 15 + 127) / 255 * 17) as u8);
+        }
+        assert!(rmse(&image, &bc2, 3) < 6.);
+    }
+
+    #[test]
+    fn test_encode_bc1_transparent() {
+        let mut image = vec![255; 4 * 4 * 4];
+        image[3] = 0;
+        let bc1 = decode_bc1(&encode_bc1(&image, 4, 4, EncodeQuality::Fast), 4, 4);
+        assert_eq!(&bc1[..8], &[0, 0, 0, 0, 255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Texture dimensions, which are the `nn::gfx::ImageDimension` of the texture.
+
+use crate::Error;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// How the array layers and depth slices of a texture are sampled.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureDimension {
+    D1 = 0,
+    D2 = 1,
+    D3 = 2,
+    Cube = 3,
+    D1Array = 4,
+    D2Array = 5,
+    D2Multisample = 6,
+    D2MultisampleArray = 7,
+    CubeArray = 8,
+}
+
+impl TextureDimension {
+    /// Dimension of a value. Values outside of `nn::gfx::ImageDimension` are corrupt data.
+    pub fn try_new(value: u8) -> Result<Self, Error> {
+        use TextureDimension::*;
+        Ok(match value {
+            0 => D1,
+            1 => D2,
+            2 => D3,
+            3 => Cube,
+            4 => D1Array,
+            5 => D2Array,
+            6 => D2Multisample,
+            7 => D2MultisampleArray,
+            8 => CubeArray,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+
+    pub fn is_cube(self) -> bool {
+        matches!(self, TextureDimension::Cube | TextureDimension::CubeArray)
+    }
+
+    pub fn is_array(self) -> bool {
+        matches!(
+            self,
+            TextureDimension::D1Array
+                | TextureDimension::D2Array
+                | TextureDimension::D2MultisampleArray
+                | TextureDimension::CubeArray
+        )
+    }
+}
+
+/// Face of a cube map. Faces are stored as consecutive array layers in this order.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Face of an array layer of a cube map or cube map array.
+    pub fn from_array_index(array_index: usize) -> Self {
+        Self::ALL[array_index % 6]
+    }
+
+    /// Short name of the face, like `+x`.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            CubeFace::PositiveX => "+x",
+            CubeFace::NegativeX => "-x",
+            CubeFace::PositiveY => "+y",
+            CubeFace::NegativeY => "-y",
+            CubeFace::PositiveZ => "+z",
+            CubeFace::NegativeZ => "-z",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(2, Some(TextureDimension::D3))]
+    #[test_case(8, Some(TextureDimension::CubeArray))]
+    #[test_case(9, None)]
+    fn test_texture_dimension(value: u8, dimension: Option<TextureDimension>) {
+        assert_eq!(TextureDimension::try_new(value).ok(), dimension);
+    }
+
+    #[test_case(0, CubeFace::PositiveX)]
+    #[test_case(5, CubeFace::NegativeZ)]
+    #[test_case(8, CubeFace::PositiveY; "second cube of array")]
+    fn test_cube_face(array_index: usize, face: CubeFace) {
+        assert_eq!(CubeFace::from_array_index(array_index), face);
+    }
+}
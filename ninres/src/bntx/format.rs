@@ -0,0 +1,432 @@
+//! Texture formats, which are the `nn::gfx::ImageFormat` of the texture.
+//! The high byte is the surface format and the low byte the format type.
+
+use crate::Error;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Channel layout and compression of a texture.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SurfaceFormat {
+    R4G4 = 0x01,
+    R8 = 0x02,
+    R4G4B4A4 = 0x03,
+    A4B4G4R4 = 0x04,
+    R5G5B5A1 = 0x05,
+    A1B5G5R5 = 0x06,
+    R5G6B5 = 0x07,
+    B5G6R5 = 0x08,
+    R8G8 = 0x09,
+    R16 = 0x0a,
+    R8G8B8A8 = 0x0b,
+    B8G8R8A8 = 0x0c,
+    R9G9B9E5 = 0x0d,
+    R10G10B10A2 = 0x0e,
+    R11G11B10 = 0x0f,
+    B10G11R11 = 0x10,
+    R10G11B11 = 0x11,
+    R16G16 = 0x12,
+    R24G8 = 0x13,
+    R32 = 0x14,
+    R16G16B16A16 = 0x15,
+    R32G8X24 = 0x16,
+    R32G32 = 0x17,
+    R32G32B32 = 0x18,
+    R32G32B32A32 = 0x19,
+    BC1 = 0x1a,
+    BC2 = 0x1b,
+    BC3 = 0x1c,
+    BC4 = 0x1d,
+    BC5 = 0x1e,
+    BC6H = 0x1f,
+    BC7 = 0x20,
+    EacR11 = 0x21,
+    EacR11G11 = 0x22,
+    Etc1 = 0x23,
+    Etc2 = 0x24,
+    Etc2Mask = 0x25,
+    Etc2Alpha = 0x26,
+    Pvrtc1Rgb2Bpp = 0x27,
+    Pvrtc1Rgb4Bpp = 0x28,
+    Pvrtc1Rgba2Bpp = 0x29,
+    Pvrtc1Rgba4Bpp = 0x2a,
+    Pvrtc2Rgba2Bpp = 0x2b,
+    Pvrtc2Rgba4Bpp = 0x2c,
+    Astc4x4 = 0x2d,
+    Astc5x4 = 0x2e,
+    Astc5x5 = 0x2f,
+    Astc6x5 = 0x30,
+    Astc6x6 = 0x31,
+    Astc8x5 = 0x32,
+    Astc8x6 = 0x33,
+    Astc8x8 = 0x34,
+    Astc10x5 = 0x35,
+    Astc10x6 = 0x36,
+    Astc10x8 = 0x37,
+    Astc10x10 = 0x38,
+    Astc12x10 = 0x39,
+    Astc12x12 = 0x3a,
+    B5G5R5A1 = 0x3b,
+}
+
+/// Interpretation of the channels of a texture.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FormatType {
+    UNorm = 0x01,
+    SNorm = 0x02,
+    UInt = 0x03,
+    SInt = 0x04,
+    Float = 0x05,
+    Srgb = 0x06,
+    DepthStencil = 0x07,
+    UIntToFloat = 0x08,
+    SIntToFloat = 0x09,
+    UFloat = 0x0a,
+}
+
+/// Source of a channel when the texture is sampled.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelSource {
+    Zero = 0,
+    One = 1,
+    Red = 2,
+    Green = 3,
+    Blue = 4,
+    Alpha = 5,
+}
+
+impl SurfaceFormat {
+    /// Surface format of a value. Unknown formats are rejected, as their texels can not be read.
+    pub fn try_new(value: u32) -> Result<Self, Error> {
+        use SurfaceFormat::*;
+        const FORMATS: [SurfaceFormat; 59] = [
+            R4G4,
+            R8,
+            R4G4B4A4,
+            A4B4G4R4,
+            R5G5B5A1,
+            A1B5G5R5,
+            R5G6B5,
+            B5G6R5,
+            R8G8,
+            R16,
+            R8G8B8A8,
+            B8G8R8A8,
+            R9G9B9E5,
+            R10G10B10A2,
+            R11G11B10,
+            B10G11R11,
+            R10G11B11,
+            R16G16,
+            R24G8,
+            R32,
+            R16G16B16A16,
+            R32G8X24,
+            R32G32,
+            R32G32B32,
+            R32G32B32A32,
+            BC1,
+            BC2,
+            BC3,
+            BC4,
+            BC5,
+            BC6H,
+            BC7,
+            EacR11,
+            EacR11G11,
+            Etc1,
+            Etc2,
+            Etc2Mask,
+            Etc2Alpha,
+            Pvrtc1Rgb2Bpp,
+            Pvrtc1Rgb4Bpp,
+            Pvrtc1Rgba2Bpp,
+            Pvrtc1Rgba4Bpp,
+            Pvrtc2Rgba2Bpp,
+            Pvrtc2Rgba4Bpp,
+            Astc4x4,
+            Astc5x4,
+            Astc5x5,
+            Astc6x5,
+            Astc6x6,
+            Astc8x5,
+            Astc8x6,
+            Astc8x8,
+            Astc10x5,
+            Astc10x6,
+            Astc10x8,
+            Astc10x10,
+            Astc12x10,
+            Astc12x12,
+            B5G5R5A1,
+        ];
+        value
+            .checked_sub(1)
+            .and_then(|index| FORMATS.get(index as usize))
+            .copied()
+            .ok_or_else(|| Error::TextureFormatNotSupported(format!("surface format {:#x}", value)))
+    }
+
+    /// Width and height in texels of a block. Uncompressed formats have blocks of one texel.
+    pub fn get_block_size(&self) -> (u32, u32) {
+        use SurfaceFormat::*;
+        match self {
+            BC1 | BC2 | BC3 | BC4 | BC5 | BC6H | BC7 => (4, 4),
+            EacR11 | EacR11G11 | Etc1 | Etc2 | Etc2Mask | Etc2Alpha => (4, 4),
+            Pvrtc1Rgb4Bpp | Pvrtc1Rgba4Bpp | Pvrtc2Rgba4Bpp => (4, 4),
+            Pvrtc1Rgb2Bpp | Pvrtc1Rgba2Bpp | Pvrtc2Rgba2Bpp => (8, 4),
+            Astc4x4 => (4, 4),
+            Astc5x4 => (5, 4),
+            Astc5x5 => (5, 5),
+            Astc6x5 => (6, 5),
+            Astc6x6 => (6, 6),
+            Astc8x5 => (8, 5),
+            Astc8x6 => (8, 6),
+            Astc8x8 => (8, 8),
+            Astc10x5 => (10, 5),
+            Astc10x6 => (10, 6),
+            Astc10x8 => (10, 8),
+            Astc10x10 => (10, 10),
+            Astc12x10 => (12, 10),
+            Astc12x12 => (12, 12),
+            _ => (1, 1),
+        }
+    }
+
+    pub fn get_bytes_per_block(&self) -> u32 {
+        use SurfaceFormat::*;
+        match self {
+            R4G4 | R8 => 1,
+            R4G4B4A4 | A4B4G4R4 | R5G5B5A1 | A1B5G5R5 | R5G6B5 | B5G6R5 | R8G8 | R16 | B5G5R5A1 => {
+                2
+            }
+            R8G8B8A8 | B8G8R8A8 | R9G9B9E5 | R10G10B10A2 | R11G11B10 | B10G11R11 | R10G11B11
+            | R16G16 | R24G8 | R32 => 4,
+            R16G16B16A16 | R32G8X24 | R32G32 => 8,
+            R32G32B32 => 12,
+            R32G32B32A32 => 16,
+            BC1 | BC4 | EacR11 | Etc1 | Etc2 | Etc2Mask => 8,
+            Pvrtc1Rgb2Bpp | Pvrtc1Rgb4Bpp | Pvrtc1Rgba2Bpp | Pvrtc1Rgba4Bpp | Pvrtc2Rgba2Bpp
+            | Pvrtc2Rgba4Bpp => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.get_block_size() != (1, 1)
+    }
+
+    pub fn is_astc(&self) -> bool {
+        (Self::Astc4x4 as u32..=Self::Astc12x12 as u32).contains(&(*self as u32))
+    }
+
+    /// Number of color channels, including alpha.
+    pub fn get_channel_count(&self) -> u32 {
+        use SurfaceFormat::*;
+        match self {
+            R8 | R16 | R32 | BC4 | EacR11 => 1,
+            R4G4 | R8G8 | R16G16 | R24G8 | R32G8X24 | R32G32 | BC5 | EacR11G11 => 2,
+            R5G6B5 | B5G6R5 | R9G9B9E5 | R11G11B10 | B10G11R11 | R10G11B11 | R32G32B32 | BC6H
+            | Etc1 | Etc2 | Pvrtc1Rgb2Bpp | Pvrtc1Rgb4Bpp => 3,
+            _ => 4,
+        }
+    }
+
+    /// RGBA index of each channel of uncompressed formats. Channels without index are skipped.
+    pub(crate) fn get_channel_order(&self) -> &'static [usize] {
+        use SurfaceFormat::*;
+        match self {
+            A4B4G4R4 | A1B5G5R5 => &[3, 2, 1, 0],
+            B5G6R5 | B10G11R11 => &[2, 1, 0],
+            B8G8R8A8 | B5G5R5A1 => &[2, 1, 0, 3],
+            R9G9B9E5 => &[0, 1, 2],
+            R32G8X24 => &[0, 1],
+            _ => &[0, 1, 2, 3],
+        }
+    }
+
+    /// Bit widths of the channels of uncompressed formats, in the order of the name.
+    pub fn get_components(&self) -> Option<&'static [u32]> {
+        use SurfaceFormat::*;
+        Some(match self {
+            R4G4 => &[4, 4],
+            R8 => &[8],
+            R4G4B4A4 | A4B4G4R4 => &[4, 4, 4, 4],
+            R5G5B5A1 | B5G5R5A1 => &[5, 5, 5, 1],
+            A1B5G5R5 => &[1, 5, 5, 5],
+            R5G6B5 | B5G6R5 => &[5, 6, 5],
+            R8G8 => &[8, 8],
+            R16 => &[16],
+            R8G8B8A8 | B8G8R8A8 => &[8, 8, 8, 8],
+            R9G9B9E5 => &[9, 9, 9, 5],
+            R10G10B10A2 => &[10, 10, 10, 2],
+            R11G11B10 => &[11, 11, 10],
+            B10G11R11 => &[10, 11, 11],
+            R10G11B11 => &[10, 11, 11],
+            R16G16 => &[16, 16],
+            R24G8 => &[24, 8],
+            R32 => &[32],
+            R16G16B16A16 => &[16, 16, 16, 16],
+            R32G8X24 => &[32, 8, 24],
+            R32G32 => &[32, 32],
+            R32G32B32 => &[32, 32, 32],
+            R32G32B32A32 => &[32, 32, 32, 32],
+            _ => return None,
+        })
+    }
+}
+
+impl FormatType {
+    /// Format type of a value. Unknown types are rejected, as their texels can not be read.
+    pub fn try_new(value: u32) -> Result<Self, Error> {
+        use FormatType::*;
+        Ok(match value {
+            0x01 => UNorm,
+            0x02 => SNorm,
+            0x03 => UInt,
+            0x04 => SInt,
+            0x05 => Float,
+            0x06 => Srgb,
+            0x07 => DepthStencil,
+            0x08 => UIntToFloat,
+            0x09 => SIntToFloat,
+            0x0a => UFloat,
+            _ => {
+                return Err(Error::TextureFormatNotSupported(format!(
+                    "format type {:#x}",
+                    value
+                )))
+            }
+        })
+    }
+}
+
+impl ChannelSource {
+    /// Channel source of a value. Values outside of `nn::gfx::ChannelMapping` are corrupt data.
+    pub fn try_new(value: u8) -> Result<Self, Error> {
+        use ChannelSource::*;
+        Ok(match value {
+            0 => Zero,
+            1 => One,
+            2 => Red,
+            3 => Green,
+            4 => Blue,
+            5 => Alpha,
+            _ => return Err(Error::CorruptData),
+        })
+    }
+}
+
+/// Replace the channels of RGBA texels with their sources.
+pub(crate) fn apply_channel_sources<T: Copy>(
+    data: &mut [T],
+    sources: &[ChannelSource; 4],
+    zero: T,
+    one: T,
+) {
+    if *sources
+        == [
+            ChannelSource::Red,
+            ChannelSource::Green,
+            ChannelSource::Blue,
+            ChannelSource::Alpha,
+        ]
+    {
+        return;
+    }
+    for texel in data.chunks_exact_mut(4) {
+        let original = [texel[0], texel[1], texel[2], texel[3]];
+        for (value, source) in texel.iter_mut().zip(sources.iter()) {
+            *value = match source {
+                ChannelSource::Zero => zero,
+                ChannelSource::One => one,
+                _ => original[*source as usize - 2],
+            };
+        }
+    }
+}
+
+/// Reorder RGBA channels, so that applying the channel sources results in the original channels.
+/// Channels, which no source refers to, are kept.
+pub(crate) fn apply_channel_sources_inverse<T: Copy>(data: &mut [T], sources: &[ChannelSource; 4]) {
+    let channels = [
+        ChannelSource::Red,
+        ChannelSource::Green,
+        ChannelSource::Blue,
+        ChannelSource::Alpha,
+    ];
+    if *sources == channels {
+        return;
+    }
+    for texel in data.chunks_exact_mut(4) {
+        let original = [texel[0], texel[1], texel[2], texel[3]];
+        for (value, channel) in texel.iter_mut().zip(channels.iter()) {
+            if let Some(index) = sources.iter().position(|source| source == channel) {
+                *value = original[index];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0x0b, SurfaceFormat::R8G8B8A8, (1, 1), 4)]
+    #[test_case(0x1a, SurfaceFormat::BC1, (4, 4), 8)]
+    #[test_case(0x30, SurfaceFormat::Astc6x5, (6, 5), 16)]
+    #[test_case(0x3b, SurfaceFormat::B5G5R5A1, (1, 1), 2)]
+    fn test_surface_format(value: u32, format: SurfaceFormat, block_size: (u32, u32), bpp: u32) {
+        let parsed = SurfaceFormat::try_new(value).unwrap();
+        assert_eq!(parsed, format);
+        assert_eq!(parsed as u32, value);
+        assert_eq!(parsed.get_block_size(), block_size);
+        assert_eq!(parsed.get_bytes_per_block(), bpp);
+    }
+
+    #[test_case(0x00)]
+    #[test_case(0x3c)]
+    fn test_unknown_surface_format(value: u32) {
+        assert!(matches!(
+            SurfaceFormat::try_new(value),
+            Err(Error::TextureFormatNotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_format_type() {
+        assert_eq!(FormatType::try_new(0x06).unwrap(), FormatType::Srgb);
+        assert!(matches!(
+            FormatType::try_new(0x0b),
+            Err(Error::TextureFormatNotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_channel_source() {
+        assert_eq!(ChannelSource::try_new(5).unwrap(), ChannelSource::Alpha);
+        assert!(matches!(ChannelSource::try_new(6), Err(Error::CorruptData)));
+    }
+
+    #[test]
+    fn test_apply_channel_sources() {
+        let mut data = [10u8, 20, 30, 40];
+        let sources = [
+            ChannelSource::Red,
+            ChannelSource::Red,
+            ChannelSource::Zero,
+            ChannelSource::Green,
+        ];
+        apply_channel_sources(&mut data, &sources, 0, 255);
+        assert_eq!(data, [10, 10, 0, 20]);
+        apply_channel_sources_inverse(&mut data, &sources);
+        assert_eq!(data[..2], [10, 20]);
+        apply_channel_sources(&mut data, &sources, 0, 255);
+        assert_eq!(data, [10, 10, 0, 20]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Generation of mipmap chains for textures that are built or replaced.
+
+use super::{linear_to_srgb, srgb_to_linear};
+use std::{cmp, f32::consts::PI};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Filter used to downsample mipmaps.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Average of the texels covered by a texel of the smaller mipmap.
+    #[default]
+    Box,
+    /// Kaiser windowed sinc, which keeps more detail than the box filter.
+    Kaiser,
+}
+
+/// Radius of the Kaiser window in texels of the smaller mipmap and its shape parameter.
+const KAISER_RADIUS: f32 = 3.;
+const KAISER_ALPHA: f32 = 4.;
+
+/// Modified Bessel function of the first kind of order zero.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.;
+    let mut term = 1.;
+    for k in 1..20 {
+        term *= (x / (2. * k as f32)).powi(2);
+        sum += term;
+    }
+    sum
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl MipFilter {
+    /// Source texels and their weights for every texel of an axis downsampled to `dst_len`.
+    fn get_taps(self, src_len: u32, dst_len: u32) -> Vec<Vec<(usize, f32)>> {
+        let scale = src_len as f32 / dst_len as f32;
+        (0..dst_len)
+            .map(|i| {
+                let center = (i as f32 + 0.5) * scale;
+                let radius = match self {
+                    MipFilter::Box => scale / 2.,
+                    MipFilter::Kaiser => KAISER_RADIUS * scale,
+                };
+                let first = (center - radius).floor() as i64;
+                let last = (center + radius).ceil() as i64;
+                let mut taps: Vec<(usize, f32)> = vec![];
+                for j in first..last {
+                    let weight = match self {
+                        // Overlap of the source texel with the footprint of the destination texel
+                        MipFilter::Box => {
+                            let start = (j as f32).max(center - radius);
+                            let end = (j as f32 + 1.).min(center + radius);
+                            (end - start).max(0.)
+                        }
+                        MipFilter::Kaiser => {
+                            let x = (j as f32 + 0.5 - center) / scale;
+                            let window = 1. - (x / KAISER_RADIUS).powi(2);
+                            if window <= 0. {
+                                0.
+                            } else {
+                                sinc(x) * bessel_i0(KAISER_ALPHA * window.sqrt())
+                                    / bessel_i0(KAISER_ALPHA)
+                            }
+                        }
+                    };
+                    if weight == 0. {
+                        continue;
+                    }
+                    // Texels outside of the image repeat the edge
+                    let index = j.clamp(0, src_len as i64 - 1) as usize;
+                    match taps.iter_mut().find(|(other, _)| *other == index) {
+                        Some(tap) => tap.1 += weight,
+                        None => taps.push((index, weight)),
+                    }
+                }
+                let sum: f32 = taps.iter().map(|(_, weight)| weight).sum();
+                for tap in taps.iter_mut() {
+                    tap.1 /= sum;
+                }
+                taps
+            })
+            .collect()
+    }
+}
+
+/// Number of mipmaps of a full chain, down to a single texel.
+pub fn get_full_mip_count(width: u32, height: u32, depth: u32) -> u32 {
+    32 - cmp::max(width, cmp::max(height, depth)).leading_zeros()
+}
+
+/// Downsample an RGBA image with `size` texels to half its size along one axis.
+fn downsample_axis(
+    data: &[[f32; 4]],
+    size: [u32; 3],
+    axis: usize,
+    filter: MipFilter,
+) -> (Vec<[f32; 4]>, [u32; 3]) {
+    let mut mip_size = size;
+    mip_size[axis] = cmp::max(1, size[axis] / 2);
+    if mip_size[axis] == size[axis] {
+        return (data.to_vec(), size);
+    }
+    let taps = filter.get_taps(size[axis], mip_size[axis]);
+    let strides = [1, size[0] as usize, (size[0] * size[1]) as usize];
+
+    let mut res = Vec::with_capacity((mip_size[0] * mip_size[1] * mip_size[2]) as usize);
+    for z in 0..mip_size[2] as usize {
+        for y in 0..mip_size[1] as usize {
+            for x in 0..mip_size[0] as usize {
+                let mut position = [x, y, z];
+                let taps = &taps[position[axis]];
+                position[axis] = 0;
+                let base: usize = position.iter().zip(&strides).map(|(p, s)| p * s).sum();
+                let mut texel = [0.; 4];
+                for (index, weight) in taps {
+                    let source = &data[base + index * strides[axis]];
+                    for (value, source) in texel.iter_mut().zip(source) {
+                        *value += source * weight;
+                    }
+                }
+                res.push(texel);
+            }
+        }
+    }
+    (res, mip_size)
+}
+
+/// Generate a chain of `mip_count` mipmaps from an RGBA8 image with depth slices after each
+/// other, starting with the image itself. Each mipmap is half the size of the previous one.
+///
+/// The color channels of sRGB images are filtered as linear values. Alpha is always linear.
+pub fn generate_mipmaps(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    depth: u32,
+    mip_count: u32,
+    filter: MipFilter,
+    srgb: bool,
+) -> Vec<Vec<u8>> {
+    let decode = |value: u8, channel: usize| {
+        let value = value as f32 / 255.;
+        if srgb && channel < 3 {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+    let encode = |value: f32, channel: usize| {
+        let value = value.clamp(0., 1.);
+        let value = if srgb && channel < 3 {
+            linear_to_srgb(value)
+        } else {
+            value
+        };
+        (value * 255.).round() as u8
+    };
+
+    let mut image: Vec<[f32; 4]> = data
+        .chunks_exact(4)
+        .map(|texel| {
+            let mut res = [0.; 4];
+            for (channel, value) in res.iter_mut().enumerate() {
+                *value = decode(texel[channel], channel);
+            }
+            res
+        })
+        .collect();
+    let mut size = [width, height, depth];
+    let mut res = vec![data.to_vec()];
+    for _ in 1..mip_count {
+        for axis in 0..3 {
+            let (mip, mip_size) = downsample_axis(&image, size, axis, filter);
+            image = mip;
+            size = mip_size;
+        }
+        res.push(
+            image
+                .iter()
+                .flat_map(|texel| {
+                    let mut res = [0; 4];
+                    for (channel, value) in res.iter_mut().enumerate() {
+                        *value = encode(texel[channel], channel);
+                    }
+                    res
+                })
+                .collect(),
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1, 1, 1, 1)]
+    #[test_case(64, 32, 1, 7)]
+    #[test_case(5, 3, 1, 3)]
+    #[test_case(4, 4, 16, 5; "3d")]
+    fn test_get_full_mip_count(width: u32, height: u32, depth: u32, mip_count: u32) {
+        assert_eq!(get_full_mip_count(width, height, depth), mip_count);
+    }
+
+    #[test]
+    fn test_generate_mipmaps_box() {
+        let image = [
+            0, 10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 110, 120, 130, 140, 150,
+        ];
+        let mips = generate_mipmaps(&image, 2, 2, 1, 2, MipFilter::Box, false);
+        assert_eq!(mips[0], image);
+        assert_eq!(mips[1], [60, 70, 80, 90]);
+    }
+
+    #[test]
+    fn test_generate_mipmaps_srgb() {
+        let image = [0, 0, 0, 0, 255, 255, 255, 255];
+        let mips = generate_mipmaps(&image, 2, 1, 1, 2, MipFilter::Box, true);
+        assert_eq!(mips[1], [188, 188, 188, 128]);
+    }
+
+    #[test_case(MipFilter::Box)]
+    #[test_case(MipFilter::Kaiser)]
+    fn test_generate_mipmaps_sizes(filter: MipFilter) {
+        let (width, height, depth) = (13, 6, 3);
+        let image: Vec<u8> = (0..width * height * depth)
+            .flat_map(|i| [100, (i % 2 * 50) as u8, 200, 255])
+            .collect();
+        let mips = generate_mipmaps(&image, width, height, depth, 4, filter, false);
+        let sizes: Vec<usize> = mips.iter().map(|mip| mip.len() / 4).collect();
+        assert_eq!(sizes, [13 * 6 * 3, 6 * 3, 3, 1]);
+        // Constant channels stay constant and alternating texels are averaged
+        for texel in mips[3].chunks_exact(4) {
+            assert_eq!([texel[0], texel[2], texel[3]], [100, 200, 255]);
+            assert!((20..=30).contains(&texel[1]), "{}", texel[1]);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Handling of sRGB encoded textures when exporting them.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// What to do with sRGB encoded color when exporting a texture.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SrgbConversion {
+    /// Keep the encoded values and leave the color space to the consumer.
+    Keep,
+    /// Convert the color channels to linear values.
+    Linearize,
+    /// Keep the encoded values and mark the exported image as sRGB.
+    Tag,
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Convert the color channels of an RGBA8 image to linear values. Alpha is always linear.
+pub fn linearize_rgba8(data: &mut [u8]) {
+    let mut table = [0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        *value = (srgb_to_linear(i as f32 / 255.) * 255.).round() as u8;
+    }
+    for texel in data.chunks_exact_mut(4) {
+        for value in &mut texel[..3] {
+            *value = table[*value as usize];
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+/// Insert an sRGB chunk with perceptual rendering intent after the header of a PNG.
+/// The data is returned unchanged if it doesn't start with a header chunk.
+pub fn tag_png_srgb(png: &[u8]) -> Vec<u8> {
+    // Signature and IHDR with its length, type, 13 bytes of data and CRC
+    const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < IHDR_END || &png[12..16] != b"IHDR" {
+        return png.to_vec();
+    }
+
+    let chunk = b"sRGB\0";
+    let mut res = Vec::with_capacity(png.len() + 13);
+    res.extend_from_slice(&png[..IHDR_END]);
+    res.extend_from_slice(&1u32.to_be_bytes());
+    res.extend_from_slice(chunk);
+    res.extend_from_slice(&crc32(chunk).to_be_bytes());
+    res.extend_from_slice(&png[IHDR_END..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 0)]
+    #[test_case(128, 55)]
+    #[test_case(188, 128)]
+    #[test_case(255, 255)]
+    fn test_linearize_rgba8(srgb: u8, linear: u8) {
+        let mut data = [srgb, srgb, srgb, srgb];
+        linearize_rgba8(&mut data);
+        assert_eq!(data, [linear, linear, linear, srgb]);
+        assert_eq!(
+            (linear_to_srgb(linear as f32 / 255.) * 255.).round() as u8,
+            srgb
+        );
+    }
+
+    #[test]
+    fn test_tag_png_srgb() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0; 17]);
+        png.extend_from_slice(b"\0\0\0\0IEND");
+        let res = tag_png_srgb(&png);
+        assert_eq!(&res[33..46], b"\0\0\0\x01sRGB\0\xae\xce\x1c\xe9".as_ref());
+        assert_eq!(&res[46..], b"\0\0\0\0IEND".as_ref());
+    }
+}
@@ -0,0 +1,218 @@
+//! Conversion of uncompressed texture formats to RGBA.
+//!
+//! Texels are little endian and the first channel of a format's name is stored in the lowest bits.
+
+use super::{bcn::half_to_f32, FormatType, SurfaceFormat};
+
+/// Decode an unsigned float with a five bit exponent and no sign, like the channels of R11G11B10.
+fn unsigned_float(value: u32, mantissa_bits: u32) -> f32 {
+    let exponent = value >> mantissa_bits;
+    let mantissa = (value & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        0x1f if mantissa == 0. => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1. + mantissa) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+fn decode_channel(value: u32, bits: u32, format_type: FormatType) -> f32 {
+    let max = ((1u64 << bits) - 1) as f32;
+    let signed = || ((value << (32 - bits)) as i32 >> (32 - bits)) as f32;
+    match format_type {
+        FormatType::Float | FormatType::UFloat => match bits {
+            32 => f32::from_bits(value),
+            16 => half_to_f32(value as u16),
+            _ => unsigned_float(value, bits - 5),
+        },
+        FormatType::DepthStencil if bits == 32 => f32::from_bits(value),
+        FormatType::SNorm => (signed() / (max / 2.).floor()).max(-1.),
+        FormatType::UInt | FormatType::UIntToFloat => value as f32,
+        FormatType::SInt | FormatType::SIntToFloat => signed(),
+        _ => value as f32 / max,
+    }
+}
+
+/// Decode an uncompressed image into RGBA32F.
+/// Missing color channels are zero and missing alpha is one.
+pub fn decode_uncompressed(
+    data: &[u8],
+    format: SurfaceFormat,
+    format_type: FormatType,
+) -> Option<Vec<f32>> {
+    let components = format.get_components()?;
+    let order = format.get_channel_order();
+    let bytes_per_texel = format.get_bytes_per_block() as usize;
+
+    let mut res = Vec::with_capacity(data.len() / bytes_per_texel * 4);
+    for texel in data.chunks_exact(bytes_per_texel) {
+        let mut bytes = [0; 16];
+        bytes[..bytes_per_texel].copy_from_slice(texel);
+        let value = u128::from_le_bytes(bytes);
+
+        let mut rgba = [0., 0., 0., 1.];
+        let mut offset = 0;
+        let mut channels = [0; 4];
+        for (i, bits) in components.iter().enumerate() {
+            let channel = (value >> offset) as u32 & ((1u64 << bits) - 1) as u32;
+            channels[i.min(3)] = channel;
+            if let Some(index) = order.get(i) {
+                rgba[*index] = decode_channel(channel, *bits, format_type);
+            }
+            offset += bits;
+        }
+        if format == SurfaceFormat::R9G9B9E5 {
+            // Nine bit mantissas without implicit one share the exponent
+            let scale = 2f32.powi(channels[3] as i32 - 15 - 9);
+            for (value, mantissa) in rgba.iter_mut().zip(channels.iter()).take(3) {
+                *value = *mantissa as f32 * scale;
+            }
+        }
+        res.extend_from_slice(&rgba);
+    }
+    Some(res)
+}
+
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if value.is_nan() {
+        sign | 0x7e00
+    } else if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // Subnormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        sign | (((mantissa >> (13 - exponent)) + 1) >> 1) as u16
+    } else {
+        let half = sign as u32 | (exponent as u32) << 10 | mantissa >> 13;
+        // Round to nearest, which may carry into the exponent
+        (half + ((mantissa >> 12) & 1)) as u16
+    }
+}
+
+fn encode_channel(value: f32, bits: u32, format_type: FormatType) -> Option<u32> {
+    let max = ((1u64 << bits) - 1) as f32;
+    let mask = ((1u64 << bits) - 1) as u32;
+    Some(match format_type {
+        FormatType::Float | FormatType::UFloat => match bits {
+            32 => value.to_bits(),
+            16 => f32_to_half(value) as u32,
+            _ => return None,
+        },
+        FormatType::DepthStencil if bits == 32 => value.to_bits(),
+        FormatType::SNorm => {
+            let max = (max / 2.).floor();
+            (value.clamp(-1., 1.) * max).round() as i32 as u32 & mask
+        }
+        FormatType::UInt | FormatType::UIntToFloat => value.clamp(0., max).round() as u32,
+        FormatType::SInt | FormatType::SIntToFloat => {
+            let max = (max / 2.).floor();
+            value.clamp(-max - 1., max).round() as i32 as u32 & mask
+        }
+        _ => (value.clamp(0., 1.) * max).round() as u32,
+    })
+}
+
+/// Encode RGBA32F into an uncompressed format, which is the inverse of [`decode_uncompressed`].
+/// Returns `None` for formats with shared exponents or small floats.
+pub fn encode_uncompressed(
+    data: &[f32],
+    format: SurfaceFormat,
+    format_type: FormatType,
+) -> Option<Vec<u8>> {
+    if format == SurfaceFormat::R9G9B9E5 {
+        return None;
+    }
+    let components = format.get_components()?;
+    let order = format.get_channel_order();
+    let bytes_per_texel = format.get_bytes_per_block() as usize;
+
+    let mut res = Vec::with_capacity(data.len() / 4 * bytes_per_texel);
+    for rgba in data.chunks_exact(4) {
+        let mut value = 0u128;
+        let mut offset = 0;
+        for (i, bits) in components.iter().enumerate() {
+            if let Some(index) = order.get(i) {
+                let channel = encode_channel(rgba[*index], *bits, format_type)?;
+                value |= (channel as u128) << offset;
+            }
+            offset += bits;
+        }
+        res.extend_from_slice(&value.to_le_bytes()[..bytes_per_texel]);
+    }
+    Some(res)
+}
+
+/// Convert RGBA8 to RGBA32F, which is the inverse of [`rgba32f_to_rgba8`].
+pub fn rgba8_to_rgba32f(data: &[u8], format_type: FormatType) -> Vec<f32> {
+    data.iter()
+        .map(|value| match format_type {
+            FormatType::SNorm => *value as f32 / 255. * 2. - 1.,
+            FormatType::UInt
+            | FormatType::SInt
+            | FormatType::UIntToFloat
+            | FormatType::SIntToFloat => *value as f32,
+            _ => *value as f32 / 255.,
+        })
+        .collect()
+}
+
+/// Convert RGBA32F to RGBA8.
+/// Signed normalized values are mapped from -1 and 1 and integers are clamped to a byte.
+pub fn rgba32f_to_rgba8(data: &[f32], format_type: FormatType) -> Vec<u8> {
+    data.iter()
+        .map(|value| match format_type {
+            FormatType::SNorm => ((value.clamp(-1., 1.) + 1.) / 2. * 255.).round() as u8,
+            FormatType::UInt
+            | FormatType::SInt
+            | FormatType::UIntToFloat
+            | FormatType::SIntToFloat => value.clamp(0., 255.) as u8,
+            _ => (value.clamp(0., 1.) * 255.).round() as u8,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[0x1f, 0xf8], SurfaceFormat::R5G6B5, FormatType::UNorm, [255, 0, 255, 255]; "r5g6b5")]
+    #[test_case(&[1, 2, 3, 4], SurfaceFormat::B8G8R8A8, FormatType::UNorm, [3, 2, 1, 4]; "b8g8r8a8")]
+    #[test_case(&[0x00, 0x3c], SurfaceFormat::R16, FormatType::Float, [255, 0, 0, 255]; "r16 float")]
+    #[test_case(&[0x81], SurfaceFormat::R8, FormatType::SNorm, [0, 128, 128, 255]; "r8 snorm")]
+    fn test_decode_uncompressed(
+        data: &[u8],
+        format: SurfaceFormat,
+        format_type: FormatType,
+        rgba: [u8; 4],
+    ) {
+        let image = decode_uncompressed(data, format, format_type).unwrap();
+        assert_eq!(rgba32f_to_rgba8(&image, format_type), rgba);
+    }
+
+    #[test_case(SurfaceFormat::R5G6B5, FormatType::UNorm; "r5g6b5")]
+    #[test_case(SurfaceFormat::B8G8R8A8, FormatType::Srgb; "b8g8r8a8")]
+    #[test_case(SurfaceFormat::R16G16B16A16, FormatType::Float; "r16g16b16a16 float")]
+    #[test_case(SurfaceFormat::R8G8, FormatType::SNorm; "r8g8 snorm")]
+    #[test_case(SurfaceFormat::R32, FormatType::UInt; "r32 uint")]
+    fn test_encode_uncompressed(format: SurfaceFormat, format_type: FormatType) {
+        let rgba = [0, 255, 128, 255, 64, 32, 16, 255];
+        let data = encode_uncompressed(&rgba8_to_rgba32f(&rgba, format_type), format, format_type)
+            .unwrap();
+        assert_eq!(data.len(), 2 * format.get_bytes_per_block() as usize);
+        let image = decode_uncompressed(&data, format, format_type).unwrap();
+        let decoded = rgba32f_to_rgba8(&image, format_type);
+        for (i, (value, expected)) in decoded.iter().zip(rgba.iter()).enumerate() {
+            if i % 4 < format.get_components().unwrap().len() {
+                assert!((*value as i32 - *expected as i32).abs() <= 4);
+            }
+        }
+    }
+}
@@ -1,72 +1,5 @@
-use once_cell::sync::Lazy;
-use std::{collections::HashMap, sync::Mutex};
-
 use crate::Error;
-
-pub static BLK_DIMS: Lazy<Mutex<HashMap<u32, (u32, u32)>>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    map.insert(0x1a, (4, 4));
-    map.insert(0x1b, (4, 4));
-    map.insert(0x1c, (4, 4));
-    map.insert(0x1d, (4, 4));
-    map.insert(0x1e, (4, 4));
-    map.insert(0x1f, (4, 4));
-    map.insert(0x20, (4, 4));
-    map.insert(0x2d, (4, 4));
-    map.insert(0x2e, (5, 4));
-    map.insert(0x2f, (5, 5));
-    map.insert(0x30, (6, 5));
-    map.insert(0x31, (6, 6));
-    map.insert(0x32, (8, 5));
-    map.insert(0x33, (8, 6));
-    map.insert(0x34, (8, 8));
-    map.insert(0x35, (10, 5));
-    map.insert(0x36, (10, 6));
-    map.insert(0x37, (10, 8));
-    map.insert(0x38, (10, 10));
-    map.insert(0x39, (12, 10));
-    map.insert(0x3a, (12, 12));
-    Mutex::new(map)
-});
-
-pub static BPPS: Lazy<Mutex<HashMap<u32, u32>>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    map.insert(0x1, 1);
-    map.insert(0x2, 1);
-    map.insert(0x3, 2);
-    map.insert(0x4, 2);
-    map.insert(0x5, 2);
-    map.insert(0x6, 2);
-    map.insert(0x7, 2);
-    map.insert(0x8, 2);
-    map.insert(0x9, 2);
-    map.insert(0xb, 4);
-    map.insert(0xc, 4);
-    map.insert(0xe, 4);
-    map.insert(0x1a, 8);
-    map.insert(0x1b, 0x10);
-    map.insert(0x1c, 0x10);
-    map.insert(0x1d, 8);
-    map.insert(0x1e, 0x10);
-    map.insert(0x1f, 0x10);
-    map.insert(0x20, 0x10);
-    map.insert(0x2d, 0x10);
-    map.insert(0x2e, 0x10);
-    map.insert(0x2f, 0x10);
-    map.insert(0x30, 0x10);
-    map.insert(0x31, 0x10);
-    map.insert(0x32, 0x10);
-    map.insert(0x33, 0x10);
-    map.insert(0x34, 0x10);
-    map.insert(0x35, 0x10);
-    map.insert(0x36, 0x10);
-    map.insert(0x37, 0x10);
-    map.insert(0x38, 0x10);
-    map.insert(0x39, 0x10);
-    map.insert(0x3a, 0x10);
-    map.insert(0x3b, 2);
-    Mutex::new(map)
-});
+use std::cmp;
 
 #[inline]
 pub fn round_up(x: u32, y: u32) -> u32 {
@@ -75,7 +8,7 @@ pub fn round_up(x: u32, y: u32) -> u32 {
 
 #[inline]
 pub fn div_round_up(n: u32, d: u32) -> u32 {
-    (n + d - 1) / d
+    n.div_ceil(d)
 }
 
 #[inline]
@@ -89,18 +22,28 @@ pub fn pow2_round_up(mut x: u32) -> u32 {
     x + 1
 }
 
+/// Address of a block in a block linear surface made of GOBs, which are 64 bytes wide and
+/// 8 rows high. Blocks of GOBs are one GOB wide, `block_height` GOBs high and `block_depth` GOBs deep.
+#[allow(clippy::too_many_arguments)]
 pub fn get_addr_block_linear(
     mut x: u32,
     y: u32,
+    z: u32,
     width: u32,
+    height: u32,
     bpp: u32,
     base_addr: u32,
     block_height: u32,
+    block_depth: u32,
 ) -> u32 {
     let image_width_in_gobs = div_round_up(width * bpp, 64);
+    let block_size = 512 * block_height * block_depth;
+    let slice_size = block_size * image_width_in_gobs * div_round_up(height, 8 * block_height);
     let gob_address = base_addr
-        + (y / (8 * block_height)) * 512 * block_height * image_width_in_gobs
-        + (x * bpp / 64) * 512 * block_height
+        + (z / block_depth) * slice_size
+        + (z % block_depth) * 512 * block_height
+        + (y / (8 * block_height)) * block_size * image_width_in_gobs
+        + (x * bpp / 64) * block_size
         + (y % (8 * block_height) / 8) * 512;
 
     x *= bpp;
@@ -113,8 +56,12 @@ pub fn get_addr_block_linear(
         + (x % 16)
 }
 
-#[allow(unused)]
+/// Block height in GOBs the GPU uses for a surface with `height` rows of blocks.
+/// Surfaces lower than a single GOB use blocks of one GOB.
 pub fn get_block_height(height: u32) -> u32 {
+    if height < 8 {
+        return 1;
+    }
     let mut block_height = pow2_round_up(height / 8);
     if block_height > 16 {
         block_height = 16;
@@ -122,56 +69,338 @@ pub fn get_block_height(height: u32) -> u32 {
     block_height
 }
 
+/// Block depth in GOBs the GPU uses for a 3D surface.
+pub fn get_block_depth(depth: u32) -> u32 {
+    match depth + depth / 2 {
+        16..=u32::MAX => 16,
+        8..=15 => 8,
+        4..=7 => 4,
+        2..=3 => 2,
+        _ => 1,
+    }
+}
+
+/// Block depth of a mipmap, which shrinks with the depth of the mipmap.
+pub fn get_mip_block_depth(depth: u32, mut block_depth: u32) -> u32 {
+    while depth <= block_depth / 2 && block_depth > 1 {
+        block_depth /= 2;
+    }
+    block_depth
+}
+
+/// GOB block height log2 and block depth of every mipmap.
+/// Mipmaps have smaller blocks once their height or depth is smaller than a block.
+pub fn get_mip_blocks(
+    height: u32,
+    depth: u32,
+    blk_height: u32,
+    block_height_log2: u32,
+    mip_count: usize,
+) -> Vec<(u32, u32)> {
+    let lines_per_block_height = (1 << block_height_log2) * 8;
+    let mut block_height_shift = 0;
+    let block_depth = get_block_depth(depth);
+
+    let mut res = Vec::with_capacity(mip_count);
+    for mip_level in 0..mip_count {
+        let height = cmp::max(1, height >> mip_level);
+        let depth = cmp::max(1, depth >> mip_level);
+        if pow2_round_up(div_round_up(height, blk_height)) < lines_per_block_height {
+            block_height_shift += 1;
+        }
+        res.push((
+            block_height_log2.saturating_sub(block_height_shift),
+            get_mip_block_depth(depth, block_depth),
+        ));
+    }
+    res
+}
+
+/// Sizes of the swizzled mipmaps of a surface with `(width, height, depth)` texels
+/// and `(blk_width, blk_height, bpp)` blocks, given the GOB blocks of every mipmap.
+pub fn get_mip_sizes(
+    (width, height, depth): (u32, u32, u32),
+    (blk_width, blk_height, bpp): (u32, u32, u32),
+    tile_mode: u16,
+    mip_blocks: &[(u32, u32)],
+) -> Vec<u32> {
+    mip_blocks
+        .iter()
+        .enumerate()
+        .map(|(mip_level, (block_height_log2, block_depth))| {
+            get_pitch_and_size(
+                cmp::max(1, width >> mip_level),
+                cmp::max(1, height >> mip_level),
+                cmp::max(1, depth >> mip_level),
+                blk_width,
+                blk_height,
+                true,
+                bpp,
+                tile_mode,
+                *block_height_log2,
+                *block_depth,
+            )
+            .1
+        })
+        .collect()
+}
+
+/// Convert a swizzled surface into linear rows of blocks.
 #[allow(clippy::too_many_arguments)]
 pub fn deswizzle(
     width: u32,
     height: u32,
+    depth: u32,
     blk_width: u32,
     blk_height: u32,
     round_pitch: bool,
     bpp: u32,
     tile_mode: u16,
     block_height_log2: u32,
+    block_depth: u32,
     buffer: Vec<u8>,
 ) -> Result<Vec<u8>, Error> {
-    if block_height_log2 > 5 {
-        return Err(Error::CorruptData);
-    }
+    swizzle_surface(
+        width,
+        height,
+        depth,
+        blk_width,
+        blk_height,
+        round_pitch,
+        bpp,
+        tile_mode,
+        block_height_log2,
+        block_depth,
+        &buffer,
+        false,
+    )
+}
 
-    let block_height = 1 << block_height_log2;
+/// Lay out linear rows of blocks as a surface, block linear or pitch linear if `tile_mode` is 1.
+/// The result has the size of the whole surface, including the padding of the GOB blocks.
+#[allow(clippy::too_many_arguments)]
+pub fn swizzle(
+    width: u32,
+    height: u32,
+    depth: u32,
+    blk_width: u32,
+    blk_height: u32,
+    round_pitch: bool,
+    bpp: u32,
+    tile_mode: u16,
+    block_height_log2: u32,
+    block_depth: u32,
+    buffer: &[u8],
+) -> Result<Vec<u8>, Error> {
+    swizzle_surface(
+        width,
+        height,
+        depth,
+        blk_width,
+        blk_height,
+        round_pitch,
+        bpp,
+        tile_mode,
+        block_height_log2,
+        block_depth,
+        buffer,
+        true,
+    )
+}
+
+/// Pitch of the rows and size of a swizzled surface, including the padding of the GOB blocks.
+#[allow(clippy::too_many_arguments)]
+pub fn get_pitch_and_size(
+    width: u32,
+    height: u32,
+    depth: u32,
+    blk_width: u32,
+    blk_height: u32,
+    round_pitch: bool,
+    bpp: u32,
+    tile_mode: u16,
+    block_height_log2: u32,
+    block_depth: u32,
+) -> (u32, u32) {
     let width = div_round_up(width, blk_width);
     let height = div_round_up(height, blk_height);
-
-    let (pitch, surf_size) = if tile_mode == 1 {
+    if tile_mode == 1 {
         let mut pitch = width * bpp;
         if round_pitch {
             pitch = round_up(pitch, 32)
         }
-        let surf_size = pitch * height;
-        (pitch, surf_size)
+        (pitch, pitch * height * depth)
     } else {
+        let block_height = 1 << block_height_log2;
         let pitch = round_up(width * bpp, 64);
-        let surf_size = pitch * round_up(height, block_height * 8);
-        (pitch, surf_size)
-    };
+        let size = pitch * round_up(height, block_height * 8) * round_up(depth, block_depth);
+        (pitch, size)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn swizzle_surface(
+    width: u32,
+    height: u32,
+    depth: u32,
+    blk_width: u32,
+    blk_height: u32,
+    round_pitch: bool,
+    bpp: u32,
+    tile_mode: u16,
+    block_height_log2: u32,
+    block_depth: u32,
+    buffer: &[u8],
+    to_swizzle: bool,
+) -> Result<Vec<u8>, Error> {
+    if block_height_log2 > 5 {
+        return Err(Error::CorruptData);
+    }
+
+    let block_height = 1 << block_height_log2;
+    let (pitch, surf_size) = get_pitch_and_size(
+        width,
+        height,
+        depth,
+        blk_width,
+        blk_height,
+        round_pitch,
+        bpp,
+        tile_mode,
+        block_height_log2,
+        block_depth,
+    );
+    let width = div_round_up(width, blk_width);
+    let height = div_round_up(height, blk_height);
 
     let mut res = vec![0; surf_size as usize];
 
-    for y in 0..height {
-        for x in 0..width {
-            let pos = if tile_mode == 1 {
-                y * pitch + x * bpp
-            } else {
-                get_addr_block_linear(x, y, width, bpp, 0, block_height)
-            };
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
+                let pos = if tile_mode == 1 {
+                    (z * height + y) * pitch + x * bpp
+                } else {
+                    get_addr_block_linear(x, y, z, width, height, bpp, 0, block_height, block_depth)
+                };
 
-            let pos2 = (y * width + x) * bpp;
+                let pos2 = ((z * height + y) * width + x) * bpp;
 
-            if pos + bpp <= surf_size {
-                res[pos2 as usize..(pos2 + bpp) as usize]
-                    .copy_from_slice(&buffer[pos as usize..(pos + bpp) as usize]);
+                let (src, dst) = if to_swizzle { (pos2, pos) } else { (pos, pos2) };
+                if pos + bpp <= surf_size && (src + bpp) as usize <= buffer.len() {
+                    res[dst as usize..(dst + bpp) as usize]
+                        .copy_from_slice(&buffer[src as usize..(src + bpp) as usize]);
+                }
             }
         }
     }
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 0, 0, 0)]
+    #[test_case(4, 1, 0, 0x30; "inside gob")]
+    #[test_case(0, 8, 0, 0x200; "next gob of block")]
+    #[test_case(0, 0, 1, 0x400; "next slice of block")]
+    #[test_case(0, 0, 2, 0x800; "next block in depth")]
+    fn test_get_addr_block_linear(x: u32, y: u32, z: u32, address: u32) {
+        assert_eq!(get_addr_block_linear(x, y, z, 16, 16, 4, 0, 2, 2), address);
+    }
+
+    #[test_case(0, 1)]
+    #[test_case(7, 1)]
+    #[test_case(8, 1)]
+    #[test_case(12, 1; "not a power of two")]
+    #[test_case(16, 2)]
+    #[test_case(40, 8)]
+    #[test_case(1024, 16)]
+    fn test_get_block_height(height: u32, block_height: u32) {
+        assert_eq!(get_block_height(height), block_height);
+    }
+
+    #[test]
+    fn test_get_block_height_of_textures() {
+        use crate::{Bfres, EmbeddedFile};
+
+        let bfres = Bfres::new(include_bytes!("../../../assets/M1_Player_MarioMdl.bfres")).unwrap();
+        let bntx = match bfres.get_external_file("textures.bntx").unwrap().get_file() {
+            Some(EmbeddedFile::BNTX(bntx)) => bntx,
+            _ => panic!("no BNTX file"),
+        };
+        for texture in bntx.get_textures() {
+            let (blk_width, blk_height) = texture.surface_format.get_block_size();
+            let block_height_log2 =
+                get_block_height(div_round_up(texture.height, blk_height)).trailing_zeros();
+            assert_eq!(block_height_log2, texture.texture_layout & 7);
+
+            // Swizzling the first mipmap again results in the data of the file
+            let (_, block_depth) =
+                get_mip_blocks(texture.height, 1, blk_height, block_height_log2, 1)[0];
+            let swizzled = swizzle(
+                texture.width,
+                texture.height,
+                1,
+                blk_width,
+                blk_height,
+                true,
+                texture.surface_format.get_bytes_per_block(),
+                texture.tile_mode,
+                block_height_log2,
+                block_depth,
+                &texture.texture_data[0][0],
+            )
+            .unwrap();
+            assert!(texture.image_data[..swizzled.len()] == swizzled[..]);
+        }
+    }
+
+    #[test_case(20, 12, 1, 4, 1, 0; "block linear")]
+    #[test_case(20, 12, 1, 4, 0, 1; "pitch linear")]
+    #[test_case(8, 8, 5, 16, 0, 0; "3d")]
+    fn test_swizzle_roundtrip(
+        width: u32,
+        height: u32,
+        depth: u32,
+        bpp: u32,
+        block_height_log2: u32,
+        tile_mode: u16,
+    ) {
+        let size = (width * height * depth * bpp) as usize;
+        let linear: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let block_depth = get_block_depth(depth);
+        let swizzled = swizzle(
+            width,
+            height,
+            depth,
+            1,
+            1,
+            true,
+            bpp,
+            tile_mode,
+            block_height_log2,
+            block_depth,
+            &linear,
+        )
+        .unwrap();
+        assert_ne!(swizzled[..size], linear[..]);
+        let res = deswizzle(
+            width,
+            height,
+            depth,
+            1,
+            1,
+            true,
+            bpp,
+            tile_mode,
+            block_height_log2,
+            block_depth,
+            swizzled,
+        )
+        .unwrap();
+        assert_eq!(res[..size], linear[..]);
+    }
+}
@@ -0,0 +1,862 @@
+use super::{
+    apply_channel_sources_inverse, encode_astc, encode_bc1, encode_bc2, encode_bc3, encode_bc4,
+    encode_bc5, encode_bc7, encode_uncompressed, generate_mipmaps, get_full_mip_count,
+    rgba8_to_rgba32f, util::*, EncodeQuality, FormatType, MipFilter, SurfaceFormat, Texture,
+    TextureDimension, BNTX,
+};
+use crate::{Error, RelocationTable, ResDict, StringPool};
+use byteorder::{ByteOrder, BE, LE};
+use std::cmp;
+
+/// Size of the file header and the NX header.
+const HEADER_SIZE: usize = 0x58;
+/// Size of the memory pool, which follows the headers.
+const MEMORY_POOL_SIZE: usize = 0x140;
+/// Size of a BRTI block without its mipmap pointers.
+const BRTI_SIZE: usize = 0x2a0;
+
+fn align(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+/// Options of [`BNTX::replace_texture`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplaceTextureOptions {
+    /// Format to encode the image to instead of the format of the original texture.
+    pub format: Option<(SurfaceFormat, FormatType)>,
+    /// Number of mipmaps instead of the mipmap count of the original texture.
+    /// It is limited to the length of a full mipmap chain.
+    pub mip_count: Option<u16>,
+    /// Quality of encoding block compressed formats.
+    pub quality: EncodeQuality,
+    /// Filter for generating mipmaps, which filters sRGB formats in linear space.
+    pub mip_filter: MipFilter,
+}
+
+/// Encode an RGBA8 image into given format. Depth slices are compressed separately.
+fn encode_rgba8(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    depth: u32,
+    surface_format: SurfaceFormat,
+    format_type: FormatType,
+    quality: EncodeQuality,
+) -> Result<Vec<u8>, Error> {
+    let normalized = matches!(format_type, FormatType::UNorm | FormatType::Srgb);
+    let signed = format_type == FormatType::SNorm;
+    let slice_size = (width * height * 4) as usize;
+    if data.len() != slice_size * depth as usize {
+        return Err(Error::ImageSizeInvalid(format!(
+            "{}x{}x{} needs {:#x} bytes instead of {:#x}",
+            width,
+            height,
+            depth,
+            slice_size * depth as usize,
+            data.len()
+        )));
+    }
+    let encode_slices = |encode: &dyn Fn(&[u8]) -> Vec<u8>| {
+        Some(data.chunks_exact(slice_size).flat_map(encode).collect())
+    };
+    match surface_format {
+        SurfaceFormat::R8G8B8A8 if normalized => Some(data.to_vec()),
+        SurfaceFormat::BC1 if normalized => {
+            encode_slices(&|slice| encode_bc1(slice, width, height, quality))
+        }
+        SurfaceFormat::BC2 if normalized => {
+            encode_slices(&|slice| encode_bc2(slice, width, height, quality))
+        }
+        SurfaceFormat::BC3 if normalized => {
+            encode_slices(&|slice| encode_bc3(slice, width, height, quality))
+        }
+        SurfaceFormat::BC4 if normalized || signed => {
+            encode_slices(&|slice| encode_bc4(slice, width, height, signed, quality))
+        }
+        SurfaceFormat::BC5 if normalized || signed => {
+            encode_slices(&|slice| encode_bc5(slice, width, height, signed, quality))
+        }
+        SurfaceFormat::BC7 if normalized => {
+            encode_slices(&|slice| encode_bc7(slice, width, height, quality))
+        }
+        format if format.is_astc() && normalized => {
+            let (block_width, block_height) = format.get_block_size();
+            encode_slices(&|slice| {
+                encode_astc(slice, width, height, block_width, block_height, quality)
+            })
+        }
+        format if !format.is_compressed() => {
+            encode_uncompressed(&rgba8_to_rgba32f(data, format_type), format, format_type)
+        }
+        _ => None,
+    }
+    .ok_or_else(|| {
+        Error::TextureFormatNotSupported(format!("{:?} {:?}", surface_format, format_type))
+    })
+}
+
+impl Texture {
+    /// Replace the image data, encoding every array layer of an RGBA8 image with its mipmaps.
+    fn set_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba8: &[u8],
+        options: &ReplaceTextureOptions,
+    ) -> Result<(), Error> {
+        let (surface_format, format_type) = options
+            .format
+            .unwrap_or((self.surface_format, self.format_type));
+        let depth = if self.dimension == TextureDimension::D3 {
+            cmp::max(1, self.depth)
+        } else {
+            1
+        };
+        if width == 0 || height == 0 {
+            return Err(Error::ImageSizeInvalid(format!("{}x{}", width, height)));
+        }
+        let layer_size = width
+            .checked_mul(height)
+            .and_then(|size| size.checked_mul(depth))
+            .and_then(|size| size.checked_mul(4))
+            .map(|size| size as usize);
+        let image_size = layer_size.and_then(|size| size.checked_mul(self.array_length as usize));
+        let (layer_size, image_size) = match (layer_size, image_size) {
+            (Some(layer_size), Some(image_size)) => (layer_size, image_size),
+            _ => {
+                return Err(Error::ImageSizeInvalid(format!(
+                    "{}x{}x{} with {} layers is too large",
+                    width, height, depth, self.array_length
+                )))
+            }
+        };
+        if rgba8.len() != image_size {
+            return Err(Error::ImageSizeInvalid(format!(
+                "{}x{}x{} with {} layers needs {:#x} bytes instead of {:#x}",
+                width,
+                height,
+                depth,
+                self.array_length,
+                image_size,
+                rgba8.len()
+            )));
+        }
+        let full_mip_count = get_full_mip_count(width, height, depth);
+        let mip_count = options
+            .mip_count
+            .unwrap_or(self.mip_count)
+            .clamp(1, full_mip_count as u16);
+
+        let (blk_width, blk_height) = surface_format.get_block_size();
+        let bpp = surface_format.get_bytes_per_block();
+        let block_height_log2 = get_block_height(div_round_up(height, blk_height)).trailing_zeros();
+        let mip_blocks = get_mip_blocks(
+            height,
+            depth,
+            blk_height,
+            block_height_log2,
+            mip_count as usize,
+        );
+
+        // Array layers are aligned to a GOB block of the first mipmap, so that
+        // the image size is a multiple of the layer size
+        let layer_alignment = if self.tile_mode == 1 || self.array_length < 2 {
+            1
+        } else {
+            (512 << mip_blocks[0].0) * mip_blocks[0].1 as usize
+        };
+        let mut image_data = vec![];
+        let mut mip_offsets = Vec::with_capacity(mip_count as usize);
+        let mut texture_data = Vec::with_capacity(self.array_length as usize);
+        for (array_index, layer) in rgba8.chunks_exact(layer_size).enumerate() {
+            let mut image = layer.to_vec();
+            apply_channel_sources_inverse(&mut image, &self.channel_sources);
+
+            let mip_images = generate_mipmaps(
+                &image,
+                width,
+                height,
+                depth,
+                mip_count as u32,
+                options.mip_filter,
+                format_type == FormatType::Srgb,
+            );
+
+            let mut mip_offset = 0;
+            let mut mips = Vec::with_capacity(mip_count as usize);
+            for (mip_level, (image, (block_height_log2, block_depth))) in
+                mip_images.iter().zip(&mip_blocks).enumerate()
+            {
+                let (mip_width, mip_height, mip_depth) = (
+                    cmp::max(1, width >> mip_level),
+                    cmp::max(1, height >> mip_level),
+                    cmp::max(1, depth >> mip_level),
+                );
+                let data = encode_rgba8(
+                    image,
+                    mip_width,
+                    mip_height,
+                    mip_depth,
+                    surface_format,
+                    format_type,
+                    options.quality,
+                )?;
+                let swizzled = swizzle(
+                    mip_width,
+                    mip_height,
+                    mip_depth,
+                    blk_width,
+                    blk_height,
+                    true,
+                    bpp,
+                    self.tile_mode,
+                    *block_height_log2,
+                    *block_depth,
+                    &data,
+                )?;
+                if array_index == 0 {
+                    mip_offsets.push(mip_offset);
+                }
+                mip_offset += swizzled.len() as u64;
+                image_data.extend(swizzled);
+                mips.push(data);
+            }
+            image_data.resize(align(image_data.len(), layer_alignment), 0);
+            texture_data.push(mips);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.surface_format = surface_format;
+        self.format_type = format_type;
+        self.mip_count = mip_count;
+        self.texture_layout = (self.texture_layout & !7) | block_height_log2;
+        self.image_size = image_data.len() as u32;
+        self.mip_offsets = mip_offsets;
+        self.image_data = image_data;
+        self.texture_data = texture_data;
+        Ok(())
+    }
+}
+
+impl BNTX {
+    /// Serialize the BNTX file.
+    ///
+    /// The headers and the memory pool are kept. The texture array, the string table,
+    /// the texture dictionary and the texture headers are written from the current textures,
+    /// followed by their image data and a new relocation table.
+    /// The relocation sections keep covering the same parts of the file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.bom.is_big_endian() {
+            self.write::<BE>()
+        } else {
+            self.write::<LE>()
+        }
+    }
+
+    /// Replace the image of a texture. The RGBA8 image contains all array layers and
+    /// depth slices after each other, like [`Texture::to_rgba8`] returns them.
+    ///
+    /// The image is encoded into the format of the original texture, unless another format is
+    /// given, and mipmaps are generated. Channels are stored so that sampling the texture with
+    /// its channel sources results in the given image again.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn replace_texture(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        rgba8: &[u8],
+        options: &ReplaceTextureOptions,
+    ) -> Result<(), Error> {
+        let index = self
+            .texture_dict
+            .find(name)
+            .ok_or_else(|| Error::TextureNotFound(name.to_string()))?;
+        self.textures[index].set_image(width, height, rgba8, options)
+    }
+
+    /// Rename a texture. Fails if another texture already has the new name.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rename_texture(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        let index = self
+            .texture_dict
+            .find(name)
+            .ok_or_else(|| Error::TextureNotFound(name.to_string()))?;
+        if self.texture_dict.find(new_name).is_some() {
+            return Err(Error::DuplicateKey(new_name.to_string()));
+        }
+        self.textures[index].name = new_name.to_string();
+        self.texture_dict = ResDict::new(self.textures.iter().map(|texture| texture.name.clone()))?;
+        Ok(())
+    }
+
+    /// Add a texture, which is a copy of another texture of this file,
+    /// e.g. to replace its image afterwards.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_texture(&mut self, name: &str, mut texture: Texture) -> Result<(), Error> {
+        self.texture_dict.insert(name.to_string())?;
+        texture.name = name.to_string();
+        self.textures.push(texture);
+        Ok(())
+    }
+
+    /// Remove a texture and return it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn remove_texture(&mut self, name: &str) -> Result<Texture, Error> {
+        let index = self
+            .texture_dict
+            .find(name)
+            .ok_or_else(|| Error::TextureNotFound(name.to_string()))?;
+        let texture = self.textures.remove(index);
+        self.texture_dict = ResDict::new(self.textures.iter().map(|texture| texture.name.clone()))?;
+        Ok(texture)
+    }
+
+    fn write<B: ByteOrder>(&self) -> Result<Vec<u8>, Error> {
+        let original = self.bom.get_ref();
+        let alignment = 1 << self.header.alignment;
+        let texture_dict = ResDict::new(self.textures.iter().map(|texture| texture.name.clone()))?;
+
+        // Texture headers of the original file are stored in order right before the texture data
+        let original_texture_count = original
+            .get(0x24..0x28)
+            .map(B::read_u32)
+            .ok_or(Error::CorruptData)?;
+        let mut brti_offsets = Vec::with_capacity(original_texture_count as usize);
+        for i in 0..original_texture_count as usize {
+            let entry = self.texture_array_offset as usize + i * 8;
+            let offset = original
+                .get(entry..entry + 8)
+                .map(B::read_u64)
+                .ok_or(Error::CorruptData)? as usize;
+            let mip_count = original
+                .get(offset + 0x16..offset + 0x18)
+                .map(B::read_u16)
+                .ok_or(Error::CorruptData)?;
+            brti_offsets.push((offset, BRTI_SIZE + mip_count as usize * 8));
+        }
+        let original_brti_start = brti_offsets
+            .iter()
+            .map(|(offset, _)| *offset)
+            .min()
+            .unwrap_or(self.texture_data_offset as usize);
+        let original_brti_end = brti_offsets
+            .iter()
+            .map(|(offset, size)| offset + size)
+            .max()
+            .unwrap_or(original_brti_start);
+        let read_offset = |offset: usize| {
+            original
+                .get(offset..offset + 8)
+                .map(B::read_u64)
+                .ok_or(Error::CorruptData)
+        };
+        let original_memory_pool_offset = read_offset(0x40)? as usize;
+        let original_string_table_offset = original
+            .get(0x16..0x18)
+            .map(B::read_u16)
+            .ok_or(Error::CorruptData)? as usize;
+        let original_texture_dict_offset = read_offset(0x38)? as usize;
+
+        // Pointers with their targets
+        let mut pointers = vec![];
+        let mut data = original
+            .get(..HEADER_SIZE)
+            .ok_or(Error::CorruptData)?
+            .to_vec();
+        if original_memory_pool_offset != 0 {
+            data.extend_from_slice(
+                original
+                    .get(
+                        original_memory_pool_offset..original_memory_pool_offset + MEMORY_POOL_SIZE,
+                    )
+                    .ok_or(Error::CorruptData)?,
+            );
+            B::write_u64(&mut data[0x40..], HEADER_SIZE as u64);
+            pointers.push((0x40, HEADER_SIZE));
+        }
+        let texture_array_offset = data.len();
+        data.resize(texture_array_offset + self.textures.len() * 8, 0);
+        B::write_u32(&mut data[0x24..], self.textures.len() as u32);
+        B::write_u64(&mut data[0x28..], texture_array_offset as u64);
+        pointers.push((0x28, texture_array_offset));
+
+        // Strings keep their order, names of new textures are added at the end
+        let string_table_offset = data.len();
+        let string_pool = StringPool::new(
+            self.string_pool
+                .get_strings()
+                .iter()
+                .chain(std::iter::once(&self.name))
+                .filter(|string| *string == &self.name || texture_dict.find(string).is_some())
+                .chain(self.textures.iter().map(|texture| &texture.name))
+                .cloned(),
+            string_table_offset as u64 + 0x14,
+        );
+        let string_offset = |string: &str| {
+            string_pool
+                .find(string)
+                .and_then(|index| string_pool.get_offset(index))
+                .unwrap_or_default() as usize
+        };
+        data.extend_from_slice(b"_STR");
+        data.resize(string_table_offset + 0x14, 0);
+        B::write_u32(
+            &mut data[string_table_offset + 0x10..],
+            string_pool.len() as u32 - 1,
+        );
+        data.extend(string_pool.write::<B>());
+        data.resize(align(data.len(), 8), 0);
+        B::write_u16(&mut data[0x16..], string_table_offset as u16);
+        // The name points to the characters after the length
+        B::write_u32(&mut data[0x10..], string_offset(&self.name) as u32 + 2);
+
+        let texture_dict_offset = data.len();
+        data.extend(texture_dict.write::<B, _>(|key| string_offset(key) as u64));
+        B::write_u64(&mut data[0x38..], texture_dict_offset as u64);
+        pointers.push((0x38, texture_dict_offset));
+        for (i, node) in texture_dict.get_nodes().iter().enumerate() {
+            pointers.push((
+                texture_dict_offset + 8 + i * 0x10 + 8,
+                string_offset(node.get_key()),
+            ));
+        }
+        // The string table block covers the texture dictionary as well
+        let brti_start = data.len();
+        let string_table_size = (brti_start - string_table_offset) as u32;
+        B::write_u32(&mut data[string_table_offset + 4..], string_table_size);
+        B::write_u32(&mut data[string_table_offset + 8..], string_table_size);
+
+        let mut new_brti_offsets = Vec::with_capacity(self.textures.len());
+        for (i, texture) in self.textures.iter().enumerate() {
+            // Added textures are copies of another texture, whose header they start from
+            let source = (texture.ptr_offset as usize)
+                .checked_sub(BRTI_SIZE)
+                .ok_or(Error::CorruptData)?;
+            let template = original
+                .get(source..source + BRTI_SIZE)
+                .filter(|template| template.starts_with(b"BRTI"))
+                .ok_or(Error::CorruptData)?;
+            let position = data.len();
+            new_brti_offsets.push(position);
+            B::write_u64(&mut data[texture_array_offset + i * 8..], position as u64);
+            pointers.push((texture_array_offset + i * 8, position));
+            data.extend_from_slice(template);
+            data.resize(position + BRTI_SIZE + texture.mip_count as usize * 8, 0);
+            texture.write_header::<B>(&mut data[position..]);
+
+            // Pointers into the texture header move along with it
+            for pointer in (position + 0x60..position + 0xa0).step_by(8) {
+                let value = B::read_u64(&data[pointer..]) as usize;
+                let value = if value == 0 {
+                    continue;
+                } else if pointer == position + 0x60 {
+                    string_offset(&texture.name)
+                } else if value < HEADER_SIZE {
+                    value
+                } else if (source..=source + BRTI_SIZE).contains(&value) {
+                    position + value - source
+                } else {
+                    return Err(Error::ModificationNotSupported(format!(
+                        "pointer to {:#x} outside of the header of texture {}",
+                        value, texture.name
+                    )));
+                };
+                B::write_u64(&mut data[pointer..], value as u64);
+                pointers.push((pointer, value));
+            }
+        }
+        let brti_end = data.len();
+        for (i, position) in new_brti_offsets.iter().enumerate() {
+            let size = if i + 1 < new_brti_offsets.len() {
+                new_brti_offsets[i + 1] - position
+            } else {
+                align(brti_end + 0x10, alignment) - 0x10 - position
+            };
+            B::write_u32(&mut data[position + 4..], size as u32);
+            B::write_u32(&mut data[position + 8..], size as u32);
+        }
+
+        data.resize(align(data.len() + 0x10, alignment) - 0x10, 0);
+        let texture_data_offset = data.len();
+        data.extend_from_slice(b"BRTD\0\0\0\0\0\0\0\0\0\0\0\0");
+        B::write_u64(&mut data[0x30..], texture_data_offset as u64);
+        pointers.push((0x30, texture_data_offset));
+        for (texture, position) in self.textures.iter().zip(new_brti_offsets.iter()) {
+            data.resize(align(data.len(), texture.alignment as usize), 0);
+            let offset = data.len();
+            for (i, mip_offset) in texture.mip_offsets.iter().enumerate() {
+                let pointer = position + BRTI_SIZE + i * 8;
+                let value = offset + *mip_offset as usize;
+                B::write_u64(&mut data[pointer..], value as u64);
+                pointers.push((pointer, value));
+            }
+            data.extend_from_slice(&texture.image_data);
+        }
+        data.resize(align(data.len(), alignment), 0);
+
+        let relocation_table_offset = data.len();
+        let data_size = relocation_table_offset - texture_data_offset;
+        B::write_u64(&mut data[texture_data_offset + 8..], data_size as u64);
+
+        // Sections keep covering the same parts of the file, which have moved
+        let block_offsets = [
+            (0, 0),
+            (original_memory_pool_offset, HEADER_SIZE),
+            (self.texture_array_offset as usize, texture_array_offset),
+            (original_string_table_offset, string_table_offset),
+            (original_texture_dict_offset, texture_dict_offset),
+            (original_brti_start, brti_start),
+            (self.texture_data_offset as usize, texture_data_offset),
+        ];
+        let move_boundary = |offset: usize| {
+            if let Some((_, new_offset)) = block_offsets.iter().find(|(block, _)| *block == offset)
+            {
+                Ok(*new_offset)
+            } else if (original_brti_end..self.texture_data_offset as usize).contains(&offset) {
+                Ok(brti_end)
+            } else if offset >= self.header.relocation_table_offset as usize {
+                Ok(relocation_table_offset)
+            } else {
+                Err(Error::ModificationNotSupported(format!(
+                    "relocation section boundary at {:#x} within a block",
+                    offset
+                )))
+            }
+        };
+        let sections = self.header.relocation_table.get_sections();
+        let regions = sections
+            .iter()
+            .map(|section| {
+                let start = move_boundary(section.offset as usize)?;
+                let end = move_boundary((section.offset + section.size) as usize)?;
+                Ok(start..end)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // A section lists all pointers, which point into it
+        let mut section_pointers = vec![vec![]; sections.len()];
+        for (pointer, value) in pointers {
+            if let Some(i) = regions
+                .iter()
+                .position(|region| region.contains(&value))
+                .or_else(|| sections.len().checked_sub(1))
+            {
+                section_pointers[i].push(pointer as u64);
+            }
+        }
+        let relocation_table = RelocationTable::new(
+            sections
+                .iter()
+                .zip(regions)
+                .zip(section_pointers)
+                .map(|((section, region), pointers)| {
+                    section.with_pointers(region.start as u32, region.len() as u32, &pointers)
+                })
+                .collect(),
+        );
+        data.extend(relocation_table.write::<B>(relocation_table_offset as u32));
+
+        B::write_u32(&mut data[0x18..], relocation_table_offset as u32);
+        let size = data.len() as u32;
+        B::write_u32(&mut data[0x1c..], size);
+        Ok(data)
+    }
+}
+
+impl Texture {
+    /// Write the fields of the BRTI header, leaving its pointers as they are.
+    fn write_header<B: ByteOrder>(&self, data: &mut [u8]) {
+        data[0x10] = self.flags;
+        data[0x11] = self.dim;
+        B::write_u16(&mut data[0x12..], self.tile_mode);
+        B::write_u16(&mut data[0x14..], self.swizzle);
+        B::write_u16(&mut data[0x16..], self.mip_count);
+        B::write_u16(&mut data[0x18..], self.sample_count);
+        B::write_u32(
+            &mut data[0x1c..],
+            (self.surface_format as u32) << 8 | self.format_type as u32,
+        );
+        B::write_u32(&mut data[0x20..], self.access_flags);
+        B::write_u32(&mut data[0x24..], self.width);
+        B::write_u32(&mut data[0x28..], self.height);
+        B::write_u32(&mut data[0x2c..], self.depth);
+        B::write_u32(&mut data[0x30..], self.array_length);
+        B::write_u32(&mut data[0x34..], self.texture_layout);
+        B::write_u32(&mut data[0x38..], self.texture_layout2);
+        B::write_u32(&mut data[0x50..], self.image_size);
+        B::write_u32(&mut data[0x54..], self.alignment);
+        for (i, source) in self.channel_sources.iter().enumerate() {
+            data[0x58 + i] = *source as u8;
+        }
+        data[0x5c] = self.dimension as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bfres, EmbeddedFile, RelocationSection};
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../../assets/M1_Player_MarioMdl.bfres");
+
+    fn read_bntx(bfres_file: &[u8]) -> (Vec<u8>, BNTX) {
+        let bfres = Bfres::new(bfres_file).unwrap();
+        let file = bfres.get_external_file("textures.bntx").unwrap();
+        match file.get_file() {
+            Some(EmbeddedFile::BNTX(bntx)) => (file.get_data().to_vec(), bntx.clone()),
+            _ => panic!("no BNTX file"),
+        }
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_round_trip_bntx(bfres_file: &[u8]) {
+        let (data, bntx) = read_bntx(bfres_file);
+
+        assert!(bntx.to_bytes().unwrap() == data);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_round_trip_single_relocation_section(bfres_file: &[u8]) {
+        let (mut data, bntx) = read_bntx(bfres_file);
+        let relocation_table_offset = bntx.header.relocation_table_offset;
+        let pointers = bntx.header.relocation_table.get_pointer_offsets();
+        let section = RelocationSection::new(0, relocation_table_offset, &pointers);
+        data.truncate(relocation_table_offset as usize);
+        data.extend(RelocationTable::new(vec![section]).write::<LE>(relocation_table_offset));
+        let size = data.len() as u32;
+        LE::write_u32(&mut data[0x1c..], size);
+
+        let mut bntx = BNTX::try_new(&data).unwrap();
+        assert!(bntx.to_bytes().unwrap() == data);
+        bntx.replace_texture("climb.0", 8, 8, &[255; 8 * 8 * 4], &Default::default())
+            .unwrap();
+        let data = bntx.to_bytes().unwrap();
+        let bntx = BNTX::try_new(&data).unwrap();
+        let sections = bntx.header.relocation_table.get_sections();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(
+            (sections[0].offset, sections[0].size as usize),
+            (0, bntx.header.relocation_table_offset as usize)
+        );
+        assert!(bntx.validate_mips().is_ok());
+        assert_eq!(
+            bntx.get_texture("climb.0").unwrap().to_rgba8(0, 0).unwrap(),
+            [255; 8 * 8 * 4]
+        );
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_rename_texture(bfres_file: &[u8]) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let image = bntx.get_texture("climb.0").unwrap().to_rgba8(0, 0).unwrap();
+        bntx.rename_texture("climb.0", "climb.renamed").unwrap();
+        assert!(matches!(
+            bntx.rename_texture("wait.0", "climb.renamed"),
+            Err(Error::DuplicateKey(_))
+        ));
+        assert!(matches!(
+            bntx.rename_texture("climb.0", "climb.1"),
+            Err(Error::TextureNotFound(_))
+        ));
+
+        let bntx = BNTX::try_new(&bntx.to_bytes().unwrap()).unwrap();
+        assert!(bntx.get_texture("climb.0").is_none());
+        assert!(bntx.string_pool.find("climb.0").is_none());
+        assert_eq!(
+            bntx.get_texture("climb.renamed")
+                .unwrap()
+                .to_rgba8(0, 0)
+                .unwrap(),
+            image
+        );
+        assert!(bntx
+            .get_textures()
+            .iter()
+            .all(|texture| bntx.get_texture(texture.get_name()).is_some()));
+        assert_eq!(bntx.name, "textures");
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_add_and_remove_texture(bfres_file: &[u8]) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let image = bntx.get_texture("climb.0").unwrap().to_rgba8(0, 0).unwrap();
+        let texture = bntx.get_texture("climb.0").unwrap().clone();
+        bntx.add_texture("climb.copy", texture.clone()).unwrap();
+        assert!(matches!(
+            bntx.add_texture("wait.0", texture),
+            Err(Error::DuplicateKey(_))
+        ));
+        bntx.replace_texture("climb.copy", 8, 8, &[255; 8 * 8 * 4], &Default::default())
+            .unwrap();
+        assert_eq!(bntx.remove_texture("wait.0").unwrap().get_name(), "wait.0");
+        assert!(matches!(
+            bntx.remove_texture("wait.0"),
+            Err(Error::TextureNotFound(_))
+        ));
+
+        let bntx = BNTX::try_new(&bntx.to_bytes().unwrap()).unwrap();
+        assert!(bntx.validate_mips().is_ok());
+        assert_eq!(bntx.get_textures().len(), 25);
+        assert!(bntx.get_texture("wait.0").is_none());
+        assert!(bntx.string_pool.find("wait.0").is_none());
+        assert_eq!(
+            bntx.get_texture("climb.copy")
+                .unwrap()
+                .to_rgba8(0, 0)
+                .unwrap(),
+            [255; 8 * 8 * 4]
+        );
+        assert_eq!(
+            bntx.get_texture("climb.0").unwrap().to_rgba8(0, 0).unwrap(),
+            image
+        );
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL, "climb.0"; "with M1 Player MarioMdl")]
+    fn test_replace_texture(bfres_file: &[u8], name: &str) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let image: Vec<u8> = (0..64 * 32)
+            .flat_map(|i| [(i % 64 * 4) as u8, 0, 255, 255])
+            .collect();
+        let options = ReplaceTextureOptions {
+            mip_count: Some(3),
+            ..Default::default()
+        };
+        bntx.replace_texture(name, 64, 32, &image, &options)
+            .unwrap();
+        assert!(bntx
+            .replace_texture("not a texture", 64, 32, &image, &options)
+            .is_err());
+        assert!(matches!(
+            bntx.replace_texture(name, 64, 16, &image, &options),
+            Err(Error::ImageSizeInvalid(_))
+        ));
+        assert!(matches!(
+            bntx.replace_texture(name, 0, 32, &[], &options),
+            Err(Error::ImageSizeInvalid(_))
+        ));
+
+        let bntx = BNTX::try_new(&bntx.to_bytes().unwrap()).unwrap();
+        let texture = bntx.get_texture(name).unwrap();
+        assert_eq!((texture.width, texture.height), (64, 32));
+        assert_eq!(texture.get_texture_data()[0].len(), 3);
+        assert_eq!(texture.to_rgba8(0, 0).unwrap(), image);
+        assert_eq!(
+            &texture.to_rgba8(0, 2).unwrap()[..8],
+            &[6, 0, 255, 255, 22, 0, 255, 255]
+        );
+        assert_eq!(bntx.get_textures().len(), 25);
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL, "climb.0"; "with M1 Player MarioMdl")]
+    fn test_replace_texture_3d(bfres_file: &[u8], name: &str) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let index = bntx.texture_dict.find(name).unwrap();
+        bntx.textures[index].dim = 3;
+        bntx.textures[index].dimension = TextureDimension::D3;
+        bntx.textures[index].depth = 4;
+        let image: Vec<u8> = (0..4 * 8 * 16)
+            .flat_map(|i| [(i / (8 * 16) * 60) as u8, 0, 255, 255])
+            .collect();
+        let options = ReplaceTextureOptions {
+            format: Some((SurfaceFormat::R8G8B8A8, FormatType::UNorm)),
+            mip_count: Some(3),
+            ..Default::default()
+        };
+        assert!(matches!(
+            bntx.replace_texture(name, 16, 8, &image[..16 * 8 * 4], &options),
+            Err(Error::ImageSizeInvalid(_))
+        ));
+        bntx.replace_texture(name, 16, 8, &image, &options).unwrap();
+
+        let bntx = BNTX::try_new(&bntx.to_bytes().unwrap()).unwrap();
+        assert!(bntx.validate_mips().is_ok());
+        let texture = bntx.get_texture(name).unwrap();
+        assert_eq!(texture.get_dimension(), TextureDimension::D3);
+        assert_eq!(texture.get_mip_size(2), (4, 2, 1));
+        assert_eq!(texture.to_rgba8(0, 0).unwrap(), image);
+        // Depth slices are downsampled as well
+        let mip: Vec<u8> = (0..2 * 4 * 8)
+            .flat_map(|i| [[30, 150][i / (4 * 8)], 0, 255, 255])
+            .collect();
+        assert_eq!(texture.to_rgba8(0, 1).unwrap(), mip);
+        assert_eq!(
+            texture.to_rgba8(0, 2).unwrap(),
+            [[90, 0, 255, 255]; 8].concat()
+        );
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL, "climb.0"; "with M1 Player MarioMdl")]
+    fn test_replace_texture_array(bfres_file: &[u8], name: &str) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let index = bntx.texture_dict.find(name).unwrap();
+        bntx.textures[index].dimension = TextureDimension::D2Array;
+        bntx.textures[index].array_length = 3;
+        let image: Vec<u8> = (0..3 * 48 * 40)
+            .flat_map(|i| [(i / (48 * 40) * 100) as u8, (i % 48) as u8, 255, 255])
+            .collect();
+        let options = ReplaceTextureOptions {
+            format: Some((SurfaceFormat::R8G8B8A8, FormatType::UNorm)),
+            mip_count: Some(3),
+            ..Default::default()
+        };
+        assert!(matches!(
+            bntx.replace_texture(name, 48, 40, &image[..2 * 48 * 40 * 4], &options),
+            Err(Error::ImageSizeInvalid(_))
+        ));
+        assert!(matches!(
+            bntx.replace_texture(name, u32::MAX, u32::MAX, &image, &options),
+            Err(Error::ImageSizeInvalid(_))
+        ));
+        bntx.replace_texture(name, 48, 40, &image, &options)
+            .unwrap();
+
+        let bntx = BNTX::try_new(&bntx.to_bytes().unwrap()).unwrap();
+        assert!(bntx.validate_mips().is_ok());
+        let texture = bntx.get_texture(name).unwrap();
+        assert_eq!(texture.get_array_length(), 3);
+        // The mipmaps of a layer take 0x4400 bytes, which are padded to a block of 8 GOBs
+        assert_eq!(texture.mip_offsets, [0, 0x3000, 0x4000]);
+        assert_eq!(texture.get_image_size(), 3 * 0x5000);
+        for (array_index, layer) in image.chunks_exact(48 * 40 * 4).enumerate() {
+            assert_eq!(texture.to_rgba8(array_index, 0).unwrap(), layer);
+            assert_eq!(
+                &texture.to_rgba8(array_index, 2).unwrap()[..4],
+                &[(array_index * 100) as u8, 2, 255, 255]
+            );
+        }
+    }
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_invalid_mip_chain(bfres_file: &[u8]) {
+        let (_, mut bntx) = read_bntx(bfres_file);
+        let options = ReplaceTextureOptions {
+            mip_count: Some(3),
+            ..Default::default()
+        };
+        bntx.replace_texture("climb.0", 64, 32, &[255; 64 * 32 * 4], &options)
+            .unwrap();
+        let mut data = bntx.to_bytes().unwrap();
+        let bntx = BNTX::try_new(&data).unwrap();
+        assert!(bntx.validate_mips().is_ok());
+        let texture = bntx.get_texture("climb.0").unwrap();
+        let offset = texture.ptr_offset as usize + 8;
+        let mip_offset = LE::read_u64(&data[offset..offset + 8]);
+        LE::write_u64(&mut data[offset..offset + 8], mip_offset + 0x200);
+
+        // Invalid mip chains are still read
+        let bntx = BNTX::try_new(&data).unwrap();
+        assert!(matches!(
+            bntx.get_texture("climb.0").unwrap().validate_mips(),
+            Err(Error::MipChainInvalid(_))
+        ));
+        assert!(bntx.get_texture("wait.0").unwrap().validate_mips().is_ok());
+        assert!(matches!(
+            bntx.validate_mips(),
+            Err(Error::MipChainInvalid(_))
+        ));
+    }
+}
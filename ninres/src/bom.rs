@@ -76,6 +76,13 @@ impl ByteOrderMark {
         }
     }
 
+    /// The whole underlying buffer.
+    pub fn get_ref(&self) -> &[u8] {
+        match self {
+            Self::BigEndian(bytes) | Self::LittleEndian(bytes) => bytes.get_ref(),
+        }
+    }
+
     pub fn set_position(&mut self, pos: u64) {
         match self {
             Self::BigEndian(bytes) | Self::LittleEndian(bytes) => bytes.set_position(pos),
@@ -97,4 +104,13 @@ impl ByteOrderMark {
     read_number!(read_i16, i16, 2);
     read_number!(read_i32, i32, 4);
     read_number!(read_i64, i64, 8);
+    read_number!(read_f32, f32, 4);
+
+    pub fn read_i8(&mut self) -> Result<i8, Error> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    pub fn is_big_endian(&self) -> bool {
+        matches!(self, Self::BigEndian(_))
+    }
 }
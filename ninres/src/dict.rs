@@ -0,0 +1,341 @@
+//! Resource dictionary (`_DIC`), which is used by BFRES, BNTX and BFSHA.
+//!
+//! The dictionary is a Patricia trie, where every node tests a single bit of the key.
+//! Bits are counted from the least significant bit of the last character.
+//!
+//! See https://github.com/Kinnay/Nintendo-File-Formats/wiki/_DIC-Structure
+
+use crate::{
+    bfres::{read_relative_offset, read_string, read_wiiu_string},
+    ByteOrderMark, Error,
+};
+
+use byteorder::ByteOrder;
+
+/// Resource dictionary mapping names to indices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResDict {
+    /// `_DIC` for BNTX files, whereas BFRES files leave it empty.
+    pub signature: [u8; 4],
+    nodes: Vec<DictNode>,
+}
+
+/// Node of a resource dictionary. The first node is the root node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DictNode {
+    /// Index of the bit, which is tested when searching through this node.
+    pub reference: u32,
+    pub left: u16,
+    pub right: u16,
+    key: String,
+}
+
+impl Default for ResDict {
+    fn default() -> Self {
+        Self {
+            signature: *b"_DIC",
+            nodes: vec![DictNode {
+                reference: u32::MAX,
+                left: 0,
+                right: 0,
+                key: String::new(),
+            }],
+        }
+    }
+}
+
+fn get_bit(key: &[u8], bit: u32) -> bool {
+    let index = bit as usize / 8;
+    if index < key.len() {
+        (key[key.len() - index - 1] >> (bit % 8)) & 1 != 0
+    } else {
+        false
+    }
+}
+
+fn first_different_bit(a: &[u8], b: &[u8]) -> u32 {
+    (0..(a.len().max(b.len()) * 8) as u32)
+        .find(|&bit| get_bit(a, bit) != get_bit(b, bit))
+        .unwrap_or_default()
+}
+
+impl DictNode {
+    pub fn get_key(&self) -> &String {
+        &self.key
+    }
+
+    fn child(&self, key: &[u8]) -> u16 {
+        if get_bit(key, self.reference) {
+            self.right
+        } else {
+            self.left
+        }
+    }
+}
+
+impl ResDict {
+    /// Build a dictionary from keys. The index of a key is its position.
+    pub fn new<I, S>(keys: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut dict = Self::default();
+        for key in keys {
+            dict.insert(key.into())?;
+        }
+        Ok(dict)
+    }
+
+    pub(crate) fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        if offset == 0 {
+            return Ok(Self::default());
+        }
+        let signature = buffer
+            .get(offset as usize..offset as usize + 4)
+            .ok_or(Error::CorruptData)?;
+        let signature = [signature[0], signature[1], signature[2], signature[3]];
+        bom.set_position(offset + 4);
+        let count = bom.read_u32()?;
+
+        let mut nodes = Vec::with_capacity(count as usize + 1);
+        for i in 0..=count as u64 {
+            bom.set_position(offset + 8 + i * 0x10);
+            let reference = bom.read_u32()?;
+            let left = bom.read_u16()?;
+            let right = bom.read_u16()?;
+            let key_offset = bom.read_u64()?;
+            let key = if key_offset == 0 {
+                String::new()
+            } else {
+                read_string(bom, buffer, key_offset)?
+            };
+            nodes.push(DictNode {
+                reference,
+                left,
+                right,
+                key,
+            });
+        }
+        if nodes
+            .iter()
+            .any(|node| node.left as usize >= nodes.len() || node.right as usize >= nodes.len())
+        {
+            return Err(Error::CorruptData);
+        }
+
+        Ok(Self { signature, nodes })
+    }
+
+    /// Read an index group of a Wii U file, which is laid out like a dictionary
+    /// with relative offsets. Returns the dictionary and the offsets of all entries.
+    pub(crate) fn read_index_group(
+        bom: &mut ByteOrderMark,
+        buffer: &[u8],
+        offset: u64,
+    ) -> Result<(Self, Vec<u64>), Error> {
+        if offset == 0 {
+            return Ok((Self::default(), vec![]));
+        }
+        bom.set_position(offset + 4);
+        let count = bom.read_u32()?;
+
+        let mut nodes = Vec::with_capacity(count as usize + 1);
+        let mut data_offsets = Vec::with_capacity(count as usize);
+        for i in 0..=count as u64 {
+            bom.set_position(offset + 8 + i * 0x10);
+            let reference = bom.read_u32()?;
+            let left = bom.read_u16()?;
+            let right = bom.read_u16()?;
+            let key_offset = read_relative_offset(bom)?;
+            let data_offset = read_relative_offset(bom)?;
+            if i > 0 {
+                data_offsets.push(data_offset);
+            }
+            nodes.push(DictNode {
+                reference,
+                left,
+                right,
+                key: read_wiiu_string(bom, buffer, key_offset)?,
+            });
+        }
+        if nodes
+            .iter()
+            .any(|node| node.left as usize >= nodes.len() || node.right as usize >= nodes.len())
+        {
+            return Err(Error::CorruptData);
+        }
+
+        Ok((
+            Self {
+                signature: [0; 4],
+                nodes,
+            },
+            data_offsets,
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All nodes including the root node.
+    pub fn get_nodes(&self) -> &Vec<DictNode> {
+        &self.nodes
+    }
+
+    /// Keys in order of their indices.
+    pub fn get_keys(&self) -> impl Iterator<Item = &String> {
+        self.nodes.iter().skip(1).map(|node| &node.key)
+    }
+
+    /// Size of the dictionary in bytes.
+    pub fn get_size(&self) -> usize {
+        8 + self.nodes.len() * 0x10
+    }
+
+    /// Search the node, whose key is the closest to given key.
+    fn search(&self, key: &[u8]) -> usize {
+        let mut prev = 0;
+        let mut current = self.nodes[0].left as usize;
+        while (self.nodes[prev].reference as i32) < (self.nodes[current].reference as i32) {
+            prev = current;
+            current = self.nodes[current].child(key) as usize;
+        }
+        current
+    }
+
+    /// Look up the index of given key.
+    pub fn find(&self, key: &str) -> Option<usize> {
+        let index = self.search(key.as_bytes());
+        if index != 0 && self.nodes[index].key == key {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Add a key, whose index will be the current length of the dictionary.
+    /// Fails if the key is already in the dictionary.
+    pub fn insert(&mut self, key: String) -> Result<usize, Error> {
+        let bytes = key.as_bytes();
+        let closest = self.search(bytes);
+        if closest != 0 && self.nodes[closest].key == key {
+            return Err(Error::DuplicateKey(key));
+        }
+        let bit = first_different_bit(bytes, self.nodes[closest].key.as_bytes());
+
+        let mut prev = 0;
+        let mut current = self.nodes[0].left as usize;
+        while (self.nodes[prev].reference as i32) < (self.nodes[current].reference as i32)
+            && self.nodes[current].reference < bit
+        {
+            prev = current;
+            current = self.nodes[current].child(bytes) as usize;
+        }
+
+        let index = self.nodes.len();
+        let (left, right) = if get_bit(bytes, bit) {
+            (current as u16, index as u16)
+        } else {
+            (index as u16, current as u16)
+        };
+        if prev == 0 || !get_bit(bytes, self.nodes[prev].reference) {
+            self.nodes[prev].left = index as u16;
+        } else {
+            self.nodes[prev].right = index as u16;
+        }
+        self.nodes.push(DictNode {
+            reference: bit,
+            left,
+            right,
+            key,
+        });
+        Ok(index - 1)
+    }
+
+    /// Serialize the dictionary. `key_offset` returns the offset of a key in the string pool.
+    pub fn write<B, F>(&self, key_offset: F) -> Vec<u8>
+    where
+        B: ByteOrder,
+        F: Fn(&str) -> u64,
+    {
+        let mut buffer = vec![0; self.get_size()];
+        buffer[..4].copy_from_slice(&self.signature);
+        B::write_u32(&mut buffer[4..8], self.len() as u32);
+        for (node, chunk) in self.nodes.iter().zip(buffer[8..].chunks_exact_mut(0x10)) {
+            B::write_u32(&mut chunk[..4], node.reference);
+            B::write_u16(&mut chunk[4..6], node.left);
+            B::write_u16(&mut chunk[6..8], node.right);
+            B::write_u64(&mut chunk[8..], key_offset(&node.key));
+        }
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::LE;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL, 0x30; "with M1 Player MarioMdl models")]
+    #[test_case(M1_PLAYER_MARIOMDL, 0x60; "with M1 Player MarioMdl skeletal anims")]
+    #[test_case(M1_PLAYER_MARIOMDL, 0x70; "with M1 Player MarioMdl material anims")]
+    #[test_case(M1_PLAYER_MARIOMDL, 0xC0; "with M1 Player MarioMdl external files")]
+    fn test_rebuild_dict(bfres_file: &[u8], header_offset: usize) {
+        let mut bom = ByteOrderMark::try_new(bfres_file.to_vec(), 0xfffe).unwrap();
+        bom.set_position(header_offset as u64);
+        let offset = bom.read_u64().unwrap();
+        let dict = ResDict::read(&mut bom, bfres_file, offset).unwrap();
+        assert!(!dict.is_empty());
+
+        let mut rebuilt = ResDict::new(dict.get_keys().cloned()).unwrap();
+        rebuilt.signature = dict.signature;
+        assert_eq!(rebuilt, dict);
+        for (i, key) in dict.get_keys().enumerate() {
+            assert_eq!(dict.find(key), Some(i));
+        }
+        assert_eq!(dict.find("not a key"), None);
+
+        let key_offsets: Vec<u64> = (0..dict.get_nodes().len() as u64)
+            .map(|i| {
+                bom.set_position(offset + 8 + i * 0x10 + 8);
+                bom.read_u64().unwrap()
+            })
+            .collect();
+        let written = rebuilt.write::<LE, _>(|key| {
+            let i = dict
+                .get_nodes()
+                .iter()
+                .position(|node| node.get_key() == key)
+                .unwrap();
+            key_offsets[i]
+        });
+        assert_eq!(
+            &written[..],
+            &bfres_file[offset as usize..offset as usize + dict.get_size()]
+        );
+    }
+
+    #[test]
+    fn test_insert_duplicate_key() {
+        let mut dict = ResDict::new(["wait.0", "climb.0"]).unwrap();
+        assert_eq!(dict.insert("jump.0".to_owned()).unwrap(), 2);
+        assert!(matches!(
+            dict.insert("climb.0".to_owned()),
+            Err(Error::DuplicateKey(key)) if key == "climb.0"
+        ));
+        assert_eq!(dict.len(), 3);
+        assert!(matches!(
+            ResDict::new(["a", "b", "a"]),
+            Err(Error::DuplicateKey(_))
+        ));
+    }
+}
@@ -13,6 +13,24 @@ pub enum NinResError {
     ByteOrderInvalid,
     #[error("CorruptData")]
     CorruptData,
+    #[error("Texture not found: {0}")]
+    TextureNotFound(String),
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Texture format not supported: {0}")]
+    TextureFormatNotSupported(String),
+    #[error("Mip chain invalid: {0}")]
+    MipChainInvalid(String),
+    #[error("Modification not supported: {0}")]
+    ModificationNotSupported(String),
+    #[error("Platform not supported: {0}")]
+    PlatformNotSupported(String),
+    #[error("Value length invalid: expected {0} components, got {1}")]
+    ValueLengthInvalid(usize, usize),
+    #[error("Image size invalid: {0}")]
+    ImageSizeInvalid(String),
+    #[error("Duplicate key: {0}")]
+    DuplicateKey(String),
     #[error(transparent)]
     TryFromSlice(#[from] TryFromSliceError),
     #[error(transparent)]
@@ -25,7 +43,7 @@ pub enum NinResError {
     ZstdError(String),
 }
 
-impl<'a> From<FromUtf8Error> for NinResError {
+impl From<FromUtf8Error> for NinResError {
     fn from(err: FromUtf8Error) -> Self {
         Self::Utf8(err.utf8_error())
     }
@@ -6,7 +6,7 @@
 //! All file formats are behind feature flags.
 //! Here is a list of available Nintendo file format features:
 //!
-//! `bfres`, `sarc`
+//! `bfres` (including BNTX, BFSHA and BNSH), `sarc`
 //!
 //! You can also enable additional features:
 //!
@@ -40,6 +40,8 @@
 //!     
 //!     match &ninres {
 //!         NinResFile::Bfres(_bfres) => {}
+//!         NinResFile::Bfsha(_bfsha) => {}
+//!         NinResFile::Bnsh(_bnsh) => {}
 //!         NinResFile::Sarc(_sarc) => {}
 //!     }
 //!
@@ -47,6 +49,37 @@
 //! # }
 //! ```
 //!
+//! # Replace textures
+//!
+//! Textures of BNTX files, which are embedded in Switch BFRES files, can be replaced by RGBA8 images.
+//! The image is encoded into the format of the original texture and mipmaps are generated.
+//! Block compressed formats (BC1, BC3, BC4, BC5, BC7 and ASTC) are encoded in software with the
+//! quality set in `ReplaceTextureOptions`. BC2 is encoded as well, so that the few textures stored
+//! in it can be replaced without changing their format. Mipmaps are downsampled with a box or Kaiser filter, in linear space
+//! for sRGB formats.
+//!
+//! ```no_run
+//! # use ninres::NinResError;
+//! #[cfg(all(not(target_arch = "wasm32"), feature = "bfres"))]
+//! fn main() -> Result<(), NinResError> {
+//!     use ninres::{Bfres, EmbeddedFile, ReplaceTextureOptions};
+//!     use std::fs::{read, write};
+//!
+//!     let mut bfres = Bfres::new(&read("../assets/M1_Player_MarioMdl.bfres")?)?;
+//!     let file = bfres.get_external_file_mut("textures.bntx").unwrap();
+//!     if let Some(EmbeddedFile::BNTX(bntx)) = file.get_file() {
+//!         let mut bntx = bntx.clone();
+//!         let image = vec![255; 64 * 64 * 4];
+//!         bntx.replace_texture("climb.0", 64, 64, &image, &ReplaceTextureOptions::default())?;
+//!         file.set_data(bntx.to_bytes()?);
+//!     }
+//!     write("M1_Player_MarioMdl.bfres", bfres.to_bytes()?)?;
+//!     Ok(())
+//! }
+//! # #[cfg(not(all(not(target_arch = "wasm32"), feature = "bfres")))]
+//! # fn main() {}
+//! ```
+//!
 
 #[cfg(feature = "tar")]
 extern crate tar_crate as tar;
@@ -60,11 +93,21 @@ extern crate derivative;
 
 #[cfg(any(feature = "bfres", feature = "sarc"))]
 mod bom;
+#[cfg(feature = "bfres")]
+mod dict;
 mod error;
+#[cfg(feature = "bfres")]
+mod rlt;
 
 #[cfg(feature = "bfres")]
 pub mod bfres;
 
+#[cfg(feature = "bfres")]
+pub mod bfsha;
+
+#[cfg(feature = "bfres")]
+pub mod bnsh;
+
 #[cfg(feature = "bfres")]
 pub mod bntx;
 
@@ -74,10 +117,18 @@ pub mod sarc;
 #[cfg(feature = "bfres")]
 pub use bfres::*;
 #[cfg(feature = "bfres")]
+pub use bfsha::*;
+#[cfg(feature = "bfres")]
+pub use bnsh::*;
+#[cfg(feature = "bfres")]
 pub use bntx::*;
 #[cfg(any(feature = "bfres", feature = "sarc"))]
 pub use bom::ByteOrderMark;
+#[cfg(feature = "bfres")]
+pub use dict::*;
 pub use error::NinResError;
+#[cfg(feature = "bfres")]
+pub use rlt::*;
 #[cfg(feature = "sarc")]
 pub use sarc::*;
 
@@ -92,9 +143,14 @@ pub type NinResResult = Result<NinResFile, Error>;
 #[cfg(any(feature = "bfres", feature = "sarc"))]
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum NinResFile {
     #[cfg(feature = "bfres")]
     Bfres(bfres::Bfres),
+    #[cfg(feature = "bfres")]
+    Bfsha(bfsha::Bfsha),
+    #[cfg(feature = "bfres")]
+    Bnsh(bnsh::BNSH),
     #[cfg(feature = "sarc")]
     Sarc(sarc::Sarc),
 }
@@ -117,6 +173,10 @@ impl NinResFile {
         match self {
             #[cfg(feature = "bfres")]
             Self::Bfres(_) => "bfres",
+            #[cfg(feature = "bfres")]
+            Self::Bfsha(_) => "bfsha",
+            #[cfg(feature = "bfres")]
+            Self::Bnsh(_) => "bnsh",
             #[cfg(feature = "sarc")]
             Self::Sarc(_) => "sarc",
         }
@@ -167,6 +227,8 @@ impl NinResFileExt {
 ///     
 ///     match &ninres {
 ///        NinResFile::Bfres(_bfres) => {}
+///        NinResFile::Bfsha(_bfsha) => {}
+///        NinResFile::Bnsh(_bnsh) => {}
 ///        NinResFile::Sarc(_sarc) => {}
 ///     }
 ///
@@ -207,6 +269,10 @@ impl NinRes for &[u8] {
             "SARC" => Ok(NinResFile::Sarc(Sarc::new(data)?)),
             #[cfg(feature = "bfres")]
             "FRES" => Ok(NinResFile::Bfres(Bfres::new(data)?)),
+            #[cfg(feature = "bfres")]
+            "FSHA" => Ok(NinResFile::Bfsha(Bfsha::try_new(data)?)),
+            #[cfg(feature = "bfres")]
+            "BNSH" => Ok(NinResFile::Bnsh(BNSH::try_new(data)?)),
             _ => Err(NinResError::TypeUnknownOrNotImplemented([
                 data[0], data[1], data[2], data[3],
             ])),
@@ -237,6 +303,10 @@ impl NinRes for &[u8] {
             "SARC" => Ok(NinResFile::Sarc(Sarc::new(data)?)),
             #[cfg(feature = "bfres")]
             "FRES" => Ok(NinResFile::Bfres(Bfres::new(data)?)),
+            #[cfg(feature = "bfres")]
+            "FSHA" => Ok(NinResFile::Bfsha(Bfsha::try_new(data)?)),
+            #[cfg(feature = "bfres")]
+            "BNSH" => Ok(NinResFile::Bnsh(BNSH::try_new(data)?)),
             _ => Err(NinResError::TypeUnknownOrNotImplemented([
                 data[0], data[1], data[2], data[3],
             ])),
@@ -271,6 +341,10 @@ impl NinRes for Vec<u8> {
             "SARC" => Ok(NinResFile::Sarc(Sarc::new(data)?)),
             #[cfg(feature = "bfres")]
             "FRES" => Ok(NinResFile::Bfres(Bfres::new(data)?)),
+            #[cfg(feature = "bfres")]
+            "FSHA" => Ok(NinResFile::Bfsha(Bfsha::try_new(data)?)),
+            #[cfg(feature = "bfres")]
+            "BNSH" => Ok(NinResFile::Bnsh(BNSH::try_new(data)?)),
             _ => Err(NinResError::TypeUnknownOrNotImplemented([
                 data[0], data[1], data[2], data[3],
             ])),
@@ -294,6 +368,10 @@ impl NinRes for Vec<u8> {
             "SARC" => Ok(NinResFile::Sarc(Sarc::new(&self[..])?)),
             #[cfg(feature = "bfres")]
             "FRES" => Ok(NinResFile::Bfres(Bfres::new(&self[..])?)),
+            #[cfg(feature = "bfres")]
+            "FSHA" => Ok(NinResFile::Bfsha(Bfsha::try_new(&self[..])?)),
+            #[cfg(feature = "bfres")]
+            "BNSH" => Ok(NinResFile::Bnsh(BNSH::try_new(&self[..])?)),
             _ => Err(NinResError::TypeUnknownOrNotImplemented([
                 self[0], self[1], self[2], self[3],
             ])),
@@ -0,0 +1,275 @@
+//! Relocation table (`_RLT`) of BFRES and BNTX files.
+//!
+//! The relocation table lists the locations of all pointers within the file,
+//! so that they can be turned into absolute addresses when the file is loaded.
+//!
+//! See https://github.com/Kinnay/Nintendo-File-Formats/wiki/_RLT-Structure
+
+use crate::{ByteOrderMark, Error};
+
+use byteorder::ByteOrder;
+
+/// Relocation table of a BFRES or BNTX file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelocationTable {
+    sections: Vec<RelocationSection>,
+}
+
+/// Region of the file. The section lists all pointers, which point into it,
+/// regardless of where the pointers themselves are located.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelocationSection {
+    /// Runtime pointer to the section, which is zero in files.
+    pub pointer: u64,
+    pub offset: u32,
+    pub size: u32,
+    entries: Vec<RelocationEntry>,
+}
+
+/// Entry describing `array_count` arrays of `offset_count` consecutive pointers,
+/// where `padding_count` pointer sized values lie between two arrays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelocationEntry {
+    pub offset: u32,
+    pub array_count: u16,
+    pub offset_count: u8,
+    pub padding_count: u8,
+}
+
+impl RelocationEntry {
+    /// Offsets of all pointers described by this entry.
+    pub fn get_pointer_offsets(&self) -> impl Iterator<Item = u64> + '_ {
+        let stride = (self.offset_count as u64 + self.padding_count as u64) * 8;
+        (0..self.array_count as u64).flat_map(move |array| {
+            (0..self.offset_count as u64).map(move |i| self.offset as u64 + array * stride + i * 8)
+        })
+    }
+}
+
+impl RelocationSection {
+    /// Build a section from the offsets of all pointers, which point into it.
+    pub fn new(offset: u32, size: u32, pointer_offsets: &[u64]) -> Self {
+        let mut pointer_offsets = pointer_offsets.to_vec();
+        pointer_offsets.sort_unstable();
+        pointer_offsets.dedup();
+        let mut section = Self {
+            pointer: 0,
+            offset,
+            size,
+            entries: vec![],
+        };
+        section.set_pointer_offsets(&pointer_offsets);
+        section
+    }
+
+    /// Section at a new location covering given pointers. The entries of this section are kept
+    /// if the pointers didn't change, since they aren't always compressed like we would.
+    pub(crate) fn with_pointers(&self, offset: u32, size: u32, pointer_offsets: &[u64]) -> Self {
+        let mut original: Vec<u64> = self.get_pointer_offsets().collect();
+        original.sort_unstable();
+        let mut sorted = pointer_offsets.to_vec();
+        sorted.sort_unstable();
+        if sorted == original {
+            Self {
+                offset,
+                size,
+                ..self.clone()
+            }
+        } else {
+            Self::new(offset, size, pointer_offsets)
+        }
+    }
+
+    pub fn get_entries(&self) -> &Vec<RelocationEntry> {
+        &self.entries
+    }
+
+    /// Offsets of all pointers within this section.
+    pub fn get_pointer_offsets(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.get_pointer_offsets())
+    }
+
+    /// Compress sorted pointer offsets into entries.
+    fn set_pointer_offsets(&mut self, offsets: &[u64]) {
+        self.entries.clear();
+        let mut i = 0;
+        while i < offsets.len() {
+            let run_length = |start: usize| {
+                let mut length = 1;
+                while start + length < offsets.len()
+                    && length < u8::MAX as usize
+                    && offsets[start + length] == offsets[start] + length as u64 * 8
+                {
+                    length += 1;
+                }
+                length
+            };
+            let offset_count = run_length(i);
+            let mut array_count = 1;
+            let mut padding_count = 0;
+
+            if let Some(&next) = offsets.get(i + offset_count) {
+                let gap = next - offsets[i] - offset_count as u64 * 8;
+                if gap % 8 == 0 && gap / 8 <= u8::MAX as u64 {
+                    let stride = offset_count as u64 * 8 + gap;
+                    let mut start = i + offset_count;
+                    while array_count < u16::MAX as usize
+                        && offsets.get(start) == Some(&(offsets[i] + array_count as u64 * stride))
+                        && run_length(start) >= offset_count
+                    {
+                        array_count += 1;
+                        start += offset_count;
+                    }
+                    if array_count > 1 {
+                        padding_count = (gap / 8) as u8;
+                    }
+                }
+            }
+
+            self.entries.push(RelocationEntry {
+                offset: offsets[i] as u32,
+                array_count: array_count as u16,
+                offset_count: offset_count as u8,
+                padding_count,
+            });
+            i += array_count * offset_count;
+        }
+    }
+}
+
+impl RelocationTable {
+    pub fn new(sections: Vec<RelocationSection>) -> Self {
+        Self { sections }
+    }
+
+    pub(crate) fn read(bom: &mut ByteOrderMark, buffer: &[u8], offset: u64) -> Result<Self, Error> {
+        if buffer.get(offset as usize..offset as usize + 4) != Some(b"_RLT") {
+            return Err(Error::CorruptData);
+        }
+        bom.set_position(offset + 8);
+        let section_count = bom.read_u32()?;
+        let entry_array_offset = offset + 0x10 + section_count as u64 * 0x18;
+
+        let mut sections = Vec::with_capacity(section_count as usize);
+        for i in 0..section_count as u64 {
+            bom.set_position(offset + 0x10 + i * 0x18);
+            let pointer = bom.read_u64()?;
+            let section_offset = bom.read_u32()?;
+            let size = bom.read_u32()?;
+            let entry_index = bom.read_u32()?;
+            let entry_count = bom.read_u32()?;
+
+            let mut entries = Vec::with_capacity(entry_count as usize);
+            bom.set_position(entry_array_offset + entry_index as u64 * 8);
+            for _ in 0..entry_count {
+                entries.push(RelocationEntry {
+                    offset: bom.read_u32()?,
+                    array_count: bom.read_u16()?,
+                    offset_count: bom.read_u8()?,
+                    padding_count: bom.read_u8()?,
+                });
+            }
+            sections.push(RelocationSection {
+                pointer,
+                offset: section_offset,
+                size,
+                entries,
+            });
+        }
+
+        Ok(Self { sections })
+    }
+
+    pub fn get_sections(&self) -> &Vec<RelocationSection> {
+        &self.sections
+    }
+
+    /// Offsets of all pointers within the file.
+    pub fn get_pointer_offsets(&self) -> Vec<u64> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.get_pointer_offsets())
+            .collect()
+    }
+
+    /// Size of the relocation table in bytes.
+    pub fn get_size(&self) -> usize {
+        0x10 + self.sections.len() * 0x18
+            + self
+                .sections
+                .iter()
+                .map(|section| section.entries.len() * 8)
+                .sum::<usize>()
+    }
+
+    /// Serialize the relocation table, which will be placed at `offset`.
+    pub fn write<B: ByteOrder>(&self, offset: u32) -> Vec<u8> {
+        let mut buffer = vec![0; self.get_size()];
+        buffer[..4].copy_from_slice(b"_RLT");
+        B::write_u32(&mut buffer[4..8], offset);
+        B::write_u32(&mut buffer[8..12], self.sections.len() as u32);
+
+        let mut entry_index = 0;
+        let mut entry_position = 0x10 + self.sections.len() * 0x18;
+        for (i, section) in self.sections.iter().enumerate() {
+            let position = 0x10 + i * 0x18;
+            B::write_u64(&mut buffer[position..], section.pointer);
+            B::write_u32(&mut buffer[position + 8..], section.offset);
+            B::write_u32(&mut buffer[position + 0xC..], section.size);
+            B::write_u32(&mut buffer[position + 0x10..], entry_index);
+            B::write_u32(&mut buffer[position + 0x14..], section.entries.len() as u32);
+            entry_index += section.entries.len() as u32;
+
+            for entry in section.entries.iter() {
+                B::write_u32(&mut buffer[entry_position..], entry.offset);
+                B::write_u16(&mut buffer[entry_position + 4..], entry.array_count);
+                buffer[entry_position + 6] = entry.offset_count;
+                buffer[entry_position + 7] = entry.padding_count;
+                entry_position += 8;
+            }
+        }
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::LE;
+    use test_case::test_case;
+
+    static M1_PLAYER_MARIOMDL: &[u8] = include_bytes!("../../assets/M1_Player_MarioMdl.bfres");
+
+    #[test_case(M1_PLAYER_MARIOMDL; "with M1 Player MarioMdl")]
+    fn test_rebuild_relocation_table(bfres_file: &[u8]) {
+        let mut bom = ByteOrderMark::try_new(bfres_file.to_vec(), 0xfffe).unwrap();
+        bom.set_position(0x18);
+        let offset = bom.read_u32().unwrap();
+        let table = RelocationTable::read(&mut bom, bfres_file, offset as u64).unwrap();
+        assert_eq!(table.get_sections().len(), 5);
+        assert_eq!(
+            &table.write::<LE>(offset)[..],
+            &bfres_file[offset as usize..offset as usize + table.get_size()]
+        );
+
+        let sections = table
+            .get_sections()
+            .iter()
+            .map(|section| {
+                let pointer_offsets: Vec<u64> = section.get_pointer_offsets().collect();
+                RelocationSection::new(section.offset, section.size, &pointer_offsets)
+            })
+            .collect();
+        let rebuilt = RelocationTable::new(sections);
+        for (section, original) in rebuilt.get_sections().iter().zip(table.get_sections()) {
+            let mut pointer_offsets: Vec<u64> = original.get_pointer_offsets().collect();
+            pointer_offsets.sort_unstable();
+            assert_eq!(
+                section.get_pointer_offsets().collect::<Vec<_>>(),
+                pointer_offsets
+            );
+        }
+    }
+}
@@ -10,15 +10,12 @@ use crate::{ByteOrderMark, Error};
 use js_sys::JsString;
 #[cfg(any(feature = "tar", feature = "zstd"))]
 use std::io::Cursor;
-use std::io::SeekFrom;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[derive(Clone, Debug)]
 pub struct Sarc {
-    header: SarcHeader,
-    sfat_header: SfatHeader,
     sfat_nodes: Vec<SfatNode>,
 }
 
@@ -58,11 +55,9 @@ impl Sarc {
     pub fn new(buffer: &[u8]) -> Result<Sarc, Error> {
         let mut bom =
             ByteOrderMark::try_new(buffer.to_vec(), u16::from_be_bytes([buffer[6], buffer[7]]))?;
-        bom.set_position(8);
-        let file_size = bom.read_u32()?;
+        bom.set_position(0xC);
         let data_offset = bom.read_u32()?;
-        let version_number = bom.read_u16()?;
-        bom.seek(SeekFrom::Current(8))?;
+        bom.set_position(0x1A);
         let node_count = bom.read_u16()?;
         let mut sfat_nodes = vec![];
         let file_name_table_offset = (0x14 + 0xC + node_count as u32 * 0x10) as usize;
@@ -113,16 +108,7 @@ impl Sarc {
                 },
             })
         }
-        Ok(Sarc {
-            header: SarcHeader {
-                byte_order: bom,
-                file_size,
-                data_offset,
-                version_number,
-            },
-            sfat_header: SfatHeader { node_count },
-            sfat_nodes,
-        })
+        Ok(Sarc { sfat_nodes })
     }
 }
 
@@ -247,13 +233,12 @@ mod tests {
     #[test_case(M1_MODEL_PACK, "M1_Model.tar"; "with M1 Model Pack")]
     #[test_case(M3_MODEL_PACK, "M3_Model.tar"; "with M3 Model Pack")]
     #[test_case(MW_MODEL_PACK, "MW_Model.tar"; "with MW Model Pack")]
-    fn test_into_tar(sarc_file: &[u8], file_name: &str) -> Result<(), Error> {
-        let sarc_file = Sarc::new(sarc_file)?;
-        let tar = sarc_file.into_tar(0o644)?;
+    fn test_into_tar(sarc_file: &[u8], file_name: &str) {
+        let sarc_file = Sarc::new(sarc_file).unwrap();
+        let tar = sarc_file.into_tar(0o644).unwrap();
 
         use std::io::Write;
-        let mut file = std::fs::File::create(file_name)?;
-        file.write_all(&tar.into_inner()[..])?;
-        Ok(())
+        let mut file = std::fs::File::create(std::env::temp_dir().join(file_name)).unwrap();
+        file.write_all(&tar.into_inner()[..]).unwrap();
     }
 }